@@ -1,10 +1,17 @@
 use anyhow::{anyhow, Context, Result};
 use clap::{Parser, Subcommand};
 use rand_chacha::ChaCha20Rng;
-use rand_core::{RngCore, SeedableRng};
-use upspa_core::protocol::{authenticate, password_update, register, secret_update, setup};
-use upspa_core::toprf::{toprf_server_eval, ToprfClient, ToprfPartial};
+use rand_core::SeedableRng;
+use upspa_core::kdf::{Argon2idParams, PasswordKdf};
+use upspa_core::password_policy::{PasswordPolicy, PolicySpec};
+use upspa_core::protocol::{
+    authenticate, decrypt_cid, deprovision, password_update, register, secret_update, setup,
+    LsRecord,
+};
+use upspa_core::suite::OprfSuite;
+use upspa_core::toprf::{toprf_server_eval_verifiable, ToprfClient, ToprfPartial};
 use upspa_core::types::{b64_encode, CtBlobB64};
+use upspa_core::SecretBytes;
 #[derive(Parser, Debug)]
 #[command(name = "upspa")]
 #[command(version)]
@@ -23,10 +30,19 @@ enum Command {
         password: String,
         #[arg(long, default_value_t = 5)]
         nsp: usize,
+        /// Explicit SP ids, e.g. `17,42,103`; overrides `--nsp` (ids `1..=nsp`).
+        #[arg(long, value_delimiter = ',')]
+        sp_ids: Option<Vec<u32>>,
         #[arg(long, default_value_t = 3)]
         tsp: usize,
         #[arg(long)]
         seed_hex: Option<String>,
+        /// Harden the password with Argon2id (default parameters) first.
+        #[arg(long)]
+        argon2id: bool,
+        /// OPRF suite: `blake3` or `ristretto255-sha512` (RFC 9497).
+        #[arg(long, default_value = "blake3")]
+        suite: String,
     },
 
     DemoFlow {
@@ -40,26 +56,100 @@ enum Command {
         new_password: String,
         #[arg(long, default_value_t = 5)]
         nsp: usize,
+        /// Explicit SP ids, e.g. `17,42,103`; overrides `--nsp` (ids `1..=nsp`).
+        #[arg(long, value_delimiter = ',')]
+        sp_ids: Option<Vec<u32>>,
         #[arg(long, default_value_t = 3)]
         tsp: usize,
+        /// Store this password for the LS in its `cj` record.
+        #[arg(long, conflicts_with = "site_policy")]
+        site_password: Option<String>,
+        /// Render the site password from `vinfo` with this policy: a preset
+        /// (`strong`, `alphanumeric`, `legacy`, `pin`) or a JSON policy.
+        #[arg(long)]
+        site_policy: Option<String>,
+        /// Harden the password with Argon2id (default parameters) first.
+        #[arg(long)]
+        argon2id: bool,
+        /// OPRF suite: `blake3` or `ristretto255-sha512` (RFC 9497).
+        #[arg(long, default_value = "blake3")]
+        suite: String,
+    },
+
+    /// Print signed `POST /v1/deprovision` bodies removing an account made by
+    /// `setup` with the same arguments and seed, and its records.
+    DeleteAccount {
+        #[arg(long)]
+        uid: String,
+        #[arg(long)]
+        password: String,
+        /// Every LS the account registered at, e.g. `LS1,LS2`.
+        #[arg(long, value_delimiter = ',')]
+        lsj: Vec<String>,
+        #[arg(long, default_value_t = 5)]
+        nsp: usize,
+        /// Explicit SP ids, e.g. `17,42,103`; overrides `--nsp` (ids `1..=nsp`).
+        #[arg(long, value_delimiter = ',')]
+        sp_ids: Option<Vec<u32>>,
+        #[arg(long, default_value_t = 3)]
+        tsp: usize,
+        #[arg(long)]
+        seed_hex: Option<String>,
+        /// Must exceed the SPs' last update time.
+        #[arg(long)]
+        timestamp: u64,
+        /// Pass if `setup` was run with `--argon2id`.
+        #[arg(long)]
+        argon2id: bool,
+        /// The `--suite` `setup` was run with.
+        #[arg(long, default_value = "blake3")]
+        suite: String,
     },
 }
 
-fn parse_seed(seed_hex: Option<String>) -> Result<[u8; 32]> {
-    let mut out = [0u8; 32];
+fn parse_seed(seed_hex: Option<String>) -> Result<SecretBytes<32>> {
+    let mut out = SecretBytes::zero();
     if let Some(h) = seed_hex {
         let bytes = hex::decode(h).map_err(|e| anyhow!("invalid hex seed: {e}"))?;
         if bytes.len() != 32 {
             return Err(anyhow!("seed_hex must be 32 bytes (64 hex chars)"));
         }
-        out.copy_from_slice(&bytes);
+        out.expose_mut().copy_from_slice(&bytes);
         Ok(out)
     } else {
-        Ok([42u8; 32])
+        Ok(SecretBytes::new([42u8; 32]))
     }
 }
 
-fn ct_to_b64<const N: usize>(ct: &upspa_core::types::CtBlob<N>) -> CtBlobB64 {
+fn parse_policy(s: &str) -> Result<PasswordPolicy> {
+    let value = if s.trim_start().starts_with('{') {
+        serde_json::from_str(s)?
+    } else {
+        serde_json::Value::String(s.to_owned())
+    };
+    let spec: PolicySpec =
+        serde_json::from_value(value).map_err(|e| anyhow!("invalid site policy: {e}"))?;
+    Ok(spec.into())
+}
+
+fn parse_suite(s: &str) -> Result<OprfSuite> {
+    serde_json::from_value(serde_json::Value::String(s.to_owned()))
+        .map_err(|e| anyhow!("invalid OPRF suite: {e}"))
+}
+
+fn resolve_kdf(argon2id: bool, rng: &mut ChaCha20Rng) -> PasswordKdf {
+    if argon2id {
+        PasswordKdf::argon2id(Argon2idParams::default(), rng)
+    } else {
+        PasswordKdf::None
+    }
+}
+
+fn resolve_sp_ids(sp_ids: Option<Vec<u32>>, nsp: usize) -> Vec<u32> {
+    sp_ids.unwrap_or_else(|| (1..=nsp as u32).collect())
+}
+
+fn ct_to_b64<const N: usize>(ct: &upspa_core::types::CtVec<N>) -> CtBlobB64 {
     ct.to_b64()
 }
 
@@ -70,24 +160,34 @@ fn main() -> Result<()> {
             uid,
             password,
             nsp,
+            sp_ids,
             tsp,
             seed_hex,
+            argon2id,
+            suite,
         } => {
             let seed = parse_seed(seed_hex)?;
-            let mut rng = ChaCha20Rng::from_seed(seed);
+            let mut rng = ChaCha20Rng::from_seed(*seed);
+            let sp_ids = resolve_sp_ids(sp_ids, nsp);
+            let kdf = resolve_kdf(argon2id, &mut rng);
+            let suite = parse_suite(&suite)?;
 
-            let (out, payloads) = setup::client_setup(uid.as_bytes(), password.as_bytes(), nsp, tsp, &mut rng);
+            let (out, payloads) =
+                setup::client_setup_with_suite(uid.as_bytes(), password.as_bytes(), &sp_ids, tsp, &kdf, suite, &mut rng)?;
 
             let json = serde_json::json!({
                 "sig_pk_b64": b64_encode(&out.sig_pk),
                 "cid": ct_to_b64(&out.cid),
-                "shares": out.shares.iter().map(|(id, k)| serde_json::json!({"sp_id": id, "k_i_b64": b64_encode(k)})).collect::<Vec<_>>(),
+                "shares": out.shares.iter().map(|(id, k)| serde_json::json!({"sp_id": id, "k_i_b64": b64_encode(k.expose())})).collect::<Vec<_>>(),
+                "commitments": out.commitments.iter().map(|(id, c)| serde_json::json!({"sp_id": id, "k_i_commit_b64": b64_encode(c)})).collect::<Vec<_>>(),
+                "kdf": out.kdf,
+                "suite": out.suite,
                 "sp_payloads": payloads.iter().map(|p| serde_json::json!({
                     "sp_id": p.sp_id,
                     "uid_b64": b64_encode(&p.uid),
                     "sig_pk_b64": b64_encode(&p.sig_pk),
                     "cid": ct_to_b64(&p.cid),
-                    "k_i_b64": b64_encode(&p.k_i),
+                    "k_i_b64": b64_encode(p.k_i.expose()),
                 })).collect::<Vec<_>>()
             });
 
@@ -100,24 +200,44 @@ fn main() -> Result<()> {
             password,
             new_password,
             nsp,
+            sp_ids,
             tsp,
+            site_password,
+            site_policy,
+            argon2id,
+            suite,
         } => {
             let mut rng = ChaCha20Rng::from_seed([7u8; 32]);
+            let sp_ids = resolve_sp_ids(sp_ids, nsp);
+            let kdf = resolve_kdf(argon2id, &mut rng);
+            let suite = parse_suite(&suite)?;
+            let record = match (site_password, site_policy) {
+                (Some(password), _) => LsRecord::Password { password },
+                (None, Some(p)) => LsRecord::Policy {
+                    policy: parse_policy(&p)?,
+                },
+                (None, None) => LsRecord::None,
+            };
 
-            let (setup_out, _payloads) = setup::client_setup(uid.as_bytes(), password.as_bytes(), nsp, tsp, &mut rng);
-            let (st, blinded) = ToprfClient::begin(password.as_bytes(), &mut rng);
+            let (setup_out, _payloads) =
+                setup::client_setup_with_suite(uid.as_bytes(), password.as_bytes(), &sp_ids, tsp, &kdf, suite, &mut rng)?;
+            let hardened = kdf.harden(password.as_bytes())?;
+            let (st, blinded) = ToprfClient::begin_with_suite(&hardened, suite, &mut rng);
             let mut partials = Vec::new();
             for (id, share_bytes) in setup_out.shares.iter().take(tsp) {
-                let y_i = toprf_server_eval(&blinded, share_bytes).context("toprf_server_eval")?;
-                partials.push(ToprfPartial { id: *id, y: y_i });
+                let (y_i, proof) = toprf_server_eval_verifiable(&blinded, share_bytes, &mut rng)
+                    .context("toprf_server_eval")?;
+                partials.push(ToprfPartial { id: *id, y: y_i, proof: Some(proof) });
             }
-            let state_key = ToprfClient::finish(password.as_bytes(), &st, &partials)?;
+            let state_key =
+                ToprfClient::finish_verified(&hardened, &st, &partials, &setup_out.commitments)?;
             let reg = register::client_register(
                 uid.as_bytes(),
                 lsj.as_bytes(),
                 &state_key,
                 &setup_out.cid,
-                nsp,
+                &sp_ids,
+                &record,
                 &mut rng,
             )?;
             let auth_q = authenticate::client_auth_prepare(
@@ -125,26 +245,28 @@ fn main() -> Result<()> {
                 lsj.as_bytes(),
                 &state_key,
                 &setup_out.cid,
-                nsp,
+                &sp_ids,
             )?;
-            let cjs = reg.per_sp.iter().take(tsp).map(|m| m.cj.clone()).collect::<Vec<_>>();
-            let auth_res = authenticate::client_auth_finish(uid.as_bytes(), lsj.as_bytes(), &auth_q.k0, &cjs)?;
+            let cjs = reg.per_sp.iter().take(tsp).map(|m| (m.sp_id, m.cj.clone())).collect::<Vec<_>>();
+            let auth_res = authenticate::client_auth_finish(uid.as_bytes(), lsj.as_bytes(), &auth_q.k0, &cjs, tsp)?;
             let su_q = secret_update::client_secret_update_prepare(
                 uid.as_bytes(),
                 lsj.as_bytes(),
                 &state_key,
                 &setup_out.cid,
-                nsp,
+                &sp_ids,
             )?;
-            let su_res = secret_update::client_secret_update_finish(uid.as_bytes(), lsj.as_bytes(), &su_q.k0, &cjs, &mut rng)?;
+            let su_res = secret_update::client_secret_update_finish(uid.as_bytes(), lsj.as_bytes(), &su_q.k0, &cjs, tsp, &mut rng)?;
             let timestamp = 1_700_000_000u64; // demo
-            let pw_res = password_update::client_password_update(
+            let cid_pt = decrypt_cid(uid.as_bytes(), &state_key, &setup_out.cid)?;
+            let pw_res = password_update::client_password_update_from_plaintext(
                 uid.as_bytes(),
-                &state_key,
-                &setup_out.cid,
-                nsp,
+                &cid_pt,
+                &sp_ids,
                 tsp,
                 new_password.as_bytes(),
+                &kdf,
+                suite,
                 timestamp,
                 &mut rng,
             )?;
@@ -157,7 +279,7 @@ fn main() -> Result<()> {
                 "registration": {
                     "to_ls": {
                         "uid": uid,
-                        "vinfo_b64": b64_encode(&reg.to_ls.vinfo)
+                        "vinfo_b64": b64_encode(reg.to_ls.vinfo.expose())
                     },
                     "per_sp": reg.per_sp.iter().map(|m| serde_json::json!({
                         "sp_id": m.sp_id,
@@ -166,12 +288,14 @@ fn main() -> Result<()> {
                     })).collect::<Vec<_>>()
                 },
                 "authentication": {
-                    "vinfo_prime_b64": b64_encode(&auth_res.vinfo_prime),
+                    "vinfo_prime_b64": b64_encode(auth_res.vinfo_prime.expose()),
                     "best_ctr": auth_res.best_ctr,
+                    "record": auth_res.record,
+                    "site_password": auth_res.site_password()?,
                 },
                 "secret_update": {
-                    "vinfo_prime_b64": b64_encode(&su_res.vinfo_prime),
-                    "vinfo_new_b64": b64_encode(&su_res.vinfo_new),
+                    "vinfo_prime_b64": b64_encode(su_res.vinfo_prime.expose()),
+                    "vinfo_new_b64": b64_encode(su_res.vinfo_new.expose()),
                     "cj_new": ct_to_b64(&su_res.cj_new),
                     "old_ctr": su_res.old_ctr,
                     "new_ctr": su_res.new_ctr,
@@ -181,13 +305,64 @@ fn main() -> Result<()> {
                     "per_sp": pw_res.per_sp.iter().map(|m| serde_json::json!({
                         "sp_id": m.sp_id,
                         "sig_b64": b64_encode(&m.sig),
-                        "k_i_new_b64": b64_encode(&m.k_i_new)
+                        "k_i_new_b64": b64_encode(m.k_i_new.expose())
                     })).collect::<Vec<_>>()
                 }
             });
 
             println!("{}", serde_json::to_string_pretty(&json)?);
         }
+
+        Command::DeleteAccount {
+            uid,
+            password,
+            lsj,
+            nsp,
+            sp_ids,
+            tsp,
+            seed_hex,
+            timestamp,
+            argon2id,
+            suite,
+        } => {
+            let seed = parse_seed(seed_hex)?;
+            let mut rng = ChaCha20Rng::from_seed(*seed);
+            let sp_ids = resolve_sp_ids(sp_ids, nsp);
+            let kdf = resolve_kdf(argon2id, &mut rng);
+            let suite = parse_suite(&suite)?;
+
+            let (setup_out, _payloads) =
+                setup::client_setup_with_suite(uid.as_bytes(), password.as_bytes(), &sp_ids, tsp, &kdf, suite, &mut rng)?;
+            let hardened = kdf.harden(password.as_bytes())?;
+            let (st, blinded) = ToprfClient::begin_with_suite(&hardened, suite, &mut rng);
+            let mut partials = Vec::new();
+            for (id, share_bytes) in setup_out.shares.iter().take(tsp) {
+                let (y_i, proof) = toprf_server_eval_verifiable(&blinded, share_bytes, &mut rng)
+                    .context("toprf_server_eval")?;
+                partials.push(ToprfPartial { id: *id, y: y_i, proof: Some(proof) });
+            }
+            let state_key =
+                ToprfClient::finish_verified(&hardened, &st, &partials, &setup_out.commitments)?;
+            let lsjs: Vec<Vec<u8>> = lsj.into_iter().map(String::into_bytes).collect();
+            let msgs = deprovision::client_delete_account(
+                uid.as_bytes(),
+                &state_key,
+                &setup_out.cid,
+                &sp_ids,
+                &lsjs,
+                timestamp,
+            )?;
+
+            let json = msgs.iter().map(|m| serde_json::json!({
+                "uid_b64": m.uid_b64,
+                "sp_id": m.sp_id,
+                "timestamp": m.timestamp,
+                "sig_b64": b64_encode(&m.sig),
+                "suids_b64": m.suids.iter().map(|s| b64_encode(s)).collect::<Vec<_>>(),
+            })).collect::<Vec<_>>();
+
+            println!("{}", serde_json::to_string_pretty(&json)?);
+        }
     }
 
     Ok(())
@@ -0,0 +1,1126 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use rand_core::OsRng;
+use tokio::task::{self, JoinError, JoinSet};
+use upspa_core::hash::hash_suid;
+use upspa_core::kdf::PasswordKdf;
+use upspa_core::protocol::authenticate::{client_auth_finish, client_auth_prepare, AuthResult};
+use upspa_core::protocol::deprovision::client_delete_account;
+use upspa_core::protocol::device::{
+    enroll_device, sign_device_revocations, verify_device_revocations, DeviceEnrollment,
+    DeviceRevocationList,
+};
+use upspa_core::protocol::key_rotation::client_key_rotation;
+use upspa_core::protocol::login::client_login_robust;
+use upspa_core::protocol::password_update::{
+    client_password_update_from_plaintext, sign_password_update, sign_password_update_finalize,
+    verify_password_update_ack, PasswordUpdateAck, PasswordUpdateAction, PasswordUpdateOutput,
+    SigFormat,
+};
+use upspa_core::protocol::recovery::{
+    client_recovery_setup, recover_cid_plaintext, RecoveryCode, RecoveryKit,
+};
+use upspa_core::protocol::register::{client_register, RegistrationOutput};
+use upspa_core::protocol::secret_update::{
+    client_secret_update_finish, survey_records, SecretUpdateOutput,
+};
+use upspa_core::protocol::setup::{client_setup_with_suite, SetupOutput};
+use upspa_core::protocol::{decrypt_cid, CidPlaintext, CipherId, CipherSp, LsRecord};
+use upspa_core::sign::SigningKey;
+use upspa_core::suite::OprfSuite;
+use upspa_core::toprf::{
+    validate_sp_ids, validate_threshold, ToprfClient, ToprfClientState, ToprfPartial,
+};
+use upspa_core::types::b64_encode;
+use upspa_core::{SecretBytes, UpspaError};
+
+use crate::config::ClientConfig;
+use crate::deprovision::AccountDeletion;
+use crate::error::ClientError;
+use crate::key_rotation::KeyRotation;
+use crate::password_update::PasswordChange;
+use crate::secret_update::{PendingSecretUpdate, Recovery, SecretUpdatePhase};
+use crate::transport::{HttpTransport, LsTransport, SpTransport};
+
+/// Result of a successful Π2 login.
+#[derive(Clone, Debug)]
+pub struct Session {
+    pub state_key: SecretBytes<32>,
+    pub cid: CipherId,
+    pub cid_pt: CidPlaintext,
+    /// SPs whose partials did not fit the accepted share set.
+    pub inconsistent: Vec<u32>,
+}
+
+/// Runs Π1–Π5 against a set of SPs. Requests to the SPs are sent
+/// concurrently; each is retried on transport errors, timeouts and 5xx.
+pub struct UpspaClient {
+    config: ClientConfig,
+    sps: Vec<Arc<dyn SpTransport>>,
+}
+
+type SpResults<T> = (Vec<(u32, T)>, Vec<(u32, ClientError)>);
+type SpTasks<T> = (JoinSet<(u32, Result<T, ClientError>)>, Vec<(task::Id, u32)>);
+type SpRecords = Vec<(u32, CipherSp)>;
+
+impl UpspaClient {
+    /// Talk to `config.sps` over HTTP.
+    pub fn new(config: ClientConfig) -> Result<Self, ClientError> {
+        let http = reqwest::Client::builder()
+            .timeout(config.request_timeout)
+            .build()
+            .map_err(|e| ClientError::Config(e.to_string()))?;
+        let sps = config
+            .sps
+            .iter()
+            .map(|e| Arc::new(HttpTransport::new(e.clone(), http.clone())) as Arc<dyn SpTransport>)
+            .collect();
+        Self::with_transports(config, sps)
+    }
+
+    /// Talk to the given SPs instead; `config.sps` and `request_timeout` are
+    /// not used.
+    pub fn with_transports(
+        config: ClientConfig,
+        sps: Vec<Arc<dyn SpTransport>>,
+    ) -> Result<Self, ClientError> {
+        let sp_ids: Vec<u32> = sps.iter().map(|sp| sp.sp_id()).collect();
+        validate_sp_ids(&sp_ids)?;
+        validate_threshold(&sp_ids, config.tsp)?;
+        Ok(Self { config, sps })
+    }
+
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    /// Π0–Π1: create the account and provision every SP. Fails unless all
+    /// SPs accept, since a missing share cannot be re-sent later.
+    pub async fn setup(&self, password: &[u8]) -> Result<SetupOutput, ClientError> {
+        let (out, payloads) = client_setup_with_suite(
+            &self.config.uid,
+            password,
+            &self.sp_ids(),
+            self.config.tsp,
+            &self.config.kdf,
+            self.config.suite,
+            &mut OsRng,
+        )?;
+        let payloads: Arc<Vec<_>> = Arc::new(payloads.into_iter().map(|p| (p.sp_id, p)).collect());
+
+        let (ok, failures) = self
+            .fan_out_all(move |sp| {
+                let payloads = payloads.clone();
+                async move { sp.setup(for_sp(&payloads, sp.sp_id())).await }
+            })
+            .await;
+        require(self.sps.len(), ok, failures)?;
+        Ok(out)
+    }
+
+    /// Π2: fetch `cid` and TOPRF partials from all SPs at once and return as
+    /// soon as the partials received so far open `cid`.
+    pub async fn login(&self, password: &[u8]) -> Result<Session, ClientError> {
+        self.login_with(
+            password,
+            self.config.commitments.as_deref(),
+            &self.config.kdf,
+            self.config.suite,
+        )
+        .await
+    }
+
+    async fn login_with(
+        &self,
+        password: &[u8],
+        commitments: Option<&[(u32, [u8; 32])]>,
+        kdf: &PasswordKdf,
+        suite: OprfSuite,
+    ) -> Result<Session, ClientError> {
+        let hardened = kdf.harden(password)?;
+        let password: &[u8] = &hardened;
+        let (state, blinded) = ToprfClient::begin_with_suite(password, suite, &mut OsRng);
+        let uid = Arc::new(self.config.uid.clone());
+
+        let result = self
+            .fan_out_until(
+                move |sp| {
+                    let uid = uid.clone();
+                    async move {
+                        let cid = sp.get_setup(&uid).await?;
+                        let partial = sp.toprf_eval(&uid, &blinded).await?;
+                        Ok((cid, partial))
+                    }
+                },
+                |got| self.try_login(password, &state, commitments, got),
+            )
+            .await;
+
+        match result {
+            Err(ClientError::Quorum { got, .. }) if got >= self.config.tsp => {
+                Err(UpspaError::NoConsistentPartials.into())
+            }
+            other => other,
+        }
+    }
+
+    /// Π3: register `lsj` at every SP; at least `tsp` must store the record.
+    pub async fn register(
+        &self,
+        session: &Session,
+        lsj: &[u8],
+        record: &LsRecord,
+    ) -> Result<RegistrationOutput, ClientError> {
+        let out = client_register(
+            &self.config.uid,
+            lsj,
+            &session.state_key,
+            &session.cid,
+            &self.sp_ids(),
+            record,
+            &mut OsRng,
+        )?;
+        let msgs: Arc<Vec<_>> = Arc::new(out.per_sp.iter().map(|m| (m.sp_id, m.clone())).collect());
+
+        let (ok, failures) = self
+            .fan_out_all(move |sp| {
+                let msgs = msgs.clone();
+                async move {
+                    let m = for_sp(&msgs, sp.sp_id());
+                    sp.record_create(&m.suid, &m.cj).await
+                }
+            })
+            .await;
+        require(self.config.tsp, ok, failures)?;
+        Ok(out)
+    }
+
+    /// Π4 read: fetch `c_j` from every SP and take the newest, which at least
+    /// `record_quorum` SPs must hold. SPs behind it are listed in
+    /// [`AuthResult::stale`], SPs whose `c_j` does not decrypt in
+    /// [`AuthResult::invalid`]; see [`UpspaClient::repair_records`].
+    pub async fn authenticate(
+        &self,
+        session: &Session,
+        lsj: &[u8],
+    ) -> Result<AuthResult, ClientError> {
+        self.authenticate_device(&session.cid_pt, lsj).await
+    }
+
+    /// [`UpspaClient::authenticate`] from a `cid` plaintext unlocked on this
+    /// device with [`upspa_core::protocol::device::unlock_device`], without a
+    /// Π2 round.
+    pub async fn authenticate_device(
+        &self,
+        cid_pt: &CidPlaintext,
+        lsj: &[u8],
+    ) -> Result<AuthResult, ClientError> {
+        let (k0, cjs, _) = self.fetch_records(cid_pt, lsj).await?;
+        Ok(client_auth_finish(
+            &self.config.uid,
+            lsj,
+            &k0,
+            &cjs,
+            self.config.record_quorum,
+        )?)
+    }
+
+    /// Write `auth.repairs` to the stale and invalid SPs; all of them must
+    /// accept.
+    pub async fn repair_records(
+        &self,
+        session: &Session,
+        lsj: &[u8],
+        auth: &AuthResult,
+    ) -> Result<(), ClientError> {
+        let Some((_, cj)) = auth.repairs.first() else {
+            return Ok(());
+        };
+        let q = client_auth_prepare(
+            &self.config.uid,
+            lsj,
+            &session.state_key,
+            &session.cid,
+            &self.sp_ids(),
+        )?;
+        let sp_ids: Vec<u32> = auth.repairs.iter().map(|(id, _)| *id).collect();
+        let (ok, failures) = self.write_records(&sp_ids, &q.per_sp, cj).await;
+        require(sp_ids.len(), ok, failures)?;
+        Ok(())
+    }
+
+    /// Π4 update: rotate the per-LS secret and write `c_{j,new}` back to every
+    /// SP; at least `tsp` must accept. The caller still has to send
+    /// `vinfo_prime`/`vinfo_new` to the LS. Prefer
+    /// [`UpspaClient::begin_secret_update`] when the LS is reachable from here.
+    pub async fn secret_update(
+        &self,
+        session: &Session,
+        lsj: &[u8],
+    ) -> Result<SecretUpdateOutput, ClientError> {
+        let (k0, cjs, suids) = self.fetch_records(&session.cid_pt, lsj).await?;
+        let out = client_secret_update_finish(
+            &self.config.uid,
+            lsj,
+            &k0,
+            &cjs,
+            self.config.record_quorum,
+            &mut OsRng,
+        )?;
+
+        let (ok, failures) = self
+            .write_records(&self.sp_ids(), &suids, &out.cj_new)
+            .await;
+        require(self.config.tsp, ok, failures)?;
+        Ok(out)
+    }
+
+    /// Stage a two-phase Π4 secret update: compute `c_{j,new}` and
+    /// `vinfo_new` without sending anything. Fails with
+    /// [`ClientError::UnfinishedUpdate`] if an earlier update is half-applied.
+    pub async fn begin_secret_update(
+        &self,
+        session: &Session,
+        lsj: &[u8],
+    ) -> Result<PendingSecretUpdate, ClientError> {
+        let (k0, cjs, suids) = self.fetch_records(&session.cid_pt, lsj).await?;
+        let survey = survey_records(&self.config.uid, lsj, &k0, &cjs)?;
+        if !survey.is_consistent() {
+            return Err(ClientError::UnfinishedUpdate {
+                stale: survey.stale.iter().map(|(id, _)| *id).collect(),
+            });
+        }
+        let out = client_secret_update_finish(
+            &self.config.uid,
+            lsj,
+            &k0,
+            &cjs,
+            self.config.record_quorum,
+            &mut OsRng,
+        )?;
+
+        Ok(PendingSecretUpdate {
+            lsj: lsj.to_vec(),
+            suids,
+            cj_new: out.cj_new,
+            vinfo_prime: out.vinfo_prime,
+            vinfo_new: out.vinfo_new,
+            old_ctr: out.old_ctr,
+            new_ctr: out.new_ctr,
+            phase: SecretUpdatePhase::Staged,
+        })
+    }
+
+    /// Drive `pending` to [`SecretUpdatePhase::Done`]: switch the LS to
+    /// `vinfo_new`, then write `c_{j,new}` to every SP. `persist` is called
+    /// after each phase change. On error `pending` keeps its progress and
+    /// can be passed in again, including after a restart.
+    ///
+    /// The LS goes first so a reader never gets a `vinfo` the LS rejects:
+    /// until an SP holds `c_{j,new}`, every read still yields `vinfo_prime`,
+    /// and once one does the LS already accepts `vinfo_new`.
+    pub async fn finish_secret_update(
+        &self,
+        pending: &mut PendingSecretUpdate,
+        ls: &dyn LsTransport,
+        mut persist: impl FnMut(&PendingSecretUpdate),
+    ) -> Result<(), ClientError> {
+        let uid = &self.config.uid;
+        if pending.phase == SecretUpdatePhase::Staged {
+            let changed = ls
+                .change_password(uid, &pending.vinfo_prime, &pending.vinfo_new)
+                .await;
+            // A crash after the LS applied the change but before it was
+            // recorded leaves the LS already on `vinfo_new`.
+            if let Err(e) = changed {
+                if !ls.check(uid, &pending.vinfo_new).await? {
+                    return Err(e);
+                }
+            }
+            pending.phase = SecretUpdatePhase::LsCommitted {
+                remaining: pending.suids.iter().map(|(id, _)| *id).collect(),
+            };
+            persist(pending);
+        }
+
+        if let SecretUpdatePhase::LsCommitted { remaining } = &pending.phase {
+            let (ok, failures) = self
+                .write_records(remaining, &pending.suids, &pending.cj_new)
+                .await;
+            let remaining: Vec<u32> = failures.iter().map(|(id, _)| *id).collect();
+            if !ok.is_empty() {
+                pending.phase = if remaining.is_empty() {
+                    SecretUpdatePhase::Done
+                } else {
+                    SecretUpdatePhase::LsCommitted {
+                        remaining: remaining.clone(),
+                    }
+                };
+                persist(pending);
+            }
+            if !failures.is_empty() {
+                return Err(ClientError::Quorum {
+                    needed: ok.len() + remaining.len(),
+                    got: ok.len(),
+                    failures,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Finish a half-applied secret update for `lsj` without saved state,
+    /// using the `ctr` in each SP's `c_j`. SPs behind the newest record are
+    /// brought up to it once the LS accepts its `vinfo`; if the LS is still
+    /// on the previous `vinfo`, it is switched first.
+    pub async fn recover_secret_update(
+        &self,
+        session: &Session,
+        lsj: &[u8],
+        ls: &dyn LsTransport,
+    ) -> Result<Recovery, ClientError> {
+        let uid = &self.config.uid;
+        let (k0, cjs, suids) = self.fetch_records(&session.cid_pt, lsj).await?;
+        let survey = survey_records(uid, lsj, &k0, &cjs)?;
+        let ctr = survey.latest_ctr;
+        let stale: Vec<u32> = survey.stale.iter().map(|(id, _)| *id).collect();
+        if stale.is_empty() {
+            return Ok(Recovery::Consistent { ctr });
+        }
+
+        let ls_committed = if ls.check(uid, &survey.vinfo_latest).await? {
+            false
+        } else {
+            match survey.vinfo_previous {
+                Some(prev) if ls.check(uid, &prev).await? => {
+                    ls.change_password(uid, &prev, &survey.vinfo_latest).await?;
+                    true
+                }
+                _ => return Ok(Recovery::Unresolved { ctr, stale }),
+            }
+        };
+
+        let (ok, failures) = self.write_records(&stale, &suids, &survey.latest).await;
+        require(stale.len(), ok, failures)?;
+        Ok(Recovery::Finalized {
+            ctr,
+            repaired: stale,
+            ls_committed,
+        })
+    }
+
+    /// Π5: re-share under `new_password` and send each SP its signed update;
+    /// at least `tsp` must accept. The SPs keep the old shares until the
+    /// update is finalized; prefer [`UpspaClient::change_password`], which
+    /// does that.
+    pub async fn password_update(
+        &self,
+        session: &Session,
+        new_password: &[u8],
+        timestamp: u64,
+    ) -> Result<PasswordUpdateOutput, ClientError> {
+        let out =
+            self.build_password_update(&session.cid_pt, new_password, &self.config.kdf, timestamp)?;
+        let msgs: Arc<Vec<_>> = Arc::new(out.per_sp.iter().map(|m| (m.sp_id, m.clone())).collect());
+
+        let (ok, failures) = self
+            .fan_out_all(move |sp| {
+                let msgs = msgs.clone();
+                async move { sp.password_update(for_sp(&msgs, sp.sp_id())).await }
+            })
+            .await;
+        require(self.config.tsp, ok, failures)?;
+        Ok(out)
+    }
+
+    /// Replace the signing key inside `cid` and send each SP the new `sig_pk`,
+    /// signed with the old key; at least `tsp` must accept. On success
+    /// `session` is switched to the new `cid`. SPs that missed the rotation
+    /// keep the old key until sent their entry of `per_sp`.
+    ///
+    /// SPs drop their recovery kit with the old key; those that held one
+    /// are listed in [`KeyRotation::recovery_voided`].
+    pub async fn rotate_signing_key(
+        &self,
+        session: &mut Session,
+        timestamp: u64,
+    ) -> Result<KeyRotation, ClientError> {
+        let out = client_key_rotation(
+            &self.config.uid,
+            &session.state_key,
+            &session.cid,
+            &self.sp_ids(),
+            timestamp,
+            &mut OsRng,
+        )?;
+        let uid = Arc::new(self.config.uid.clone());
+        let (had_kit, _) = self
+            .fan_out_all(move |sp| {
+                let uid = uid.clone();
+                async move { sp.get_recovery(&uid).await }
+            })
+            .await;
+        let msgs: Arc<Vec<_>> = Arc::new(out.per_sp.iter().map(|m| (m.sp_id, m.clone())).collect());
+
+        let (ok, failures) = self
+            .fan_out_all(move |sp| {
+                let msgs = msgs.clone();
+                async move { sp.key_rotation(for_sp(&msgs, sp.sp_id())).await }
+            })
+            .await;
+        let rotated = require(self.config.tsp, ok, failures)?;
+        let recovery_voided = rotated
+            .iter()
+            .map(|(id, ())| *id)
+            .filter(|id| had_kit.iter().any(|(kit_id, _)| kit_id == id))
+            .collect();
+
+        session.cid_pt = decrypt_cid(&self.config.uid, &session.state_key, &out.cid_new)?;
+        session.cid = out.cid_new.clone();
+        Ok(KeyRotation {
+            rotation: out,
+            recovery_voided,
+        })
+    }
+
+    /// Remove the account from every SP: the setup record and the record of
+    /// each LS in `lsjs`. SPs that miss the request keep the account and are
+    /// reported in `lagging`; nothing is required of a quorum, since any SP
+    /// left holding the account is one too many.
+    pub async fn delete_account(
+        &self,
+        session: &Session,
+        lsjs: &[Vec<u8>],
+        timestamp: u64,
+    ) -> Result<AccountDeletion, ClientError> {
+        let per_sp = client_delete_account(
+            &self.config.uid,
+            &session.state_key,
+            &session.cid,
+            &self.sp_ids(),
+            lsjs,
+            timestamp,
+        )?;
+        let msgs: Arc<Vec<_>> = Arc::new(per_sp.iter().map(|m| (m.sp_id, m.clone())).collect());
+
+        let (ok, lagging) = self
+            .fan_out_all(move |sp| {
+                let msgs = msgs.clone();
+                async move { sp.deprovision(for_sp(&msgs, sp.sp_id())).await }
+            })
+            .await;
+        Ok(AccountDeletion {
+            per_sp,
+            deleted: ok.into_iter().map(|(id, ())| id).collect(),
+            lagging,
+        })
+    }
+
+    /// Wrap the session's `cid` plaintext under `device_key` so this device
+    /// can unlock until `expires_at` without Π2. Nothing is sent to the SPs;
+    /// store the result on the device.
+    pub fn enroll_device(
+        &self,
+        session: &Session,
+        device_key: &[u8; 32],
+        device_id: [u8; 16],
+        expires_at: u64,
+    ) -> Result<DeviceEnrollment, ClientError> {
+        Ok(enroll_device(
+            &self.config.uid,
+            &session.cid_pt,
+            device_key,
+            device_id,
+            expires_at,
+            &mut OsRng,
+        )?)
+    }
+
+    /// Publish a revocation list naming every device in `revoked`; at least
+    /// `tsp` SPs must store it. `issued_at` must exceed that of the last list.
+    pub async fn revoke_devices(
+        &self,
+        session: &Session,
+        revoked: Vec<[u8; 16]>,
+        issued_at: u64,
+    ) -> Result<DeviceRevocationList, ClientError> {
+        let list = sign_device_revocations(
+            &session.cid_pt.signing_key,
+            &self.config.uid,
+            issued_at,
+            revoked,
+        );
+        let shared = Arc::new(list.clone());
+
+        let (ok, failures) = self
+            .fan_out_all(move |sp| {
+                let list = shared.clone();
+                async move { sp.put_device_revocations(&list).await }
+            })
+            .await;
+        require(self.config.tsp, ok, failures)?;
+        Ok(list)
+    }
+
+    /// The newest revocation list any SP holds that verifies under `sig_pk`,
+    /// or `None` if no SP has one. Fails only if no SP answers.
+    pub async fn device_revocations(
+        &self,
+        sig_pk: &[u8; 32],
+    ) -> Result<Option<DeviceRevocationList>, ClientError> {
+        let uid = Arc::new(self.config.uid.clone());
+        let (ok, failures) = self
+            .fan_out_all(move |sp| {
+                let uid = uid.clone();
+                async move {
+                    match sp.get_device_revocations(&uid).await {
+                        Ok(list) => Ok(Some(list)),
+                        Err(ClientError::Sp { status: 404, .. }) => Ok(None),
+                        Err(e) => Err(e),
+                    }
+                }
+            })
+            .await;
+        let lists = require(1, ok, failures)?;
+        let uid_b64 = b64_encode(&self.config.uid);
+        Ok(lists
+            .into_iter()
+            .filter_map(|(_, list)| list)
+            .filter(|l| l.uid_b64 == uid_b64 && verify_device_revocations(l, sig_pk).is_ok())
+            .max_by_key(|l| l.issued_at))
+    }
+
+    /// Π5 with confirmation. Check `old_password` by logging in, send each SP
+    /// its update and collect the signed acknowledgements, then log in with
+    /// `new_password` against the new commitments. Only then are the SPs told
+    /// to drop their old shares.
+    ///
+    /// If fewer than `tsp` SPs acknowledge, or the new password does not open
+    /// `cid_new`, every SP that took the update is told to restore its old
+    /// shares and [`ClientError::RolledBack`] is returned. Acknowledgements
+    /// are checked against `config.sp_keys`, which must be set.
+    pub async fn change_password(
+        &self,
+        old_password: &[u8],
+        new_password: &[u8],
+        timestamp: u64,
+    ) -> Result<PasswordChange, ClientError> {
+        self.sp_keys()?;
+        let session = self.login(old_password).await?;
+        let update =
+            self.build_password_update(&session.cid_pt, new_password, &self.config.kdf, timestamp)?;
+        self.confirm_password_update(&session.cid_pt.signing_key, update, new_password, timestamp)
+            .await
+    }
+
+    /// Move the account to password hardening `kdf`, e.g. from an account
+    /// made without it to Argon2id: Π5 with confirmation, as
+    /// [`UpspaClient::change_password`], keeping `password`. On success the
+    /// SPs only answer to the hardened password, so `kdf` (also returned in
+    /// `update.kdf`) must replace `config.kdf` for every later login. The
+    /// account stays on `config.suite`.
+    pub async fn migrate_kdf(
+        &self,
+        password: &[u8],
+        kdf: &PasswordKdf,
+        timestamp: u64,
+    ) -> Result<PasswordChange, ClientError> {
+        self.sp_keys()?;
+        let session = self.login(password).await?;
+        let update = self.build_password_update(&session.cid_pt, password, kdf, timestamp)?;
+        self.confirm_password_update(&session.cid_pt.signing_key, update, password, timestamp)
+            .await
+    }
+
+    /// Escrow a recovery copy of `cid` at every SP and return the codes that
+    /// open it; at least `tsp` SPs must store it. Any `threshold` of the
+    /// `n_codes` codes later stand in for the password in
+    /// [`UpspaClient::recover`]. A new kit replaces the old one, and rotating
+    /// the signing key voids it.
+    pub async fn enable_recovery(
+        &self,
+        session: &Session,
+        n_codes: usize,
+        threshold: usize,
+        timestamp: u64,
+    ) -> Result<RecoveryKit, ClientError> {
+        let kit = client_recovery_setup(
+            &self.config.uid,
+            &session.state_key,
+            &session.cid,
+            &self.sp_ids(),
+            n_codes,
+            threshold,
+            timestamp,
+            &mut OsRng,
+        )?;
+        let msgs: Arc<Vec<_>> = Arc::new(kit.per_sp.iter().map(|m| (m.sp_id, m.clone())).collect());
+
+        let (ok, failures) = self
+            .fan_out_all(move |sp| {
+                let msgs = msgs.clone();
+                async move { sp.recovery_escrow(for_sp(&msgs, sp.sp_id())).await }
+            })
+            .await;
+        require(self.config.tsp, ok, failures)?;
+        Ok(kit)
+    }
+
+    /// Set `new_password` without the old one: open the escrowed copy of
+    /// `cid` with `codes`, then run Π5 with confirmation as
+    /// [`UpspaClient::change_password`] does. Fails with
+    /// [`UpspaError::Aead`] if no stored copy opens under `codes`.
+    pub async fn recover(
+        &self,
+        codes: &[RecoveryCode],
+        new_password: &[u8],
+        timestamp: u64,
+    ) -> Result<PasswordChange, ClientError> {
+        self.sp_keys()?;
+        let uid = Arc::new(self.config.uid.clone());
+        let (ok, failures) = self
+            .fan_out_all(move |sp| {
+                let uid = uid.clone();
+                async move { sp.get_recovery(&uid).await }
+            })
+            .await;
+        let copies = require(1, ok, failures)?;
+
+        let mut cid_pt = None;
+        for (_, recovery_cid) in &copies {
+            if let Ok(pt) = recover_cid_plaintext(&self.config.uid, recovery_cid, codes) {
+                cid_pt = Some(pt);
+                break;
+            }
+        }
+        let cid_pt = cid_pt.ok_or(UpspaError::Aead)?;
+
+        let update =
+            self.build_password_update(&cid_pt, new_password, &self.config.kdf, timestamp)?;
+        self.confirm_password_update(&cid_pt.signing_key, update, new_password, timestamp)
+            .await
+    }
+
+    /// Send `update`, check the acknowledgements and a login under
+    /// `new_password`, then commit; roll back on failure. Every SP that took
+    /// the update is committed or rolled back, acknowledged or not.
+    async fn confirm_password_update(
+        &self,
+        signing_key: &SigningKey,
+        update: PasswordUpdateOutput,
+        new_password: &[u8],
+        timestamp: u64,
+    ) -> Result<PasswordChange, ClientError> {
+        let msgs: Arc<Vec<_>> =
+            Arc::new(update.per_sp.iter().map(|m| (m.sp_id, m.clone())).collect());
+
+        let (applied, mut lagging) = self
+            .fan_out_all(move |sp| {
+                let msgs = msgs.clone();
+                async move { sp.password_update(for_sp(&msgs, sp.sp_id())).await }
+            })
+            .await;
+        let applied_ids: Vec<u32> = applied.iter().map(|(id, _)| *id).collect();
+        let mut acks = Vec::with_capacity(applied.len());
+        for (sp_id, ack) in applied {
+            match self.check_ack(&update, timestamp, sp_id, &ack) {
+                Ok(()) => acks.push(ack),
+                Err(e) => lagging.push((sp_id, e)),
+            }
+        }
+        lagging.sort_by_key(|(id, _)| *id);
+
+        if acks.len() < self.config.tsp {
+            let reason = ClientError::Quorum {
+                needed: self.config.tsp,
+                got: acks.len(),
+                failures: lagging,
+            };
+            return Err(self
+                .roll_back(signing_key, &applied_ids, timestamp, reason)
+                .await);
+        }
+
+        let verified = match self
+            .login_with(
+                new_password,
+                Some(&update.commitments),
+                &update.kdf,
+                update.suite,
+            )
+            .await
+        {
+            Ok(s) if s.cid == update.cid_new => Ok(s),
+            Ok(_) => Err(UpspaError::Aead.into()),
+            Err(e) => Err(e),
+        };
+        let new_session = match verified {
+            Ok(s) => s,
+            Err(reason) => {
+                return Err(self
+                    .roll_back(signing_key, &applied_ids, timestamp, reason)
+                    .await)
+            }
+        };
+
+        let (_, uncommitted) = self
+            .finalize_password_update(
+                signing_key,
+                &applied_ids,
+                timestamp,
+                PasswordUpdateAction::Commit,
+            )
+            .await;
+        Ok(PasswordChange {
+            update,
+            session: new_session,
+            acks,
+            lagging,
+            uncommitted,
+        })
+    }
+
+    /// Build the Π5 messages for `new_password` hardened with `kdf`, under
+    /// `config.suite` and signed in `config.pwd_update_format`.
+    fn build_password_update(
+        &self,
+        cid_pt: &CidPlaintext,
+        new_password: &[u8],
+        kdf: &PasswordKdf,
+        timestamp: u64,
+    ) -> Result<PasswordUpdateOutput, ClientError> {
+        let mut out = client_password_update_from_plaintext(
+            &self.config.uid,
+            cid_pt,
+            &self.sp_ids(),
+            self.config.tsp,
+            new_password,
+            kdf,
+            self.config.suite,
+            timestamp,
+            &mut OsRng,
+        )?;
+        if self.config.pwd_update_format != SigFormat::Legacy {
+            for m in &mut out.per_sp {
+                *m = sign_password_update(
+                    &cid_pt.signing_key,
+                    &self.config.uid,
+                    m.sp_id,
+                    timestamp,
+                    &out.cid_new,
+                    m.k_i_new.clone(),
+                    self.config.pwd_update_format,
+                );
+            }
+        }
+        Ok(out)
+    }
+
+    fn check_ack(
+        &self,
+        update: &PasswordUpdateOutput,
+        timestamp: u64,
+        sp_id: u32,
+        ack: &PasswordUpdateAck,
+    ) -> Result<(), ClientError> {
+        let sp_keys = self.sp_keys()?;
+        let invalid = ClientError::InvalidResponse {
+            sp_id,
+            field: "ack",
+        };
+        let (Some(sp_pk), Some(k_i_commit)) = (
+            sp_keys.iter().find(|(id, _)| *id == sp_id),
+            update.commitments.iter().find(|(id, _)| *id == sp_id),
+        ) else {
+            return Err(invalid);
+        };
+        verify_password_update_ack(
+            &sp_pk.1,
+            &self.config.uid,
+            &update.cid_new,
+            &k_i_commit.1,
+            timestamp,
+            sp_id,
+            ack,
+        )
+        .map_err(|_| invalid)
+    }
+
+    /// Ask `sp_ids` to restore the shares replaced at `timestamp`, and wrap
+    /// `reason` with the SPs that did not.
+    async fn roll_back(
+        &self,
+        signing_key: &SigningKey,
+        sp_ids: &[u32],
+        timestamp: u64,
+        reason: ClientError,
+    ) -> ClientError {
+        let (_, not_restored) = self
+            .finalize_password_update(
+                signing_key,
+                sp_ids,
+                timestamp,
+                PasswordUpdateAction::Rollback,
+            )
+            .await;
+        ClientError::RolledBack {
+            reason: Box::new(reason),
+            not_restored,
+        }
+    }
+
+    async fn finalize_password_update(
+        &self,
+        signing_key: &SigningKey,
+        sp_ids: &[u32],
+        timestamp: u64,
+        action: PasswordUpdateAction,
+    ) -> SpResults<()> {
+        let msgs: Arc<Vec<_>> = Arc::new(
+            sp_ids
+                .iter()
+                .map(|id| {
+                    let msg = sign_password_update_finalize(
+                        signing_key,
+                        &self.config.uid,
+                        *id,
+                        timestamp,
+                        action,
+                    );
+                    (*id, msg)
+                })
+                .collect(),
+        );
+        self.fan_out(sp_ids, move |sp| {
+            let msgs = msgs.clone();
+            async move { sp.password_update_finalize(for_sp(&msgs, sp.sp_id())).await }
+        })
+        .await
+    }
+
+    /// `config.sp_keys`; Π5 with confirmation cannot check acknowledgements
+    /// without them.
+    fn sp_keys(&self) -> Result<&[(u32, [u8; 32])], ClientError> {
+        self.config.sp_keys.as_deref().ok_or_else(|| {
+            ClientError::Config("sp_keys are required to confirm a password update".into())
+        })
+    }
+
+    fn sp_ids(&self) -> Vec<u32> {
+        self.sps.iter().map(|sp| sp.sp_id()).collect()
+    }
+
+    fn try_login(
+        &self,
+        password: &[u8],
+        state: &ToprfClientState,
+        commitments: Option<&[(u32, [u8; 32])]>,
+        got: &[(u32, (CipherId, ToprfPartial))],
+    ) -> Option<Session> {
+        if got.len() < self.config.tsp {
+            return None;
+        }
+        let partials: Vec<ToprfPartial> = got.iter().map(|(_, (_, p))| p.clone()).collect();
+        let mut cids: Vec<&CipherId> = Vec::new();
+        for (_, (cid, _)) in got {
+            if !cids.contains(&cid) {
+                cids.push(cid);
+            }
+        }
+
+        cids.into_iter().find_map(|cid| {
+            let (out, cid_pt) = client_login_robust(
+                &self.config.uid,
+                password,
+                state,
+                &partials,
+                self.config.tsp,
+                commitments,
+                cid,
+            )
+            .ok()?;
+            Some(Session {
+                state_key: out.state_key,
+                cid: cid.clone(),
+                cid_pt,
+                inconsistent: out.inconsistent,
+            })
+        })
+    }
+
+    /// `K0`, the `c_j` of at least `tsp` SPs, and every SP's `SUid` for `lsj`.
+    async fn fetch_records(
+        &self,
+        cid_pt: &CidPlaintext,
+        lsj: &[u8],
+    ) -> Result<(SecretBytes<32>, SpRecords, Vec<(u32, [u8; 32])>), ClientError> {
+        let per_sp: Vec<(u32, [u8; 32])> = self
+            .sp_ids()
+            .into_iter()
+            .map(|id| (id, hash_suid(&cid_pt.rsp, lsj, id)))
+            .collect();
+        let suids = Arc::new(per_sp.clone());
+
+        let (ok, failures) = self
+            .fan_out_all(move |sp| {
+                let suids = suids.clone();
+                async move { sp.record_get(for_sp(&suids, sp.sp_id())).await }
+            })
+            .await;
+        let cjs = require(self.config.tsp, ok, failures)?;
+        Ok((cid_pt.k0.clone(), cjs, per_sp))
+    }
+
+    /// Write `cj` to the SPs in `sp_ids`.
+    async fn write_records(
+        &self,
+        sp_ids: &[u32],
+        suids: &[(u32, [u8; 32])],
+        cj: &CipherSp,
+    ) -> SpResults<()> {
+        let (suids, cj) = (Arc::new(suids.to_vec()), Arc::new(cj.clone()));
+        self.fan_out(sp_ids, move |sp| {
+            let (suids, cj) = (suids.clone(), cj.clone());
+            async move { sp.record_update(for_sp(&suids, sp.sp_id()), &cj).await }
+        })
+        .await
+    }
+
+    /// One task per SP in `sp_ids`, with the SP each task id belongs to.
+    fn spawn_all<T, F, Fut>(&self, sp_ids: &[u32], f: F) -> SpTasks<T>
+    where
+        T: Send + 'static,
+        F: Fn(Arc<dyn SpTransport>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<T, ClientError>> + Send + 'static,
+    {
+        let f = Arc::new(f);
+        let (mut set, mut ids) = (JoinSet::new(), Vec::new());
+        for sp in self.sps.iter().filter(|sp| sp_ids.contains(&sp.sp_id())) {
+            let (sp, f) = (sp.clone(), f.clone());
+            let (retries, backoff) = (self.config.retries, self.config.retry_backoff);
+            let sp_id = sp.sp_id();
+            let handle = set.spawn(async move {
+                let mut attempt = 0;
+                loop {
+                    match f(sp.clone()).await {
+                        Err(e) if e.is_retryable() && attempt < retries => {
+                            attempt += 1;
+                            tokio::time::sleep(backoff * attempt).await;
+                        }
+                        r => return (sp.sp_id(), r),
+                    }
+                }
+            });
+            ids.push((handle.id(), sp_id));
+        }
+        (set, ids)
+    }
+
+    /// Send to every SP and wait for all of them.
+    async fn fan_out_all<T, F, Fut>(&self, f: F) -> SpResults<T>
+    where
+        T: Send + 'static,
+        F: Fn(Arc<dyn SpTransport>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<T, ClientError>> + Send + 'static,
+    {
+        self.fan_out(&self.sp_ids(), f).await
+    }
+
+    /// Send to the SPs in `sp_ids` and wait for all of them.
+    async fn fan_out<T, F, Fut>(&self, sp_ids: &[u32], f: F) -> SpResults<T>
+    where
+        T: Send + 'static,
+        F: Fn(Arc<dyn SpTransport>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<T, ClientError>> + Send + 'static,
+    {
+        let (mut set, ids) = self.spawn_all(sp_ids, f);
+        let (mut ok, mut failures) = (Vec::new(), Vec::new());
+        while let Some(joined) = set.join_next().await {
+            match joined_result(joined, &ids) {
+                (id, Ok(v)) => ok.push((id, v)),
+                (id, Err(e)) => failures.push((id, e)),
+            }
+        }
+        ok.sort_by_key(|(id, _)| *id);
+        failures.sort_by_key(|(id, _)| *id);
+        (ok, failures)
+    }
+
+    /// Send to every SP and stop as soon as `done` accepts the successes
+    /// received so far; outstanding requests are cancelled.
+    async fn fan_out_until<T, R, F, Fut>(
+        &self,
+        f: F,
+        mut done: impl FnMut(&[(u32, T)]) -> Option<R>,
+    ) -> Result<R, ClientError>
+    where
+        T: Send + 'static,
+        F: Fn(Arc<dyn SpTransport>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<T, ClientError>> + Send + 'static,
+    {
+        let (mut set, ids) = self.spawn_all(&self.sp_ids(), f);
+        let (mut ok, mut failures) = (Vec::new(), Vec::new());
+        while let Some(joined) = set.join_next().await {
+            match joined_result(joined, &ids) {
+                (id, Ok(v)) => {
+                    ok.push((id, v));
+                    if let Some(r) = done(&ok) {
+                        return Ok(r);
+                    }
+                }
+                (id, Err(e)) => failures.push((id, e)),
+            }
+        }
+        failures.sort_by_key(|(id, _)| *id);
+        Err(ClientError::Quorum {
+            needed: self.config.tsp,
+            got: ok.len(),
+            failures,
+        })
+    }
+}
+
+/// A finished SP task's result. A panic in the task is re-raised; a task
+/// cancelled by the runtime counts as a transport failure of its SP.
+fn joined_result<T>(
+    joined: Result<(u32, Result<T, ClientError>), JoinError>,
+    ids: &[(task::Id, u32)],
+) -> (u32, Result<T, ClientError>) {
+    match joined {
+        Ok(r) => r,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(e) => {
+            let sp_id = ids
+                .iter()
+                .find(|(id, _)| *id == e.id())
+                .expect("every task is spawned by spawn_all")
+                .1;
+            let message = "request cancelled".to_string();
+            (sp_id, Err(ClientError::Transport { sp_id, message }))
+        }
+    }
+}
+
+fn for_sp<T>(items: &[(u32, T)], sp_id: u32) -> &T {
+    &items
+        .iter()
+        .find(|(id, _)| *id == sp_id)
+        .expect("one payload per configured SP")
+        .1
+}
+
+fn require<T>(
+    needed: usize,
+    ok: Vec<(u32, T)>,
+    failures: Vec<(u32, ClientError)>,
+) -> Result<Vec<(u32, T)>, ClientError> {
+    if ok.len() < needed {
+        return Err(ClientError::Quorum {
+            needed,
+            got: ok.len(),
+            failures,
+        });
+    }
+    Ok(ok)
+}
@@ -0,0 +1,117 @@
+use std::time::Duration;
+
+use upspa_core::kdf::PasswordKdf;
+use upspa_core::protocol::password_update::SigFormat;
+use upspa_core::suite::OprfSuite;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpEndpoint {
+    pub sp_id: u32,
+    /// e.g. `http://127.0.0.1:8080`, without the `/v1` prefix.
+    pub base_url: String,
+}
+
+impl SpEndpoint {
+    pub fn new(sp_id: u32, base_url: impl Into<String>) -> Self {
+        Self {
+            sp_id,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ClientConfig {
+    pub uid: Vec<u8>,
+    pub sps: Vec<SpEndpoint>,
+    pub tsp: usize,
+    /// How many SPs must hold the newest `c_j` before Π4 uses it. Defaults
+    /// to `tsp`.
+    pub record_quorum: usize,
+    /// Per-request timeout, covering connect and body.
+    pub request_timeout: Duration,
+    /// Extra attempts after a transport error, timeout or 5xx.
+    pub retries: u32,
+    /// Delay before retry `n` is `n * retry_backoff`.
+    pub retry_backoff: Duration,
+    /// Setup-time commitments `K_i`; when set, Π2 partials must carry valid
+    /// DLEQ proofs against them.
+    pub commitments: Option<Vec<(u32, [u8; 32])>>,
+    /// Each SP's Ed25519 key. Π5 acknowledgements must verify against it;
+    /// [`crate::UpspaClient::change_password`] and the other confirmed
+    /// updates fail without it.
+    pub sp_keys: Option<Vec<(u32, [u8; 32])>>,
+    /// Layout Π5 requests are signed in. Defaults to
+    /// [`SigFormat::Legacy`], which the Rust and Go SPs both accept.
+    pub pwd_update_format: SigFormat,
+    /// Password hardening from [`upspa_core::protocol::setup::SetupOutput`];
+    /// defaults to none, for accounts made without it.
+    pub kdf: PasswordKdf,
+    /// OPRF suite from [`upspa_core::protocol::setup::SetupOutput`];
+    /// defaults to BLAKE3, for accounts made before the suite was selectable.
+    pub suite: OprfSuite,
+}
+
+impl ClientConfig {
+    pub fn new(uid: impl Into<Vec<u8>>, sps: Vec<SpEndpoint>, tsp: usize) -> Self {
+        Self {
+            uid: uid.into(),
+            sps,
+            tsp,
+            record_quorum: tsp,
+            request_timeout: Duration::from_secs(10),
+            retries: 2,
+            retry_backoff: Duration::from_millis(200),
+            commitments: None,
+            sp_keys: None,
+            pwd_update_format: SigFormat::Legacy,
+            kdf: PasswordKdf::None,
+            suite: OprfSuite::Blake3,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    pub fn with_retries(mut self, retries: u32, backoff: Duration) -> Self {
+        self.retries = retries;
+        self.retry_backoff = backoff;
+        self
+    }
+
+    pub fn with_record_quorum(mut self, quorum: usize) -> Self {
+        self.record_quorum = quorum;
+        self
+    }
+
+    pub fn with_commitments(mut self, commitments: Vec<(u32, [u8; 32])>) -> Self {
+        self.commitments = Some(commitments);
+        self
+    }
+
+    pub fn with_sp_keys(mut self, sp_keys: Vec<(u32, [u8; 32])>) -> Self {
+        self.sp_keys = Some(sp_keys);
+        self
+    }
+
+    pub fn with_pwd_update_format(mut self, format: SigFormat) -> Self {
+        self.pwd_update_format = format;
+        self
+    }
+
+    pub fn with_kdf(mut self, kdf: PasswordKdf) -> Self {
+        self.kdf = kdf;
+        self
+    }
+
+    pub fn with_suite(mut self, suite: OprfSuite) -> Self {
+        self.suite = suite;
+        self
+    }
+
+    pub fn sp_ids(&self) -> Vec<u32> {
+        self.sps.iter().map(|sp| sp.sp_id).collect()
+    }
+}
@@ -0,0 +1,15 @@
+use upspa_core::protocol::deprovision::DeprovisionSpMessage;
+
+use crate::error::ClientError;
+
+/// Result of [`crate::UpspaClient::delete_account`].
+#[derive(Clone, Debug)]
+pub struct AccountDeletion {
+    /// The signed request for each SP; resend an entry to an SP that missed
+    /// it.
+    pub per_sp: Vec<DeprovisionSpMessage>,
+    /// SPs that dropped the account, by sp_id.
+    pub deleted: Vec<u32>,
+    /// SPs that still hold the account.
+    pub lagging: Vec<(u32, ClientError)>,
+}
@@ -0,0 +1,70 @@
+use upspa_core::UpspaError;
+
+#[derive(Clone, Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error(transparent)]
+    Core(#[from] UpspaError),
+
+    #[error("invalid client config: {0}")]
+    Config(String),
+
+    #[error("sp {sp_id}: HTTP {status} {code}: {message}")]
+    Sp {
+        sp_id: u32,
+        status: u16,
+        code: String,
+        message: String,
+    },
+
+    #[error("sp {sp_id}: transport error: {message}")]
+    Transport { sp_id: u32, message: String },
+
+    #[error("sp {sp_id}: request timed out")]
+    Timeout { sp_id: u32 },
+
+    #[error("sp {sp_id}: malformed response field {field}")]
+    InvalidResponse { sp_id: u32, field: &'static str },
+
+    #[error("LS: HTTP {status} {code}: {message}")]
+    Ls {
+        status: u16,
+        code: String,
+        message: String,
+    },
+
+    #[error("LS: transport error: {message}")]
+    LsTransport { message: String },
+
+    /// A previous secret update for this LS was not finished; run
+    /// `UpspaClient::recover_secret_update` first.
+    #[error("secret update unfinished: SPs {stale:?} hold an older record")]
+    UnfinishedUpdate { stale: Vec<u32> },
+
+    /// A Π5 update was undone because of `reason`; the SPs in
+    /// `not_restored` may still hold the new shares.
+    #[error("password update rolled back: {reason}")]
+    RolledBack {
+        reason: Box<ClientError>,
+        not_restored: Vec<(u32, ClientError)>,
+    },
+
+    #[error("only {got} of the {needed} required SPs succeeded")]
+    Quorum {
+        needed: usize,
+        got: usize,
+        failures: Vec<(u32, ClientError)>,
+    },
+}
+
+impl ClientError {
+    /// Whether retrying the same request may succeed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ClientError::Transport { .. }
+            | ClientError::Timeout { .. }
+            | ClientError::LsTransport { .. } => true,
+            ClientError::Sp { status, .. } | ClientError::Ls { status, .. } => *status >= 500,
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,12 @@
+use upspa_core::protocol::key_rotation::KeyRotationOutput;
+
+/// Result of [`crate::UpspaClient::rotate_signing_key`].
+#[derive(Clone, Debug)]
+pub struct KeyRotation {
+    pub rotation: KeyRotationOutput,
+    /// SPs that took the rotation and dropped the recovery kit they held,
+    /// by sp_id. If non-empty, the codes from
+    /// [`crate::UpspaClient::enable_recovery`] no longer work; escrow a new
+    /// kit.
+    pub recovery_voided: Vec<u32>,
+}
@@ -0,0 +1,20 @@
+#![forbid(unsafe_code)]
+
+pub mod client;
+pub mod config;
+pub mod deprovision;
+pub mod error;
+pub mod key_rotation;
+pub mod model;
+pub mod password_update;
+pub mod secret_update;
+pub mod transport;
+
+pub use client::{Session, UpspaClient};
+pub use config::{ClientConfig, SpEndpoint};
+pub use deprovision::AccountDeletion;
+pub use error::ClientError;
+pub use key_rotation::KeyRotation;
+pub use password_update::PasswordChange;
+pub use secret_update::{PendingSecretUpdate, Recovery, SecretUpdatePhase};
+pub use transport::{HttpTransport, LsHttpTransport, LsTransport, MemoryTransport, SpTransport};
@@ -0,0 +1,19 @@
+use upspa_core::protocol::password_update::{PasswordUpdateAck, PasswordUpdateOutput};
+
+use crate::client::Session;
+use crate::error::ClientError;
+
+/// Result of [`crate::UpspaClient::change_password`].
+#[derive(Clone, Debug)]
+pub struct PasswordChange {
+    pub update: PasswordUpdateOutput,
+    /// Login under the new password, checked against `update.commitments`.
+    pub session: Session,
+    /// Acknowledgements of the SPs that took the update, by sp_id.
+    pub acks: Vec<PasswordUpdateAck>,
+    /// SPs that rejected the update or whose acknowledgement did not verify.
+    /// They may still serve the old shares.
+    pub lagging: Vec<(u32, ClientError)>,
+    /// SPs that took the update but did not confirm dropping the old shares.
+    pub uncommitted: Vec<(u32, ClientError)>,
+}
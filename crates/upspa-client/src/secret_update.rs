@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+use upspa_core::protocol::CipherSp;
+use upspa_core::SecretBytes;
+
+/// Where a two-phase secret update stands. Phases only move forward.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "phase", rename_all = "snake_case")]
+pub enum SecretUpdatePhase {
+    /// `c_{j,new}` is computed but nothing has been sent.
+    Staged,
+    /// The LS accepts `vinfo_new`; these SPs still hold the old `c_j`.
+    LsCommitted { remaining: Vec<u32> },
+    /// Every SP holds `c_{j,new}`.
+    Done,
+}
+
+/// Resumable state of a Π4 secret update, from
+/// [`crate::UpspaClient::begin_secret_update`].
+///
+/// Persist it after every phase change and pass it back to
+/// [`crate::UpspaClient::finish_secret_update`] after a crash. It holds
+/// `vinfo_prime` and `vinfo_new`, so store it as carefully as the LS
+/// password itself.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PendingSecretUpdate {
+    pub lsj: Vec<u8>,
+    pub suids: Vec<(u32, [u8; 32])>,
+    pub cj_new: CipherSp,
+    pub vinfo_prime: SecretBytes<32>,
+    pub vinfo_new: SecretBytes<32>,
+    pub old_ctr: u64,
+    pub new_ctr: u64,
+    pub phase: SecretUpdatePhase,
+}
+
+/// Result of [`crate::UpspaClient::recover_secret_update`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Recovery {
+    /// All reachable SPs already hold the record with this `ctr`.
+    Consistent { ctr: u64 },
+    /// The newest record was written to `repaired`, after committing its
+    /// `vinfo` at the LS if `ls_committed`.
+    Finalized {
+        ctr: u64,
+        repaired: Vec<u32>,
+        ls_committed: bool,
+    },
+    /// The LS accepts neither the newest nor the previous `vinfo`; the LS
+    /// password has to be reset out of band. Nothing was written.
+    Unresolved { ctr: u64, stale: Vec<u32> },
+}
@@ -0,0 +1,296 @@
+use async_trait::async_trait;
+use reqwest::{Method, RequestBuilder};
+use serde::de::DeserializeOwned;
+use upspa_core::dleq::{DleqProof, DLEQ_PROOF_LEN};
+use upspa_core::protocol::deprovision::DeprovisionSpMessage;
+use upspa_core::protocol::device::DeviceRevocationList;
+use upspa_core::protocol::key_rotation::KeyRotationSpMessage;
+use upspa_core::protocol::password_update::{
+    PasswordUpdateAck, PasswordUpdateFinalizeSpMessage, PasswordUpdateSpMessage,
+};
+use upspa_core::protocol::recovery::RecoverySpMessage;
+use upspa_core::protocol::setup::SetupSpPayload;
+use upspa_core::protocol::{CipherId, CipherSp};
+use upspa_core::toprf::ToprfPartial;
+use upspa_core::types::{b64_decode_array, b64_encode};
+
+use crate::config::SpEndpoint;
+use crate::error::ClientError;
+use crate::model::{
+    DeprovisionRequest, DeviceRevocationBody, ErrorResponse, KeyRotationRequest,
+    PasswordUpdateFinalizeRequest, PasswordUpdateRequest, PasswordUpdateResponse,
+    RecordCreateRequest, RecordResponse, RecordUpdateRequest, RecoveryRequest, RecoveryResponse,
+    SetupRequest, SetupResponse, ToprfEvalRequest, ToprfEvalResponse,
+};
+use crate::transport::SpTransport;
+
+/// JSON over HTTP, as described in `docs/apis.md`. Cheap to clone; clones
+/// share the connection pool.
+#[derive(Clone, Debug)]
+pub struct HttpTransport {
+    endpoint: SpEndpoint,
+    http: reqwest::Client,
+}
+
+impl HttpTransport {
+    pub fn new(endpoint: SpEndpoint, http: reqwest::Client) -> Self {
+        Self { endpoint, http }
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.http
+            .request(method, format!("{}{path}", self.endpoint.base_url))
+    }
+
+    fn invalid(&self, field: &'static str) -> impl FnOnce(upspa_core::UpspaError) -> ClientError {
+        let sp_id = self.sp_id();
+        move |_| ClientError::InvalidResponse { sp_id, field }
+    }
+
+    async fn json<T: DeserializeOwned>(&self, req: RequestBuilder) -> Result<T, ClientError> {
+        let body = self.send(req).await?;
+        serde_json::from_slice(&body).map_err(|_| ClientError::InvalidResponse {
+            sp_id: self.sp_id(),
+            field: "body",
+        })
+    }
+
+    async fn send(&self, req: RequestBuilder) -> Result<Vec<u8>, ClientError> {
+        let sp_id = self.sp_id();
+        let transport = |e: reqwest::Error| {
+            if e.is_timeout() {
+                ClientError::Timeout { sp_id }
+            } else {
+                ClientError::Transport {
+                    sp_id,
+                    message: e.to_string(),
+                }
+            }
+        };
+
+        let resp = req.send().await.map_err(transport)?;
+        let status = resp.status();
+        let body = resp.bytes().await.map_err(transport)?;
+        if status.is_success() {
+            return Ok(body.to_vec());
+        }
+
+        let (code, message) = match serde_json::from_slice::<ErrorResponse>(&body) {
+            Ok(e) => (e.error.code, e.error.message),
+            Err(_) => (
+                "unknown".to_string(),
+                String::from_utf8_lossy(&body).into_owned(),
+            ),
+        };
+        Err(ClientError::Sp {
+            sp_id,
+            status: status.as_u16(),
+            code,
+            message,
+        })
+    }
+}
+
+#[async_trait]
+impl SpTransport for HttpTransport {
+    fn sp_id(&self) -> u32 {
+        self.endpoint.sp_id
+    }
+
+    async fn setup(&self, payload: &SetupSpPayload) -> Result<(), ClientError> {
+        let req = SetupRequest {
+            uid_b64: b64_encode(&payload.uid),
+            sig_pk_b64: b64_encode(&payload.sig_pk),
+            cid: payload.cid.to_b64(),
+            k_i_b64: b64_encode(payload.k_i.expose()),
+        };
+        self.send(self.request(Method::POST, "/v1/setup").json(&req))
+            .await
+            .map(drop)
+    }
+
+    async fn get_setup(&self, uid: &[u8]) -> Result<CipherId, ClientError> {
+        let path = format!("/v1/setup/{}", b64_encode(uid));
+        let resp: SetupResponse = self.json(self.request(Method::GET, &path)).await?;
+        CipherId::from_b64(&resp.cid).map_err(self.invalid("cid"))
+    }
+
+    async fn toprf_eval(
+        &self,
+        uid: &[u8],
+        blinded: &[u8; 32],
+    ) -> Result<ToprfPartial, ClientError> {
+        let req = ToprfEvalRequest {
+            uid_b64: b64_encode(uid),
+            blinded_b64: b64_encode(blinded),
+        };
+        let resp: ToprfEvalResponse = self
+            .json(self.request(Method::POST, "/v1/toprf/eval").json(&req))
+            .await?;
+        if resp.sp_id != self.sp_id() {
+            return Err(ClientError::InvalidResponse {
+                sp_id: self.sp_id(),
+                field: "sp_id",
+            });
+        }
+        let y = b64_decode_array::<32>(&resp.y_b64).map_err(self.invalid("y"))?;
+        let proof = match &resp.proof_b64 {
+            Some(p) => {
+                let bytes = b64_decode_array::<DLEQ_PROOF_LEN>(p).map_err(self.invalid("proof"))?;
+                Some(DleqProof::from_bytes(&bytes))
+            }
+            None => None,
+        };
+        Ok(ToprfPartial {
+            id: resp.sp_id,
+            y,
+            proof,
+        })
+    }
+
+    async fn record_create(&self, suid: &[u8; 32], cj: &CipherSp) -> Result<(), ClientError> {
+        let req = RecordCreateRequest {
+            suid_b64: b64_encode(suid),
+            cj: cj.to_b64(),
+        };
+        self.send(self.request(Method::POST, "/v1/records").json(&req))
+            .await
+            .map(drop)
+    }
+
+    async fn record_get(&self, suid: &[u8; 32]) -> Result<CipherSp, ClientError> {
+        let path = format!("/v1/records/{}", b64_encode(suid));
+        let resp: RecordResponse = self.json(self.request(Method::GET, &path)).await?;
+        CipherSp::from_b64(&resp.cj).map_err(self.invalid("cj"))
+    }
+
+    async fn record_update(&self, suid: &[u8; 32], cj: &CipherSp) -> Result<(), ClientError> {
+        let path = format!("/v1/records/{}", b64_encode(suid));
+        let req = RecordUpdateRequest { cj: cj.to_b64() };
+        self.send(self.request(Method::PUT, &path).json(&req))
+            .await
+            .map(drop)
+    }
+
+    async fn password_update(
+        &self,
+        msg: &PasswordUpdateSpMessage,
+    ) -> Result<PasswordUpdateAck, ClientError> {
+        let req = PasswordUpdateRequest {
+            uid_b64: msg.uid_b64.clone(),
+            sp_id: msg.sp_id,
+            timestamp: msg.timestamp,
+            sig_b64: b64_encode(&msg.sig),
+            cid_new: msg.cid_new.to_b64(),
+            k_i_new_b64: b64_encode(msg.k_i_new.expose()),
+            sig_format: msg.format,
+        };
+        let resp: PasswordUpdateResponse = self
+            .json(self.request(Method::POST, "/v1/password-update").json(&req))
+            .await?;
+        Ok(PasswordUpdateAck {
+            sp_id: resp.sp_id,
+            timestamp: resp.timestamp,
+            sig: b64_decode_array::<64>(&resp.sig_b64).map_err(self.invalid("sig"))?,
+        })
+    }
+
+    async fn key_rotation(&self, msg: &KeyRotationSpMessage) -> Result<(), ClientError> {
+        let req = KeyRotationRequest {
+            uid_b64: msg.uid_b64.clone(),
+            sp_id: msg.sp_id,
+            timestamp: msg.timestamp,
+            sig_b64: b64_encode(&msg.sig),
+            sig_pk_new_b64: b64_encode(&msg.sig_pk_new),
+            cid_new: msg.cid_new.to_b64(),
+        };
+        self.send(self.request(Method::POST, "/v1/key-rotation").json(&req))
+            .await
+            .map(drop)
+    }
+
+    async fn password_update_finalize(
+        &self,
+        msg: &PasswordUpdateFinalizeSpMessage,
+    ) -> Result<(), ClientError> {
+        let req = PasswordUpdateFinalizeRequest {
+            uid_b64: msg.uid_b64.clone(),
+            sp_id: msg.sp_id,
+            timestamp: msg.timestamp,
+            action: msg.action,
+            sig_b64: b64_encode(&msg.sig),
+        };
+        self.send(
+            self.request(Method::POST, "/v1/password-update/finalize")
+                .json(&req),
+        )
+        .await
+        .map(drop)
+    }
+
+    async fn deprovision(&self, msg: &DeprovisionSpMessage) -> Result<(), ClientError> {
+        let req = DeprovisionRequest {
+            uid_b64: msg.uid_b64.clone(),
+            sp_id: msg.sp_id,
+            timestamp: msg.timestamp,
+            sig_b64: b64_encode(&msg.sig),
+            suids_b64: msg.suids.iter().map(|s| b64_encode(s)).collect(),
+        };
+        self.send(self.request(Method::POST, "/v1/deprovision").json(&req))
+            .await
+            .map(drop)
+    }
+
+    async fn recovery_escrow(&self, msg: &RecoverySpMessage) -> Result<(), ClientError> {
+        let req = RecoveryRequest {
+            uid_b64: msg.uid_b64.clone(),
+            sp_id: msg.sp_id,
+            timestamp: msg.timestamp,
+            sig_b64: b64_encode(&msg.sig),
+            recovery_cid: msg.recovery_cid.to_b64(),
+        };
+        self.send(self.request(Method::POST, "/v1/recovery").json(&req))
+            .await
+            .map(drop)
+    }
+
+    async fn get_recovery(&self, uid: &[u8]) -> Result<CipherId, ClientError> {
+        let path = format!("/v1/recovery/{}", b64_encode(uid));
+        let resp: RecoveryResponse = self.json(self.request(Method::GET, &path)).await?;
+        CipherId::from_b64(&resp.recovery_cid).map_err(self.invalid("recovery_cid"))
+    }
+
+    async fn put_device_revocations(&self, list: &DeviceRevocationList) -> Result<(), ClientError> {
+        let req = DeviceRevocationBody {
+            uid_b64: list.uid_b64.clone(),
+            issued_at: list.issued_at,
+            revoked_b64: list.revoked.iter().map(|id| b64_encode(id)).collect(),
+            sig_b64: b64_encode(&list.sig),
+        };
+        self.send(
+            self.request(Method::POST, "/v1/devices/revocations")
+                .json(&req),
+        )
+        .await
+        .map(drop)
+    }
+
+    async fn get_device_revocations(
+        &self,
+        uid: &[u8],
+    ) -> Result<DeviceRevocationList, ClientError> {
+        let path = format!("/v1/devices/revocations/{}", b64_encode(uid));
+        let resp: DeviceRevocationBody = self.json(self.request(Method::GET, &path)).await?;
+        Ok(DeviceRevocationList {
+            uid_b64: resp.uid_b64,
+            issued_at: resp.issued_at,
+            revoked: resp
+                .revoked_b64
+                .iter()
+                .map(|id| b64_decode_array::<16>(id))
+                .collect::<Result<_, _>>()
+                .map_err(self.invalid("revoked"))?,
+            sig: b64_decode_array::<64>(&resp.sig_b64).map_err(self.invalid("sig"))?,
+        })
+    }
+}
@@ -0,0 +1,95 @@
+use async_trait::async_trait;
+use reqwest::RequestBuilder;
+use upspa_core::types::b64_encode;
+
+use crate::error::ClientError;
+use crate::model::{ErrorResponse, LsChangePasswordRequest, LsLoginRequest};
+
+/// The LS-facing half of Π4: the LS only ever sees `vinfo` values as the
+/// account password.
+#[async_trait]
+pub trait LsTransport: Send + Sync {
+    /// Whether the LS currently accepts `vinfo` for `uid`. A rejected
+    /// credential is `Ok(false)`, not an error.
+    async fn check(&self, uid: &[u8], vinfo: &[u8; 32]) -> Result<bool, ClientError>;
+
+    /// Replace `vinfo_prime` with `vinfo_new` as the account password.
+    async fn change_password(
+        &self,
+        uid: &[u8],
+        vinfo_prime: &[u8; 32],
+        vinfo_new: &[u8; 32],
+    ) -> Result<(), ClientError>;
+}
+
+/// The reference LS API from `docs/apis.md`.
+#[derive(Clone, Debug)]
+pub struct LsHttpTransport {
+    base_url: String,
+    http: reqwest::Client,
+}
+
+impl LsHttpTransport {
+    pub fn new(base_url: impl Into<String>, http: reqwest::Client) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            http,
+        }
+    }
+
+    async fn send(&self, req: RequestBuilder) -> Result<(), ClientError> {
+        let transport = |e: reqwest::Error| ClientError::LsTransport {
+            message: e.to_string(),
+        };
+        let resp = req.send().await.map_err(transport)?;
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(());
+        }
+
+        let body = resp.bytes().await.map_err(transport)?;
+        let (code, message) = match serde_json::from_slice::<ErrorResponse>(&body) {
+            Ok(e) => (e.error.code, e.error.message),
+            Err(_) => (
+                "unknown".to_string(),
+                String::from_utf8_lossy(&body).into_owned(),
+            ),
+        };
+        Err(ClientError::Ls {
+            status: status.as_u16(),
+            code,
+            message,
+        })
+    }
+}
+
+#[async_trait]
+impl LsTransport for LsHttpTransport {
+    async fn check(&self, uid: &[u8], vinfo: &[u8; 32]) -> Result<bool, ClientError> {
+        let req = LsLoginRequest {
+            uid_b64: b64_encode(uid),
+            vinfo_prime_b64: b64_encode(vinfo),
+        };
+        let url = format!("{}/login", self.base_url);
+        match self.send(self.http.post(url).json(&req)).await {
+            Ok(()) => Ok(true),
+            Err(ClientError::Ls { status: 401, .. }) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn change_password(
+        &self,
+        uid: &[u8],
+        vinfo_prime: &[u8; 32],
+        vinfo_new: &[u8; 32],
+    ) -> Result<(), ClientError> {
+        let req = LsChangePasswordRequest {
+            uid_b64: b64_encode(uid),
+            vinfo_prime_b64: b64_encode(vinfo_prime),
+            vinfo_new_b64: b64_encode(vinfo_new),
+        };
+        let url = format!("{}/change-password", self.base_url);
+        self.send(self.http.post(url).json(&req)).await
+    }
+}
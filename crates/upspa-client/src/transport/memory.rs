@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use rand_core::{OsRng, RngCore};
+use upspa_core::protocol::deprovision::{verify_deprovision_for_sp, DeprovisionSpMessage};
+use upspa_core::protocol::device::{verify_device_revocations, DeviceRevocationList};
+use upspa_core::protocol::key_rotation::{verify_key_rotation_for_sp, KeyRotationSpMessage};
+use upspa_core::protocol::password_update::{
+    sign_password_update_ack, verify_password_update_finalize_for_sp,
+    verify_password_update_for_sp, PasswordUpdateAck, PasswordUpdateAction, PasswordUpdateError,
+    PasswordUpdateFinalizeSpMessage, PasswordUpdateSpMessage,
+};
+use upspa_core::protocol::recovery::{verify_recovery_for_sp, RecoverySpMessage};
+use upspa_core::protocol::setup::SetupSpPayload;
+use upspa_core::protocol::{CipherId, CipherSp};
+use upspa_core::sign::SigningKey;
+use upspa_core::toprf::{toprf_server_eval_verifiable, ToprfPartial};
+use upspa_core::types::b64_decode;
+
+use crate::error::ClientError;
+use crate::transport::SpTransport;
+
+#[derive(Clone, PartialEq)]
+struct Setup {
+    sig_pk: [u8; 32],
+    cid: CipherId,
+    k_i: [u8; 32],
+    last_pwd_update_time: u64,
+    /// `cid`/`k_i` replaced by the last Π5 update, until it is finalized.
+    previous: Option<(CipherId, [u8; 32])>,
+    recovery_cid: Option<CipherId>,
+    device_revocations: Option<DeviceRevocationList>,
+}
+
+#[derive(Default)]
+struct State {
+    setups: HashMap<Vec<u8>, Setup>,
+    records: HashMap<[u8; 32], CipherSp>,
+}
+
+/// An SP held in process memory, for tests. Applies the same checks and
+/// returns the same status codes as the reference SP. Clones share state.
+#[derive(Clone)]
+pub struct MemoryTransport {
+    sp_id: u32,
+    signing_key: SigningKey,
+    state: Arc<Mutex<State>>,
+    offline: Arc<AtomicBool>,
+}
+
+impl MemoryTransport {
+    pub fn new(sp_id: u32) -> Self {
+        let mut seed = [0u8; 32];
+        OsRng.fill_bytes(&mut seed);
+        Self {
+            sp_id,
+            signing_key: SigningKey::from_bytes(&seed),
+            state: Arc::default(),
+            offline: Arc::default(),
+        }
+    }
+
+    /// Key this SP signs Π5 acknowledgements with.
+    pub fn public_key(&self) -> [u8; 32] {
+        self.signing_key.verifying_key().to_bytes()
+    }
+
+    /// While offline, every call fails with a retryable
+    /// [`ClientError::Transport`].
+    pub fn set_offline(&self, offline: bool) {
+        self.offline.store(offline, Ordering::SeqCst);
+    }
+
+    /// Run `f` on the stored state, or fail as an unreachable SP would.
+    fn with_state<T>(
+        &self,
+        f: impl FnOnce(&mut State) -> Result<T, ClientError>,
+    ) -> Result<T, ClientError> {
+        if self.offline.load(Ordering::SeqCst) {
+            return Err(ClientError::Transport {
+                sp_id: self.sp_id,
+                message: "offline".into(),
+            });
+        }
+        f(&mut self.state.lock().expect("memory SP state poisoned"))
+    }
+
+    fn reject(&self, status: u16, code: &str, message: impl Into<String>) -> ClientError {
+        ClientError::Sp {
+            sp_id: self.sp_id,
+            status,
+            code: code.into(),
+            message: message.into(),
+        }
+    }
+
+    fn decode_uid(&self, uid_b64: &str) -> Result<Vec<u8>, ClientError> {
+        b64_decode(uid_b64)
+            .map_err(|_| self.reject(400, "invalid_uid", "invalid uid format or length"))
+    }
+
+    fn not_found(&self, what: &str) -> ClientError {
+        self.reject(404, "not_found", format!("{what} not found"))
+    }
+
+    fn rejected_update(&self, e: impl Into<PasswordUpdateError>) -> ClientError {
+        let e = e.into();
+        let (status, code) = match e {
+            PasswordUpdateError::SpIdMismatch { .. } => (400, "sp_id_mismatch"),
+            PasswordUpdateError::StaleTimestamp { .. } => (409, "stale_timestamp"),
+            PasswordUpdateError::Signature => (401, "invalid_signature"),
+            PasswordUpdateError::NotPending { .. } => (409, "no_pending_update"),
+        };
+        self.reject(status, code, e.to_string())
+    }
+}
+
+#[async_trait]
+impl SpTransport for MemoryTransport {
+    fn sp_id(&self) -> u32 {
+        self.sp_id
+    }
+
+    async fn setup(&self, payload: &SetupSpPayload) -> Result<(), ClientError> {
+        let rec = Setup {
+            sig_pk: payload.sig_pk,
+            cid: payload.cid.clone(),
+            k_i: *payload.k_i,
+            last_pwd_update_time: 0,
+            previous: None,
+            recovery_cid: None,
+            device_revocations: None,
+        };
+        self.with_state(|s| match s.setups.get(&payload.uid) {
+            Some(existing) if *existing != rec => Err(self.reject(
+                409,
+                "conflict",
+                "setup already exists with different values",
+            )),
+            _ => {
+                s.setups.insert(payload.uid.clone(), rec);
+                Ok(())
+            }
+        })
+    }
+
+    async fn get_setup(&self, uid: &[u8]) -> Result<CipherId, ClientError> {
+        self.with_state(|s| {
+            s.setups
+                .get(uid)
+                .map(|rec| rec.cid.clone())
+                .ok_or_else(|| self.not_found("setup"))
+        })
+    }
+
+    async fn toprf_eval(
+        &self,
+        uid: &[u8],
+        blinded: &[u8; 32],
+    ) -> Result<ToprfPartial, ClientError> {
+        let k_i = self.with_state(|s| {
+            s.setups
+                .get(uid)
+                .map(|rec| rec.k_i)
+                .ok_or_else(|| self.not_found("setup"))
+        })?;
+        let (y, proof) = toprf_server_eval_verifiable(blinded, &k_i, &mut OsRng)
+            .map_err(|_| self.reject(400, "invalid_blinded", "invalid blinded format or length"))?;
+        Ok(ToprfPartial {
+            id: self.sp_id,
+            y,
+            proof: Some(proof),
+        })
+    }
+
+    async fn record_create(&self, suid: &[u8; 32], cj: &CipherSp) -> Result<(), ClientError> {
+        self.with_state(|s| {
+            if s.records.contains_key(suid) {
+                return Err(self.reject(409, "conflict", "record already exists"));
+            }
+            s.records.insert(*suid, cj.clone());
+            Ok(())
+        })
+    }
+
+    async fn record_get(&self, suid: &[u8; 32]) -> Result<CipherSp, ClientError> {
+        self.with_state(|s| {
+            s.records
+                .get(suid)
+                .cloned()
+                .ok_or_else(|| self.not_found("record"))
+        })
+    }
+
+    async fn record_update(&self, suid: &[u8; 32], cj: &CipherSp) -> Result<(), ClientError> {
+        self.with_state(|s| match s.records.get_mut(suid) {
+            Some(existing) => {
+                *existing = cj.clone();
+                Ok(())
+            }
+            None => Err(self.not_found("record")),
+        })
+    }
+
+    async fn password_update(
+        &self,
+        msg: &PasswordUpdateSpMessage,
+    ) -> Result<PasswordUpdateAck, ClientError> {
+        let uid = self.decode_uid(&msg.uid_b64)?;
+        self.with_state(|s| {
+            let rec = s
+                .setups
+                .get_mut(&uid)
+                .ok_or_else(|| self.not_found("setup"))?;
+            verify_password_update_for_sp(self.sp_id, msg, &rec.sig_pk, rec.last_pwd_update_time)
+                .map_err(|e| self.rejected_update(e))?;
+            let ack = sign_password_update_ack(&self.signing_key, &uid, msg).map_err(|_| {
+                self.reject(400, "invalid_k_i_new", "invalid k_i_new format or length")
+            })?;
+            let old_cid = std::mem::replace(&mut rec.cid, msg.cid_new.clone());
+            let old_k_i = std::mem::replace(&mut rec.k_i, *msg.k_i_new);
+            rec.previous = Some((old_cid, old_k_i));
+            rec.last_pwd_update_time = msg.timestamp;
+            Ok(ack)
+        })
+    }
+
+    async fn key_rotation(&self, msg: &KeyRotationSpMessage) -> Result<(), ClientError> {
+        let uid = self.decode_uid(&msg.uid_b64)?;
+        self.with_state(|s| {
+            let rec = s
+                .setups
+                .get_mut(&uid)
+                .ok_or_else(|| self.not_found("setup"))?;
+            verify_key_rotation_for_sp(self.sp_id, msg, &rec.sig_pk, rec.last_pwd_update_time)
+                .map_err(|e| self.rejected_update(e))?;
+            rec.sig_pk = msg.sig_pk_new;
+            rec.cid = msg.cid_new.clone();
+            rec.previous = None;
+            rec.recovery_cid = None;
+            rec.last_pwd_update_time = msg.timestamp;
+            Ok(())
+        })
+    }
+
+    async fn password_update_finalize(
+        &self,
+        msg: &PasswordUpdateFinalizeSpMessage,
+    ) -> Result<(), ClientError> {
+        let uid = self.decode_uid(&msg.uid_b64)?;
+        self.with_state(|s| {
+            let rec = s
+                .setups
+                .get_mut(&uid)
+                .ok_or_else(|| self.not_found("setup"))?;
+            verify_password_update_finalize_for_sp(
+                self.sp_id,
+                msg,
+                &rec.sig_pk,
+                rec.last_pwd_update_time,
+            )
+            .map_err(|e| self.rejected_update(e))?;
+            let (cid, k_i) = rec.previous.take().ok_or_else(|| {
+                self.reject(
+                    409,
+                    "no_pending_update",
+                    "no pending password update with this timestamp",
+                )
+            })?;
+            if msg.action == PasswordUpdateAction::Rollback {
+                rec.cid = cid;
+                rec.k_i = k_i;
+            }
+            Ok(())
+        })
+    }
+
+    async fn deprovision(&self, msg: &DeprovisionSpMessage) -> Result<(), ClientError> {
+        let uid = self.decode_uid(&msg.uid_b64)?;
+        self.with_state(|s| {
+            let rec = s.setups.get(&uid).ok_or_else(|| self.not_found("setup"))?;
+            verify_deprovision_for_sp(self.sp_id, msg, &rec.sig_pk, rec.last_pwd_update_time)
+                .map_err(|e| self.rejected_update(e))?;
+            for suid in &msg.suids {
+                s.records.remove(suid);
+            }
+            s.setups.remove(&uid);
+            Ok(())
+        })
+    }
+
+    async fn recovery_escrow(&self, msg: &RecoverySpMessage) -> Result<(), ClientError> {
+        let uid = self.decode_uid(&msg.uid_b64)?;
+        self.with_state(|s| {
+            let rec = s
+                .setups
+                .get_mut(&uid)
+                .ok_or_else(|| self.not_found("setup"))?;
+            verify_recovery_for_sp(self.sp_id, msg, &rec.sig_pk, rec.last_pwd_update_time)
+                .map_err(|e| self.rejected_update(e))?;
+            rec.recovery_cid = Some(msg.recovery_cid.clone());
+            rec.last_pwd_update_time = msg.timestamp;
+            Ok(())
+        })
+    }
+
+    async fn get_recovery(&self, uid: &[u8]) -> Result<CipherId, ClientError> {
+        self.with_state(|s| {
+            s.setups
+                .get(uid)
+                .and_then(|rec| rec.recovery_cid.clone())
+                .ok_or_else(|| self.not_found("recovery"))
+        })
+    }
+
+    async fn put_device_revocations(&self, list: &DeviceRevocationList) -> Result<(), ClientError> {
+        let uid = self.decode_uid(&list.uid_b64)?;
+        self.with_state(|s| {
+            let rec = s
+                .setups
+                .get_mut(&uid)
+                .ok_or_else(|| self.not_found("setup"))?;
+            verify_device_revocations(list, &rec.sig_pk).map_err(|e| self.rejected_update(e))?;
+            if let Some(stored) = &rec.device_revocations {
+                if stored.issued_at >= list.issued_at {
+                    return Err(self.rejected_update(PasswordUpdateError::StaleTimestamp {
+                        last: stored.issued_at,
+                        got: list.issued_at,
+                    }));
+                }
+            }
+            rec.device_revocations = Some(list.clone());
+            Ok(())
+        })
+    }
+
+    async fn get_device_revocations(
+        &self,
+        uid: &[u8],
+    ) -> Result<DeviceRevocationList, ClientError> {
+        self.with_state(|s| {
+            s.setups
+                .get(uid)
+                .and_then(|rec| rec.device_revocations.clone())
+                .ok_or_else(|| self.not_found("device revocations"))
+        })
+    }
+}
@@ -0,0 +1,84 @@
+use async_trait::async_trait;
+use upspa_core::protocol::deprovision::DeprovisionSpMessage;
+use upspa_core::protocol::device::DeviceRevocationList;
+use upspa_core::protocol::key_rotation::KeyRotationSpMessage;
+use upspa_core::protocol::password_update::{
+    PasswordUpdateAck, PasswordUpdateFinalizeSpMessage, PasswordUpdateSpMessage,
+};
+use upspa_core::protocol::recovery::RecoverySpMessage;
+use upspa_core::protocol::setup::SetupSpPayload;
+use upspa_core::protocol::{CipherId, CipherSp};
+use upspa_core::toprf::ToprfPartial;
+
+use crate::error::ClientError;
+
+pub mod http;
+pub mod ls;
+pub mod memory;
+
+pub use http::HttpTransport;
+pub use ls::{LsHttpTransport, LsTransport};
+pub use memory::MemoryTransport;
+
+/// One SP as seen by the client: the SP-facing half of Π1–Π5, in terms of
+/// the structs `upspa_core::protocol` produces and consumes.
+///
+/// Implementations report SP-side rejections as [`ClientError::Sp`] with the
+/// HTTP status and error code from `docs/apis.md`, so retry and quorum logic
+/// behaves the same whatever carries the request.
+#[async_trait]
+pub trait SpTransport: Send + Sync {
+    fn sp_id(&self) -> u32;
+
+    /// Π1: store `sig_pk`, `cid` and `k_i`.
+    async fn setup(&self, payload: &SetupSpPayload) -> Result<(), ClientError>;
+
+    /// The `cid` stored for `uid`.
+    async fn get_setup(&self, uid: &[u8]) -> Result<CipherId, ClientError>;
+
+    /// Π2: this SP's partial for `blinded`, with its DLEQ proof if the SP
+    /// sends one.
+    async fn toprf_eval(&self, uid: &[u8], blinded: &[u8; 32])
+        -> Result<ToprfPartial, ClientError>;
+
+    /// Π3: store a new `c_j` under `SUid`.
+    async fn record_create(&self, suid: &[u8; 32], cj: &CipherSp) -> Result<(), ClientError>;
+
+    async fn record_get(&self, suid: &[u8; 32]) -> Result<CipherSp, ClientError>;
+
+    /// Π4: replace an existing `c_j`.
+    async fn record_update(&self, suid: &[u8; 32], cj: &CipherSp) -> Result<(), ClientError>;
+
+    /// Π5: apply a signed `cid`/`k_i` replacement. The SP keeps the old pair
+    /// until the update is finalized.
+    async fn password_update(
+        &self,
+        msg: &PasswordUpdateSpMessage,
+    ) -> Result<PasswordUpdateAck, ClientError>;
+
+    /// Replace `sig_pk` and `cid`, authorized by the current key.
+    async fn key_rotation(&self, msg: &KeyRotationSpMessage) -> Result<(), ClientError>;
+
+    /// Commit or roll back the last Π5 update.
+    async fn password_update_finalize(
+        &self,
+        msg: &PasswordUpdateFinalizeSpMessage,
+    ) -> Result<(), ClientError>;
+
+    /// Delete the listed records and then the user's setup record.
+    async fn deprovision(&self, msg: &DeprovisionSpMessage) -> Result<(), ClientError>;
+
+    /// Store the recovery copy of `cid`, replacing any earlier one.
+    async fn recovery_escrow(&self, msg: &RecoverySpMessage) -> Result<(), ClientError>;
+
+    /// The recovery copy of `cid` stored for `uid`.
+    async fn get_recovery(&self, uid: &[u8]) -> Result<CipherId, ClientError>;
+
+    /// Store `list` if it is newer than the one held.
+    async fn put_device_revocations(&self, list: &DeviceRevocationList) -> Result<(), ClientError>;
+
+    /// The newest device revocation list stored for `uid`, as sent; the
+    /// caller checks its signature.
+    async fn get_device_revocations(&self, uid: &[u8])
+        -> Result<DeviceRevocationList, ClientError>;
+}
@@ -0,0 +1,174 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::extract::Request;
+use axum::http::StatusCode;
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use tokio::net::TcpListener;
+
+use upspa_client::{ClientConfig, ClientError, SpEndpoint, UpspaClient};
+use upspa_core::protocol::LsRecord;
+use upspa_core::UpspaError;
+use upspa_sp::api::router;
+use upspa_sp::store::MemoryStore;
+use upspa_sp::SpService;
+
+fn sp_router(sp_id: u32) -> Router {
+    router(Arc::new(SpService::new(
+        sp_id,
+        Box::new(MemoryStore::new()),
+    )))
+}
+
+async fn serve(app: Router) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{addr}")
+}
+
+/// Accepts connections and never answers.
+async fn hang() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut held = Vec::new();
+        while let Ok((conn, _)) = listener.accept().await {
+            held.push(conn);
+        }
+    });
+    format!("http://{addr}")
+}
+
+async fn closed_port() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    format!("http://{}", listener.local_addr().unwrap())
+}
+
+fn fast(config: ClientConfig) -> ClientConfig {
+    config
+        .with_timeout(Duration::from_millis(500))
+        .with_retries(1, Duration::from_millis(10))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn full_flow_against_live_sps() {
+    let mut sps = Vec::new();
+    for id in [1, 2, 3] {
+        sps.push(SpEndpoint::new(id, serve(sp_router(id)).await + "/"));
+    }
+    let config = fast(ClientConfig::new(b"user123".to_vec(), sps, 2));
+    let client = UpspaClient::new(config.clone()).unwrap();
+
+    let setup = client.setup(b"pw").await.unwrap();
+    let client = UpspaClient::new(config.with_commitments(setup.commitments.clone())).unwrap();
+
+    let session = client.login(b"pw").await.unwrap();
+    assert!(session.inconsistent.is_empty());
+    assert_eq!(session.cid_pt.sp_ids, vec![1, 2, 3]);
+    assert!(matches!(
+        client.login(b"wrong").await,
+        Err(ClientError::Core(UpspaError::NoConsistentPartials))
+    ));
+
+    let record = LsRecord::Password {
+        password: "hunter2".into(),
+    };
+    let reg = client.register(&session, b"LS1", &record).await.unwrap();
+    let auth = client.authenticate(&session, b"LS1").await.unwrap();
+    assert_eq!(auth.vinfo_prime, reg.to_ls.vinfo);
+    assert_eq!(auth.record, record);
+
+    let su = client.secret_update(&session, b"LS1").await.unwrap();
+    assert_eq!(su.vinfo_prime, reg.to_ls.vinfo);
+    let auth = client.authenticate(&session, b"LS1").await.unwrap();
+    assert_eq!((&auth.vinfo_prime, auth.best_ctr), (&su.vinfo_new, 1));
+
+    let pu = client.password_update(&session, b"pw2", 1).await.unwrap();
+    let client =
+        UpspaClient::new(client.config().clone().with_commitments(pu.commitments)).unwrap();
+    assert!(client.login(b"pw").await.is_err());
+    let session = client.login(b"pw2").await.unwrap();
+    let auth = client.authenticate(&session, b"LS1").await.unwrap();
+    assert_eq!(auth.vinfo_prime, su.vinfo_new);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn threshold_survives_down_and_hanging_sps() {
+    let mut sps = Vec::new();
+    for id in [1, 2, 3] {
+        sps.push(SpEndpoint::new(id, serve(sp_router(id)).await));
+    }
+    let client = UpspaClient::new(fast(ClientConfig::new(b"u".to_vec(), sps.clone(), 2))).unwrap();
+    client.setup(b"pw").await.unwrap();
+    let session = client.login(b"pw").await.unwrap();
+    client
+        .register(&session, b"LS1", &LsRecord::None)
+        .await
+        .unwrap();
+
+    // With SP 3 hanging, Π2 returns once SPs 1 and 2 have answered.
+    sps[2].base_url = hang().await;
+    let degraded =
+        UpspaClient::new(fast(ClientConfig::new(b"u".to_vec(), sps.clone(), 2))).unwrap();
+    let started = Instant::now();
+    degraded.login(b"pw").await.unwrap();
+    assert!(started.elapsed() < Duration::from_millis(500));
+
+    // SP 2 refusing connections as well leaves one record, below threshold.
+    sps[1].base_url = closed_port().await;
+    let client = UpspaClient::new(fast(ClientConfig::new(b"u".to_vec(), sps, 2))).unwrap();
+    match client.authenticate(&session, b"LS1").await {
+        Err(ClientError::Quorum {
+            needed: 2,
+            got: 1,
+            failures,
+        }) => {
+            assert_eq!(failures.len(), 2);
+            assert!(matches!(
+                failures[0],
+                (2, ClientError::Transport { sp_id: 2, .. })
+            ));
+            assert!(matches!(
+                failures[1],
+                (3, ClientError::Timeout { sp_id: 3 })
+            ));
+        }
+        other => panic!("expected quorum error, got {other:?}"),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn retries_5xx_but_not_4xx() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    let flaky = sp_router(1).layer(middleware::from_fn(move |req: Request, next: Next| {
+        let counter = counter.clone();
+        async move {
+            if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                return StatusCode::SERVICE_UNAVAILABLE.into_response();
+            }
+            let resp: Response = next.run(req).await;
+            resp
+        }
+    }));
+    let sps = vec![SpEndpoint::new(1, serve(flaky).await)];
+    let client = UpspaClient::new(fast(ClientConfig::new(b"u".to_vec(), sps, 1))).unwrap();
+
+    client.setup(b"pw").await.unwrap();
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    match client.setup(b"pw").await {
+        Err(ClientError::Quorum { failures, .. }) => {
+            assert!(matches!(
+                &failures[..],
+                [(1, ClientError::Sp { status: 409, .. })]
+            ));
+        }
+        other => panic!("expected conflict, got {other:?}"),
+    }
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+}
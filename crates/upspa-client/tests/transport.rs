@@ -0,0 +1,638 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::routing::post;
+use axum::{Json, Router};
+use rand_core::OsRng;
+use serde_json::json;
+use tokio::net::TcpListener;
+
+use upspa_client::{
+    ClientConfig, ClientError, HttpTransport, MemoryTransport, SpEndpoint, SpTransport, UpspaClient,
+};
+use upspa_core::hash::hash_suid;
+use upspa_core::kdf::{Argon2idParams, PasswordKdf};
+use upspa_core::protocol::decrypt_cid;
+use upspa_core::protocol::device::unlock_device;
+use upspa_core::protocol::password_update::{
+    client_password_update, sign_password_update_finalize, PasswordUpdateAction, SigFormat,
+};
+use upspa_core::protocol::setup::client_setup;
+use upspa_core::protocol::{CipherSp, LsRecord, KDF_ARGON2ID, KDF_NONE};
+use upspa_core::sign::SigningKey;
+use upspa_core::toprf::{ToprfClient, ToprfPartial};
+use upspa_core::types::b64_encode;
+use upspa_core::{OprfSuite, UpspaError};
+use upspa_sp::api::router;
+use upspa_sp::store::MemoryStore;
+use upspa_sp::SpService;
+
+fn config(tsp: usize) -> ClientConfig {
+    ClientConfig::new(b"user123".to_vec(), Vec::new(), tsp)
+        .with_retries(1, Duration::from_millis(1))
+}
+
+/// Fixed acknowledgement key for the HTTP SPs, so tests can pin it.
+fn sp_signing_key(sp_id: u32) -> SigningKey {
+    SigningKey::from_bytes(&[sp_id as u8; 32])
+}
+
+fn http_sp_key(sp_id: u32) -> (u32, [u8; 32]) {
+    (sp_id, sp_signing_key(sp_id).verifying_key().to_bytes())
+}
+
+fn mem_keys(mems: &[MemoryTransport]) -> Vec<(u32, [u8; 32])> {
+    mems.iter().map(|m| (m.sp_id(), m.public_key())).collect()
+}
+
+async fn http_sp(sp_id: u32) -> HttpTransport {
+    let app = router(Arc::new(
+        SpService::new(sp_id, Box::new(MemoryStore::new())).with_signing_key(sp_signing_key(sp_id)),
+    ));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    HttpTransport::new(
+        SpEndpoint::new(sp_id, format!("http://{addr}")),
+        reqwest::Client::new(),
+    )
+}
+
+fn sp_status(r: Result<impl std::fmt::Debug, ClientError>) -> (u16, String) {
+    match r {
+        Err(ClientError::Sp { status, code, .. }) => (status, code),
+        other => panic!("expected SP rejection, got {other:?}"),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn client_runs_over_memory_transports() {
+    let mems: Vec<MemoryTransport> = [5, 9, 12].into_iter().map(MemoryTransport::new).collect();
+    let sps: Vec<Arc<dyn SpTransport>> = mems
+        .iter()
+        .map(|m| Arc::new(m.clone()) as Arc<dyn SpTransport>)
+        .collect();
+    let client = UpspaClient::with_transports(config(2), sps.clone()).unwrap();
+
+    let setup = client.setup(b"pw").await.unwrap();
+    let client =
+        UpspaClient::with_transports(config(2).with_commitments(setup.commitments), sps).unwrap();
+    let session = client.login(b"pw").await.unwrap();
+    assert_eq!(session.cid_pt.sp_ids, vec![5, 9, 12]);
+
+    let reg = client
+        .register(&session, b"LS1", &LsRecord::None)
+        .await
+        .unwrap();
+
+    mems[1].set_offline(true);
+    let su = client.secret_update(&session, b"LS1").await.unwrap();
+    assert_eq!(su.vinfo_prime, reg.to_ls.vinfo);
+
+    mems[2].set_offline(true);
+    match client.authenticate(&session, b"LS1").await {
+        Err(ClientError::Quorum {
+            needed: 2,
+            got: 1,
+            failures,
+        }) => {
+            let ids: Vec<u32> = failures.iter().map(|(id, _)| *id).collect();
+            assert_eq!(ids, vec![9, 12]);
+        }
+        other => panic!("expected quorum error, got {other:?}"),
+    }
+
+    // SP 9 missed the secret update, so only SP 5 vouches for ctr 1.
+    mems[1].set_offline(false);
+    assert!(matches!(
+        client.authenticate(&session, b"LS1").await,
+        Err(ClientError::Core(UpspaError::RecordQuorum {
+            ctr: 1,
+            got: 1,
+            needed: 2,
+            ..
+        }))
+    ));
+
+    // With SP 12 back the newest record has its quorum; SP 9 gets repaired.
+    mems[2].set_offline(false);
+    let auth = client.authenticate(&session, b"LS1").await.unwrap();
+    assert_eq!((&auth.vinfo_prime, auth.best_ctr), (&su.vinfo_new, 1));
+    assert_eq!(auth.sp_ctrs, vec![(5, 1), (9, 0), (12, 1)]);
+    assert_eq!(auth.stale, vec![9]);
+    client
+        .repair_records(&session, b"LS1", &auth)
+        .await
+        .unwrap();
+
+    mems[0].set_offline(true);
+    let auth = client.authenticate(&session, b"LS1").await.unwrap();
+    assert_eq!((auth.best_ctr, auth.stale), (1, Vec::new()));
+
+    // A garbled record on SP 12 is skipped and repaired like a stale one.
+    mems[0].set_offline(false);
+    let suid = hash_suid(&session.cid_pt.rsp, b"LS1", 12);
+    let mut garbled = mems[2].record_get(&suid).await.unwrap();
+    garbled.ct[0] ^= 1;
+    mems[2].record_update(&suid, &garbled).await.unwrap();
+    let auth = client.authenticate(&session, b"LS1").await.unwrap();
+    assert_eq!((auth.best_ctr, auth.invalid.clone()), (1, vec![12]));
+    client
+        .repair_records(&session, b"LS1", &auth)
+        .await
+        .unwrap();
+    let auth = client.authenticate(&session, b"LS1").await.unwrap();
+    assert!(auth.invalid.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn memory_and_http_transports_agree() {
+    let transports: [Box<dyn SpTransport>; 2] = [
+        Box::new(MemoryTransport::new(1)),
+        Box::new(http_sp(1).await),
+    ];
+
+    for sp in transports {
+        let uid = b"user123";
+        assert_eq!(
+            sp_status(sp.get_setup(uid).await),
+            (404, "not_found".into())
+        );
+
+        let (out, payloads) = client_setup(uid, b"pw", &[1, 2], 2, &mut OsRng).unwrap();
+        sp.setup(&payloads[0]).await.unwrap();
+        sp.setup(&payloads[0]).await.unwrap();
+        let (_, other) = client_setup(uid, b"pw", &[1, 2], 2, &mut OsRng).unwrap();
+        assert_eq!(
+            sp_status(sp.setup(&other[0]).await),
+            (409, "conflict".into())
+        );
+        assert_eq!(sp.get_setup(uid).await.unwrap(), out.cid);
+
+        let (_, blinded) = ToprfClient::begin(b"pw", &mut OsRng);
+        let partial: ToprfPartial = sp.toprf_eval(uid, &blinded).await.unwrap();
+        assert_eq!(partial.id, 1);
+        assert!(partial.proof.is_some());
+
+        let suid = [3u8; 32];
+        let cj = CipherSp::from_slice(&[7u8; 24 + 41 + 16]).unwrap();
+        assert_eq!(
+            sp_status(sp.record_get(&suid).await),
+            (404, "not_found".into())
+        );
+        assert_eq!(
+            sp_status(sp.record_update(&suid, &cj).await),
+            (404, "not_found".into())
+        );
+        sp.record_create(&suid, &cj).await.unwrap();
+        assert_eq!(
+            sp_status(sp.record_create(&suid, &cj).await),
+            (409, "conflict".into())
+        );
+        assert_eq!(sp.record_get(&suid).await.unwrap(), cj);
+
+        let state_key = {
+            let (state, blinded) = ToprfClient::begin(b"pw", &mut OsRng);
+            let partials: Vec<ToprfPartial> = out
+                .shares
+                .iter()
+                .map(|(id, k)| ToprfPartial {
+                    id: *id,
+                    y: upspa_core::toprf::toprf_server_eval(&blinded, k).unwrap(),
+                    proof: None,
+                })
+                .collect();
+            ToprfClient::finish(b"pw", &state, &partials).unwrap()
+        };
+        let pu = client_password_update(
+            uid,
+            &state_key,
+            &out.cid,
+            &[1, 2],
+            2,
+            b"pw2",
+            10,
+            &mut OsRng,
+        )
+        .unwrap();
+        assert_eq!(
+            sp_status(sp.password_update(&pu.per_sp[1]).await),
+            (400, "sp_id_mismatch".into())
+        );
+        let ack = sp.password_update(&pu.per_sp[0]).await.unwrap();
+        assert_eq!((ack.sp_id, ack.timestamp), (1, 10));
+        assert_eq!(
+            sp_status(sp.password_update(&pu.per_sp[0]).await),
+            (409, "stale_timestamp".into())
+        );
+        assert_eq!(sp.get_setup(uid).await.unwrap(), pu.cid_new);
+
+        let ssk = decrypt_cid(uid, &state_key, &out.cid).unwrap().signing_key;
+        let rollback = |timestamp| {
+            sign_password_update_finalize(&ssk, uid, 1, timestamp, PasswordUpdateAction::Rollback)
+        };
+        assert_eq!(
+            sp_status(sp.password_update_finalize(&rollback(9)).await),
+            (409, "no_pending_update".into())
+        );
+        sp.password_update_finalize(&rollback(10)).await.unwrap();
+        assert_eq!(sp.get_setup(uid).await.unwrap(), out.cid);
+        assert_eq!(
+            sp_status(sp.password_update_finalize(&rollback(10)).await),
+            (409, "no_pending_update".into())
+        );
+    }
+}
+
+/// An SP without verifiable mode, like the Go SP: `/v1/toprf/eval` answers
+/// with `proof_b64` set to `proof`, or without the field.
+async fn toprf_only_sp(y: [u8; 32], proof: Option<&'static str>) -> HttpTransport {
+    let body = match proof {
+        Some(p) => json!({ "sp_id": 1, "y_b64": b64_encode(&y), "proof_b64": p }),
+        None => json!({ "sp_id": 1, "y_b64": b64_encode(&y) }),
+    };
+    let app = Router::new().route("/v1/toprf/eval", post(move || async move { Json(body) }));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    HttpTransport::new(
+        SpEndpoint::new(1, format!("http://{addr}")),
+        reqwest::Client::new(),
+    )
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn http_transport_accepts_partials_without_proofs() {
+    let (_, blinded) = ToprfClient::begin(b"pw", &mut OsRng);
+
+    let sp = toprf_only_sp(blinded, None).await;
+    let partial = sp.toprf_eval(b"user123", &blinded).await.unwrap();
+    assert_eq!((partial.id, partial.y), (1, blinded));
+    assert!(partial.proof.is_none());
+
+    let sp = toprf_only_sp(blinded, Some("AAAA")).await;
+    assert!(matches!(
+        sp.toprf_eval(b"user123", &blinded).await,
+        Err(ClientError::InvalidResponse {
+            sp_id: 1,
+            field: "proof"
+        })
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn change_password_commits_or_rolls_back() {
+    let mems: Vec<MemoryTransport> = [1, 2, 3].into_iter().map(MemoryTransport::new).collect();
+    let sps: Vec<Arc<dyn SpTransport>> = mems
+        .iter()
+        .map(|m| Arc::new(m.clone()) as Arc<dyn SpTransport>)
+        .collect();
+    let sp_keys: Vec<(u32, [u8; 32])> = mems.iter().map(|m| (m.sp_id(), m.public_key())).collect();
+    let client =
+        UpspaClient::with_transports(config(2).with_sp_keys(sp_keys.clone()), sps.clone()).unwrap();
+    client.setup(b"pw").await.unwrap();
+
+    assert!(client.change_password(b"wrong", b"pw2", 1).await.is_err());
+    let unpinned = UpspaClient::with_transports(config(2), sps.clone()).unwrap();
+    assert!(matches!(
+        unpinned.change_password(b"pw", b"pw2", 1).await,
+        Err(ClientError::Config(_))
+    ));
+
+    // SP 3 misses the update; the other two acknowledge and commit.
+    mems[2].set_offline(true);
+    let change = client.change_password(b"pw", b"pw2", 1).await.unwrap();
+    let acked: Vec<u32> = change.acks.iter().map(|a| a.sp_id).collect();
+    assert_eq!(acked, vec![1, 2]);
+    let lagging: Vec<u32> = change.lagging.iter().map(|(id, _)| *id).collect();
+    assert_eq!(lagging, vec![3]);
+    assert!(change.uncommitted.is_empty());
+    assert_eq!(change.session.cid, change.update.cid_new);
+    mems[2].set_offline(false);
+    assert!(client.login(b"pw2").await.is_ok());
+
+    // With two SP keys wrong, only one ack verifies: all three SPs took the
+    // update, and all three are rolled back.
+    let mut bad_keys = sp_keys.clone();
+    bad_keys[0].1 = mems[2].public_key();
+    bad_keys[1].1 = mems[2].public_key();
+    let strict =
+        UpspaClient::with_transports(config(2).with_sp_keys(bad_keys), sps.clone()).unwrap();
+    match strict.change_password(b"pw2", b"pw3", 2).await {
+        Err(ClientError::RolledBack {
+            reason,
+            not_restored,
+        }) => {
+            assert!(matches!(*reason, ClientError::Quorum { got: 1, .. }));
+            assert!(not_restored.is_empty());
+        }
+        other => panic!("expected rollback, got {other:?}"),
+    }
+    assert!(client.login(b"pw2").await.is_ok());
+    assert!(client.login(b"pw3").await.is_err());
+
+    // SP 1's ack does not verify, but it took the update and is committed
+    // along with the others.
+    let mut bad_keys = sp_keys;
+    bad_keys[0].1 = mems[2].public_key();
+    let partly = UpspaClient::with_transports(config(2).with_sp_keys(bad_keys), sps).unwrap();
+    let change = partly.change_password(b"pw2", b"pw3", 3).await.unwrap();
+    let lagging: Vec<u32> = change.lagging.iter().map(|(id, _)| *id).collect();
+    assert_eq!(lagging, vec![1]);
+    assert!(change.uncommitted.is_empty());
+    let rollback = sign_password_update_finalize(
+        &change.session.cid_pt.signing_key,
+        &client.config().uid,
+        1,
+        3,
+        PasswordUpdateAction::Rollback,
+    );
+    assert_eq!(
+        sp_status(mems[0].password_update_finalize(&rollback).await),
+        (409, "no_pending_update".into())
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rotated_key_signs_later_updates() {
+    let mems: Vec<MemoryTransport> = [1, 2, 3].into_iter().map(MemoryTransport::new).collect();
+    let sps: Vec<Arc<dyn SpTransport>> = mems
+        .iter()
+        .map(|m| Arc::new(m.clone()) as Arc<dyn SpTransport>)
+        .collect();
+    let client =
+        UpspaClient::with_transports(config(2).with_sp_keys(mem_keys(&mems)), sps).unwrap();
+    let setup = client.setup(b"pw").await.unwrap();
+    let mut session = client.login(b"pw").await.unwrap();
+
+    mems[2].set_offline(true);
+    let rotated = client.rotate_signing_key(&mut session, 1).await.unwrap();
+    assert!(rotated.recovery_voided.is_empty());
+    let rot = rotated.rotation;
+    assert_ne!(rot.sig_pk_new, setup.sig_pk);
+    assert_eq!(session.cid, rot.cid_new);
+    assert_eq!(
+        session.cid_pt.signing_key.verifying_key().to_bytes(),
+        rot.sig_pk_new
+    );
+
+    // SP 3 catches up from the same message; a replay elsewhere is refused.
+    mems[2].set_offline(false);
+    mems[2].key_rotation(&rot.per_sp[2]).await.unwrap();
+    assert_eq!(
+        sp_status(mems[0].key_rotation(&rot.per_sp[0]).await),
+        (409, "stale_timestamp".into())
+    );
+
+    let change = client.change_password(b"pw", b"pw2", 2).await.unwrap();
+    assert!(change.lagging.is_empty());
+    assert_eq!(change.session.cid_pt.ssk_bytes, session.cid_pt.ssk_bytes);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn command_signed_updates_reach_strict_sps() {
+    let mut sps: Vec<Arc<dyn SpTransport>> = Vec::new();
+    for sp_id in [1, 2, 3] {
+        let service = SpService::new(sp_id, Box::new(MemoryStore::new()))
+            .with_signing_key(sp_signing_key(sp_id))
+            .with_accept_legacy_signatures(false);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = router(Arc::new(service));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        sps.push(Arc::new(HttpTransport::new(
+            SpEndpoint::new(sp_id, format!("http://{addr}")),
+            reqwest::Client::new(),
+        )));
+    }
+
+    let legacy = UpspaClient::with_transports(config(2), sps.clone()).unwrap();
+    legacy.setup(b"pw").await.unwrap();
+    let session = legacy.login(b"pw").await.unwrap();
+    match legacy.password_update(&session, b"pw2", 1).await {
+        Err(ClientError::Quorum { got: 0, .. }) => {}
+        other => panic!("expected legacy signatures to be refused, got {other:?}"),
+    }
+
+    let cfg = config(2)
+        .with_pwd_update_format(SigFormat::Command)
+        .with_sp_keys([1, 2, 3].map(http_sp_key).to_vec());
+    let client = UpspaClient::with_transports(cfg, sps).unwrap();
+    let change = client.change_password(b"pw", b"pw2", 2).await.unwrap();
+    assert!(change.lagging.is_empty());
+    assert!(change
+        .update
+        .per_sp
+        .iter()
+        .all(|m| m.format == SigFormat::Command));
+    assert!(client.login(b"pw2").await.is_ok());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn delete_account_removes_setup_and_records() {
+    let mems: Vec<MemoryTransport> = [1, 2].into_iter().map(MemoryTransport::new).collect();
+    let mut sps: Vec<Arc<dyn SpTransport>> = mems
+        .iter()
+        .map(|m| Arc::new(m.clone()) as Arc<dyn SpTransport>)
+        .collect();
+    let http = http_sp(3).await;
+    sps.push(Arc::new(http.clone()));
+    let client = UpspaClient::with_transports(config(2), sps).unwrap();
+    client.setup(b"pw").await.unwrap();
+    let session = client.login(b"pw").await.unwrap();
+    for lsj in [b"LS1", b"LS2"] {
+        client
+            .register(&session, lsj, &LsRecord::None)
+            .await
+            .unwrap();
+    }
+    let lsjs = vec![b"LS1".to_vec(), b"LS2".to_vec()];
+
+    // SP 2 misses the deletion and is reported; resending its entry works.
+    mems[1].set_offline(true);
+    let deletion = client.delete_account(&session, &lsjs, 1).await.unwrap();
+    assert_eq!(deletion.deleted, vec![1, 3]);
+    let lagging: Vec<u32> = deletion.lagging.iter().map(|(id, _)| *id).collect();
+    assert_eq!(lagging, vec![2]);
+    mems[1].set_offline(false);
+    mems[1].deprovision(&deletion.per_sp[1]).await.unwrap();
+
+    let uid = client.config().uid.clone();
+    for (i, sp) in [
+        &mems[0] as &dyn SpTransport,
+        &mems[1] as &dyn SpTransport,
+        &http as &dyn SpTransport,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        assert_eq!(sp_status(sp.get_setup(&uid).await).0, 404);
+        for lsj in &lsjs {
+            let suid = hash_suid(&session.cid_pt.rsp, lsj, i as u32 + 1);
+            assert_eq!(sp_status(sp.record_get(&suid).await).0, 404);
+        }
+    }
+    assert!(client.login(b"pw").await.is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn recovery_codes_reset_a_forgotten_password() {
+    let mems: Vec<MemoryTransport> = [1, 2].into_iter().map(MemoryTransport::new).collect();
+    let mut sps: Vec<Arc<dyn SpTransport>> = mems
+        .iter()
+        .map(|m| Arc::new(m.clone()) as Arc<dyn SpTransport>)
+        .collect();
+    sps.push(Arc::new(http_sp(3).await));
+    let mut sp_keys = mem_keys(&mems);
+    sp_keys.push(http_sp_key(3));
+    let client = UpspaClient::with_transports(config(2).with_sp_keys(sp_keys), sps).unwrap();
+    client.setup(b"pw").await.unwrap();
+    let session = client.login(b"pw").await.unwrap();
+    client
+        .register(&session, b"LS1", &LsRecord::None)
+        .await
+        .unwrap();
+
+    let kit = client.enable_recovery(&session, 5, 3, 1).await.unwrap();
+    assert_eq!(kit.codes.len(), 5);
+
+    assert!(matches!(
+        client.recover(&kit.codes[..2], b"pw2", 2).await,
+        Err(ClientError::Core(UpspaError::Aead))
+    ));
+
+    // Works with an SP down, like any Π5 update with `tsp` SPs up.
+    mems[0].set_offline(true);
+    let change = client.recover(&kit.codes[2..], b"pw2", 2).await.unwrap();
+    assert_eq!(change.session.cid_pt.rsp, session.cid_pt.rsp);
+    mems[0].set_offline(false);
+    let mut relogged = client.login(b"pw2").await.unwrap();
+    assert!(client.login(b"pw").await.is_err());
+    let auth = client.authenticate(&relogged, b"LS1").await.unwrap();
+    assert!(auth.stale.is_empty());
+
+    // Rotating the signing key voids the kit, and says so.
+    let rot = client.rotate_signing_key(&mut relogged, 3).await.unwrap();
+    assert_eq!(rot.recovery_voided, vec![1, 2, 3]);
+    assert_eq!(
+        sp_status(mems[1].get_recovery(&client.config().uid).await),
+        (404, "not_found".into())
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn enrolled_device_reads_records_without_login() {
+    let mems: Vec<MemoryTransport> = [1, 2].into_iter().map(MemoryTransport::new).collect();
+    let mut sps: Vec<Arc<dyn SpTransport>> = mems
+        .iter()
+        .map(|m| Arc::new(m.clone()) as Arc<dyn SpTransport>)
+        .collect();
+    sps.push(Arc::new(http_sp(3).await));
+    let client = UpspaClient::with_transports(config(2), sps).unwrap();
+    let setup = client.setup(b"pw").await.unwrap();
+    let session = client.login(b"pw").await.unwrap();
+    let reg = client
+        .register(&session, b"LS1", &LsRecord::None)
+        .await
+        .unwrap();
+
+    let (device_id, device_key) = ([4u8; 16], [5u8; 32]);
+    let enrollment = client
+        .enroll_device(&session, &device_key, device_id, 1_000)
+        .unwrap();
+    let uid = client.config().uid.clone();
+    assert!(client
+        .device_revocations(&setup.sig_pk)
+        .await
+        .unwrap()
+        .is_none());
+
+    let cid_pt = unlock_device(&uid, &device_key, &enrollment, 10, None).unwrap();
+    let auth = client.authenticate_device(&cid_pt, b"LS1").await.unwrap();
+    assert_eq!(auth.vinfo_prime, reg.to_ls.vinfo);
+
+    // One SP misses the revocation; the newest list still wins.
+    mems[0].set_offline(true);
+    client
+        .revoke_devices(&session, vec![device_id], 20)
+        .await
+        .unwrap();
+    mems[0].set_offline(false);
+    let list = client
+        .device_revocations(&setup.sig_pk)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(list.issued_at, 20);
+    assert!(matches!(
+        unlock_device(&uid, &device_key, &enrollment, 30, Some(&list)),
+        Err(UpspaError::DeviceRevoked)
+    ));
+    assert_eq!(
+        sp_status(mems[1].put_device_revocations(&list).await),
+        (409, "stale_timestamp".into())
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn migrate_kdf_hardens_an_existing_account() {
+    let mems: Vec<MemoryTransport> = [1, 2, 3].into_iter().map(MemoryTransport::new).collect();
+    let sps: Vec<Arc<dyn SpTransport>> = mems
+        .iter()
+        .map(|m| Arc::new(m.clone()) as Arc<dyn SpTransport>)
+        .collect();
+    let client =
+        UpspaClient::with_transports(config(2).with_sp_keys(mem_keys(&mems)), sps.clone()).unwrap();
+    client.setup(b"pw").await.unwrap();
+    let session = client.login(b"pw").await.unwrap();
+    assert_eq!(session.cid_pt.kdf_id, KDF_NONE);
+    let reg = client
+        .register(&session, b"LS1", &LsRecord::None)
+        .await
+        .unwrap();
+
+    let params = Argon2idParams {
+        m_cost_kib: 64,
+        t_cost: 1,
+        p_cost: 1,
+    };
+    let kdf = PasswordKdf::argon2id(params, &mut OsRng);
+    let change = client.migrate_kdf(b"pw", &kdf, 1).await.unwrap();
+    assert_eq!(change.update.kdf, kdf);
+    assert_eq!(change.session.cid_pt.kdf_id, KDF_ARGON2ID);
+
+    // The same password no longer works without the new config.
+    assert!(client.login(b"pw").await.is_err());
+    let migrated = UpspaClient::with_transports(config(2).with_kdf(kdf), sps).unwrap();
+    let session = migrated.login(b"pw").await.unwrap();
+    let auth = migrated.authenticate(&session, b"LS1").await.unwrap();
+    assert_eq!(auth.vinfo_prime, reg.to_ls.vinfo);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rfc9497_suite_runs_end_to_end() {
+    let mems: Vec<MemoryTransport> = [1, 2, 3].into_iter().map(MemoryTransport::new).collect();
+    let sps: Vec<Arc<dyn SpTransport>> = mems
+        .iter()
+        .map(|m| Arc::new(m.clone()) as Arc<dyn SpTransport>)
+        .collect();
+    let cfg = config(2)
+        .with_suite(OprfSuite::Ristretto255Sha512)
+        .with_sp_keys(mem_keys(&mems));
+    let client = UpspaClient::with_transports(cfg, sps.clone()).unwrap();
+    let out = client.setup(b"pw").await.unwrap();
+    assert_eq!(out.suite, OprfSuite::Ristretto255Sha512);
+    let session = client.login(b"pw").await.unwrap();
+    client
+        .register(&session, b"LS1", &LsRecord::None)
+        .await
+        .unwrap();
+
+    let change = client.change_password(b"pw", b"pw2", 1).await.unwrap();
+    assert_eq!(change.update.suite, OprfSuite::Ristretto255Sha512);
+    assert!(client.login(b"pw2").await.is_ok());
+
+    // The SPs are unchanged, but a BLAKE3 client derives another state key.
+    let blake3 = UpspaClient::with_transports(config(2), sps).unwrap();
+    assert!(blake3.login(b"pw2").await.is_err());
+}
@@ -3,8 +3,9 @@ use chacha20poly1305::{
     XChaCha20Poly1305, XNonce,
 };
 use rand_core::RngCore;
+use zeroize::Zeroizing;
 
-use crate::types::{CtBlob, NONCE_LEN, TAG_LEN, UpspaError};
+use crate::types::{CtBlob, CtVec, NONCE_LEN, TAG_LEN, UpspaError};
 pub fn xchacha_encrypt_detached<const PT_LEN: usize>(
     key: &[u8; 32],
     aad: &[u8],
@@ -48,6 +49,52 @@ pub fn xchacha_decrypt_detached<const PT_LEN: usize>(
     Ok(pt)
 }
 
+/// [`xchacha_encrypt_detached`] for variable-length plaintexts.
+pub fn xchacha_encrypt_detached_vec<const MIN_LEN: usize>(
+    key: &[u8; 32],
+    aad: &[u8],
+    plaintext: &[u8],
+    rng: &mut impl RngCore,
+) -> CtVec<MIN_LEN> {
+    debug_assert!(plaintext.len() >= MIN_LEN);
+    let cipher = XChaCha20Poly1305::new_from_slice(key).unwrap();
+
+    let mut nonce = [0u8; NONCE_LEN];
+    rng.fill_bytes(&mut nonce);
+    let xnonce = XNonce::from_slice(&nonce);
+
+    let mut ct = plaintext.to_vec();
+    let tag = cipher
+        .encrypt_in_place_detached(xnonce, aad, &mut ct)
+        .expect("XChaCha20-Poly1305 encryption should not fail for in-memory buffers");
+
+    let mut tag_bytes = [0u8; TAG_LEN];
+    tag_bytes.copy_from_slice(tag.as_slice());
+
+    CtVec {
+        nonce,
+        ct,
+        tag: tag_bytes,
+    }
+}
+
+/// Decrypt a detached-tag [`CtVec`] using XChaCha20-Poly1305. The
+/// plaintext is wiped when dropped.
+pub fn xchacha_decrypt_detached_vec<const MIN_LEN: usize>(
+    key: &[u8; 32],
+    aad: &[u8],
+    blob: &CtVec<MIN_LEN>,
+) -> Result<Zeroizing<Vec<u8>>, AeadError> {
+    let cipher = XChaCha20Poly1305::new_from_slice(key).unwrap();
+    let xnonce = XNonce::from_slice(&blob.nonce);
+
+    let mut pt = Zeroizing::new(blob.ct.clone());
+    let tag = GenericArray::from_slice(&blob.tag);
+
+    cipher.decrypt_in_place_detached(xnonce, aad, &mut pt, tag)?;
+    Ok(pt)
+}
+
 impl From<AeadError> for UpspaError {
     fn from(_: AeadError) -> Self {
         UpspaError::Aead
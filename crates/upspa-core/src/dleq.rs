@@ -0,0 +1,88 @@
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+use rand_core::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::hash::hash_dleq_challenge;
+use crate::toprf::{random_scalar, scalar_from_canonical_bytes};
+use crate::types::UpspaError;
+
+pub const DLEQ_PROOF_LEN: usize = 64;
+
+/// Layout: `c(32) || s(32)`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DleqProof {
+    pub c: [u8; 32],
+    pub s: [u8; 32],
+}
+
+impl DleqProof {
+    pub fn to_bytes(&self) -> [u8; DLEQ_PROOF_LEN] {
+        let mut out = [0u8; DLEQ_PROOF_LEN];
+        out[0..32].copy_from_slice(&self.c);
+        out[32..64].copy_from_slice(&self.s);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8; DLEQ_PROOF_LEN]) -> Self {
+        let mut c = [0u8; 32];
+        let mut s = [0u8; 32];
+        c.copy_from_slice(&bytes[0..32]);
+        s.copy_from_slice(&bytes[32..64]);
+        Self { c, s }
+    }
+}
+
+/// Chaum-Pedersen proof that `log_G(commitment) == log_blinded(y) == k`,
+/// i.e. that an SP's partial uses the share it committed to at setup.
+pub fn dleq_prove(
+    k: &Scalar,
+    commitment: &RistrettoPoint,
+    blinded: &RistrettoPoint,
+    y: &RistrettoPoint,
+    rng: &mut impl RngCore,
+) -> DleqProof {
+    let t = random_scalar(rng);
+    let a1 = RISTRETTO_BASEPOINT_POINT * t;
+    let a2 = blinded * t;
+
+    let c = hash_dleq_challenge(
+        &commitment.compress().to_bytes(),
+        &blinded.compress().to_bytes(),
+        &y.compress().to_bytes(),
+        &a1.compress().to_bytes(),
+        &a2.compress().to_bytes(),
+    );
+    let s = t - c * k;
+
+    DleqProof {
+        c: c.to_bytes(),
+        s: s.to_bytes(),
+    }
+}
+
+pub fn dleq_verify(
+    commitment: &RistrettoPoint,
+    blinded: &RistrettoPoint,
+    y: &RistrettoPoint,
+    proof: &DleqProof,
+) -> Result<(), UpspaError> {
+    let c = scalar_from_canonical_bytes(&proof.c)?;
+    let s = scalar_from_canonical_bytes(&proof.s)?;
+
+    let a1 = RISTRETTO_BASEPOINT_POINT * s + commitment * c;
+    let a2 = blinded * s + y * c;
+
+    let expected = hash_dleq_challenge(
+        &commitment.compress().to_bytes(),
+        &blinded.compress().to_bytes(),
+        &y.compress().to_bytes(),
+        &a1.compress().to_bytes(),
+        &a2.compress().to_bytes(),
+    );
+    if expected != c {
+        return Err(UpspaError::InvalidProof);
+    }
+    Ok(())
+}
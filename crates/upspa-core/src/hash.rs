@@ -1,5 +1,10 @@
 use blake3;
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_COMPRESSED;
 use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+use sha2::{Digest, Sha512};
+
+use crate::types::UpspaError;
 pub fn hash_to_point(msg: &[u8]) -> RistrettoPoint {
     let mut hasher = blake3::Hasher::new();
     hasher.update(b"uptspa/hash_to_point");
@@ -23,6 +28,56 @@ pub fn oprf_finalize(password: &[u8], y: &RistrettoPoint) -> [u8; 32] {
     r.copy_from_slice(out.as_bytes());
     r
 }
+/// RFC 9497 contextString for ristretto255-SHA512 in OPRF mode (0x00).
+pub const RFC9497_CONTEXT: &[u8] = b"OPRFV1-\x00-ristretto255-SHA512";
+/// RFC 9497 `HashToGroup`: hash_to_ristretto255 (RFC 9380) with
+/// expand_message_xmd over SHA-512 and DST `"HashToGroup-" || contextString`.
+pub fn rfc9497_hash_to_group(input: &[u8]) -> RistrettoPoint {
+    let mut dst = b"HashToGroup-".to_vec();
+    dst.extend_from_slice(RFC9497_CONTEXT);
+    let dst_len = [dst.len() as u8];
+
+    // len_in_bytes = 64 is a single SHA-512 block, so ell = 1.
+    let b0 = Sha512::new()
+        .chain_update([0u8; 128])
+        .chain_update(input)
+        .chain_update(64u16.to_be_bytes())
+        .chain_update([0u8])
+        .chain_update(&dst)
+        .chain_update(dst_len)
+        .finalize();
+    let b1 = Sha512::new()
+        .chain_update(b0)
+        .chain_update([1u8])
+        .chain_update(&dst)
+        .chain_update(dst_len)
+        .finalize();
+
+    let mut wide = [0u8; 64];
+    wide.copy_from_slice(&b1);
+    RistrettoPoint::from_uniform_bytes(&wide)
+}
+/// RFC 9497 `Finalize`: SHA-512 over the length-prefixed input and unblinded
+/// element, then `"Finalize"`. The prefix is 2 bytes, so `input` must be
+/// shorter than 2^16 bytes.
+pub fn rfc9497_finalize(input: &[u8], y: &RistrettoPoint) -> Result<[u8; 64], UpspaError> {
+    let input_len = u16::try_from(input.len()).map_err(|_| UpspaError::InvalidLength {
+        expected: u16::MAX as usize,
+        got: input.len(),
+    })?;
+    let y_bytes = y.compress().to_bytes();
+
+    let out = Sha512::new()
+        .chain_update(input_len.to_be_bytes())
+        .chain_update(input)
+        .chain_update((y_bytes.len() as u16).to_be_bytes())
+        .chain_update(y_bytes)
+        .chain_update(b"Finalize")
+        .finalize();
+    let mut r = [0u8; 64];
+    r.copy_from_slice(&out);
+    Ok(r)
+}
 pub fn hash_suid(rsp: &[u8; 32], lsj: &[u8], i: u32) -> [u8; 32] {
     let mut h = blake3::Hasher::new();
     h.update(b"uptspa/suid");
@@ -35,6 +90,16 @@ pub fn hash_suid(rsp: &[u8; 32], lsj: &[u8], i: u32) -> [u8; 32] {
     r.copy_from_slice(out.as_bytes());
     r
 }
+pub fn hash_recovery_key(secret: &[u8; 32]) -> [u8; 32] {
+    let mut h = blake3::Hasher::new();
+    h.update(b"uptspa/recovery-key");
+    h.update(secret);
+
+    let out = h.finalize();
+    let mut r = [0u8; 32];
+    r.copy_from_slice(out.as_bytes());
+    r
+}
 pub fn hash_vinfo(rlsj: &[u8; 32], lsj: &[u8]) -> [u8; 32] {
     let mut h = blake3::Hasher::new();
     h.update(b"uptspa/vinfo");
@@ -46,3 +111,23 @@ pub fn hash_vinfo(rlsj: &[u8; 32], lsj: &[u8]) -> [u8; 32] {
     r.copy_from_slice(out.as_bytes());
     r
 }
+pub fn hash_dleq_challenge(
+    commitment: &[u8; 32],
+    blinded: &[u8; 32],
+    y: &[u8; 32],
+    a1: &[u8; 32],
+    a2: &[u8; 32],
+) -> Scalar {
+    let mut h = blake3::Hasher::new();
+    h.update(b"uptspa/dleq");
+    h.update(&RISTRETTO_BASEPOINT_COMPRESSED.to_bytes());
+    h.update(commitment);
+    h.update(blinded);
+    h.update(y);
+    h.update(a1);
+    h.update(a2);
+
+    let mut wide = [0u8; 64];
+    h.finalize_xof().fill(&mut wide);
+    Scalar::from_bytes_mod_order_wide(&wide)
+}
@@ -0,0 +1,88 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::protocol::{KDF_ARGON2ID, KDF_NONE};
+use crate::types::UpspaError;
+
+/// Length of the Argon2id output fed to the TOPRF.
+pub const ARGON2ID_OUTPUT_LEN: usize = 32;
+
+/// Argon2id (v0x13) cost parameters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Argon2idParams {
+    /// Memory in KiB.
+    pub m_cost_kib: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+}
+
+impl Default for Argon2idParams {
+    /// The OWASP minimum: 19 MiB, 2 passes, 1 lane.
+    fn default() -> Self {
+        Self {
+            m_cost_kib: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+/// How the password is hardened before `hash_to_point`, so each guess
+/// against the TOPRF output of compromised SPs costs a full KDF run.
+///
+/// The client keeps this next to the setup commitments: login needs it
+/// before `cid` can be opened. Its [`PasswordKdf::id`] is also recorded as
+/// `kdf_id` inside `cid`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kdf", rename_all = "snake_case")]
+pub enum PasswordKdf {
+    /// The password goes to the TOPRF unchanged; accounts made before
+    /// hardening existed.
+    #[default]
+    None,
+    Argon2id {
+        params: Argon2idParams,
+        salt: [u8; 16],
+    },
+}
+
+impl PasswordKdf {
+    /// Argon2id under `params` with a fresh salt.
+    pub fn argon2id<R: RngCore + CryptoRng>(params: Argon2idParams, rng: &mut R) -> Self {
+        let mut salt = [0u8; 16];
+        rng.fill_bytes(&mut salt);
+        PasswordKdf::Argon2id { params, salt }
+    }
+
+    /// The `kdf_id` recorded in `cid`.
+    pub fn id(&self) -> u8 {
+        match self {
+            PasswordKdf::None => KDF_NONE,
+            PasswordKdf::Argon2id { .. } => KDF_ARGON2ID,
+        }
+    }
+
+    /// The bytes that stand in for `password` everywhere in the TOPRF:
+    /// `hash_to_point` at setup and login, `oprf_finalize`, and Π5.
+    pub fn harden(&self, password: &[u8]) -> Result<Zeroizing<Vec<u8>>, UpspaError> {
+        match self {
+            PasswordKdf::None => Ok(Zeroizing::new(password.to_vec())),
+            PasswordKdf::Argon2id { params, salt } => {
+                let params = Params::new(
+                    params.m_cost_kib,
+                    params.t_cost,
+                    params.p_cost,
+                    Some(ARGON2ID_OUTPUT_LEN),
+                )
+                .map_err(|_| UpspaError::InvalidKdf)?;
+                let mut out = Zeroizing::new(vec![0u8; ARGON2ID_OUTPUT_LEN]);
+                Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                    .hash_password_into(password, salt, &mut out)
+                    .map_err(|_| UpspaError::InvalidKdf)?;
+                Ok(out)
+            }
+        }
+    }
+}
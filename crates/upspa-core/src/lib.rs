@@ -1,20 +1,34 @@
 #![forbid(unsafe_code)]
 
 pub mod aead;
+pub mod dleq;
 pub mod hash;
+pub mod kdf;
+pub mod password_policy;
 pub mod protocol;
+pub mod secret;
 pub mod sign;
+pub mod suite;
 pub mod toprf;
 pub mod types;
 
 pub mod crypto {
     pub use crate::aead::{xchacha_decrypt_detached, xchacha_encrypt_detached};
-    pub use crate::hash::{hash_suid, hash_to_point, hash_vinfo, oprf_finalize};
+    pub use crate::hash::{
+        hash_suid, hash_to_point, hash_vinfo, oprf_finalize, rfc9497_finalize,
+        rfc9497_hash_to_group,
+    };
+    pub use crate::dleq::{dleq_prove, dleq_verify, DleqProof};
     pub use crate::toprf::{
         lagrange_coeffs_at_zero, random_scalar, toprf_client_eval, toprf_client_eval_from_partials,
-        toprf_gen, ToprfClient, ToprfClientState, ToprfPartial,
+        toprf_gen, toprf_gen_for_ids, toprf_refresh_commitments, toprf_refresh_share,
+        toprf_server_eval_verifiable, toprf_share_commitment, toprf_zero_shares, validate_sp_ids,
+        validate_threshold, ToprfClient, ToprfClientState, ToprfPartial,
     };
     pub use crate::types::{CtBlob, NONCE_LEN, TAG_LEN, UpspaError};
 }
 
+pub use kdf::PasswordKdf;
+pub use secret::SecretBytes;
+pub use suite::OprfSuite;
 pub use types::UpspaError;
@@ -0,0 +1,289 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::UpspaError;
+
+const LOWER: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPER: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+pub const DEFAULT_SYMBOLS: &str = "!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+
+/// Upper bound on [`PasswordPolicy::length`].
+pub const MAX_PASSWORD_LEN: u16 = 256;
+const MAX_RENDER_ATTEMPTS: usize = 10_000;
+
+/// Whether a character class may, must, or must not appear.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClassRule {
+    Forbidden,
+    Allowed,
+    Required,
+}
+
+impl ClassRule {
+    fn to_byte(self) -> u8 {
+        match self {
+            ClassRule::Forbidden => 0,
+            ClassRule::Allowed => 1,
+            ClassRule::Required => 2,
+        }
+    }
+
+    fn from_byte(b: u8) -> Result<Self, UpspaError> {
+        match b {
+            0 => Ok(ClassRule::Forbidden),
+            1 => Ok(ClassRule::Allowed),
+            2 => Ok(ClassRule::Required),
+            _ => Err(UpspaError::InvalidPolicy("unknown class rule")),
+        }
+    }
+}
+
+/// What a site accepts as a password.
+///
+/// Encoded as `length_le(2) || lower(1) || upper(1) || digits(1) || symbols(1)
+/// || len(1) || symbol_set || len(1) || banned`, with rules as 0/1/2 for
+/// forbidden/allowed/required.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PasswordPolicy {
+    pub length: u16,
+    pub lower: ClassRule,
+    pub upper: ClassRule,
+    pub digits: ClassRule,
+    pub symbols: ClassRule,
+    /// ASCII punctuation that counts as the symbol class.
+    pub symbol_set: String,
+    /// Characters removed from every class.
+    pub banned: String,
+}
+
+/// Named policies for common site rules.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyPreset {
+    /// 20 characters, all four classes required.
+    Strong,
+    /// 16 letters and digits, each class required.
+    Alphanumeric,
+    /// 12 characters from a small symbol set that legacy forms accept.
+    Legacy,
+    /// 6-digit PIN.
+    Pin,
+}
+
+impl PolicyPreset {
+    pub fn policy(self) -> PasswordPolicy {
+        use ClassRule::*;
+        let (length, symbols, symbol_set) = match self {
+            PolicyPreset::Strong => (20, Required, DEFAULT_SYMBOLS),
+            PolicyPreset::Alphanumeric => (16, Forbidden, ""),
+            PolicyPreset::Legacy => (12, Required, "!#$%*-_"),
+            PolicyPreset::Pin => {
+                return PasswordPolicy {
+                    length: 6,
+                    lower: Forbidden,
+                    upper: Forbidden,
+                    digits: Required,
+                    symbols: Forbidden,
+                    symbol_set: String::new(),
+                    banned: String::new(),
+                }
+            }
+        };
+        PasswordPolicy {
+            length,
+            lower: Required,
+            upper: Required,
+            digits: Required,
+            symbols,
+            symbol_set: symbol_set.into(),
+            banned: String::new(),
+        }
+    }
+}
+
+impl From<PolicyPreset> for PasswordPolicy {
+    fn from(preset: PolicyPreset) -> Self {
+        preset.policy()
+    }
+}
+
+/// A preset name or a full policy, as accepted by the WASM and CLI front
+/// ends (`"strong"` or `{"length": 12, ...}` in JSON).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PolicySpec {
+    Preset(PolicyPreset),
+    Custom(PasswordPolicy),
+}
+
+impl From<PolicySpec> for PasswordPolicy {
+    fn from(spec: PolicySpec) -> Self {
+        match spec {
+            PolicySpec::Preset(p) => p.policy(),
+            PolicySpec::Custom(p) => p,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(8 + self.symbol_set.len() + self.banned.len());
+        out.extend_from_slice(&self.length.to_le_bytes());
+        for rule in [self.lower, self.upper, self.digits, self.symbols] {
+            out.push(rule.to_byte());
+        }
+        for s in [&self.symbol_set, &self.banned] {
+            out.push(s.len() as u8);
+            out.extend_from_slice(s.as_bytes());
+        }
+        out
+    }
+
+    pub fn from_bytes(input: &[u8]) -> Result<Self, UpspaError> {
+        let bad = UpspaError::InvalidPolicy("truncated encoding");
+        if input.len() < 6 {
+            return Err(bad);
+        }
+        let length = u16::from_le_bytes([input[0], input[1]]);
+        let rules = [
+            ClassRule::from_byte(input[2])?,
+            ClassRule::from_byte(input[3])?,
+            ClassRule::from_byte(input[4])?,
+            ClassRule::from_byte(input[5])?,
+        ];
+
+        let mut rest = &input[6..];
+        let mut strings = Vec::with_capacity(2);
+        for _ in 0..2 {
+            let (&len, tail) = rest.split_first().ok_or(bad.clone())?;
+            let s = tail.get(..len as usize).ok_or(bad.clone())?;
+            let s =
+                std::str::from_utf8(s).map_err(|_| UpspaError::InvalidPolicy("invalid UTF-8"))?;
+            strings.push(s.to_owned());
+            rest = &tail[len as usize..];
+        }
+        if !rest.is_empty() {
+            return Err(bad);
+        }
+
+        let banned = strings.pop().unwrap_or_default();
+        let symbol_set = strings.pop().unwrap_or_default();
+        let policy = PasswordPolicy {
+            length,
+            lower: rules[0],
+            upper: rules[1],
+            digits: rules[2],
+            symbols: rules[3],
+            symbol_set,
+            banned,
+        };
+        policy.classes()?;
+        Ok(policy)
+    }
+
+    /// The usable alphabet of each non-forbidden class, after removing
+    /// `banned`, with its rule. Also checks the policy can be satisfied.
+    fn classes(&self) -> Result<Vec<(Vec<u8>, ClassRule)>, UpspaError> {
+        if !(self.symbol_set.len() <= 255
+            && self.banned.len() <= 255
+            && self.symbol_set.bytes().all(|b| b.is_ascii_punctuation()))
+        {
+            return Err(UpspaError::InvalidPolicy(
+                "symbol set must be at most 255 ASCII punctuation characters",
+            ));
+        }
+        if self.length == 0 || self.length > MAX_PASSWORD_LEN {
+            return Err(UpspaError::InvalidPolicy("length out of range"));
+        }
+
+        let mut classes = Vec::new();
+        for (set, rule) in [
+            (LOWER, self.lower),
+            (UPPER, self.upper),
+            (DIGITS, self.digits),
+            (self.symbol_set.as_str(), self.symbols),
+        ] {
+            if rule == ClassRule::Forbidden {
+                continue;
+            }
+            let mut chars: Vec<u8> = set
+                .bytes()
+                .filter(|b| !self.banned.as_bytes().contains(b))
+                .collect();
+            chars.sort_unstable();
+            chars.dedup();
+            if chars.is_empty() {
+                if rule == ClassRule::Required {
+                    return Err(UpspaError::InvalidPolicy(
+                        "required class has no characters",
+                    ));
+                }
+                continue;
+            }
+            classes.push((chars, rule));
+        }
+
+        if classes.is_empty() {
+            return Err(UpspaError::InvalidPolicy("no allowed characters"));
+        }
+        let required = classes
+            .iter()
+            .filter(|(_, r)| *r == ClassRule::Required)
+            .count();
+        if required > self.length as usize {
+            return Err(UpspaError::InvalidPolicy(
+                "more required classes than characters",
+            ));
+        }
+        Ok(classes)
+    }
+}
+
+/// Render `vinfo` as a password that satisfies `policy`.
+///
+/// The result is uniform over all passwords the policy accepts: characters are
+/// drawn from a BLAKE3 XOF over `vinfo` by rejection sampling, and candidates
+/// missing a required class are discarded. The same inputs always give the
+/// same password.
+pub fn render_site_password(
+    vinfo: &[u8; 32],
+    policy: &PasswordPolicy,
+) -> Result<String, UpspaError> {
+    let classes = policy.classes()?;
+    let alphabet: Vec<u8> = classes
+        .iter()
+        .flat_map(|(c, _)| c.iter().copied())
+        .collect();
+    let n = alphabet.len() as u16;
+    let limit = u16::MAX - u16::MAX % n;
+
+    let mut h = blake3::Hasher::new();
+    h.update(b"uptspa/site-password");
+    h.update(vinfo);
+    h.update(&policy.to_bytes());
+    let mut xof = h.finalize_xof();
+
+    let mut next_char = || loop {
+        let mut b = [0u8; 2];
+        xof.fill(&mut b);
+        let r = u16::from_le_bytes(b);
+        if r < limit {
+            return alphabet[(r % n) as usize];
+        }
+    };
+
+    for _ in 0..MAX_RENDER_ATTEMPTS {
+        let candidate: Vec<u8> = (0..policy.length).map(|_| next_char()).collect();
+        let satisfied = classes
+            .iter()
+            .filter(|(_, rule)| *rule == ClassRule::Required)
+            .all(|(chars, _)| candidate.iter().any(|c| chars.contains(c)));
+        if satisfied {
+            return Ok(String::from_utf8(candidate).expect("alphabet is ASCII"));
+        }
+    }
+    Err(UpspaError::InvalidPolicy(
+        "policy too restrictive to render",
+    ))
+}
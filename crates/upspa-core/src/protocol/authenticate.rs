@@ -1,66 +1,82 @@
 use serde::{Deserialize, Serialize};
 use crate::hash::{hash_suid, hash_vinfo};
-use crate::protocol::{decrypt_cid, decrypt_cj, CipherId, CipherSp};
+use crate::password_policy::render_site_password;
+use crate::protocol::records::select_record;
+use crate::protocol::{decrypt_cid, CipherId, CipherSp, LsRecord};
+use crate::secret::SecretBytes;
+use crate::toprf::validate_sp_ids;
 use crate::types::UpspaError;
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AuthQueries {
-    pub k0: [u8; 32],
+    pub k0: SecretBytes<32>,
     pub per_sp: Vec<(u32, [u8; 32])>, // (sp_id, SUid_{i,j})
 }
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AuthResult {
-    pub vinfo_prime: [u8; 32],
+    pub vinfo_prime: SecretBytes<32>,
     pub best_ctr: u64,
+    /// Per-LS record stored in the newest `c_j`.
+    pub record: LsRecord,
+    /// `ctr` returned by each SP.
+    pub sp_ctrs: Vec<(u32, u64)>,
+    /// SPs that returned an older or conflicting `c_j`.
+    pub stale: Vec<u32>,
+    /// SPs whose `c_j` did not decrypt; they are skipped.
+    pub invalid: Vec<u32>,
+    /// `(sp_id, c_j)` record updates for the stale and invalid SPs.
+    pub repairs: Vec<(u32, CipherSp)>,
+}
+
+impl AuthResult {
+    /// The password to submit to the LS, if the record defines one: the stored
+    /// password, or `vinfo_prime` rendered under the stored policy.
+    pub fn site_password(&self) -> Result<Option<String>, UpspaError> {
+        match &self.record {
+            LsRecord::Password { password } => Ok(Some(password.clone())),
+            LsRecord::Policy { policy } => render_site_password(&self.vinfo_prime, policy).map(Some),
+            _ => Ok(None),
+        }
+    }
 }
 pub fn client_auth_prepare(
     uid: &[u8],
     lsj: &[u8],
     password_state_key: &[u8; 32],
     cid: &CipherId,
-    nsp: usize,
+    sp_ids: &[u32],
 ) -> Result<AuthQueries, UpspaError> {
+    validate_sp_ids(sp_ids)?;
     let cid_pt = decrypt_cid(uid, password_state_key, cid)?;
     let rsp = cid_pt.rsp;
     let k0 = cid_pt.k0;
 
-    let mut per_sp = Vec::with_capacity(nsp);
-    for i in 1..=nsp {
-        let suid = hash_suid(&rsp, lsj, i as u32);
-        per_sp.push((i as u32, suid));
+    let mut per_sp = Vec::with_capacity(sp_ids.len());
+    for &sp_id in sp_ids {
+        let suid = hash_suid(&rsp, lsj, sp_id);
+        per_sp.push((sp_id, suid));
     }
 
     Ok(AuthQueries { k0, per_sp })
 }
+/// Π4 finish: open the SPs' `c_j` copies, given as `(sp_id, c_j)`, and take
+/// the newest. At least `quorum` SPs must hold that record; the others are
+/// reported in [`AuthResult::stale`] with a repair for each. Blobs that do not
+/// decrypt are skipped and listed in [`AuthResult::invalid`].
 pub fn client_auth_finish(
     uid: &[u8],
     lsj: &[u8],
     k0: &[u8; 32],
-    cjs: &[CipherSp],
+    cjs: &[(u32, CipherSp)],
+    quorum: usize,
 ) -> Result<AuthResult, UpspaError> {
-    if cjs.is_empty() {
-        return Err(UpspaError::InvalidLength {
-            expected: 1,
-            got: 0,
-        });
-    }
-
-    let mut best_ctr: u64 = 0;
-    let mut best_rlsj = [0u8; 32];
-    let mut any_ok = false;
-
-    for cj in cjs {
-        let pt = decrypt_cj(uid, k0, cj)?;
-        any_ok = true;
-        if pt.ctr >= best_ctr {
-            best_ctr = pt.ctr;
-            best_rlsj = pt.rlsj;
-        }
-    }
-
-    if !any_ok {
-        return Err(UpspaError::Aead);
-    }
-
-    let vinfo_prime = hash_vinfo(&best_rlsj, lsj);
-    Ok(AuthResult { vinfo_prime, best_ctr })
+    let sel = select_record(uid, k0, cjs, quorum)?;
+    Ok(AuthResult {
+        vinfo_prime: SecretBytes::new(hash_vinfo(&sel.pt.rlsj, lsj)),
+        best_ctr: sel.pt.ctr,
+        repairs: sel.repairs(),
+        sp_ctrs: sel.sp_ctrs,
+        stale: sel.stale,
+        invalid: sel.invalid,
+        record: sel.pt.record,
+    })
 }
@@ -0,0 +1,137 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::sign::{sign_detached, verify_detached, SigningKey};
+
+const COMMAND_TAG: &[u8] = b"uptspa/command/v1";
+
+/// What a [`SignedCommand`] asks an SP to do. The kind is part of the signed
+/// bytes, so a signature for one command never verifies as another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommandKind {
+    PasswordUpdate,
+    PasswordUpdateFinalize,
+    ShareRefresh,
+    KeyRotation,
+    Deprovision,
+    RecoveryEscrow,
+    DeviceRevocation,
+}
+
+impl CommandKind {
+    /// Wire identifier; never reuse a retired value.
+    pub fn id(self) -> u16 {
+        match self {
+            CommandKind::PasswordUpdate => 1,
+            CommandKind::PasswordUpdateFinalize => 2,
+            CommandKind::ShareRefresh => 3,
+            CommandKind::KeyRotation => 4,
+            CommandKind::Deprovision => 5,
+            CommandKind::RecoveryEscrow => 6,
+            CommandKind::DeviceRevocation => 7,
+        }
+    }
+}
+
+/// Why an SP must reject a [`SignedCommand`].
+#[derive(Clone, Debug, thiserror::Error, PartialEq, Eq)]
+pub enum CommandError {
+    #[error("sp_id mismatch: expected {expected}, got {got}")]
+    SpIdMismatch { expected: u32, got: u32 },
+
+    #[error("stale timestamp: {got} is not greater than last {last}")]
+    StaleTimestamp { last: u64, got: u64 },
+
+    #[error("invalid command signature")]
+    Signature,
+}
+
+/// A client request to one SP, signed with the key inside `cid`. `body` is
+/// the command-specific payload; see the `*_command_body` builders.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SignedCommand {
+    pub kind: CommandKind,
+    pub uid_b64: String,
+    pub sp_id: u32,
+    pub timestamp: u64,
+    pub body: Vec<u8>,
+
+    #[serde(with = "serde_big_array::BigArray")]
+    pub sig: [u8; 64],
+}
+
+/// Bytes signed for a command.
+///
+/// Layout: `"uptspa/command/v1"(17) || kind_le(2) || uid_len_le(4) || uid || sp_id_le(4) || timestamp_le(8) || body_len_le(4) || body`.
+pub fn command_sig_msg(
+    kind: CommandKind,
+    uid: &[u8],
+    sp_id: u32,
+    timestamp: u64,
+    body: &[u8],
+) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(COMMAND_TAG.len() + 22 + uid.len() + body.len());
+    msg.extend_from_slice(COMMAND_TAG);
+    msg.extend_from_slice(&kind.id().to_le_bytes());
+    msg.extend_from_slice(&(uid.len() as u32).to_le_bytes());
+    msg.extend_from_slice(uid);
+    msg.extend_from_slice(&sp_id.to_le_bytes());
+    msg.extend_from_slice(&timestamp.to_le_bytes());
+    msg.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    msg.extend_from_slice(body);
+    msg
+}
+
+pub fn sign_command(
+    signing_key: &SigningKey,
+    kind: CommandKind,
+    uid: &[u8],
+    sp_id: u32,
+    timestamp: u64,
+    body: Vec<u8>,
+) -> SignedCommand {
+    let msg = command_sig_msg(kind, uid, sp_id, timestamp, &body);
+    SignedCommand {
+        kind,
+        uid_b64: URL_SAFE_NO_PAD.encode(uid),
+        sp_id,
+        timestamp,
+        body,
+        sig: sign_detached(signing_key, &msg),
+    }
+}
+
+/// Check only the signature of `cmd` under `sig_pk`.
+pub fn verify_command(cmd: &SignedCommand, sig_pk: &[u8; 32]) -> Result<(), CommandError> {
+    let uid = URL_SAFE_NO_PAD
+        .decode(&cmd.uid_b64)
+        .map_err(|_| CommandError::Signature)?;
+    let msg = command_sig_msg(cmd.kind, &uid, cmd.sp_id, cmd.timestamp, &cmd.body);
+    verify_detached(sig_pk, &msg, &cmd.sig).map_err(|_| CommandError::Signature)
+}
+
+/// SP-side check shared by every state-changing command: addressed to
+/// `sp_id`, `timestamp` newer than `last_timestamp`, and signed under
+/// `sig_pk`.
+pub fn verify_command_for_sp(
+    sp_id: u32,
+    cmd: &SignedCommand,
+    sig_pk: &[u8; 32],
+    last_timestamp: u64,
+) -> Result<(), CommandError> {
+    if cmd.sp_id != sp_id {
+        return Err(CommandError::SpIdMismatch {
+            expected: sp_id,
+            got: cmd.sp_id,
+        });
+    }
+    if cmd.timestamp <= last_timestamp {
+        return Err(CommandError::StaleTimestamp {
+            last: last_timestamp,
+            got: cmd.timestamp,
+        });
+    }
+    verify_command(cmd, sig_pk)
+}
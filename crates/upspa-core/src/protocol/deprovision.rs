@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+
+use crate::hash::hash_suid;
+use crate::protocol::command::{
+    command_sig_msg, sign_command, verify_command_for_sp, CommandError, CommandKind,
+    SignedCommand,
+};
+use crate::protocol::{decrypt_cid, CipherId};
+use crate::sign::SigningKey;
+use crate::toprf::validate_sp_ids;
+use crate::types::UpspaError;
+
+/// Signed request asking one SP to drop the user's setup record and the
+/// listed records.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeprovisionSpMessage {
+    pub uid_b64: String,
+    pub sp_id: u32,
+    pub timestamp: u64,
+
+    #[serde(with = "serde_big_array::BigArray")]
+    pub sig: [u8; 64],
+
+    /// `SUid_{i,j}` of every record to delete at this SP.
+    pub suids: Vec<[u8; 32]>,
+}
+
+impl DeprovisionSpMessage {
+    /// This request as a [`CommandKind::Deprovision`] envelope.
+    pub fn command(&self) -> SignedCommand {
+        SignedCommand {
+            kind: CommandKind::Deprovision,
+            uid_b64: self.uid_b64.clone(),
+            sp_id: self.sp_id,
+            timestamp: self.timestamp,
+            body: deprovision_command_body(&self.suids),
+            sig: self.sig,
+        }
+    }
+}
+
+/// Body of a deprovision command: `count_le(4) || suid(32)*count`.
+pub fn deprovision_command_body(suids: &[[u8; 32]]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(4 + 32 * suids.len());
+    body.extend_from_slice(&(suids.len() as u32).to_le_bytes());
+    for suid in suids {
+        body.extend_from_slice(suid);
+    }
+    body
+}
+
+/// Bytes signed for one SP's deprovision request: a
+/// [`CommandKind::Deprovision`] envelope around [`deprovision_command_body`].
+pub fn deprovision_sig_msg(uid: &[u8], timestamp: u64, sp_id: u32, suids: &[[u8; 32]]) -> Vec<u8> {
+    command_sig_msg(
+        CommandKind::Deprovision,
+        uid,
+        sp_id,
+        timestamp,
+        &deprovision_command_body(suids),
+    )
+}
+
+pub fn sign_deprovision(
+    signing_key: &SigningKey,
+    uid: &[u8],
+    sp_id: u32,
+    timestamp: u64,
+    suids: Vec<[u8; 32]>,
+) -> DeprovisionSpMessage {
+    let cmd = sign_command(
+        signing_key,
+        CommandKind::Deprovision,
+        uid,
+        sp_id,
+        timestamp,
+        deprovision_command_body(&suids),
+    );
+    DeprovisionSpMessage {
+        uid_b64: cmd.uid_b64,
+        sp_id,
+        timestamp,
+        sig: cmd.sig,
+        suids,
+    }
+}
+
+/// Delete the account from every SP in `sp_ids`: each is sent its setup
+/// record's removal together with `SUid_{i,j}` for every LS in `lsjs`, so no
+/// record stays behind. Any SP that does not apply its message keeps the
+/// account; the message can be resent as long as nothing newer reached it.
+pub fn client_delete_account(
+    uid: &[u8],
+    password_state_key: &[u8; 32],
+    cid: &CipherId,
+    sp_ids: &[u32],
+    lsjs: &[Vec<u8>],
+    timestamp: u64,
+) -> Result<Vec<DeprovisionSpMessage>, UpspaError> {
+    validate_sp_ids(sp_ids)?;
+    let cid_pt = decrypt_cid(uid, password_state_key, cid)?;
+    Ok(sp_ids
+        .iter()
+        .map(|&sp_id| {
+            let suids = lsjs
+                .iter()
+                .map(|lsj| hash_suid(&cid_pt.rsp, lsj, sp_id))
+                .collect();
+            sign_deprovision(&cid_pt.signing_key, uid, sp_id, timestamp, suids)
+        })
+        .collect())
+}
+
+/// SP-side check, with the same sp_id and timestamp rules as Π5.
+pub fn verify_deprovision_for_sp(
+    sp_id: u32,
+    msg: &DeprovisionSpMessage,
+    sig_pk: &[u8; 32],
+    last_timestamp: u64,
+) -> Result<(), CommandError> {
+    verify_command_for_sp(sp_id, &msg.command(), sig_pk, last_timestamp)
+}
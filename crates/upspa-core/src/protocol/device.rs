@@ -0,0 +1,173 @@
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::aead::{xchacha_decrypt_detached_vec, xchacha_encrypt_detached_vec};
+use crate::protocol::command::{
+    sign_command, verify_command, CommandError, CommandKind, SignedCommand,
+};
+use crate::protocol::{parse_cipherid_pt, CidPlaintext, CipherId, CID_VERSION};
+use crate::sign::SigningKey;
+use crate::types::{b64_encode, UpspaError};
+
+/// `sp_id` of a revocation list: it is not addressed to one SP, and every SP
+/// and device checks the same signature.
+pub const DEVICE_REVOCATION_SP_ID: u32 = 0;
+
+/// The `cid` plaintext wrapped under a key held by one device, so the device
+/// can unlock without a TOPRF round. Stored on the device only.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeviceEnrollment {
+    pub device_id: [u8; 16],
+    /// Key that signs revocation lists; the one inside `wrapped`.
+    pub sig_pk: [u8; 32],
+    /// Unix time after which the enrollment no longer unlocks.
+    pub expires_at: u64,
+    pub wrapped: CipherId,
+}
+
+/// Devices the user has revoked, signed with the key inside `cid`. A newer
+/// list (`issued_at`) replaces an older one, so it must repeat every device
+/// that should stay revoked.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceRevocationList {
+    pub uid_b64: String,
+    pub issued_at: u64,
+    pub revoked: Vec<[u8; 16]>,
+
+    #[serde(with = "serde_big_array::BigArray")]
+    pub sig: [u8; 64],
+}
+
+impl DeviceRevocationList {
+    /// This list as a [`CommandKind::DeviceRevocation`] envelope.
+    pub fn command(&self) -> SignedCommand {
+        SignedCommand {
+            kind: CommandKind::DeviceRevocation,
+            uid_b64: self.uid_b64.clone(),
+            sp_id: DEVICE_REVOCATION_SP_ID,
+            timestamp: self.issued_at,
+            body: device_revocation_command_body(&self.revoked),
+            sig: self.sig,
+        }
+    }
+
+    pub fn is_revoked(&self, device_id: &[u8; 16]) -> bool {
+        self.revoked.contains(device_id)
+    }
+}
+
+/// AAD for a wrapped `cid` plaintext:
+/// `uid || "|device" || device_id(16) || sig_pk(32) || expires_at_le(8) || version(1)`.
+pub fn device_aad(uid: &[u8], device_id: &[u8; 16], sig_pk: &[u8; 32], expires_at: u64) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(uid.len() + 7 + 16 + 32 + 8 + 1);
+    aad.extend_from_slice(uid);
+    aad.extend_from_slice(b"|device");
+    aad.extend_from_slice(device_id);
+    aad.extend_from_slice(sig_pk);
+    aad.extend_from_slice(&expires_at.to_le_bytes());
+    aad.push(CID_VERSION);
+    aad
+}
+
+/// Body of a revocation list: `count_le(4) || device_id(16) * count`.
+pub fn device_revocation_command_body(revoked: &[[u8; 16]]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(4 + 16 * revoked.len());
+    body.extend_from_slice(&(revoked.len() as u32).to_le_bytes());
+    for id in revoked {
+        body.extend_from_slice(id);
+    }
+    body
+}
+
+/// Wrap `cid_pt` under `device_key` until `expires_at`. `device_key` should
+/// never leave the device, e.g. a platform keystore key.
+pub fn enroll_device<R: RngCore + CryptoRng>(
+    uid: &[u8],
+    cid_pt: &CidPlaintext,
+    device_key: &[u8; 32],
+    device_id: [u8; 16],
+    expires_at: u64,
+    rng: &mut R,
+) -> Result<DeviceEnrollment, UpspaError> {
+    let sig_pk = cid_pt.signing_key.verifying_key().to_bytes();
+    let pt = cid_pt.upgraded(&cid_pt.sp_ids);
+    let aad = device_aad(uid, &device_id, &sig_pk, expires_at);
+    Ok(DeviceEnrollment {
+        device_id,
+        sig_pk,
+        expires_at,
+        wrapped: xchacha_encrypt_detached_vec(device_key, &aad, &pt.to_bytes()?, rng),
+    })
+}
+
+/// Sign a revocation list listing every device in `revoked`.
+pub fn sign_device_revocations(
+    signing_key: &SigningKey,
+    uid: &[u8],
+    issued_at: u64,
+    revoked: Vec<[u8; 16]>,
+) -> DeviceRevocationList {
+    let cmd = sign_command(
+        signing_key,
+        CommandKind::DeviceRevocation,
+        uid,
+        DEVICE_REVOCATION_SP_ID,
+        issued_at,
+        device_revocation_command_body(&revoked),
+    );
+    DeviceRevocationList {
+        uid_b64: cmd.uid_b64,
+        issued_at,
+        revoked,
+        sig: cmd.sig,
+    }
+}
+
+/// Check the signature of `list` under `sig_pk`.
+pub fn verify_device_revocations(
+    list: &DeviceRevocationList,
+    sig_pk: &[u8; 32],
+) -> Result<(), CommandError> {
+    verify_command(&list.command(), sig_pk)
+}
+
+/// Unwrap `enrollment` on the device, without contacting any SP. Fails with
+/// [`UpspaError::DeviceExpired`] at or after `expires_at`, and with
+/// [`UpspaError::DeviceRevoked`] if `revocations` lists the device.
+///
+/// `revocations` is the newest list the device has seen; it must verify
+/// under the enrollment's `sig_pk`. The caller keeps the highest `issued_at`
+/// it accepted and refuses older lists, or an SP could hide a revocation by
+/// serving a stale one. After a key rotation, lists are signed by the new
+/// key and no longer verify, so existing enrollments stop unlocking once a
+/// new list reaches them.
+pub fn unlock_device(
+    uid: &[u8],
+    device_key: &[u8; 32],
+    enrollment: &DeviceEnrollment,
+    now: u64,
+    revocations: Option<&DeviceRevocationList>,
+) -> Result<CidPlaintext, UpspaError> {
+    let aad = device_aad(
+        uid,
+        &enrollment.device_id,
+        &enrollment.sig_pk,
+        enrollment.expires_at,
+    );
+    let pt = xchacha_decrypt_detached_vec(device_key, &aad, &enrollment.wrapped)?;
+    if now >= enrollment.expires_at {
+        return Err(UpspaError::DeviceExpired {
+            expires_at: enrollment.expires_at,
+        });
+    }
+    if let Some(list) = revocations {
+        if list.uid_b64 != b64_encode(uid) {
+            return Err(UpspaError::Signature);
+        }
+        verify_device_revocations(list, &enrollment.sig_pk).map_err(|_| UpspaError::Signature)?;
+        if list.is_revoked(&enrollment.device_id) {
+            return Err(UpspaError::DeviceRevoked);
+        }
+    }
+    parse_cipherid_pt(CID_VERSION, &pt)
+}
@@ -0,0 +1,143 @@
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::command::{
+    command_sig_msg, sign_command, verify_command_for_sp, CommandError, CommandKind,
+    SignedCommand,
+};
+use crate::protocol::{decrypt_cid, encrypt_cid, CidPlaintext, CipherId};
+use crate::secret::SecretBytes;
+use crate::sign::SigningKey;
+use crate::toprf::validate_threshold;
+use crate::types::UpspaError;
+
+/// Signed request asking one SP to replace the user's `sig_pk` and `cid`.
+/// `sig` is made with the key being retired.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeyRotationSpMessage {
+    pub uid_b64: String,
+    pub sp_id: u32,
+    pub timestamp: u64,
+
+    #[serde(with = "serde_big_array::BigArray")]
+    pub sig: [u8; 64],
+
+    pub sig_pk_new: [u8; 32],
+    pub cid_new: CipherId,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeyRotationOutput {
+    pub sig_pk_new: [u8; 32],
+    pub cid_new: CipherId,
+    pub per_sp: Vec<KeyRotationSpMessage>,
+}
+
+impl KeyRotationSpMessage {
+    /// This request as a [`CommandKind::KeyRotation`] envelope.
+    pub fn command(&self) -> SignedCommand {
+        SignedCommand {
+            kind: CommandKind::KeyRotation,
+            uid_b64: self.uid_b64.clone(),
+            sp_id: self.sp_id,
+            timestamp: self.timestamp,
+            body: key_rotation_command_body(&self.sig_pk_new, &self.cid_new),
+            sig: self.sig,
+        }
+    }
+}
+
+/// Body of a key rotation command:
+/// `sig_pk_new(32) || cid_new.nonce(24) || cid_new.ct || cid_new.tag(16)`.
+pub fn key_rotation_command_body(sig_pk_new: &[u8; 32], cid_new: &CipherId) -> Vec<u8> {
+    let mut body = Vec::with_capacity(32 + 40 + cid_new.ct.len());
+    body.extend_from_slice(sig_pk_new);
+    body.extend_from_slice(&cid_new.nonce);
+    body.extend_from_slice(&cid_new.ct);
+    body.extend_from_slice(&cid_new.tag);
+    body
+}
+
+/// Bytes signed for one SP's key rotation request: a
+/// [`CommandKind::KeyRotation`] envelope around [`key_rotation_command_body`].
+pub fn key_rotation_sig_msg(
+    uid: &[u8],
+    sig_pk_new: &[u8; 32],
+    cid_new: &CipherId,
+    timestamp: u64,
+    sp_id: u32,
+) -> Vec<u8> {
+    command_sig_msg(
+        CommandKind::KeyRotation,
+        uid,
+        sp_id,
+        timestamp,
+        &key_rotation_command_body(sig_pk_new, cid_new),
+    )
+}
+
+/// Replace the Ed25519 signing key inside `cid`. The password, state key,
+/// `Rsp` and `K0` stay the same; `cid` is re-encrypted with the new key and
+/// each SP is sent the new `sig_pk`, authorized by the old key. `sp_ids` is
+/// recorded in `cid_new`, so it must be non-empty, non-zero and distinct.
+pub fn client_key_rotation<R: RngCore + CryptoRng>(
+    uid: &[u8],
+    password_state_key: &[u8; 32],
+    cid: &CipherId,
+    sp_ids: &[u32],
+    timestamp: u64,
+    rng: &mut R,
+) -> Result<KeyRotationOutput, UpspaError> {
+    validate_threshold(sp_ids, 1)?;
+    let cid_pt = decrypt_cid(uid, password_state_key, cid)?;
+    let mut ssk_bytes = SecretBytes::<32>::zero();
+    rng.fill_bytes(ssk_bytes.expose_mut());
+    let pt_new = CidPlaintext {
+        signing_key: SigningKey::from_bytes(&ssk_bytes),
+        ssk_bytes,
+        ..cid_pt.upgraded(sp_ids)
+    };
+    let sig_pk_new = pt_new.signing_key.verifying_key().to_bytes();
+    let cid_new = encrypt_cid(uid, password_state_key, &pt_new, rng)?;
+    let body = key_rotation_command_body(&sig_pk_new, &cid_new);
+
+    let per_sp = sp_ids
+        .iter()
+        .map(|sp_id| {
+            let cmd = sign_command(
+                &cid_pt.signing_key,
+                CommandKind::KeyRotation,
+                uid,
+                *sp_id,
+                timestamp,
+                body.clone(),
+            );
+            KeyRotationSpMessage {
+                uid_b64: cmd.uid_b64,
+                sp_id: *sp_id,
+                timestamp,
+                sig: cmd.sig,
+                sig_pk_new,
+                cid_new: cid_new.clone(),
+            }
+        })
+        .collect();
+
+    Ok(KeyRotationOutput {
+        sig_pk_new,
+        cid_new,
+        per_sp,
+    })
+}
+
+/// SP-side check against the currently stored `sig_pk`, with the same
+/// sp_id and timestamp rules as Π5. SPs should share the Π5 timestamp, so a
+/// rotation cannot be replayed to bring back a retired key.
+pub fn verify_key_rotation_for_sp(
+    sp_id: u32,
+    msg: &KeyRotationSpMessage,
+    sig_pk: &[u8; 32],
+    last_timestamp: u64,
+) -> Result<(), CommandError> {
+    verify_command_for_sp(sp_id, &msg.command(), sig_pk, last_timestamp)
+}
@@ -0,0 +1,32 @@
+use crate::protocol::{decrypt_cid, CidPlaintext, CipherId};
+use crate::toprf::{RobustToprfOutput, ToprfClient, ToprfClientState, ToprfPartial};
+use crate::types::UpspaError;
+
+/// Π2 finish that tolerates faulty SPs: combines `partials` robustly and only
+/// accepts a state key that actually opens `cid`.
+///
+/// Returns the robust output (state key plus inconsistent sp_ids) together
+/// with the decrypted `cid` plaintext.
+pub fn client_login_robust(
+    uid: &[u8],
+    password: &[u8],
+    state: &ToprfClientState,
+    partials: &[ToprfPartial],
+    tsp: usize,
+    commitments: Option<&[(u32, [u8; 32])]>,
+    cid: &CipherId,
+) -> Result<(RobustToprfOutput, CidPlaintext), UpspaError> {
+    let mut cid_pt = None;
+    let out = ToprfClient::finish_robust(password, state, partials, tsp, commitments, |key| {
+        match decrypt_cid(uid, key, cid) {
+            Ok(pt) => {
+                cid_pt = Some(pt);
+                true
+            }
+            Err(_) => false,
+        }
+    })?;
+
+    let cid_pt = cid_pt.ok_or(UpspaError::NoConsistentPartials)?;
+    Ok((out, cid_pt))
+}
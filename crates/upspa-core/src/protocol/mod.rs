@@ -1,15 +1,38 @@
+use std::fmt;
 use ed25519_dalek::SigningKey;
-use crate::aead::xchacha_decrypt_detached;
-use crate::types::{CtBlob, NONCE_LEN, TAG_LEN, UpspaError};
+use rand_core::{CryptoRng, RngCore};
+use crate::aead::{xchacha_decrypt_detached_vec, xchacha_encrypt_detached_vec};
+use crate::password_policy::PasswordPolicy;
+use crate::secret::SecretBytes;
+use serde::{Deserialize, Serialize};
+use crate::types::{CtVec, UpspaError, MAX_CT_LEN};
+use zeroize::{Zeroize, Zeroizing};
 pub mod authenticate;
+pub mod command;
+pub mod deprovision;
+pub mod device;
+pub mod key_rotation;
+pub mod login;
 pub mod password_update;
+pub mod reconfigure;
+pub mod records;
+pub mod recovery;
 pub mod register;
 pub mod secret_update;
 pub mod setup;
+pub mod share_refresh;
+/// Length of the v1 (unversioned) plaintexts; later versions are longer.
 pub const CIPHERID_PT_LEN: usize = 96;
 pub const CIPHERSP_PT_LEN: usize = 40;
-pub type CipherId = CtBlob<CIPHERID_PT_LEN>;
-pub type CipherSp = CtBlob<CIPHERSP_PT_LEN>;
+/// Formats written by this client. Older versions are still decrypted.
+pub const CID_VERSION: u8 = 2;
+pub const CJ_VERSION: u8 = 3;
+/// `kdf_id` for a password fed to the TOPRF as-is.
+pub const KDF_NONE: u8 = 0;
+/// `kdf_id` for a password hardened with Argon2id; see [`crate::kdf::PasswordKdf`].
+pub const KDF_ARGON2ID: u8 = 1;
+pub type CipherId = CtVec<CIPHERID_PT_LEN>;
+pub type CipherSp = CtVec<CIPHERSP_PT_LEN>;
 
 /// AAD for encrypting/decrypting `cid` (a.k.a. cipherid).
 pub fn cipherid_aad(uid: &[u8]) -> Vec<u8> {
@@ -27,84 +50,478 @@ pub fn ciphersp_aad(uid: &[u8]) -> Vec<u8> {
     aad
 }
 
+/// [`cipherid_aad`] for format `version`: `uid || "|cipherid" || "|v" || version`.
+/// v1 keeps the original unversioned AAD.
+pub fn cipherid_aad_v(uid: &[u8], version: u8) -> Vec<u8> {
+    let mut aad = cipherid_aad(uid);
+    if version > 1 {
+        aad.extend_from_slice(b"|v");
+        aad.push(version);
+    }
+    aad
+}
+
+/// [`ciphersp_aad`] for format `version`, like [`cipherid_aad_v`].
+pub fn ciphersp_aad_v(uid: &[u8], version: u8) -> Vec<u8> {
+    let mut aad = ciphersp_aad(uid);
+    if version > 1 {
+        aad.extend_from_slice(b"|v");
+        aad.push(version);
+    }
+    aad
+}
+
 /// Plaintext contained inside `cid` (Π1 step 5).
 ///
-/// Layout: `ssk(32) || Rsp(32) || K0(32)`.
+/// v1 layout: `ssk(32) || Rsp(32) || K0(32)`.
+/// v2 layout: `version(1) || kdf_id(1) || ssk(32) || Rsp(32) || K0(32) || count_le(2) || sp_id_le(4)*count`.
 #[derive(Clone, Debug)]
 pub struct CidPlaintext {
-    pub ssk_bytes: [u8; 32],
+    pub version: u8,
+    /// How the password is pre-processed before the TOPRF; [`KDF_NONE`] in v1.
+    pub kdf_id: u8,
+    pub ssk_bytes: SecretBytes<32>,
     pub signing_key: SigningKey,
-    pub rsp: [u8; 32],
-    pub k0: [u8; 32],
+    pub rsp: SecretBytes<32>,
+    pub k0: SecretBytes<32>,
+    /// SPs the account is provisioned on; empty in v1.
+    pub sp_ids: Vec<u32>,
 }
 
 impl CidPlaintext {
-    pub fn to_bytes(&self) -> [u8; CIPHERID_PT_LEN] {
-        let mut pt = [0u8; CIPHERID_PT_LEN];
-        pt[0..32].copy_from_slice(&self.ssk_bytes);
-        pt[32..64].copy_from_slice(&self.rsp);
-        pt[64..96].copy_from_slice(&self.k0);
-        pt
+    pub fn new(ssk_bytes: [u8; 32], rsp: [u8; 32], k0: [u8; 32], sp_ids: &[u32]) -> Self {
+        Self {
+            version: CID_VERSION,
+            kdf_id: KDF_NONE,
+            ssk_bytes: SecretBytes::new(ssk_bytes),
+            signing_key: SigningKey::from_bytes(&ssk_bytes),
+            rsp: SecretBytes::new(rsp),
+            k0: SecretBytes::new(k0),
+            sp_ids: sp_ids.to_vec(),
+        }
+    }
+
+    /// Encode in the layout of `self.version`; the buffer is wiped when
+    /// dropped. Fails with [`UpspaError::InvalidLength`] if `sp_ids` does
+    /// not fit its 2-byte count.
+    pub fn to_bytes(&self) -> Result<Zeroizing<Vec<u8>>, UpspaError> {
+        let mut pt = Zeroizing::new(Vec::with_capacity(
+            CIPHERID_PT_LEN + 4 + 4 * self.sp_ids.len(),
+        ));
+        if self.version > 1 {
+            pt.push(self.version);
+            pt.push(self.kdf_id);
+        }
+        pt.extend_from_slice(self.ssk_bytes.expose());
+        pt.extend_from_slice(self.rsp.expose());
+        pt.extend_from_slice(self.k0.expose());
+        if self.version > 1 {
+            let count =
+                u16::try_from(self.sp_ids.len()).map_err(|_| UpspaError::InvalidLength {
+                    expected: u16::MAX as usize,
+                    got: self.sp_ids.len(),
+                })?;
+            pt.extend_from_slice(&count.to_le_bytes());
+            for id in &self.sp_ids {
+                pt.extend_from_slice(&id.to_le_bytes());
+            }
+        }
+        Ok(pt)
+    }
+
+    /// The same secrets in the current format, recording `sp_ids`.
+    pub fn upgraded(&self, sp_ids: &[u32]) -> Self {
+        Self {
+            version: CID_VERSION,
+            sp_ids: sp_ids.to_vec(),
+            ..self.clone()
+        }
     }
 }
 
-pub fn parse_cipherid_pt(pt: &[u8; CIPHERID_PT_LEN]) -> CidPlaintext {
-    let mut ssk_bytes = [0u8; 32];
-    ssk_bytes.copy_from_slice(&pt[0..32]);
+/// Parse a decrypted `cid` plaintext of format `version`.
+pub fn parse_cipherid_pt(version: u8, pt: &[u8]) -> Result<CidPlaintext, UpspaError> {
+    let (kdf_id, body) = match version {
+        1 => (KDF_NONE, pt),
+        2 => {
+            if pt.len() < 2 + CIPHERID_PT_LEN + 2 {
+                return Err(UpspaError::InvalidLength {
+                    expected: 2 + CIPHERID_PT_LEN + 2,
+                    got: pt.len(),
+                });
+            }
+            if pt[0] != version {
+                return Err(UpspaError::UnsupportedVersion(pt[0]));
+            }
+            (pt[1], &pt[2..])
+        }
+        v => return Err(UpspaError::UnsupportedVersion(v)),
+    };
+
+    let mut ssk_bytes = SecretBytes::<32>::zero();
+    ssk_bytes.expose_mut().copy_from_slice(&body[0..32]);
 
-    let mut rsp = [0u8; 32];
-    rsp.copy_from_slice(&pt[32..64]);
+    let mut rsp = SecretBytes::<32>::zero();
+    rsp.expose_mut().copy_from_slice(&body[32..64]);
 
-    let mut k0 = [0u8; 32];
-    k0.copy_from_slice(&pt[64..96]);
+    let mut k0 = SecretBytes::<32>::zero();
+    k0.expose_mut().copy_from_slice(&body[64..96]);
+
+    let mut sp_ids = Vec::new();
+    if version > 1 {
+        let count = u16::from_le_bytes([body[96], body[97]]) as usize;
+        let ids = &body[98..];
+        if ids.len() != 4 * count {
+            return Err(UpspaError::InvalidLength {
+                expected: 2 + CIPHERID_PT_LEN + 2 + 4 * count,
+                got: pt.len(),
+            });
+        }
+        sp_ids = ids
+            .chunks_exact(4)
+            .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect();
+    } else if body.len() != CIPHERID_PT_LEN {
+        return Err(UpspaError::InvalidLength {
+            expected: CIPHERID_PT_LEN,
+            got: body.len(),
+        });
+    }
 
     let signing_key = SigningKey::from_bytes(&ssk_bytes);
 
-    CidPlaintext {
+    Ok(CidPlaintext {
+        version,
+        kdf_id,
         ssk_bytes,
         signing_key,
         rsp,
         k0,
-    }
+        sp_ids,
+    })
 }
 
-/// Decrypt `cid` using the derived `state_key`.
+/// Encrypt `pt` under `state_key`, binding its format version in the AAD.
+/// Fails if `pt` cannot be encoded, see [`CidPlaintext::to_bytes`].
+pub fn encrypt_cid<R: RngCore + CryptoRng>(
+    uid: &[u8],
+    state_key: &[u8; 32],
+    pt: &CidPlaintext,
+    rng: &mut R,
+) -> Result<CipherId, UpspaError> {
+    let aad = cipherid_aad_v(uid, pt.version);
+    Ok(xchacha_encrypt_detached_vec(state_key, &aad, &pt.to_bytes()?, rng))
+}
+
+/// Decrypt `cid` using the derived `state_key`. Accepts every known format
+/// version; the version is authenticated through the AAD.
 pub fn decrypt_cid(
     uid: &[u8],
     state_key: &[u8; 32],
     cid: &CipherId,
 ) -> Result<CidPlaintext, UpspaError> {
-    let aad = cipherid_aad(uid);
-    let pt = xchacha_decrypt_detached(state_key, &aad, cid)?;
-    Ok(parse_cipherid_pt(&pt))
+    for version in (2..=CID_VERSION).rev() {
+        let aad = cipherid_aad_v(uid, version);
+        if let Ok(pt) = xchacha_decrypt_detached_vec(state_key, &aad, cid) {
+            return parse_cipherid_pt(version, &pt);
+        }
+    }
+    if cid.ct.len() != CIPHERID_PT_LEN {
+        return Err(UpspaError::Aead);
+    }
+    let pt = xchacha_decrypt_detached_vec(state_key, &cipherid_aad(uid), cid)?;
+    parse_cipherid_pt(1, &pt)
+}
+
+/// Re-encrypt `cid` in the current format under the same `state_key`.
+/// SPs only accept a new `cid` through Π5, which always writes the current
+/// format; this is for copies the client keeps itself.
+pub fn upgrade_cid<R: RngCore + CryptoRng>(
+    uid: &[u8],
+    state_key: &[u8; 32],
+    cid: &CipherId,
+    sp_ids: &[u32],
+    rng: &mut R,
+) -> Result<CipherId, UpspaError> {
+    let pt = decrypt_cid(uid, state_key, cid)?.upgraded(sp_ids);
+    encrypt_cid(uid, state_key, &pt, rng)
+}
+
+/// Typed per-LS secret stored alongside `R^{ls_j}` (v3 `c_j` and later).
+///
+/// Encoded as `kind(1) || body`:
+/// - `0` [`LsRecord::None`]: empty body; the LS only checks `vinfo`.
+/// - `1` [`LsRecord::Password`]: UTF-8 password for a legacy LS.
+/// - `2` [`LsRecord::Totp`]: `digits(1) || period_le(4) || seed`.
+/// - `3` [`LsRecord::RecoveryCodes`]: `(len_le(2) || UTF-8 code)*`.
+/// - `4` [`LsRecord::Policy`]: [`PasswordPolicy::to_bytes`]; the site password
+///   is rendered from `vinfo` with [`crate::password_policy::render_site_password`].
+///
+/// `Debug` leaves out the secrets, and they are wiped on drop.
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LsRecord {
+    #[default]
+    None,
+    Password { password: String },
+    Totp { seed: Vec<u8>, digits: u8, period: u32 },
+    RecoveryCodes { codes: Vec<String> },
+    Policy { policy: PasswordPolicy },
+}
+
+impl fmt::Debug for LsRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LsRecord::None => f.write_str("None"),
+            LsRecord::Password { .. } => f
+                .debug_struct("Password")
+                .field("password", &format_args!("REDACTED"))
+                .finish(),
+            LsRecord::Totp { digits, period, .. } => f
+                .debug_struct("Totp")
+                .field("seed", &format_args!("REDACTED"))
+                .field("digits", digits)
+                .field("period", period)
+                .finish(),
+            LsRecord::RecoveryCodes { codes } => f
+                .debug_struct("RecoveryCodes")
+                .field("codes", &format_args!("[REDACTED; {}]", codes.len()))
+                .finish(),
+            LsRecord::Policy { policy } => f.debug_struct("Policy").field("policy", policy).finish(),
+        }
+    }
+}
+
+impl Drop for LsRecord {
+    fn drop(&mut self) {
+        match self {
+            LsRecord::Password { password } => password.zeroize(),
+            LsRecord::Totp { seed, .. } => seed.zeroize(),
+            LsRecord::RecoveryCodes { codes } => codes.zeroize(),
+            LsRecord::None | LsRecord::Policy { .. } => {}
+        }
+    }
+}
+
+impl LsRecord {
+    /// Fails with [`UpspaError::InvalidRecord`] if a recovery code does not
+    /// fit its 2-byte length. The buffer is wiped when dropped, and sized up
+    /// front so no partial copy is left behind by a reallocation.
+    pub fn to_bytes(&self) -> Result<Zeroizing<Vec<u8>>, UpspaError> {
+        let mut out = Zeroizing::new(Vec::with_capacity(1 + self.body_len()));
+        match self {
+            LsRecord::None => out.push(0),
+            LsRecord::Password { password } => {
+                out.push(1);
+                out.extend_from_slice(password.as_bytes());
+            }
+            LsRecord::Totp {
+                seed,
+                digits,
+                period,
+            } => {
+                out.push(2);
+                out.push(*digits);
+                out.extend_from_slice(&period.to_le_bytes());
+                out.extend_from_slice(seed);
+            }
+            LsRecord::RecoveryCodes { codes } => {
+                out.push(3);
+                for code in codes {
+                    let len = u16::try_from(code.len()).map_err(|_| UpspaError::InvalidRecord)?;
+                    out.extend_from_slice(&len.to_le_bytes());
+                    out.extend_from_slice(code.as_bytes());
+                }
+            }
+            LsRecord::Policy { policy } => {
+                out.push(4);
+                out.extend_from_slice(&policy.to_bytes());
+            }
+        }
+        Ok(out)
+    }
+
+    /// Length of [`LsRecord::to_bytes`] after the kind byte.
+    fn body_len(&self) -> usize {
+        match self {
+            LsRecord::None => 0,
+            LsRecord::Password { password } => password.len(),
+            LsRecord::Totp { seed, .. } => 1 + 4 + seed.len(),
+            LsRecord::RecoveryCodes { codes } => codes.iter().map(|c| 2 + c.len()).sum(),
+            LsRecord::Policy { policy } => policy.to_bytes().len(),
+        }
+    }
+
+    pub fn from_bytes(input: &[u8]) -> Result<Self, UpspaError> {
+        let (&kind, body) = input.split_first().ok_or(UpspaError::InvalidRecord)?;
+        let utf8 = |b: &[u8]| String::from_utf8(b.to_vec()).map_err(|_| UpspaError::InvalidRecord);
+        match kind {
+            0 if body.is_empty() => Ok(LsRecord::None),
+            1 => Ok(LsRecord::Password {
+                password: utf8(body)?,
+            }),
+            2 if body.len() >= 5 => Ok(LsRecord::Totp {
+                digits: body[0],
+                period: u32::from_le_bytes([body[1], body[2], body[3], body[4]]),
+                seed: body[5..].to_vec(),
+            }),
+            3 => {
+                let mut codes = Vec::new();
+                let mut rest = body;
+                while !rest.is_empty() {
+                    if rest.len() < 2 {
+                        return Err(UpspaError::InvalidRecord);
+                    }
+                    let len = u16::from_le_bytes([rest[0], rest[1]]) as usize;
+                    let code = rest.get(2..2 + len).ok_or(UpspaError::InvalidRecord)?;
+                    codes.push(utf8(code)?);
+                    rest = &rest[2 + len..];
+                }
+                Ok(LsRecord::RecoveryCodes { codes })
+            }
+            4 => Ok(LsRecord::Policy {
+                policy: PasswordPolicy::from_bytes(body).map_err(|_| UpspaError::InvalidRecord)?,
+            }),
+            _ => Err(UpspaError::InvalidRecord),
+        }
+    }
 }
 
 /// Plaintext contained inside `c_j` / `c_{j,new}`.
 ///
-/// Layout: `R^{ls_j}(32) || ctr(8)`.
+/// v1 layout: `R^{ls_j}(32) || ctr(8)`.
+/// v2 layout: `version(1) || R^{ls_j}(32) || ctr(8)`.
+/// v3 layout: `version(1) || R^{ls_j}(32) || ctr(8) || record` (see [`LsRecord`]).
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct CipherSpPlaintext {
-    pub rlsj: [u8; 32],
+    pub version: u8,
+    pub rlsj: SecretBytes<32>,
     pub ctr: u64,
+    /// Always [`LsRecord::None`] before v3.
+    pub record: LsRecord,
+}
+
+impl CipherSpPlaintext {
+    pub fn new(rlsj: [u8; 32], ctr: u64) -> Self {
+        Self::with_record(rlsj, ctr, LsRecord::None)
+    }
+
+    pub fn with_record(rlsj: [u8; 32], ctr: u64, record: LsRecord) -> Self {
+        Self {
+            version: CJ_VERSION,
+            rlsj: SecretBytes::new(rlsj),
+            ctr,
+            record,
+        }
+    }
+
+    /// Encode in the layout of `self.version`; the buffer is wiped when
+    /// dropped.
+    pub fn to_bytes(&self) -> Result<Zeroizing<Vec<u8>>, UpspaError> {
+        let record = if self.version > 2 {
+            self.record.to_bytes()?
+        } else {
+            Zeroizing::new(Vec::new())
+        };
+        let mut pt = Zeroizing::new(Vec::with_capacity(1 + CIPHERSP_PT_LEN + record.len()));
+        if self.version > 1 {
+            pt.push(self.version);
+        }
+        pt.extend_from_slice(self.rlsj.expose());
+        pt.extend_from_slice(&self.ctr.to_le_bytes());
+        pt.extend_from_slice(&record);
+        Ok(pt)
+    }
 }
 
-pub fn parse_ciphersp_pt(pt: &[u8; CIPHERSP_PT_LEN]) -> CipherSpPlaintext {
-    let mut rlsj = [0u8; 32];
-    rlsj.copy_from_slice(&pt[0..32]);
+/// Parse a decrypted `c_j` plaintext of format `version`.
+pub fn parse_ciphersp_pt(version: u8, pt: &[u8]) -> Result<CipherSpPlaintext, UpspaError> {
+    let body = match version {
+        1 => pt,
+        2 | 3 => {
+            if pt.first() != Some(&version) {
+                return Err(UpspaError::UnsupportedVersion(pt.first().copied().unwrap_or(0)));
+            }
+            &pt[1..]
+        }
+        v => return Err(UpspaError::UnsupportedVersion(v)),
+    };
+    let (body, record) = if version > 2 {
+        let (body, record) = body.split_at(body.len().min(CIPHERSP_PT_LEN));
+        (body, LsRecord::from_bytes(record)?)
+    } else {
+        (body, LsRecord::None)
+    };
+    if body.len() != CIPHERSP_PT_LEN {
+        return Err(UpspaError::InvalidLength {
+            expected: CIPHERSP_PT_LEN,
+            got: body.len(),
+        });
+    }
+
+    let mut rlsj = SecretBytes::<32>::zero();
+    rlsj.expose_mut().copy_from_slice(&body[0..32]);
 
     let mut ctr_bytes = [0u8; 8];
-    ctr_bytes.copy_from_slice(&pt[32..40]);
+    ctr_bytes.copy_from_slice(&body[32..40]);
     let ctr = u64::from_le_bytes(ctr_bytes);
 
-    CipherSpPlaintext { rlsj, ctr }
+    Ok(CipherSpPlaintext {
+        version,
+        rlsj,
+        ctr,
+        record,
+    })
+}
+
+/// Encrypt `pt` under `K0`, binding its format version in the AAD. Fails
+/// with [`UpspaError::InvalidLength`] if the plaintext is longer than an SP
+/// stores.
+pub fn encrypt_cj<R: RngCore + CryptoRng>(
+    uid: &[u8],
+    k0: &[u8; 32],
+    pt: &CipherSpPlaintext,
+    rng: &mut R,
+) -> Result<CipherSp, UpspaError> {
+    let bytes = pt.to_bytes()?;
+    if bytes.len() > MAX_CT_LEN {
+        return Err(UpspaError::InvalidLength {
+            expected: MAX_CT_LEN,
+            got: bytes.len(),
+        });
+    }
+    let aad = ciphersp_aad_v(uid, pt.version);
+    Ok(xchacha_encrypt_detached_vec(k0, &aad, &bytes, rng))
 }
 
+/// Decrypt `c_j`, accepting every known format version.
 pub fn decrypt_cj(
     uid: &[u8],
     k0: &[u8; 32],
     cj: &CipherSp,
 ) -> Result<CipherSpPlaintext, UpspaError> {
-    let aad = ciphersp_aad(uid);
-    let pt = xchacha_decrypt_detached(k0, &aad, cj)?;
-    Ok(parse_ciphersp_pt(&pt))
+    for version in (2..=CJ_VERSION).rev() {
+        let aad = ciphersp_aad_v(uid, version);
+        if let Ok(pt) = xchacha_decrypt_detached_vec(k0, &aad, cj) {
+            return parse_ciphersp_pt(version, &pt);
+        }
+    }
+    if cj.ct.len() != CIPHERSP_PT_LEN {
+        return Err(UpspaError::Aead);
+    }
+    let pt = xchacha_decrypt_detached_vec(k0, &ciphersp_aad(uid), cj)?;
+    parse_ciphersp_pt(1, &pt)
+}
+
+/// Re-encrypt `c_j` in the current format; the result can be written back
+/// with a plain record update.
+pub fn upgrade_cj<R: RngCore + CryptoRng>(
+    uid: &[u8],
+    k0: &[u8; 32],
+    cj: &CipherSp,
+    rng: &mut R,
+) -> Result<CipherSp, UpspaError> {
+    let mut pt = decrypt_cj(uid, k0, cj)?;
+    pt.version = CJ_VERSION;
+    encrypt_cj(uid, k0, &pt, rng)
 }
@@ -2,15 +2,41 @@ use rand_core::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
 use base64::Engine;
 use base64::engine::general_purpose::URL_SAFE_NO_PAD;
-use crate::aead::xchacha_encrypt_detached;
-use crate::hash::{hash_to_point, oprf_finalize};
-use crate::protocol::{cipherid_aad, decrypt_cid, CipherId, CIPHERID_PT_LEN};
-use crate::sign::sign_detached;
-use crate::toprf::toprf_gen;
+use crate::kdf::PasswordKdf;
+use crate::protocol::command::{
+    command_sig_msg, sign_command, verify_command, verify_command_for_sp, CommandError,
+    CommandKind, SignedCommand,
+};
+use crate::protocol::{decrypt_cid, encrypt_cid, CidPlaintext, CipherId, CIPHERID_PT_LEN};
+use crate::secret::SecretBytes;
+use crate::suite::OprfSuite;
+use crate::sign::{sign_detached, verify_detached, SigningKey};
+use crate::toprf::{toprf_commitments, toprf_gen_for_ids, toprf_share_commitment};
 use crate::types::UpspaError;
-pub const PWD_UPDATE_SIG_MSG_LEN: usize = 24 + 96 + 16 + 32 + 8 + 4;
+/// Length of [`pwd_update_sig_msg`] for a v1 `cid_new`. Longer v2 `cid`s
+/// give a longer message.
+pub const PWD_UPDATE_SIG_MSG_LEN: usize = 24 + CIPHERID_PT_LEN + 16 + 32 + 8 + 4;
+const PWD_UPDATE_ACK_TAG: &[u8] = b"uptspa/pwd-update-ack";
 
+/// Which bytes a Π5 `sig` covers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SigFormat {
+    /// The original [`pwd_update_sig_msg`] layout. It is byte-identical to
+    /// the fixed [`PWD_UPDATE_SIG_MSG_LEN`]-byte message only for a v1
+    /// `cid_new`; an SP that assumes that length cannot verify a v2 `cid`.
+    #[default]
+    Legacy,
+    /// A [`CommandKind::PasswordUpdate`] envelope around
+    /// [`password_update_command_body`].
+    Command,
+}
 
+impl SigFormat {
+    pub fn is_legacy(&self) -> bool {
+        *self == SigFormat::Legacy
+    }
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PasswordUpdateSpMessage {
@@ -21,77 +47,442 @@ pub struct PasswordUpdateSpMessage {
     #[serde(with = "serde_big_array::BigArray")]
     pub sig: [u8; 64],
 
-    #[serde(with = "serde_big_array::BigArray")]
-    pub k_i_new: [u8; 32],
+    pub k_i_new: SecretBytes<32>,
 
     pub cid_new: CipherId,
+
+    #[serde(default)]
+    pub format: SigFormat,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PasswordUpdateOutput {
     pub cid_new: CipherId,
     pub per_sp: Vec<PasswordUpdateSpMessage>,
+    /// Commitments to the new shares; replace the ones stored at setup.
+    pub commitments: Vec<(u32, [u8; 32])>,
+    /// Hardening of the new password; replaces the one kept from setup.
+    #[serde(default)]
+    pub kdf: PasswordKdf,
+    /// OPRF suite of the new shares; replaces the one kept from setup.
+    #[serde(default)]
+    pub suite: OprfSuite,
+}
+
+/// Why an SP must reject a Π5 request.
+#[derive(Clone, Debug, thiserror::Error, PartialEq, Eq)]
+pub enum PasswordUpdateError {
+    #[error("sp_id mismatch: expected {expected}, got {got}")]
+    SpIdMismatch { expected: u32, got: u32 },
+
+    #[error("stale timestamp: {got} is not greater than last update {last}")]
+    StaleTimestamp { last: u64, got: u64 },
+
+    #[error("invalid password-update signature")]
+    Signature,
+
+    #[error("timestamp {got} does not name the pending update ({last})")]
+    NotPending { last: u64, got: u64 },
+}
+
+impl From<CommandError> for PasswordUpdateError {
+    fn from(e: CommandError) -> Self {
+        match e {
+            CommandError::SpIdMismatch { expected, got } => {
+                PasswordUpdateError::SpIdMismatch { expected, got }
+            }
+            CommandError::StaleTimestamp { last, got } => {
+                PasswordUpdateError::StaleTimestamp { last, got }
+            }
+            CommandError::Signature => PasswordUpdateError::Signature,
+        }
+    }
+}
+
+/// An SP's signed statement that it now holds `cid_new` and the share
+/// behind `K_i_new`, returned for each accepted Π5 request.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PasswordUpdateAck {
+    pub sp_id: u32,
+    pub timestamp: u64,
+
+    #[serde(with = "serde_big_array::BigArray")]
+    pub sig: [u8; 64],
+}
+
+/// What an SP does with the share set a Π5 update replaced.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PasswordUpdateAction {
+    /// The new password works: drop the old `cid`/`k_i` for good.
+    Commit,
+    /// Too few SPs took the update: restore the old `cid`/`k_i`.
+    Rollback,
+}
+
+impl PasswordUpdateAction {
+    fn tag_byte(self) -> u8 {
+        match self {
+            PasswordUpdateAction::Commit => 1,
+            PasswordUpdateAction::Rollback => 2,
+        }
+    }
+}
+
+/// Signed request closing the Π5 update made at `timestamp`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PasswordUpdateFinalizeSpMessage {
+    pub uid_b64: String,
+    pub sp_id: u32,
+    pub timestamp: u64,
+    pub action: PasswordUpdateAction,
+
+    #[serde(with = "serde_big_array::BigArray")]
+    pub sig: [u8; 64],
+}
+
+/// Bytes signed for one SP's Π5 request.
+///
+/// Layout: `cid_new.nonce(24) || cid_new.ct || cid_new.tag(16) || k_i_new(32) || timestamp_le(8) || sp_id_le(4)`.
+/// `cid_new.ct` is 96 bytes for a v1 `cid`, giving [`PWD_UPDATE_SIG_MSG_LEN`].
+pub fn pwd_update_sig_msg(
+    cid_new: &CipherId,
+    k_i_new: &[u8; 32],
+    timestamp: u64,
+    sp_id: u32,
+) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(PWD_UPDATE_SIG_MSG_LEN - CIPHERID_PT_LEN + cid_new.ct.len());
+    msg.extend_from_slice(&cid_new.nonce);
+    msg.extend_from_slice(&cid_new.ct);
+    msg.extend_from_slice(&cid_new.tag);
+    msg.extend_from_slice(k_i_new);
+    msg.extend_from_slice(&timestamp.to_le_bytes());
+    msg.extend_from_slice(&sp_id.to_le_bytes());
+    msg
+}
+
+/// Body of a Π5 command: `cid_new.nonce(24) || cid_new.ct || cid_new.tag(16) || k_i_new(32)`.
+pub fn password_update_command_body(cid_new: &CipherId, k_i_new: &[u8; 32]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(40 + cid_new.ct.len() + 32);
+    body.extend_from_slice(&cid_new.nonce);
+    body.extend_from_slice(&cid_new.ct);
+    body.extend_from_slice(&cid_new.tag);
+    body.extend_from_slice(k_i_new);
+    body
+}
+
+impl PasswordUpdateSpMessage {
+    /// This request as a [`CommandKind::PasswordUpdate`] envelope. Only
+    /// meaningful when `format` is [`SigFormat::Command`].
+    pub fn command(&self) -> SignedCommand {
+        SignedCommand {
+            kind: CommandKind::PasswordUpdate,
+            uid_b64: self.uid_b64.clone(),
+            sp_id: self.sp_id,
+            timestamp: self.timestamp,
+            body: password_update_command_body(&self.cid_new, &self.k_i_new),
+            sig: self.sig,
+        }
+    }
+}
+
+/// Sign one SP's Π5 request in the given `format`.
+pub fn sign_password_update(
+    signing_key: &SigningKey,
+    uid: &[u8],
+    sp_id: u32,
+    timestamp: u64,
+    cid_new: &CipherId,
+    k_i_new: SecretBytes<32>,
+    format: SigFormat,
+) -> PasswordUpdateSpMessage {
+    let sig = match format {
+        SigFormat::Legacy => sign_detached(
+            signing_key,
+            &pwd_update_sig_msg(cid_new, &k_i_new, timestamp, sp_id),
+        ),
+        SigFormat::Command => {
+            let body = password_update_command_body(cid_new, &k_i_new);
+            sign_command(signing_key, CommandKind::PasswordUpdate, uid, sp_id, timestamp, body).sig
+        }
+    };
+    PasswordUpdateSpMessage {
+        uid_b64: URL_SAFE_NO_PAD.encode(uid),
+        sp_id,
+        timestamp,
+        sig,
+        k_i_new,
+        cid_new: cid_new.clone(),
+        format,
+    }
 }
 
+/// SP-side Π5 check: `timestamp` must be newer than `last_timestamp` and
+/// `sig` must verify under `sig_pk` over the message rebuilt from `msg`
+/// (which binds `msg.sp_id`) in `msg.format`.
+pub fn verify_password_update(
+    msg: &PasswordUpdateSpMessage,
+    sig_pk: &[u8; 32],
+    last_timestamp: u64,
+) -> Result<(), PasswordUpdateError> {
+    if msg.timestamp <= last_timestamp {
+        return Err(PasswordUpdateError::StaleTimestamp {
+            last: last_timestamp,
+            got: msg.timestamp,
+        });
+    }
 
+    match msg.format {
+        SigFormat::Legacy => {
+            let sig_msg = pwd_update_sig_msg(&msg.cid_new, &msg.k_i_new, msg.timestamp, msg.sp_id);
+            verify_detached(sig_pk, &sig_msg, &msg.sig).map_err(|_| PasswordUpdateError::Signature)
+        }
+        SigFormat::Command => Ok(verify_command(&msg.command(), sig_pk)?),
+    }
+}
+
+/// Like [`verify_password_update`], but also requires the request to be
+/// addressed to `sp_id`, so a message signed for one SP cannot be replayed
+/// at another.
+pub fn verify_password_update_for_sp(
+    sp_id: u32,
+    msg: &PasswordUpdateSpMessage,
+    sig_pk: &[u8; 32],
+    last_timestamp: u64,
+) -> Result<(), PasswordUpdateError> {
+    match msg.format {
+        SigFormat::Legacy => {
+            if msg.sp_id != sp_id {
+                return Err(PasswordUpdateError::SpIdMismatch {
+                    expected: sp_id,
+                    got: msg.sp_id,
+                });
+            }
+            verify_password_update(msg, sig_pk, last_timestamp)
+        }
+        SigFormat::Command => {
+            Ok(verify_command_for_sp(sp_id, &msg.command(), sig_pk, last_timestamp)?)
+        }
+    }
+}
+
+/// Bytes an SP signs to acknowledge a Π5 request.
+///
+/// Layout: `"uptspa/pwd-update-ack"(21) || uid_len_le(4) || uid || K_i_new(32) || cid_new.nonce(24) || cid_new.ct || cid_new.tag(16) || timestamp_le(8) || sp_id_le(4)`.
+/// SP keys are shared across users, so unlike the client's messages this
+/// one binds `uid`.
+pub fn pwd_update_ack_msg(
+    uid: &[u8],
+    cid_new: &CipherId,
+    k_i_commit: &[u8; 32],
+    timestamp: u64,
+    sp_id: u32,
+) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(
+        PWD_UPDATE_ACK_TAG.len() + 4 + uid.len() + 32 + 40 + cid_new.ct.len() + 12,
+    );
+    msg.extend_from_slice(PWD_UPDATE_ACK_TAG);
+    msg.extend_from_slice(&(uid.len() as u32).to_le_bytes());
+    msg.extend_from_slice(uid);
+    msg.extend_from_slice(k_i_commit);
+    msg.extend_from_slice(&cid_new.nonce);
+    msg.extend_from_slice(&cid_new.ct);
+    msg.extend_from_slice(&cid_new.tag);
+    msg.extend_from_slice(&timestamp.to_le_bytes());
+    msg.extend_from_slice(&sp_id.to_le_bytes());
+    msg
+}
+
+/// SP side: acknowledge an accepted Π5 request with the SP's own key.
+pub fn sign_password_update_ack(
+    sp_key: &SigningKey,
+    uid: &[u8],
+    msg: &PasswordUpdateSpMessage,
+) -> Result<PasswordUpdateAck, UpspaError> {
+    let k_i_commit = toprf_share_commitment(&msg.k_i_new)?;
+    let ack_msg = pwd_update_ack_msg(uid, &msg.cid_new, &k_i_commit, msg.timestamp, msg.sp_id);
+    Ok(PasswordUpdateAck {
+        sp_id: msg.sp_id,
+        timestamp: msg.timestamp,
+        sig: sign_detached(sp_key, &ack_msg),
+    })
+}
+
+/// Client side: check that `ack` is `sp_id`'s signature over the update made
+/// at `timestamp`, with `k_i_commit` taken from
+/// [`PasswordUpdateOutput::commitments`].
+pub fn verify_password_update_ack(
+    sp_pk: &[u8; 32],
+    uid: &[u8],
+    cid_new: &CipherId,
+    k_i_commit: &[u8; 32],
+    timestamp: u64,
+    sp_id: u32,
+    ack: &PasswordUpdateAck,
+) -> Result<(), UpspaError> {
+    if ack.sp_id != sp_id || ack.timestamp != timestamp {
+        return Err(UpspaError::Signature);
+    }
+    let ack_msg = pwd_update_ack_msg(uid, cid_new, k_i_commit, timestamp, sp_id);
+    verify_detached(sp_pk, &ack_msg, &ack.sig)
+}
+
+impl PasswordUpdateFinalizeSpMessage {
+    /// This request as a [`CommandKind::PasswordUpdateFinalize`] envelope.
+    pub fn command(&self) -> SignedCommand {
+        SignedCommand {
+            kind: CommandKind::PasswordUpdateFinalize,
+            uid_b64: self.uid_b64.clone(),
+            sp_id: self.sp_id,
+            timestamp: self.timestamp,
+            body: vec![self.action.tag_byte()],
+            sig: self.sig,
+        }
+    }
+}
+
+/// Bytes signed for one SP's finalize request: a
+/// [`CommandKind::PasswordUpdateFinalize`] envelope whose body is the single
+/// action byte, 1 for commit and 2 for rollback.
+pub fn pwd_update_finalize_sig_msg(
+    uid: &[u8],
+    action: PasswordUpdateAction,
+    timestamp: u64,
+    sp_id: u32,
+) -> Vec<u8> {
+    command_sig_msg(
+        CommandKind::PasswordUpdateFinalize,
+        uid,
+        sp_id,
+        timestamp,
+        &[action.tag_byte()],
+    )
+}
+
+pub fn sign_password_update_finalize(
+    signing_key: &SigningKey,
+    uid: &[u8],
+    sp_id: u32,
+    timestamp: u64,
+    action: PasswordUpdateAction,
+) -> PasswordUpdateFinalizeSpMessage {
+    let cmd = sign_command(
+        signing_key,
+        CommandKind::PasswordUpdateFinalize,
+        uid,
+        sp_id,
+        timestamp,
+        vec![action.tag_byte()],
+    );
+    PasswordUpdateFinalizeSpMessage {
+        uid_b64: cmd.uid_b64,
+        sp_id,
+        timestamp,
+        action,
+        sig: cmd.sig,
+    }
+}
+
+/// SP-side check for a finalize request: it must name the last applied
+/// update, i.e. `timestamp == last_timestamp`, so it can never touch a later
+/// one.
+pub fn verify_password_update_finalize_for_sp(
+    sp_id: u32,
+    msg: &PasswordUpdateFinalizeSpMessage,
+    sig_pk: &[u8; 32],
+    last_timestamp: u64,
+) -> Result<(), PasswordUpdateError> {
+    if msg.sp_id != sp_id {
+        return Err(PasswordUpdateError::SpIdMismatch {
+            expected: sp_id,
+            got: msg.sp_id,
+        });
+    }
+    if msg.timestamp != last_timestamp {
+        return Err(PasswordUpdateError::NotPending {
+            last: last_timestamp,
+            got: msg.timestamp,
+        });
+    }
+
+    Ok(verify_command(&msg.command(), sig_pk)?)
+}
+
+/// Π5 under an unhardened `new_password` and the BLAKE3 suite; see
+/// [`client_password_update_from_plaintext`] to choose a [`PasswordKdf`] or
+/// [`OprfSuite`].
+#[allow(clippy::too_many_arguments)]
 pub fn client_password_update<R: RngCore + CryptoRng>(
     uid: &[u8],
     old_password_state_key: &[u8; 32],
     cid_old: &CipherId,
-    nsp: usize,
+    sp_ids: &[u32],
     tsp: usize,
     new_password: &[u8],
     timestamp: u64,
     rng: &mut R,
 ) -> Result<PasswordUpdateOutput, UpspaError> {
-    assert!(tsp >= 1 && tsp <= nsp);
     let cid_pt = decrypt_cid(uid, old_password_state_key, cid_old)?;
-    let (new_master_sk, new_shares) = toprf_gen(nsp, tsp, rng);
-    let cipherid_pt_bytes: [u8; CIPHERID_PT_LEN] = cid_pt.to_bytes();
-    let signing_key = cid_pt.signing_key;
-    let p_new = hash_to_point(new_password);
+    client_password_update_from_plaintext(
+        uid,
+        &cid_pt,
+        sp_ids,
+        tsp,
+        new_password,
+        &PasswordKdf::None,
+        OprfSuite::Blake3,
+        timestamp,
+        rng,
+    )
+}
+
+/// Π5 starting from an already decrypted `cid`, e.g. one rebuilt by
+/// [`crate::protocol::recovery::recover_cid_plaintext`] when the old password
+/// is lost. `new_password` is hardened with `new_kdf`, which may differ from
+/// the account's current one: re-running Π5 with the same password and a new
+/// `new_kdf` or `new_suite` migrates the account.
+#[allow(clippy::too_many_arguments)]
+pub fn client_password_update_from_plaintext<R: RngCore + CryptoRng>(
+    uid: &[u8],
+    cid_pt: &CidPlaintext,
+    sp_ids: &[u32],
+    tsp: usize,
+    new_password: &[u8],
+    new_kdf: &PasswordKdf,
+    new_suite: OprfSuite,
+    timestamp: u64,
+    rng: &mut R,
+) -> Result<PasswordUpdateOutput, UpspaError> {
+    let new_password = new_kdf.harden(new_password)?;
+    let (new_master_sk, new_shares) = toprf_gen_for_ids(sp_ids, tsp, rng)?;
+    let signing_key = cid_pt.signing_key.clone();
+    let p_new = new_suite.hash_to_group(&new_password);
     let y_new = p_new * new_master_sk;
-    let new_state_key: [u8; 32] = oprf_finalize(new_password, &y_new);
-    let aad = cipherid_aad(uid);
-    let cid_new = xchacha_encrypt_detached(&new_state_key, &aad, &cipherid_pt_bytes, rng);
+    let new_state_key = SecretBytes::new(new_suite.finalize(&new_password, &y_new)?);
+    let pt_new = CidPlaintext {
+        kdf_id: new_kdf.id(),
+        ..cid_pt.upgraded(sp_ids)
+    };
+    let cid_new = encrypt_cid(uid, &new_state_key, &pt_new, rng)?;
     let mut per_sp = Vec::with_capacity(new_shares.len());
 
-// compute once before loop
-let uid_b64_str = URL_SAFE_NO_PAD.encode(uid);
-
-for (sp_id, share) in new_shares.iter() {
-    let k_i_new = share.to_bytes();
-
-    let mut msg = [0u8; PWD_UPDATE_SIG_MSG_LEN];
-    let mut off = 0;
-    msg[off..off + 24].copy_from_slice(&cid_new.nonce);
-    off += 24;
-    msg[off..off + 96].copy_from_slice(&cid_new.ct);
-    off += 96;
-    msg[off..off + 16].copy_from_slice(&cid_new.tag);
-    off += 16;
-    msg[off..off + 32].copy_from_slice(&k_i_new);
-    off += 32;
-    msg[off..off + 8].copy_from_slice(&timestamp.to_le_bytes());
-    off += 8;
-
-    // IMPORTANT: sp_id here is the share's SP id; convert to u32 for signing
-    let sp_id_u32: u32 = (*sp_id) as u32;
-    msg[off..off + 4].copy_from_slice(&sp_id_u32.to_le_bytes());
-    off += 4;
-    debug_assert_eq!(off, PWD_UPDATE_SIG_MSG_LEN);
-
-    let sig = sign_detached(&signing_key, &msg);
-
-    per_sp.push(PasswordUpdateSpMessage {
-        uid_b64: uid_b64_str.clone(),
-        sp_id: sp_id_u32,
-        timestamp,
-        sig,
-        k_i_new,
-        cid_new: cid_new.clone(),
-    });
-}
+    for (sp_id, share) in new_shares.iter() {
+        per_sp.push(sign_password_update(
+            &signing_key,
+            uid,
+            *sp_id,
+            timestamp,
+            &cid_new,
+            SecretBytes::new(share.to_bytes()),
+            SigFormat::Legacy,
+        ));
+    }
 
-Ok(PasswordUpdateOutput { cid_new, per_sp })
-}
\ No newline at end of file
+    Ok(PasswordUpdateOutput {
+        cid_new,
+        per_sp,
+        commitments: toprf_commitments(&new_shares),
+        kdf: new_kdf.clone(),
+        suite: new_suite,
+    })
+}
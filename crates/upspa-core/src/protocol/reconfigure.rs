@@ -0,0 +1,130 @@
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::hash::hash_suid;
+use crate::protocol::deprovision::{sign_deprovision, DeprovisionSpMessage};
+use crate::protocol::password_update::{sign_password_update, PasswordUpdateSpMessage, SigFormat};
+use crate::protocol::register::RegistrationSpMessage;
+use crate::protocol::setup::SetupSpPayload;
+use crate::protocol::{decrypt_cid, decrypt_cj, encrypt_cid, CipherId, CipherSp};
+use crate::secret::SecretBytes;
+use crate::suite::OprfSuite;
+use crate::toprf::{toprf_commitments, toprf_gen_for_ids, validate_sp_ids};
+use crate::types::UpspaError;
+
+/// A record the client already has: the LS identity and its `c_j`, as
+/// fetched from any current SP.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KnownRecord {
+    pub lsj: Vec<u8>,
+    pub cj: CipherSp,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReconfigureOutput {
+    pub cid_new: CipherId,
+    /// Commitments for the new share set; replace the stored ones.
+    pub commitments: Vec<(u32, [u8; 32])>,
+    /// Π1 payloads for SPs joining the set.
+    pub setups: Vec<SetupSpPayload>,
+    /// Records to create at joining SPs, under their own `SUid`.
+    pub record_copies: Vec<RegistrationSpMessage>,
+    /// Π5 requests for SPs that stay in the set.
+    pub updates: Vec<PasswordUpdateSpMessage>,
+    /// Deprovision requests for SPs leaving the set.
+    pub deletions: Vec<DeprovisionSpMessage>,
+}
+
+/// Move the account from `old_sp_ids` to `new_sp_ids` with threshold
+/// `new_tsp`.
+///
+/// The SPs cannot reshare `k` among themselves, so the client samples a fresh
+/// TOPRF key for the new set and re-encrypts the `cid` secrets, now listing
+/// `new_sp_ids`, under the state key derived from `password` with the
+/// account's `suite`. Passing a different password than the current one also
+/// performs a Π5 password change.
+///
+/// Apply in order: `setups`, `record_copies`, `updates`, then `deletions`, so
+/// the new set holds every record before anything is removed from the old one.
+#[allow(clippy::too_many_arguments)]
+pub fn client_reconfigure<R: RngCore + CryptoRng>(
+    uid: &[u8],
+    password: &[u8],
+    suite: OprfSuite,
+    password_state_key: &[u8; 32],
+    cid: &CipherId,
+    old_sp_ids: &[u32],
+    new_sp_ids: &[u32],
+    new_tsp: usize,
+    records: &[KnownRecord],
+    timestamp: u64,
+    rng: &mut R,
+) -> Result<ReconfigureOutput, UpspaError> {
+    validate_sp_ids(old_sp_ids)?;
+    let cid_pt = decrypt_cid(uid, password_state_key, cid)?;
+    for r in records {
+        decrypt_cj(uid, &cid_pt.k0, &r.cj)?;
+    }
+
+    let (master_sk, shares) = toprf_gen_for_ids(new_sp_ids, new_tsp, rng)?;
+    let y = suite.hash_to_group(password) * master_sk;
+    let state_key_new = suite.finalize(password, &y)?;
+    let cid_new = encrypt_cid(uid, &state_key_new, &cid_pt.upgraded(new_sp_ids), rng)?;
+
+    let sig_pk = cid_pt.signing_key.verifying_key().to_bytes();
+    let mut setups = Vec::new();
+    let mut record_copies = Vec::new();
+    let mut updates = Vec::new();
+
+    for (sp_id, share) in shares.iter() {
+        let k_i = SecretBytes::new(share.to_bytes());
+        if old_sp_ids.contains(sp_id) {
+            updates.push(sign_password_update(
+                &cid_pt.signing_key,
+                uid,
+                *sp_id,
+                timestamp,
+                &cid_new,
+                k_i,
+                SigFormat::Legacy,
+            ));
+            continue;
+        }
+
+        setups.push(SetupSpPayload {
+            sp_id: *sp_id,
+            uid: uid.to_vec(),
+            sig_pk,
+            cid: cid_new.clone(),
+            k_i,
+        });
+        for r in records {
+            record_copies.push(RegistrationSpMessage {
+                sp_id: *sp_id,
+                suid: hash_suid(&cid_pt.rsp, &r.lsj, *sp_id),
+                cj: r.cj.clone(),
+            });
+        }
+    }
+
+    let deletions = old_sp_ids
+        .iter()
+        .filter(|id| !new_sp_ids.contains(id))
+        .map(|&sp_id| {
+            let suids = records
+                .iter()
+                .map(|r| hash_suid(&cid_pt.rsp, &r.lsj, sp_id))
+                .collect();
+            sign_deprovision(&cid_pt.signing_key, uid, sp_id, timestamp, suids)
+        })
+        .collect();
+
+    Ok(ReconfigureOutput {
+        cid_new,
+        commitments: toprf_commitments(&shares),
+        setups,
+        record_copies,
+        updates,
+        deletions,
+    })
+}
@@ -0,0 +1,139 @@
+use std::cmp::Reverse;
+
+use crate::protocol::{decrypt_cj, CipherSp, CipherSpPlaintext};
+use crate::toprf::validate_sp_ids;
+use crate::types::UpspaError;
+
+/// The newest `c_j` among the copies read from the SPs, as chosen by
+/// [`select_record`].
+#[derive(Clone, Debug)]
+pub struct RecordSelection {
+    pub pt: CipherSpPlaintext,
+    pub cj: CipherSp,
+    /// `ctr` of each SP's copy that opened, in input order.
+    pub sp_ctrs: Vec<(u32, u64)>,
+    /// SPs whose copy differs from `cj`: an older `ctr`, or a different
+    /// `R^{ls_j}` under the same `ctr`.
+    pub stale: Vec<u32>,
+    /// SPs whose copy did not decrypt or parse.
+    pub invalid: Vec<u32>,
+}
+
+impl RecordSelection {
+    /// Record updates that bring every stale or invalid SP to `cj`.
+    pub fn repairs(&self) -> Vec<(u32, CipherSp)> {
+        self.stale
+            .iter()
+            .chain(&self.invalid)
+            .map(|id| (*id, self.cj.clone()))
+            .collect()
+    }
+}
+
+/// One SP's `c_j` together with its plaintext.
+#[derive(Clone, Debug)]
+pub struct OpenedRecord<'a> {
+    pub sp_id: u32,
+    pub pt: CipherSpPlaintext,
+    pub cj: &'a CipherSp,
+}
+
+/// Decrypt each SP's `c_j`, setting aside the ones that fail. Returns the
+/// opened records and the sp_ids whose blobs did not open; fails only if
+/// none did.
+pub fn open_records<'a>(
+    uid: &[u8],
+    k0: &[u8; 32],
+    cjs: &'a [(u32, CipherSp)],
+) -> Result<(Vec<OpenedRecord<'a>>, Vec<u32>), UpspaError> {
+    if cjs.is_empty() {
+        return Err(UpspaError::InvalidLength {
+            expected: 1,
+            got: 0,
+        });
+    }
+
+    let (mut opened, mut invalid) = (Vec::with_capacity(cjs.len()), Vec::new());
+    for (sp_id, cj) in cjs {
+        match decrypt_cj(uid, k0, cj) {
+            Ok(pt) => opened.push(OpenedRecord {
+                sp_id: *sp_id,
+                pt,
+                cj,
+            }),
+            Err(_) => invalid.push(*sp_id),
+        }
+    }
+    if opened.is_empty() {
+        return Err(UpspaError::InvalidRecords(invalid));
+    }
+    Ok((opened, invalid))
+}
+
+/// Pick the record with the highest `ctr` from `(sp_id, c_j)` pairs and
+/// require at least `quorum` SPs to hold exactly that record. Blobs that do
+/// not open are skipped and reported in `invalid`.
+///
+/// An SP can replay an older `c_j` it once held, but cannot forge a newer
+/// one, so a rollback shows up as a lower `ctr` and lands in `stale`.
+pub fn select_record(
+    uid: &[u8],
+    k0: &[u8; 32],
+    cjs: &[(u32, CipherSp)],
+    quorum: usize,
+) -> Result<RecordSelection, UpspaError> {
+    let ids: Vec<u32> = cjs.iter().map(|(id, _)| *id).collect();
+    validate_sp_ids(&ids)?;
+    if quorum == 0 {
+        return Err(UpspaError::InvalidThreshold {
+            tsp: quorum,
+            nsp: cjs.len(),
+        });
+    }
+
+    let (opened, invalid) = open_records(uid, k0, cjs)?;
+    let newest = newest_record(&opened);
+
+    let stale: Vec<u32> = opened
+        .iter()
+        .filter(|r| !same_record(r, newest))
+        .map(|r| r.sp_id)
+        .collect();
+    let confirmed = opened.len() - stale.len();
+    if confirmed < quorum {
+        return Err(UpspaError::RecordQuorum {
+            ctr: newest.pt.ctr,
+            got: confirmed,
+            needed: quorum,
+            invalid,
+        });
+    }
+
+    Ok(RecordSelection {
+        pt: newest.pt.clone(),
+        cj: newest.cj.clone(),
+        sp_ctrs: opened.iter().map(|r| (r.sp_id, r.pt.ctr)).collect(),
+        stale,
+        invalid,
+    })
+}
+
+/// The record with the highest `ctr` among the non-empty output of
+/// [`open_records`]. Different records under the same `ctr` go to the one
+/// more SPs hold, then to the one held by the lowest sp_id, so the choice
+/// does not depend on the order the SPs answered in.
+pub fn newest_record<'r, 'a>(opened: &'r [OpenedRecord<'a>]) -> &'r OpenedRecord<'a> {
+    opened
+        .iter()
+        .max_by_key(|r| (r.pt.ctr, holders(opened, r), Reverse(r.sp_id)))
+        .expect("open_records returns at least one record")
+}
+
+/// Whether `a` and `b` are the same record.
+pub fn same_record(a: &OpenedRecord<'_>, b: &OpenedRecord<'_>) -> bool {
+    (a.pt.ctr, &a.pt.rlsj) == (b.pt.ctr, &b.pt.rlsj)
+}
+
+fn holders(opened: &[OpenedRecord<'_>], r: &OpenedRecord<'_>) -> usize {
+    opened.iter().filter(|o| same_record(o, r)).count()
+}
@@ -0,0 +1,236 @@
+use std::fmt;
+use std::str::FromStr;
+
+use curve25519_dalek::scalar::Scalar;
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::hash::hash_recovery_key;
+use crate::protocol::command::{
+    command_sig_msg, sign_command, verify_command_for_sp, CommandError, CommandKind,
+    SignedCommand,
+};
+use crate::protocol::{decrypt_cid, encrypt_cid, CidPlaintext, CipherId};
+use crate::secret::SecretBytes;
+use crate::sign::SigningKey;
+use crate::toprf::{lagrange_coeffs_at_zero, scalar_from_canonical_bytes, toprf_gen_for_ids};
+use crate::types::{b64_decode, b64_encode, UpspaError};
+
+const RECOVERY_CODE_PREFIX: &str = "upspa-rc1-";
+const RECOVERY_CODE_CHECK_LEN: usize = 4;
+
+/// One Shamir share of the recovery secret, for printing or handing to a
+/// trusted contact. Any `threshold` codes of a kit rebuild `cid`.
+///
+/// Printed as `upspa-rc1-<id>-<b64url(share || check)>`, where `check` is
+/// 4 bytes of a hash over `id` and `share` that catches typos.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecoveryCode {
+    pub id: u32,
+    pub share: SecretBytes<32>,
+}
+
+impl RecoveryCode {
+    fn check(&self) -> [u8; RECOVERY_CODE_CHECK_LEN] {
+        let mut h = blake3::Hasher::new();
+        h.update(b"uptspa/recovery-code");
+        h.update(&self.id.to_le_bytes());
+        h.update(self.share.expose());
+        let mut out = [0u8; RECOVERY_CODE_CHECK_LEN];
+        out.copy_from_slice(&h.finalize().as_bytes()[..RECOVERY_CODE_CHECK_LEN]);
+        out
+    }
+}
+
+impl fmt::Display for RecoveryCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut payload = self.share.expose().to_vec();
+        payload.extend_from_slice(&self.check());
+        write!(
+            f,
+            "{RECOVERY_CODE_PREFIX}{}-{}",
+            self.id,
+            b64_encode(&payload)
+        )
+    }
+}
+
+impl FromStr for RecoveryCode {
+    type Err = UpspaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (id, payload) = s
+            .trim()
+            .strip_prefix(RECOVERY_CODE_PREFIX)
+            .and_then(|rest| rest.split_once('-'))
+            .ok_or(UpspaError::InvalidRecoveryCode)?;
+        let id: u32 = id.parse().map_err(|_| UpspaError::InvalidRecoveryCode)?;
+        let payload = b64_decode(payload).map_err(|_| UpspaError::InvalidRecoveryCode)?;
+        if id == 0 || payload.len() != 32 + RECOVERY_CODE_CHECK_LEN {
+            return Err(UpspaError::InvalidRecoveryCode);
+        }
+        let mut share = SecretBytes::<32>::zero();
+        share.expose_mut().copy_from_slice(&payload[..32]);
+        let code = RecoveryCode { id, share };
+        if code.check()[..] != payload[32..] {
+            return Err(UpspaError::InvalidRecoveryCode);
+        }
+        Ok(code)
+    }
+}
+
+/// Signed request asking one SP to hold `recovery_cid` for the user.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecoverySpMessage {
+    pub uid_b64: String,
+    pub sp_id: u32,
+    pub timestamp: u64,
+
+    #[serde(with = "serde_big_array::BigArray")]
+    pub sig: [u8; 64],
+
+    pub recovery_cid: CipherId,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecoveryKit {
+    /// The `cid` plaintext encrypted under the recovery key; every SP keeps
+    /// a copy.
+    pub recovery_cid: CipherId,
+    pub codes: Vec<RecoveryCode>,
+    pub threshold: usize,
+    pub per_sp: Vec<RecoverySpMessage>,
+}
+
+impl RecoverySpMessage {
+    /// This request as a [`CommandKind::RecoveryEscrow`] envelope.
+    pub fn command(&self) -> SignedCommand {
+        SignedCommand {
+            kind: CommandKind::RecoveryEscrow,
+            uid_b64: self.uid_b64.clone(),
+            sp_id: self.sp_id,
+            timestamp: self.timestamp,
+            body: recovery_command_body(&self.recovery_cid),
+            sig: self.sig,
+        }
+    }
+}
+
+/// Body of a recovery escrow command:
+/// `recovery_cid.nonce(24) || recovery_cid.ct || recovery_cid.tag(16)`.
+pub fn recovery_command_body(recovery_cid: &CipherId) -> Vec<u8> {
+    let mut body = Vec::with_capacity(40 + recovery_cid.ct.len());
+    body.extend_from_slice(&recovery_cid.nonce);
+    body.extend_from_slice(&recovery_cid.ct);
+    body.extend_from_slice(&recovery_cid.tag);
+    body
+}
+
+/// Bytes signed for one SP's recovery escrow request.
+pub fn recovery_sig_msg(
+    uid: &[u8],
+    recovery_cid: &CipherId,
+    timestamp: u64,
+    sp_id: u32,
+) -> Vec<u8> {
+    command_sig_msg(
+        CommandKind::RecoveryEscrow,
+        uid,
+        sp_id,
+        timestamp,
+        &recovery_command_body(recovery_cid),
+    )
+}
+
+pub fn sign_recovery(
+    signing_key: &SigningKey,
+    uid: &[u8],
+    sp_id: u32,
+    timestamp: u64,
+    recovery_cid: &CipherId,
+) -> RecoverySpMessage {
+    let cmd = sign_command(
+        signing_key,
+        CommandKind::RecoveryEscrow,
+        uid,
+        sp_id,
+        timestamp,
+        recovery_command_body(recovery_cid),
+    );
+    RecoverySpMessage {
+        uid_b64: cmd.uid_b64,
+        sp_id,
+        timestamp,
+        sig: cmd.sig,
+        recovery_cid: recovery_cid.clone(),
+    }
+}
+
+/// Split a fresh recovery secret into `n_codes` codes, any `threshold` of
+/// which open a copy of the `cid` plaintext, and sign the copy for each SP.
+///
+/// The copy holds the current signing key, so a later key rotation makes the
+/// kit useless; SPs drop it then and a new kit must be made.
+#[allow(clippy::too_many_arguments)]
+pub fn client_recovery_setup<R: RngCore + CryptoRng>(
+    uid: &[u8],
+    password_state_key: &[u8; 32],
+    cid: &CipherId,
+    sp_ids: &[u32],
+    n_codes: usize,
+    threshold: usize,
+    timestamp: u64,
+    rng: &mut R,
+) -> Result<RecoveryKit, UpspaError> {
+    let cid_pt = decrypt_cid(uid, password_state_key, cid)?;
+    let code_ids: Vec<u32> = (1..=n_codes as u32).collect();
+    let (secret, shares) = toprf_gen_for_ids(&code_ids, threshold, rng)?;
+    let recovery_key = SecretBytes::new(hash_recovery_key(&secret.to_bytes()));
+    let recovery_cid = encrypt_cid(uid, &recovery_key, &cid_pt.upgraded(sp_ids), rng)?;
+
+    let per_sp = sp_ids
+        .iter()
+        .map(|sp_id| sign_recovery(&cid_pt.signing_key, uid, *sp_id, timestamp, &recovery_cid))
+        .collect();
+    Ok(RecoveryKit {
+        recovery_cid,
+        codes: shares
+            .into_iter()
+            .map(|(id, share)| RecoveryCode {
+                id,
+                share: SecretBytes::new(share.to_bytes()),
+            })
+            .collect(),
+        threshold,
+        per_sp,
+    })
+}
+
+/// Rebuild the `cid` plaintext from `recovery_cid` and at least `threshold`
+/// codes of the same kit. Too few or mismatched codes fail with
+/// [`UpspaError::Aead`]. Follow up with
+/// [`crate::protocol::password_update::client_password_update_from_plaintext`]
+/// to set a new password.
+pub fn recover_cid_plaintext(
+    uid: &[u8],
+    recovery_cid: &CipherId,
+    codes: &[RecoveryCode],
+) -> Result<CidPlaintext, UpspaError> {
+    let ids: Vec<u32> = codes.iter().map(|c| c.id).collect();
+    let lambdas = lagrange_coeffs_at_zero(&ids)?;
+    let mut secret = Scalar::ZERO;
+    for (code, lambda) in codes.iter().zip(lambdas) {
+        secret += lambda * scalar_from_canonical_bytes(&code.share)?;
+    }
+    decrypt_cid(uid, &hash_recovery_key(&secret.to_bytes()), recovery_cid)
+}
+
+/// SP-side check, with the same sp_id and timestamp rules as Π5.
+pub fn verify_recovery_for_sp(
+    sp_id: u32,
+    msg: &RecoverySpMessage,
+    sig_pk: &[u8; 32],
+    last_timestamp: u64,
+) -> Result<(), CommandError> {
+    verify_command_for_sp(sp_id, &msg.command(), sig_pk, last_timestamp)
+}
@@ -1,9 +1,10 @@
 use rand_core::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
 
-use crate::aead::xchacha_encrypt_detached;
 use crate::hash::{hash_suid, hash_vinfo};
-use crate::protocol::{ciphersp_aad, decrypt_cid, CipherId, CipherSp, CIPHERSP_PT_LEN};
+use crate::protocol::{decrypt_cid, encrypt_cj, CipherId, CipherSp, CipherSpPlaintext, LsRecord};
+use crate::secret::SecretBytes;
+use crate::toprf::validate_sp_ids;
 use crate::types::UpspaError;
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RegistrationSpMessage {
@@ -14,7 +15,7 @@ pub struct RegistrationSpMessage {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RegistrationLsMessage {
     pub uid: Vec<u8>,
-    pub vinfo: [u8; 32],
+    pub vinfo: SecretBytes<32>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -22,31 +23,33 @@ pub struct RegistrationOutput {
     pub per_sp: Vec<RegistrationSpMessage>,
     pub to_ls: RegistrationLsMessage,
 }
+/// Π3 for `lsj`. `record` is stored in `c_j` next to `R^{ls_j}` and comes
+/// back from [`crate::protocol::authenticate::client_auth_finish`]; pass
+/// [`LsRecord::None`] for an LS that only checks `vinfo`.
 pub fn client_register<R: RngCore + CryptoRng>(
     uid: &[u8],
     lsj: &[u8],
     password_state_key: &[u8; 32],
     cid: &CipherId,
-    nsp: usize,
+    sp_ids: &[u32],
+    record: &LsRecord,
     rng: &mut R,
 ) -> Result<RegistrationOutput, UpspaError> {
+    validate_sp_ids(sp_ids)?;
     let cid_pt = decrypt_cid(uid, password_state_key, cid)?;
     let rsp = cid_pt.rsp;
     let k0 = cid_pt.k0;
-    let mut per_sp = Vec::with_capacity(nsp);
-    let mut rlsj = [0u8; 32];
-    rng.fill_bytes(&mut rlsj);
+    let mut per_sp = Vec::with_capacity(sp_ids.len());
+    let mut rlsj = SecretBytes::<32>::zero();
+    rng.fill_bytes(rlsj.expose_mut());
     let ctr: u64 = 0;
-    let mut ciphersp_pt = [0u8; CIPHERSP_PT_LEN];
-    ciphersp_pt[0..32].copy_from_slice(&rlsj);
-    ciphersp_pt[32..40].copy_from_slice(&ctr.to_le_bytes());
-    let aad = ciphersp_aad(uid);
-    let cj = xchacha_encrypt_detached(&k0, &aad, &ciphersp_pt, rng);
+    let pt = CipherSpPlaintext::with_record(*rlsj, ctr, record.clone());
+    let cj = encrypt_cj(uid, &k0, &pt, rng)?;
     let vinfo = hash_vinfo(&rlsj, lsj);
-    for i in 1..=nsp {
-        let suid = hash_suid(&rsp, lsj, i as u32);
+    for &sp_id in sp_ids {
+        let suid = hash_suid(&rsp, lsj, sp_id);
         per_sp.push(RegistrationSpMessage {
-            sp_id: i as u32,
+            sp_id,
             suid,
             cj: cj.clone(),
         });
@@ -54,7 +57,7 @@ pub fn client_register<R: RngCore + CryptoRng>(
 
     let to_ls = RegistrationLsMessage {
         uid: uid.to_vec(),
-        vinfo,
+        vinfo: SecretBytes::new(vinfo),
     };
 
     Ok(RegistrationOutput { per_sp, to_ls })
@@ -1,24 +1,30 @@
+use std::cmp::Reverse;
+
 use rand_core::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
 
-use crate::aead::xchacha_encrypt_detached;
 use crate::hash::{hash_suid, hash_vinfo};
-use crate::protocol::{ciphersp_aad, decrypt_cid, decrypt_cj, CipherId, CipherSp, CIPHERSP_PT_LEN};
+use crate::protocol::records::{newest_record, open_records, same_record, select_record};
+use crate::protocol::{decrypt_cid, encrypt_cj, CipherId, CipherSp, CipherSpPlaintext};
+use crate::secret::SecretBytes;
+use crate::toprf::validate_sp_ids;
 use crate::types::UpspaError;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SecretUpdateQueries {
-    pub k0: [u8; 32],
+    pub k0: SecretBytes<32>,
     pub per_sp: Vec<(u32, [u8; 32])>, // (sp_id, SUid_{i,j})
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SecretUpdateOutput {
-    pub vinfo_prime: [u8; 32],
-    pub vinfo_new: [u8; 32],
+    pub vinfo_prime: SecretBytes<32>,
+    pub vinfo_new: SecretBytes<32>,
     pub cj_new: CipherSp,
     pub old_ctr: u64,
     pub new_ctr: u64,
+    /// SPs whose `c_j` did not decrypt; `cj_new` replaces it as well.
+    pub invalid: Vec<u32>,
 }
 
 pub fn client_secret_update_prepare(
@@ -26,64 +32,46 @@ pub fn client_secret_update_prepare(
     lsj: &[u8],
     password_state_key: &[u8; 32],
     cid: &CipherId,
-    nsp: usize,
+    sp_ids: &[u32],
 ) -> Result<SecretUpdateQueries, UpspaError> {
+    validate_sp_ids(sp_ids)?;
     let cid_pt = decrypt_cid(uid, password_state_key, cid)?;
     let rsp = cid_pt.rsp;
     let k0 = cid_pt.k0;
 
-    let mut per_sp = Vec::with_capacity(nsp);
-    for i in 1..=nsp {
-        let suid = hash_suid(&rsp, lsj, i as u32);
-        per_sp.push((i as u32, suid));
+    let mut per_sp = Vec::with_capacity(sp_ids.len());
+    for &sp_id in sp_ids {
+        let suid = hash_suid(&rsp, lsj, sp_id);
+        per_sp.push((sp_id, suid));
     }
 
     Ok(SecretUpdateQueries { k0, per_sp })
 }
+/// Π4 secret update from the SPs' `(sp_id, c_j)` copies: rotate
+/// `R^{ls_j}` on top of the newest record, which at least `quorum` SPs must
+/// hold.
 pub fn client_secret_update_finish<R: RngCore + CryptoRng>(
     uid: &[u8],
     lsj: &[u8],
     k0: &[u8; 32],
-    cjs: &[CipherSp],
+    cjs: &[(u32, CipherSp)],
+    quorum: usize,
     rng: &mut R,
 ) -> Result<SecretUpdateOutput, UpspaError> {
-    if cjs.is_empty() {
-        return Err(UpspaError::InvalidLength {
-            expected: 1,
-            got: 0,
-        });
-    }
-
-    let mut old_ctr: u64 = 0;
-    let mut old_rlsj = [0u8; 32];
-    let mut any_ok = false;
-
-    for cj in cjs {
-        let pt = decrypt_cj(uid, k0, cj)?;
-        any_ok = true;
-        if pt.ctr >= old_ctr {
-            old_ctr = pt.ctr;
-            old_rlsj = pt.rlsj;
-        }
-    }
-
-    if !any_ok {
-        return Err(UpspaError::Aead);
-    }
+    let sel = select_record(uid, k0, cjs, quorum)?;
+    let (old_ctr, old_rlsj, record) = (sel.pt.ctr, sel.pt.rlsj, sel.pt.record);
+    let invalid = sel.invalid;
 
-    let vinfo_prime = hash_vinfo(&old_rlsj, lsj);
-    let mut new_rlsj = [0u8; 32];
-    rng.fill_bytes(&mut new_rlsj);
+    let vinfo_prime = SecretBytes::new(hash_vinfo(&old_rlsj, lsj));
+    let mut new_rlsj = SecretBytes::<32>::zero();
+    rng.fill_bytes(new_rlsj.expose_mut());
     let new_ctr = old_ctr.wrapping_add(1);
 
-    let mut pt = [0u8; CIPHERSP_PT_LEN];
-    pt[0..32].copy_from_slice(&new_rlsj);
-    pt[32..40].copy_from_slice(&new_ctr.to_le_bytes());
+    // The per-LS record is carried over unchanged.
+    let pt = CipherSpPlaintext::with_record(*new_rlsj, new_ctr, record);
+    let cj_new = encrypt_cj(uid, k0, &pt, rng)?;
 
-    let aad = ciphersp_aad(uid);
-    let cj_new = xchacha_encrypt_detached(k0, &aad, &pt, rng);
-
-    let vinfo_new = hash_vinfo(&new_rlsj, lsj);
+    let vinfo_new = SecretBytes::new(hash_vinfo(&new_rlsj, lsj));
 
     Ok(SecretUpdateOutput {
         vinfo_prime,
@@ -91,5 +79,63 @@ pub fn client_secret_update_finish<R: RngCore + CryptoRng>(
         cj_new,
         old_ctr,
         new_ctr,
+        invalid,
+    })
+}
+
+/// The `c_j` copies held by each SP, as decrypted by [`survey_records`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordSurvey {
+    /// Highest `ctr` seen, its `c_j` and the `vinfo` it yields.
+    pub latest_ctr: u64,
+    pub latest: CipherSp,
+    pub vinfo_latest: SecretBytes<32>,
+    /// SPs holding an older `c_j`, or another one under the same `ctr`, with
+    /// their `ctr`.
+    pub stale: Vec<(u32, u64)>,
+    /// `vinfo` of the newest record other than `latest`, if any SP has one.
+    pub vinfo_previous: Option<SecretBytes<32>>,
+    /// SPs whose `c_j` did not decrypt.
+    pub invalid: Vec<u32>,
+}
+
+impl RecordSurvey {
+    pub fn is_consistent(&self) -> bool {
+        self.stale.is_empty()
+    }
+}
+
+/// Compare the `ctr` in each SP's `c_j` to find a half-applied secret update:
+/// SPs whose copy differs from the newest one, as picked by
+/// [`newest_record`], are listed in `stale`, and blobs that do not decrypt
+/// in `invalid`.
+pub fn survey_records(
+    uid: &[u8],
+    lsj: &[u8],
+    k0: &[u8; 32],
+    cjs: &[(u32, CipherSp)],
+) -> Result<RecordSurvey, UpspaError> {
+    let (opened, invalid) = open_records(uid, k0, cjs)?;
+    let newest = newest_record(&opened);
+    let latest_ctr = newest.pt.ctr;
+
+    let stale: Vec<(u32, u64)> = opened
+        .iter()
+        .filter(|r| !same_record(r, newest))
+        .map(|r| (r.sp_id, r.pt.ctr))
+        .collect();
+    let vinfo_previous = opened
+        .iter()
+        .filter(|r| !same_record(r, newest))
+        .max_by_key(|r| (r.pt.ctr, Reverse(r.sp_id)))
+        .map(|r| SecretBytes::new(hash_vinfo(&r.pt.rlsj, lsj)));
+
+    Ok(RecordSurvey {
+        latest_ctr,
+        latest: newest.cj.clone(),
+        vinfo_latest: SecretBytes::new(hash_vinfo(&newest.pt.rlsj, lsj)),
+        stale,
+        vinfo_previous,
+        invalid,
     })
 }
@@ -1,61 +1,100 @@
 use ed25519_dalek::SigningKey;
 use rand_core::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
-use crate::aead::xchacha_encrypt_detached;
-use crate::hash::{hash_to_point, oprf_finalize};
-use crate::protocol::{cipherid_aad, CipherId, CIPHERID_PT_LEN};
-use crate::toprf::toprf_gen;
+use crate::kdf::PasswordKdf;
+use crate::protocol::{encrypt_cid, CidPlaintext, CipherId};
+use crate::secret::SecretBytes;
+use crate::suite::OprfSuite;
+use crate::toprf::{toprf_commitments, toprf_gen_for_ids};
+use crate::types::UpspaError;
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SetupSpPayload {
     pub sp_id: u32,
     pub uid: Vec<u8>,
     pub sig_pk: [u8; 32],
     pub cid: CipherId,
-    pub k_i: [u8; 32],
+    pub k_i: SecretBytes<32>,
 }
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SetupOutput {
     pub sig_pk: [u8; 32],
     pub cid: CipherId,
-    pub shares: Vec<(u32, [u8; 32])>,
+    pub shares: Vec<(u32, SecretBytes<32>)>,
+    /// `(sp_id, K_i = k_i * G)`; kept by the client to verify SP partials.
+    pub commitments: Vec<(u32, [u8; 32])>,
+    /// Password hardening the account was made with; login needs it.
+    #[serde(default)]
+    pub kdf: PasswordKdf,
+    /// OPRF suite the account was made with; login needs it.
+    #[serde(default)]
+    pub suite: OprfSuite,
 }
+/// Π1 for the SPs in `sp_ids` (stable, non-zero, distinct) with threshold `tsp`.
 pub fn client_setup<R: RngCore + CryptoRng>(
     uid: &[u8],
     password: &[u8],
-    nsp: usize,
+    sp_ids: &[u32],
     tsp: usize,
     rng: &mut R,
-) -> (SetupOutput, Vec<SetupSpPayload>) {
-    assert!(tsp >= 1 && tsp <= nsp);
-    let mut rsp = [0u8; 32];
-    rng.fill_bytes(&mut rsp);
-    let (master_sk, shares) = toprf_gen(nsp, tsp, rng);
+) -> Result<(SetupOutput, Vec<SetupSpPayload>), UpspaError> {
+    client_setup_with_kdf(uid, password, sp_ids, tsp, &PasswordKdf::None, rng)
+}
+
+/// [`client_setup`] with `password` hardened by `kdf` first.
+pub fn client_setup_with_kdf<R: RngCore + CryptoRng>(
+    uid: &[u8],
+    password: &[u8],
+    sp_ids: &[u32],
+    tsp: usize,
+    kdf: &PasswordKdf,
+    rng: &mut R,
+) -> Result<(SetupOutput, Vec<SetupSpPayload>), UpspaError> {
+    client_setup_with_suite(uid, password, sp_ids, tsp, kdf, OprfSuite::Blake3, rng)
+}
+
+/// [`client_setup_with_kdf`] under OPRF `suite`.
+pub fn client_setup_with_suite<R: RngCore + CryptoRng>(
+    uid: &[u8],
+    password: &[u8],
+    sp_ids: &[u32],
+    tsp: usize,
+    kdf: &PasswordKdf,
+    suite: OprfSuite,
+    rng: &mut R,
+) -> Result<(SetupOutput, Vec<SetupSpPayload>), UpspaError> {
+    let password = kdf.harden(password)?;
+    let mut rsp = SecretBytes::<32>::zero();
+    rng.fill_bytes(rsp.expose_mut());
+    let (master_sk, shares) = toprf_gen_for_ids(sp_ids, tsp, rng)?;
     let signing_key = SigningKey::generate(rng);
-    let ssk_bytes = signing_key.to_bytes();
+    let ssk_bytes = SecretBytes::new(signing_key.to_bytes());
     let sig_pk = signing_key.verifying_key().to_bytes();
-    let mut k0 = [0u8; 32];
-    rng.fill_bytes(&mut k0);
-    let p = hash_to_point(password);
+    let mut k0 = SecretBytes::<32>::zero();
+    rng.fill_bytes(k0.expose_mut());
+    let p = suite.hash_to_group(&password);
     let y = p * master_sk;
-    let state_key: [u8; 32] = oprf_finalize(password, &y);
+    let state_key = SecretBytes::new(suite.finalize(&password, &y)?);
 
-    let mut pt = [0u8; CIPHERID_PT_LEN];
-    pt[0..32].copy_from_slice(&ssk_bytes);
-    pt[32..64].copy_from_slice(&rsp);
-    pt[64..96].copy_from_slice(&k0);
-
-    let aad = cipherid_aad(uid);
-    let cid = xchacha_encrypt_detached(&state_key, &aad, &pt, rng);
+    let pt = CidPlaintext {
+        kdf_id: kdf.id(),
+        ..CidPlaintext::new(*ssk_bytes, *rsp, *k0, sp_ids)
+    };
+    let cid = encrypt_cid(uid, &state_key, &pt, rng)?;
 
-    let shares_bytes: Vec<(u32, [u8; 32])> = shares
+    let shares_bytes: Vec<(u32, SecretBytes<32>)> = shares
         .iter()
-        .map(|(id, s)| (*id, s.to_bytes()))
+        .map(|(id, s)| (*id, SecretBytes::new(s.to_bytes())))
         .collect();
 
+    let commitments = toprf_commitments(&shares);
+
     let out = SetupOutput {
         sig_pk,
         cid: cid.clone(),
         shares: shares_bytes.clone(),
+        commitments,
+        kdf: kdf.clone(),
+        suite,
     };
     let payloads = shares_bytes
         .iter()
@@ -64,9 +103,9 @@ pub fn client_setup<R: RngCore + CryptoRng>(
             uid: uid.to_vec(),
             sig_pk,
             cid: cid.clone(),
-            k_i: *share_bytes,
+            k_i: share_bytes.clone(),
         })
         .collect();
 
-    (out, payloads)
+    Ok((out, payloads))
 }
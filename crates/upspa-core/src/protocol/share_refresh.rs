@@ -0,0 +1,110 @@
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::command::{
+    command_sig_msg, sign_command, verify_command_for_sp, CommandError, CommandKind,
+    SignedCommand,
+};
+use crate::protocol::{decrypt_cid, CipherId};
+use crate::toprf::{toprf_commitments, toprf_zero_shares};
+use crate::types::UpspaError;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShareRefreshSpMessage {
+    pub uid_b64: String,
+    pub sp_id: u32,
+    pub timestamp: u64,
+
+    #[serde(with = "serde_big_array::BigArray")]
+    pub sig: [u8; 64],
+
+    /// Share of zero the SP adds to its `k_i`.
+    #[serde(with = "serde_big_array::BigArray")]
+    pub delta: [u8; 32],
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShareRefreshOutput {
+    pub per_sp: Vec<ShareRefreshSpMessage>,
+    /// `delta_i * G`; fold into the stored commitments with
+    /// [`crate::toprf::toprf_refresh_commitments`].
+    pub delta_commitments: Vec<(u32, [u8; 32])>,
+}
+
+impl ShareRefreshSpMessage {
+    /// This request as a [`CommandKind::ShareRefresh`] envelope; the body is
+    /// `delta`.
+    pub fn command(&self) -> SignedCommand {
+        SignedCommand {
+            kind: CommandKind::ShareRefresh,
+            uid_b64: self.uid_b64.clone(),
+            sp_id: self.sp_id,
+            timestamp: self.timestamp,
+            body: self.delta.to_vec(),
+            sig: self.sig,
+        }
+    }
+}
+
+/// Bytes signed for one SP's share refresh request: a
+/// [`CommandKind::ShareRefresh`] envelope with `delta(32)` as body.
+pub fn share_refresh_sig_msg(uid: &[u8], delta: &[u8; 32], timestamp: u64, sp_id: u32) -> Vec<u8> {
+    command_sig_msg(CommandKind::ShareRefresh, uid, sp_id, timestamp, delta)
+}
+
+/// SP-side check, with the same rules as
+/// [`crate::protocol::password_update::verify_password_update_for_sp`].
+/// SPs should share one monotonic timestamp between Π5 and refreshes, since
+/// replaying a delta would corrupt the share.
+pub fn verify_share_refresh_for_sp(
+    sp_id: u32,
+    msg: &ShareRefreshSpMessage,
+    sig_pk: &[u8; 32],
+    last_timestamp: u64,
+) -> Result<(), CommandError> {
+    verify_command_for_sp(sp_id, &msg.command(), sig_pk, last_timestamp)
+}
+
+/// Re-randomize the TOPRF shares around the same secret. The password, the
+/// state key and `cid` stay unchanged; each SP adds its `delta` to `k_i`.
+/// Every SP in `sp_ids` must apply the refresh, otherwise mixed old/new
+/// shares no longer interpolate to the secret.
+pub fn client_share_refresh<R: RngCore + CryptoRng>(
+    uid: &[u8],
+    password_state_key: &[u8; 32],
+    cid: &CipherId,
+    sp_ids: &[u32],
+    tsp: usize,
+    timestamp: u64,
+    rng: &mut R,
+) -> Result<ShareRefreshOutput, UpspaError> {
+    let cid_pt = decrypt_cid(uid, password_state_key, cid)?;
+    let deltas = toprf_zero_shares(sp_ids, tsp, rng)?;
+
+    let per_sp = deltas
+        .iter()
+        .map(|(sp_id, d)| {
+            let delta = d.to_bytes();
+            let cmd = sign_command(
+                &cid_pt.signing_key,
+                CommandKind::ShareRefresh,
+                uid,
+                *sp_id,
+                timestamp,
+                delta.to_vec(),
+            );
+            ShareRefreshSpMessage {
+                uid_b64: cmd.uid_b64,
+                sp_id: *sp_id,
+                timestamp,
+                sig: cmd.sig,
+                delta,
+            }
+        })
+        .collect();
+
+    Ok(ShareRefreshOutput {
+        per_sp,
+        delta_commitments: toprf_commitments(&deltas),
+    })
+}
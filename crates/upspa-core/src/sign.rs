@@ -1,4 +1,6 @@
-use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use ed25519_dalek::{Signature, Signer, Verifier, VerifyingKey};
+
+pub use ed25519_dalek::SigningKey;
 
 use crate::types::UpspaError;
 
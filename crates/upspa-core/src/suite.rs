@@ -0,0 +1,44 @@
+use curve25519_dalek::ristretto::RistrettoPoint;
+use serde::{Deserialize, Serialize};
+
+use crate::hash::{hash_to_point, oprf_finalize, rfc9497_finalize, rfc9497_hash_to_group};
+use crate::types::UpspaError;
+
+/// The hash-to-group and finalize pair the TOPRF runs under. SPs only
+/// multiply by their share, so the suite is a client-side choice; like
+/// [`crate::kdf::PasswordKdf`] it is kept next to the setup commitments and
+/// must match at every login.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OprfSuite {
+    /// BLAKE3 with this repo's own domain tags; accounts made before the
+    /// suite was selectable.
+    #[default]
+    #[serde(rename = "blake3")]
+    Blake3,
+    /// RFC 9497 ristretto255-SHA512 in OPRF mode.
+    #[serde(rename = "ristretto255-sha512")]
+    Ristretto255Sha512,
+}
+
+impl OprfSuite {
+    pub fn hash_to_group(&self, input: &[u8]) -> RistrettoPoint {
+        match self {
+            OprfSuite::Blake3 => hash_to_point(input),
+            OprfSuite::Ristretto255Sha512 => rfc9497_hash_to_group(input),
+        }
+    }
+
+    /// The state key for `input` and unblinded element `y`. The RFC suite
+    /// keeps the first 32 bytes of its 64-byte `Finalize` output, and fails
+    /// for inputs of 2^16 bytes or more.
+    pub fn finalize(&self, input: &[u8], y: &RistrettoPoint) -> Result<[u8; 32], UpspaError> {
+        match self {
+            OprfSuite::Blake3 => Ok(oprf_finalize(input, y)),
+            OprfSuite::Ristretto255Sha512 => {
+                let mut out = [0u8; 32];
+                out.copy_from_slice(&rfc9497_finalize(input, y)?[..32]);
+                Ok(out)
+            }
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use curve25519_dalek::{
+    constants::RISTRETTO_BASEPOINT_POINT,
     ristretto::{CompressedRistretto, RistrettoPoint},
     scalar::Scalar,
     traits::Identity,
@@ -6,36 +7,74 @@ use curve25519_dalek::{
 use rand_core::RngCore;
 use serde::{Deserialize, Serialize};
 
+use crate::dleq::{dleq_prove, dleq_verify, DleqProof};
 use crate::hash::{hash_to_point, oprf_finalize};
+use crate::secret::SecretBytes;
+use crate::suite::OprfSuite;
 use crate::types::UpspaError;
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ToprfPartial {
     pub id: u32,
     pub y: [u8; 32],
+    /// Present when the SP answered in verifiable mode.
+    #[serde(default)]
+    pub proof: Option<DleqProof>,
 }
 
+/// The blinding scalar between [`ToprfClient::begin`] and `finish`.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ToprfClientState {
-    pub r: [u8; 32],
+    pub r: SecretBytes<32>,
+    /// Suite `begin` blinded under; `finish` finalizes under the same one.
+    #[serde(default)]
+    pub suite: OprfSuite,
+}
+
+/// Result of [`ToprfClient::finish_robust`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RobustToprfOutput {
+    pub state_key: SecretBytes<32>,
+    /// sp_ids whose partials are malformed, fail their DLEQ proof, or do not
+    /// lie on the polynomial defined by the accepted set.
+    pub inconsistent: Vec<u32>,
 }
 
 pub struct ToprfClient;
 
 impl ToprfClient {
+    /// Blind `password`. For an account made with a
+    /// [`crate::kdf::PasswordKdf`], pass [`crate::kdf::PasswordKdf::harden`]'s
+    /// output here and to `finish`.
     pub fn begin(password: &[u8], rng: &mut impl RngCore) -> (ToprfClientState, [u8; 32]) {
+        Self::begin_with_suite(password, OprfSuite::Blake3, rng)
+    }
+
+    /// [`ToprfClient::begin`] under `suite`, the one the account was set up
+    /// with.
+    pub fn begin_with_suite(
+        password: &[u8],
+        suite: OprfSuite,
+        rng: &mut impl RngCore,
+    ) -> (ToprfClientState, [u8; 32]) {
         let r = random_scalar(rng);
-        let p = hash_to_point(password);
+        let p = suite.hash_to_group(password);
         let blinded = p * r;
         let blinded_bytes = blinded.compress().to_bytes();
-        (ToprfClientState { r: r.to_bytes() }, blinded_bytes)
+        (
+            ToprfClientState {
+                r: SecretBytes::new(r.to_bytes()),
+                suite,
+            },
+            blinded_bytes,
+        )
     }
 
     pub fn finish(
         password: &[u8],
         state: &ToprfClientState,
         partials: &[ToprfPartial],
-    ) -> Result<[u8; 32], UpspaError> {
+    ) -> Result<SecretBytes<32>, UpspaError> {
         if partials.is_empty() {
             return Err(UpspaError::InvalidLength {
                 expected: 1,
@@ -49,7 +88,7 @@ impl ToprfClient {
         }
 
         let ids: Vec<u32> = partials.iter().map(|p| p.id).collect();
-        let lambdas = lagrange_coeffs_at_zero(&ids);
+        let lambdas = lagrange_coeffs_at_zero(&ids)?;
 
         let mut acc = RistrettoPoint::identity();
         for (p, l) in partials.iter().zip(lambdas) {
@@ -58,7 +97,146 @@ impl ToprfClient {
         }
 
         let y = acc * r.invert();
-        Ok(oprf_finalize(password, &y))
+        Ok(SecretBytes::new(state.suite.finalize(password, &y)?))
+    }
+
+    /// Verifiable-mode [`ToprfClient::finish`]: every partial must carry a
+    /// DLEQ proof against the SP's setup-time commitment `K_i`. Fails with
+    /// `InvalidPartials` listing each sp_id whose proof is missing or wrong.
+    pub fn finish_verified(
+        password: &[u8],
+        state: &ToprfClientState,
+        partials: &[ToprfPartial],
+        commitments: &[(u32, [u8; 32])],
+    ) -> Result<SecretBytes<32>, UpspaError> {
+        let bad = Self::unverified_partials(password, state, partials, commitments)?;
+        if !bad.is_empty() {
+            return Err(UpspaError::InvalidPartials(bad));
+        }
+        Self::finish(password, state, partials)
+    }
+
+    /// Robust combine for when more than `tsp` partials arrived and some may
+    /// be wrong. Partials failing their proof (if `commitments` is given) are
+    /// dropped first; then `tsp`-subsets of the rest are tried until `accept`
+    /// confirms a state key, e.g. by successfully decrypting `cid`.
+    pub fn finish_robust(
+        password: &[u8],
+        state: &ToprfClientState,
+        partials: &[ToprfPartial],
+        tsp: usize,
+        commitments: Option<&[(u32, [u8; 32])]>,
+        mut accept: impl FnMut(&[u8; 32]) -> bool,
+    ) -> Result<RobustToprfOutput, UpspaError> {
+        if tsp == 0 {
+            return Err(UpspaError::InvalidLength {
+                expected: 1,
+                got: 0,
+            });
+        }
+        let mut seen = Vec::with_capacity(partials.len());
+        for p in partials {
+            if seen.contains(&p.id) {
+                return Err(UpspaError::InvalidPartials(vec![p.id]));
+            }
+            seen.push(p.id);
+        }
+
+        let r = scalar_from_canonical_bytes(&state.r)?;
+        if r == Scalar::ZERO {
+            return Err(UpspaError::InvalidScalar);
+        }
+        let r_inv = r.invert();
+
+        let unverified = match commitments {
+            Some(c) => Self::unverified_partials(password, state, partials, c)?,
+            None => Vec::new(),
+        };
+
+        let mut inconsistent = Vec::new();
+        let mut decoded: Vec<(u32, RistrettoPoint)> = Vec::with_capacity(partials.len());
+        for p in partials {
+            match point_from_bytes(&p.y) {
+                Ok(y) if p.id != 0 => decoded.push((p.id, y)),
+                _ => inconsistent.push(p.id),
+            }
+        }
+        let candidates: Vec<(u32, RistrettoPoint)> = decoded
+            .iter()
+            .filter(|(id, _)| !unverified.contains(id))
+            .cloned()
+            .collect();
+        if candidates.len() < tsp {
+            return Err(UpspaError::InvalidLength {
+                expected: tsp,
+                got: candidates.len(),
+            });
+        }
+
+        let mut idx: Vec<usize> = (0..tsp).collect();
+        loop {
+            let ids: Vec<u32> = idx.iter().map(|&i| candidates[i].0).collect();
+            let ys: Vec<RistrettoPoint> = idx.iter().map(|&i| candidates[i].1).collect();
+
+            let mut acc = RistrettoPoint::identity();
+            for (y, l) in ys.iter().zip(lagrange_coeffs_at_zero(&ids)?) {
+                acc += y * l;
+            }
+            let state_key = SecretBytes::new(state.suite.finalize(password, &(acc * r_inv))?);
+
+            if accept(&state_key) {
+                for (id, y) in decoded.iter() {
+                    if ids.contains(id) || unverified.contains(id) {
+                        continue;
+                    }
+                    let mut expected = RistrettoPoint::identity();
+                    for (y_i, l) in ys.iter().zip(lagrange_coeffs_at(&ids, *id)) {
+                        expected += y_i * l;
+                    }
+                    if expected != *y {
+                        inconsistent.push(*id);
+                    }
+                }
+                inconsistent.extend(&unverified);
+                inconsistent.sort_unstable();
+                inconsistent.dedup();
+                return Ok(RobustToprfOutput {
+                    state_key,
+                    inconsistent,
+                });
+            }
+
+            if !next_combination(&mut idx, candidates.len()) {
+                return Err(UpspaError::NoConsistentPartials);
+            }
+        }
+    }
+
+    /// sp_ids of the partials that fail DLEQ verification.
+    pub fn unverified_partials(
+        password: &[u8],
+        state: &ToprfClientState,
+        partials: &[ToprfPartial],
+        commitments: &[(u32, [u8; 32])],
+    ) -> Result<Vec<u32>, UpspaError> {
+        let r = scalar_from_canonical_bytes(&state.r)?;
+        let blinded = state.suite.hash_to_group(password) * r;
+
+        let mut bad = Vec::new();
+        for p in partials {
+            let commitment = commitments.iter().find(|(id, _)| *id == p.id);
+            let ok = match (commitment, &p.proof) {
+                (Some((_, k_commit)), Some(proof)) => point_from_bytes(k_commit)
+                    .and_then(|k| Ok((k, point_from_bytes(&p.y)?)))
+                    .and_then(|(k, y)| dleq_verify(&k, &blinded, &y, proof))
+                    .is_ok(),
+                _ => false,
+            };
+            if !ok {
+                bad.push(p.id);
+            }
+        }
+        Ok(bad)
     }
 }
 
@@ -71,6 +249,67 @@ pub fn toprf_gen(nsp: usize, tsp: usize, rng: &mut impl RngCore) -> (Scalar, Vec
         coeffs.push(random_scalar(rng));
     }
 
+    let ids: Vec<u32> = (1..=nsp as u32).collect();
+    (a0, shamir_shares(&coeffs, &ids))
+}
+
+/// [`toprf_gen`] evaluated at arbitrary `sp_ids` instead of `1..=nsp`.
+pub fn toprf_gen_for_ids(
+    sp_ids: &[u32],
+    tsp: usize,
+    rng: &mut impl RngCore,
+) -> Result<(Scalar, Vec<(u32, Scalar)>), UpspaError> {
+    validate_threshold(sp_ids, tsp)?;
+
+    let a0 = random_scalar(rng);
+    let mut coeffs = vec![a0];
+    for _ in 1..tsp {
+        coeffs.push(random_scalar(rng));
+    }
+
+    Ok((a0, shamir_shares(&coeffs, sp_ids)))
+}
+
+/// Share ids are evaluation points: they must be non-zero and distinct.
+pub fn validate_sp_ids(sp_ids: &[u32]) -> Result<(), UpspaError> {
+    for (i, id) in sp_ids.iter().enumerate() {
+        if *id == 0 || sp_ids[..i].contains(id) {
+            return Err(UpspaError::InvalidSpId(*id));
+        }
+    }
+    Ok(())
+}
+
+/// [`validate_sp_ids`] plus `1 <= tsp <= sp_ids.len()`.
+pub fn validate_threshold(sp_ids: &[u32], tsp: usize) -> Result<(), UpspaError> {
+    validate_sp_ids(sp_ids)?;
+    if tsp == 0 || tsp > sp_ids.len() {
+        return Err(UpspaError::InvalidThreshold {
+            tsp,
+            nsp: sp_ids.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Shares of zero for proactive refresh: adding `delta_i` to every `k_i`
+/// re-randomizes the sharing without changing the secret it reconstructs.
+pub fn toprf_zero_shares(
+    sp_ids: &[u32],
+    tsp: usize,
+    rng: &mut impl RngCore,
+) -> Result<Vec<(u32, Scalar)>, UpspaError> {
+    validate_threshold(sp_ids, tsp)?;
+
+    let mut coeffs = vec![Scalar::ZERO];
+    for _ in 1..tsp {
+        coeffs.push(random_scalar(rng));
+    }
+
+    Ok(shamir_shares(&coeffs, sp_ids))
+}
+
+fn shamir_shares(coeffs: &[Scalar], sp_ids: &[u32]) -> Vec<(u32, Scalar)> {
     fn eval(coeffs: &[Scalar], x: Scalar) -> Scalar {
         let mut acc = Scalar::ZERO;
         let mut pow = Scalar::ONE;
@@ -81,15 +320,90 @@ pub fn toprf_gen(nsp: usize, tsp: usize, rng: &mut impl RngCore) -> (Scalar, Vec
         acc
     }
 
-    let mut shares = Vec::with_capacity(nsp);
-    for i in 1..=nsp {
-        shares.push((i as u32, eval(&coeffs, Scalar::from(i as u64))));
+    sp_ids
+        .iter()
+        .map(|&i| (i, eval(coeffs, Scalar::from(i as u64))))
+        .collect()
+}
+
+/// SP side of a share refresh: `k_i' = k_i + delta_i`.
+pub fn toprf_refresh_share(share: &[u8; 32], delta: &[u8; 32]) -> Result<[u8; 32], UpspaError> {
+    let k = scalar_from_canonical_bytes(share)?;
+    let d = scalar_from_canonical_bytes(delta)?;
+    Ok((k + d).to_bytes())
+}
+
+/// Client side of a share refresh: `K_i' = K_i + delta_i * G`, using the
+/// delta commitments returned alongside the refresh messages. Fails with
+/// `InvalidPartials` listing sp_ids that have no delta commitment.
+pub fn toprf_refresh_commitments(
+    commitments: &[(u32, [u8; 32])],
+    delta_commitments: &[(u32, [u8; 32])],
+) -> Result<Vec<(u32, [u8; 32])>, UpspaError> {
+    let missing: Vec<u32> = commitments
+        .iter()
+        .filter(|(id, _)| !delta_commitments.iter().any(|(d, _)| d == id))
+        .map(|(id, _)| *id)
+        .collect();
+    if !missing.is_empty() {
+        return Err(UpspaError::InvalidPartials(missing));
+    }
+
+    let mut out = Vec::with_capacity(commitments.len());
+    for (id, k_commit) in commitments {
+        let (_, d_commit) = delta_commitments
+            .iter()
+            .find(|(d, _)| d == id)
+            .ok_or(UpspaError::InvalidSpId(*id))?;
+        let k = point_from_bytes(k_commit)?;
+        let d = point_from_bytes(d_commit)?;
+        out.push((*id, (k + d).compress().to_bytes()));
+    }
+    Ok(out)
+}
+
+/// Lagrange coefficients for evaluating the polynomial through `ids` at `x`.
+pub fn lagrange_coeffs_at(ids: &[u32], x: u32) -> Vec<Scalar> {
+    let x = Scalar::from(x as u64);
+    let xs: Vec<Scalar> = ids.iter().map(|&i| Scalar::from(i as u64)).collect();
+    let mut lambdas = Vec::with_capacity(xs.len());
+
+    for i in 0..xs.len() {
+        let mut num = Scalar::ONE;
+        let mut den = Scalar::ONE;
+        for j in 0..xs.len() {
+            if i != j {
+                num *= x - xs[j];
+                den *= xs[i] - xs[j];
+            }
+        }
+        lambdas.push(num * den.invert());
     }
+    lambdas
+}
 
-    (a0, shares)
+/// Advance `idx` to the next `idx.len()`-combination of `0..n` in
+/// lexicographic order; returns `false` after the last one.
+fn next_combination(idx: &mut [usize], n: usize) -> bool {
+    let k = idx.len();
+    let mut i = k;
+    while i > 0 {
+        i -= 1;
+        if idx[i] < n - k + i {
+            idx[i] += 1;
+            for j in i + 1..k {
+                idx[j] = idx[j - 1] + 1;
+            }
+            return true;
+        }
+    }
+    false
 }
 
-pub fn lagrange_coeffs_at_zero(ids: &[u32]) -> Vec<Scalar> {
+/// Lagrange coefficients at `x = 0`. `ids` must be non-zero and distinct;
+/// otherwise the interpolation is meaningless and `InvalidSpId` is returned.
+pub fn lagrange_coeffs_at_zero(ids: &[u32]) -> Result<Vec<Scalar>, UpspaError> {
+    validate_sp_ids(ids)?;
     let xs: Vec<Scalar> = ids.iter().map(|&i| Scalar::from(i as u64)).collect();
     let mut lambdas = Vec::with_capacity(xs.len());
 
@@ -104,7 +418,7 @@ pub fn lagrange_coeffs_at_zero(ids: &[u32]) -> Vec<Scalar> {
         }
         lambdas.push(num * den.invert());
     }
-    lambdas
+    Ok(lambdas)
 }
 
 
@@ -173,3 +487,32 @@ pub fn toprf_server_eval(
     let y = b * k;
     Ok(y.compress().to_bytes())
 }
+
+/// Public commitment `K_i = k_i * G` to an SP's share.
+pub fn toprf_share_commitment(share: &[u8; 32]) -> Result<[u8; 32], UpspaError> {
+    let k = scalar_from_canonical_bytes(share)?;
+    Ok((RISTRETTO_BASEPOINT_POINT * k).compress().to_bytes())
+}
+
+/// Commitments for every share produced by [`toprf_gen`].
+pub fn toprf_commitments(shares: &[(u32, Scalar)]) -> Vec<(u32, [u8; 32])> {
+    shares
+        .iter()
+        .map(|(id, k)| (*id, (RISTRETTO_BASEPOINT_POINT * k).compress().to_bytes()))
+        .collect()
+}
+
+/// Verifiable-mode [`toprf_server_eval`]: also returns a DLEQ proof that `y_i`
+/// was computed with the share behind [`toprf_share_commitment`].
+pub fn toprf_server_eval_verifiable(
+    blinded: &[u8; 32],
+    share: &[u8; 32],
+    rng: &mut impl RngCore,
+) -> Result<([u8; 32], DleqProof), UpspaError> {
+    let b = point_from_bytes(blinded)?;
+    let k = scalar_from_canonical_bytes(share)?;
+    let y = b * k;
+    let commitment = RISTRETTO_BASEPOINT_POINT * k;
+    let proof = dleq_prove(&k, &commitment, &b, &y, rng);
+    Ok((y.compress().to_bytes(), proof))
+}
@@ -77,6 +77,77 @@ impl<const PT_LEN: usize> CtBlob<PT_LEN> {
     }
 }
 
+/// Upper bound on the ciphertext length of a [`CtVec`].
+pub const MAX_CT_LEN: usize = 4096;
+
+/// Variable-length [`CtBlob`] for versioned plaintexts. `MIN_LEN` is the
+/// shortest valid ciphertext (the v1 layout); the wire format is the same
+/// [`CtBlobB64`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CtVec<const MIN_LEN: usize> {
+    pub nonce: [u8; NONCE_LEN],
+    pub ct: Vec<u8>,
+    pub tag: [u8; TAG_LEN],
+}
+
+impl<const MIN_LEN: usize> CtVec<MIN_LEN> {
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut v = Vec::with_capacity(NONCE_LEN + self.ct.len() + TAG_LEN);
+        v.extend_from_slice(&self.nonce);
+        v.extend_from_slice(&self.ct);
+        v.extend_from_slice(&self.tag);
+        v
+    }
+
+    pub fn from_slice(input: &[u8]) -> Result<Self, CtBlobParseError> {
+        let ct_len = input.len().saturating_sub(NONCE_LEN + TAG_LEN);
+        if !(MIN_LEN..=MAX_CT_LEN).contains(&ct_len) {
+            return Err(CtBlobParseError::InvalidLength {
+                expected: NONCE_LEN + MIN_LEN + TAG_LEN,
+                got: input.len(),
+            });
+        }
+
+        let mut nonce = [0u8; NONCE_LEN];
+        let mut tag = [0u8; TAG_LEN];
+        nonce.copy_from_slice(&input[..NONCE_LEN]);
+        tag.copy_from_slice(&input[NONCE_LEN + ct_len..]);
+        let ct = input[NONCE_LEN..NONCE_LEN + ct_len].to_vec();
+        Ok(Self { nonce, ct, tag })
+    }
+
+    pub fn to_b64(&self) -> CtBlobB64 {
+        CtBlobB64 {
+            nonce: b64_encode(&self.nonce),
+            ct: b64_encode(&self.ct),
+            tag: b64_encode(&self.tag),
+        }
+    }
+
+    pub fn from_b64(b64: &CtBlobB64) -> Result<Self, UpspaError> {
+        let nonce = b64_decode_array::<NONCE_LEN>(&b64.nonce)?;
+        let ct = b64_decode(&b64.ct)?;
+        let tag = b64_decode_array::<TAG_LEN>(&b64.tag)?;
+        if !(MIN_LEN..=MAX_CT_LEN).contains(&ct.len()) {
+            return Err(UpspaError::InvalidLength {
+                expected: MIN_LEN,
+                got: ct.len(),
+            });
+        }
+        Ok(Self { nonce, ct, tag })
+    }
+}
+
+impl<const N: usize> From<CtBlob<N>> for CtVec<N> {
+    fn from(blob: CtBlob<N>) -> Self {
+        Self {
+            nonce: blob.nonce,
+            ct: blob.ct.to_vec(),
+            tag: blob.tag,
+        }
+    }
+}
+
 #[derive(Clone, Debug, thiserror::Error)]
 pub enum UpspaError {
     #[error("invalid length: expected {expected}, got {got}")]
@@ -99,6 +170,53 @@ pub enum UpspaError {
 
     #[error("ct blob parse error")]
     CtParse,
+
+    #[error("invalid DLEQ proof")]
+    InvalidProof,
+
+    #[error("invalid TOPRF partials from sp_ids {0:?}")]
+    InvalidPartials(Vec<u32>),
+
+    #[error("no consistent set of TOPRF partials")]
+    NoConsistentPartials,
+
+    #[error("invalid or duplicate sp_id {0}")]
+    InvalidSpId(u32),
+
+    #[error("invalid threshold: tsp {tsp} with {nsp} SPs")]
+    InvalidThreshold { tsp: usize, nsp: usize },
+
+    #[error("unsupported plaintext format version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("malformed per-LS record")]
+    InvalidRecord,
+
+    #[error("newest record (ctr {ctr}) held by {got} SPs, {needed} required; undecryptable from sp_ids {invalid:?}")]
+    RecordQuorum {
+        ctr: u64,
+        got: usize,
+        needed: usize,
+        invalid: Vec<u32>,
+    },
+
+    #[error("undecryptable records from sp_ids {0:?}")]
+    InvalidRecords(Vec<u32>),
+
+    #[error("invalid password policy: {0}")]
+    InvalidPolicy(&'static str),
+
+    #[error("malformed recovery code")]
+    InvalidRecoveryCode,
+
+    #[error("device enrollment expired at {expires_at}")]
+    DeviceExpired { expires_at: u64 },
+
+    #[error("device has been revoked")]
+    DeviceRevoked,
+
+    #[error("invalid password KDF parameters")]
+    InvalidKdf,
 }
 
 pub fn b64_encode(bytes: &[u8]) -> String {
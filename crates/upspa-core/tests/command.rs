@@ -0,0 +1,136 @@
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+
+use upspa_core::protocol::command::{
+    command_sig_msg, sign_command, verify_command, verify_command_for_sp, CommandError, CommandKind,
+};
+use upspa_core::protocol::decrypt_cid;
+use upspa_core::protocol::deprovision::{sign_deprovision, verify_deprovision_for_sp};
+use upspa_core::protocol::password_update::{
+    client_password_update, pwd_update_sig_msg, sign_password_update,
+    verify_password_update_for_sp, PasswordUpdateError, SigFormat,
+};
+use upspa_core::protocol::setup;
+use upspa_core::sign::{verify_detached, SigningKey};
+use upspa_core::toprf::{toprf_server_eval, ToprfClient, ToprfPartial};
+
+#[test]
+fn command_layout_and_checks() {
+    let key = SigningKey::from_bytes(&[7u8; 32]);
+    let pk = key.verifying_key().to_bytes();
+
+    let msg = command_sig_msg(CommandKind::Deprovision, b"alice", 3, 9, b"body");
+    assert_eq!(&msg[..17], b"uptspa/command/v1");
+    assert_eq!(&msg[17..19], &5u16.to_le_bytes());
+    assert_eq!(&msg[19..23], &5u32.to_le_bytes());
+    assert_eq!(&msg[23..28], b"alice");
+    assert_eq!(&msg[28..32], &3u32.to_le_bytes());
+    assert_eq!(&msg[32..40], &9u64.to_le_bytes());
+    assert_eq!(&msg[40..44], &4u32.to_le_bytes());
+    assert_eq!(&msg[44..], b"body");
+
+    let cmd = sign_command(
+        &key,
+        CommandKind::Deprovision,
+        b"alice",
+        3,
+        9,
+        b"body".to_vec(),
+    );
+    verify_command(&cmd, &pk).unwrap();
+    verify_command_for_sp(3, &cmd, &pk, 8).unwrap();
+    assert_eq!(
+        verify_command_for_sp(4, &cmd, &pk, 8),
+        Err(CommandError::SpIdMismatch {
+            expected: 4,
+            got: 3
+        })
+    );
+    assert_eq!(
+        verify_command_for_sp(3, &cmd, &pk, 9),
+        Err(CommandError::StaleTimestamp { last: 9, got: 9 })
+    );
+
+    // The kind is signed: the same bytes cannot be replayed as another command.
+    let mut other = cmd.clone();
+    other.kind = CommandKind::ShareRefresh;
+    assert_eq!(verify_command(&other, &pk), Err(CommandError::Signature));
+
+    // So is the uid.
+    let mut other = cmd.clone();
+    other.uid_b64 = "Ym9i".to_string();
+    assert_eq!(verify_command(&other, &pk), Err(CommandError::Signature));
+
+    let dep = sign_deprovision(&key, b"alice", 3, 9, vec![[1u8; 32], [2u8; 32]]);
+    verify_deprovision_for_sp(3, &dep, &pk, 0).unwrap();
+    assert_eq!(dep.command().body.len(), 4 + 64);
+}
+
+#[test]
+fn password_update_accepts_both_sig_formats() {
+    let uid = b"user123";
+    let mut rng = ChaCha20Rng::from_seed([44u8; 32]);
+    let (out, _) = setup::client_setup(uid, b"pw", &[1, 2, 3], 2, &mut rng).unwrap();
+
+    let (state, blinded) = ToprfClient::begin(b"pw", &mut rng);
+    let partials: Vec<ToprfPartial> = out.shares[..2]
+        .iter()
+        .map(|(id, k)| ToprfPartial {
+            id: *id,
+            y: toprf_server_eval(&blinded, k).unwrap(),
+            proof: None,
+        })
+        .collect();
+    let state_key = ToprfClient::finish(b"pw", &state, &partials).unwrap();
+    let signing_key = decrypt_cid(uid, &state_key, &out.cid).unwrap().signing_key;
+
+    let upd = client_password_update(
+        uid,
+        &state_key,
+        &out.cid,
+        &[1, 2, 3],
+        2,
+        b"pw2",
+        7,
+        &mut rng,
+    )
+    .unwrap();
+    let legacy = &upd.per_sp[0];
+    assert_eq!(legacy.format, SigFormat::Legacy);
+    let sig_msg = pwd_update_sig_msg(&legacy.cid_new, &legacy.k_i_new, 7, 1);
+    verify_detached(&out.sig_pk, &sig_msg, &legacy.sig).unwrap();
+    verify_password_update_for_sp(1, legacy, &out.sig_pk, 0).unwrap();
+
+    let command = sign_password_update(
+        &signing_key,
+        uid,
+        1,
+        7,
+        &upd.cid_new,
+        legacy.k_i_new.clone(),
+        SigFormat::Command,
+    );
+    verify_password_update_for_sp(1, &command, &out.sig_pk, 0).unwrap();
+    assert_ne!(command.sig, legacy.sig);
+
+    // A signature only verifies in the format it was made for.
+    let mut relabeled = command.clone();
+    relabeled.format = SigFormat::Legacy;
+    assert_eq!(
+        verify_password_update_for_sp(1, &relabeled, &out.sig_pk, 0),
+        Err(PasswordUpdateError::Signature)
+    );
+    let mut relabeled = legacy.clone();
+    relabeled.format = SigFormat::Command;
+    assert_eq!(
+        verify_password_update_for_sp(1, &relabeled, &out.sig_pk, 0),
+        Err(PasswordUpdateError::Signature)
+    );
+
+    // Messages without a `format` field are legacy.
+    let mut json = serde_json::to_value(legacy).unwrap();
+    json.as_object_mut().unwrap().remove("format");
+    let parsed: upspa_core::protocol::password_update::PasswordUpdateSpMessage =
+        serde_json::from_value(json).unwrap();
+    assert_eq!(parsed.format, SigFormat::Legacy);
+}
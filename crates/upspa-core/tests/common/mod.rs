@@ -0,0 +1,39 @@
+// Not every test binary uses every helper.
+#![allow(dead_code)]
+
+use rand_core::RngCore;
+
+use upspa_core::toprf::{toprf_server_eval, ToprfClient, ToprfPartial};
+use upspa_core::{OprfSuite, SecretBytes};
+
+/// Honest partials from `shares` for one blinded element, without proofs.
+pub fn partials(blinded: &[u8; 32], shares: &[(u32, SecretBytes<32>)]) -> Vec<ToprfPartial> {
+    shares
+        .iter()
+        .map(|(id, k)| ToprfPartial {
+            id: *id,
+            y: toprf_server_eval(blinded, k).unwrap(),
+            proof: None,
+        })
+        .collect()
+}
+
+/// The state key a login with `password` reaches against `shares`.
+pub fn login(
+    password: &[u8],
+    shares: &[(u32, SecretBytes<32>)],
+    rng: &mut impl RngCore,
+) -> SecretBytes<32> {
+    login_with_suite(password, OprfSuite::Blake3, shares, rng)
+}
+
+/// [`login`] under `suite`.
+pub fn login_with_suite(
+    password: &[u8],
+    suite: OprfSuite,
+    shares: &[(u32, SecretBytes<32>)],
+    rng: &mut impl RngCore,
+) -> SecretBytes<32> {
+    let (state, blinded) = ToprfClient::begin_with_suite(password, suite, rng);
+    ToprfClient::finish(password, &state, &partials(&blinded, shares)).unwrap()
+}
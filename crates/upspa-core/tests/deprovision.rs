@@ -0,0 +1,77 @@
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+
+use upspa_core::protocol::deprovision::{client_delete_account, verify_deprovision_for_sp};
+use upspa_core::protocol::command::CommandError;
+use upspa_core::protocol::{register, setup, LsRecord};
+use upspa_core::toprf::{toprf_server_eval, ToprfClient, ToprfPartial};
+use upspa_core::UpspaError;
+
+#[test]
+fn delete_account_lists_every_suid_per_sp() {
+    let uid = b"user123";
+    let mut rng = ChaCha20Rng::from_seed([55u8; 32]);
+    let (out, _) = setup::client_setup(uid, b"pw", &[1, 2, 3], 2, &mut rng).unwrap();
+
+    let (state, blinded) = ToprfClient::begin(b"pw", &mut rng);
+    let partials: Vec<ToprfPartial> = out.shares[..2]
+        .iter()
+        .map(|(id, k)| ToprfPartial {
+            id: *id,
+            y: toprf_server_eval(&blinded, k).unwrap(),
+            proof: None,
+        })
+        .collect();
+    let state_key = ToprfClient::finish(b"pw", &state, &partials).unwrap();
+
+    let lsjs = [b"LS1".to_vec(), b"LS2".to_vec()];
+    let regs: Vec<_> = lsjs
+        .iter()
+        .map(|lsj| {
+            register::client_register(
+                uid,
+                lsj,
+                &state_key,
+                &out.cid,
+                &[1, 2, 3],
+                &LsRecord::None,
+                &mut rng,
+            )
+            .unwrap()
+        })
+        .collect();
+
+    let msgs = client_delete_account(uid, &state_key, &out.cid, &[1, 2, 3], &lsjs, 40).unwrap();
+    assert_eq!(msgs.len(), 3);
+    for (i, m) in msgs.iter().enumerate() {
+        assert_eq!(m.sp_id, i as u32 + 1);
+        let expected: Vec<[u8; 32]> = regs.iter().map(|r| r.per_sp[i].suid).collect();
+        assert_eq!(m.suids, expected);
+        verify_deprovision_for_sp(m.sp_id, m, &out.sig_pk, 39).unwrap();
+    }
+
+    assert_eq!(
+        verify_deprovision_for_sp(2, &msgs[0], &out.sig_pk, 0),
+        Err(CommandError::SpIdMismatch {
+            expected: 2,
+            got: 1
+        })
+    );
+    assert_eq!(
+        verify_deprovision_for_sp(1, &msgs[0], &out.sig_pk, 40),
+        Err(CommandError::StaleTimestamp { last: 40, got: 40 })
+    );
+
+    // Dropping a listed suid breaks the signature.
+    let mut partial = msgs[0].clone();
+    partial.suids.pop();
+    assert_eq!(
+        verify_deprovision_for_sp(1, &partial, &out.sig_pk, 0),
+        Err(CommandError::Signature)
+    );
+
+    assert!(matches!(
+        client_delete_account(uid, &[0u8; 32], &out.cid, &[1, 2, 3], &lsjs, 40),
+        Err(UpspaError::Aead)
+    ));
+}
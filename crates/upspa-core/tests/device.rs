@@ -0,0 +1,81 @@
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+
+use upspa_core::protocol::device::{
+    enroll_device, sign_device_revocations, unlock_device, verify_device_revocations,
+};
+use upspa_core::protocol::command::CommandError;
+use upspa_core::protocol::{decrypt_cid, setup};
+use upspa_core::sign::SigningKey;
+use upspa_core::toprf::{toprf_server_eval, ToprfClient, ToprfPartial};
+use upspa_core::UpspaError;
+
+#[test]
+fn enrolled_device_unlocks_until_expired_or_revoked() {
+    let uid = b"user123";
+    let mut rng = ChaCha20Rng::from_seed([77u8; 32]);
+    let (out, _) = setup::client_setup(uid, b"pw", &[1, 2, 3], 2, &mut rng).unwrap();
+    let (state, blinded) = ToprfClient::begin(b"pw", &mut rng);
+    let partials: Vec<ToprfPartial> = out.shares[..2]
+        .iter()
+        .map(|(id, k)| ToprfPartial {
+            id: *id,
+            y: toprf_server_eval(&blinded, k).unwrap(),
+            proof: None,
+        })
+        .collect();
+    let state_key = ToprfClient::finish(b"pw", &state, &partials).unwrap();
+    let cid_pt = decrypt_cid(uid, &state_key, &out.cid).unwrap();
+
+    let (laptop, phone) = ([1u8; 16], [2u8; 16]);
+    let device_key = [9u8; 32];
+    let enrollment = enroll_device(uid, &cid_pt, &device_key, laptop, 100, &mut rng).unwrap();
+    assert_eq!(enrollment.sig_pk, out.sig_pk);
+
+    let unlocked = unlock_device(uid, &device_key, &enrollment, 50, None).unwrap();
+    assert_eq!(unlocked.to_bytes().unwrap(), cid_pt.to_bytes().unwrap());
+    assert!(matches!(
+        unlock_device(uid, &[8u8; 32], &enrollment, 50, None),
+        Err(UpspaError::Aead)
+    ));
+    assert!(matches!(
+        unlock_device(uid, &device_key, &enrollment, 100, None),
+        Err(UpspaError::DeviceExpired { expires_at: 100 })
+    ));
+
+    // The expiry is bound to the wrapped key, not just stored next to it.
+    let mut extended = enrollment.clone();
+    extended.expires_at = u64::MAX;
+    assert!(matches!(
+        unlock_device(uid, &device_key, &extended, 150, None),
+        Err(UpspaError::Aead)
+    ));
+
+    let list = sign_device_revocations(&cid_pt.signing_key, uid, 60, vec![phone]);
+    verify_device_revocations(&list, &out.sig_pk).unwrap();
+    unlock_device(uid, &device_key, &enrollment, 70, Some(&list)).unwrap();
+
+    let list = sign_device_revocations(&cid_pt.signing_key, uid, 61, vec![phone, laptop]);
+    assert!(matches!(
+        unlock_device(uid, &device_key, &enrollment, 70, Some(&list)),
+        Err(UpspaError::DeviceRevoked)
+    ));
+
+    // Dropping a device from a signed list breaks the signature.
+    let mut trimmed = list.clone();
+    trimmed.revoked.pop();
+    assert_eq!(
+        verify_device_revocations(&trimmed, &out.sig_pk),
+        Err(CommandError::Signature)
+    );
+    assert!(matches!(
+        unlock_device(uid, &device_key, &enrollment, 70, Some(&trimmed)),
+        Err(UpspaError::Signature)
+    ));
+
+    let forged = sign_device_revocations(&SigningKey::from_bytes(&[3u8; 32]), uid, 62, vec![]);
+    assert!(matches!(
+        unlock_device(uid, &device_key, &enrollment, 70, Some(&forged)),
+        Err(UpspaError::Signature)
+    ));
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+
+use upspa_core::kdf::{Argon2idParams, PasswordKdf};
+use upspa_core::protocol::password_update::client_password_update_from_plaintext;
+use upspa_core::protocol::setup::{client_setup, client_setup_with_kdf, SetupOutput};
+use upspa_core::protocol::{decrypt_cid, KDF_ARGON2ID, KDF_NONE};
+use upspa_core::{OprfSuite, SecretBytes, UpspaError};
+
+use common::login;
+
+// Small enough for debug-build tests; real accounts use the default.
+const PARAMS: Argon2idParams = Argon2idParams {
+    m_cost_kib: 64,
+    t_cost: 1,
+    p_cost: 1,
+};
+
+#[test]
+fn argon2id_depends_on_password_and_salt() {
+    let mut rng = ChaCha20Rng::from_seed([81u8; 32]);
+    let kdf = PasswordKdf::argon2id(PARAMS, &mut rng);
+    assert_eq!((PasswordKdf::None.id(), kdf.id()), (KDF_NONE, KDF_ARGON2ID));
+    assert_eq!(*PasswordKdf::None.harden(b"pw").unwrap(), b"pw".to_vec());
+
+    let h = kdf.harden(b"pw").unwrap();
+    assert_eq!(h.len(), 32);
+    assert_eq!(kdf.harden(b"pw").unwrap(), h);
+    assert_ne!(kdf.harden(b"pw2").unwrap(), h);
+    assert_ne!(
+        PasswordKdf::argon2id(PARAMS, &mut rng)
+            .harden(b"pw")
+            .unwrap(),
+        h
+    );
+
+    let bad = PasswordKdf::Argon2id {
+        params: Argon2idParams {
+            m_cost_kib: 1,
+            ..PARAMS
+        },
+        salt: [0u8; 16],
+    };
+    assert!(matches!(bad.harden(b"pw"), Err(UpspaError::InvalidKdf)));
+}
+
+#[test]
+fn setup_with_kdf_needs_the_hardened_password() {
+    let uid = b"user123";
+    let mut rng = ChaCha20Rng::from_seed([82u8; 32]);
+    let kdf = PasswordKdf::argon2id(PARAMS, &mut rng);
+    let (out, _) = client_setup_with_kdf(uid, b"pw", &[1, 2, 3], 2, &kdf, &mut rng).unwrap();
+    assert_eq!(out.kdf, kdf);
+
+    let raw = login(b"pw", &out.shares[..2], &mut rng);
+    assert!(decrypt_cid(uid, &raw, &out.cid).is_err());
+    let hardened = login(&kdf.harden(b"pw").unwrap(), &out.shares[..2], &mut rng);
+    let cid_pt = decrypt_cid(uid, &hardened, &out.cid).unwrap();
+    assert_eq!(cid_pt.kdf_id, KDF_ARGON2ID);
+}
+
+#[test]
+fn password_update_migrates_old_accounts() {
+    let uid = b"user123";
+    let mut rng = ChaCha20Rng::from_seed([83u8; 32]);
+    let (out, _) = client_setup(uid, b"pw", &[1, 2, 3], 2, &mut rng).unwrap();
+    assert_eq!(out.kdf, PasswordKdf::None);
+
+    // Outputs saved before hardening existed read back as unhardened.
+    let mut saved = serde_json::to_value(&out).unwrap();
+    saved.as_object_mut().unwrap().remove("kdf");
+    let saved: SetupOutput = serde_json::from_value(saved).unwrap();
+    assert_eq!(saved.kdf, PasswordKdf::None);
+
+    let state_key = login(b"pw", &out.shares[..2], &mut rng);
+    let cid_pt = decrypt_cid(uid, &state_key, &out.cid).unwrap();
+    assert_eq!(cid_pt.kdf_id, KDF_NONE);
+
+    // Same password, new KDF: fresh shares and a `cid` only the hardened
+    // password opens.
+    let kdf = PasswordKdf::argon2id(PARAMS, &mut rng);
+    let upd = client_password_update_from_plaintext(
+        uid,
+        &cid_pt,
+        &[1, 2, 3],
+        2,
+        b"pw",
+        &kdf,
+        OprfSuite::Blake3,
+        5,
+        &mut rng,
+    )
+    .unwrap();
+    assert_eq!(upd.kdf, kdf);
+    let new_shares: Vec<(u32, SecretBytes<32>)> = upd
+        .per_sp
+        .iter()
+        .map(|m| (m.sp_id, m.k_i_new.clone()))
+        .collect();
+
+    let raw = login(b"pw", &new_shares[..2], &mut rng);
+    assert!(decrypt_cid(uid, &raw, &upd.cid_new).is_err());
+    let hardened = login(&kdf.harden(b"pw").unwrap(), &new_shares[1..], &mut rng);
+    let migrated = decrypt_cid(uid, &hardened, &upd.cid_new).unwrap();
+    assert_eq!(migrated.kdf_id, KDF_ARGON2ID);
+    assert_eq!((migrated.rsp, migrated.k0), (cid_pt.rsp, cid_pt.k0));
+}
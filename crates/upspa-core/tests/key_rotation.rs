@@ -0,0 +1,97 @@
+mod common;
+
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+
+use upspa_core::protocol::command::CommandError;
+use upspa_core::protocol::decrypt_cid;
+use upspa_core::protocol::key_rotation::{client_key_rotation, verify_key_rotation_for_sp};
+use upspa_core::protocol::password_update::{client_password_update, pwd_update_sig_msg};
+use upspa_core::protocol::setup;
+use upspa_core::sign::verify_detached;
+use upspa_core::toprf::{toprf_server_eval, ToprfClient, ToprfPartial};
+use upspa_core::UpspaError;
+
+use common::login;
+
+#[test]
+fn rotation_replaces_signing_key_and_keeps_secrets() {
+    let uid = b"user123";
+    let mut rng = ChaCha20Rng::from_seed([33u8; 32]);
+    let (out, _) = setup::client_setup(uid, b"pw", &[1, 2, 3], 2, &mut rng).unwrap();
+
+    let (state, blinded) = ToprfClient::begin(b"pw", &mut rng);
+    let partials: Vec<ToprfPartial> = out.shares[..2]
+        .iter()
+        .map(|(id, k)| ToprfPartial {
+            id: *id,
+            y: toprf_server_eval(&blinded, k).unwrap(),
+            proof: None,
+        })
+        .collect();
+    let state_key = ToprfClient::finish(b"pw", &state, &partials).unwrap();
+    let old = decrypt_cid(uid, &state_key, &out.cid).unwrap();
+
+    let rot = client_key_rotation(uid, &state_key, &out.cid, &[1, 2, 3], 5, &mut rng).unwrap();
+    assert_ne!(rot.sig_pk_new, out.sig_pk);
+    for m in &rot.per_sp {
+        verify_key_rotation_for_sp(m.sp_id, m, &out.sig_pk, 0).unwrap();
+    }
+
+    // Same state key opens cid_new; only the signing key changed.
+    let new = decrypt_cid(uid, &state_key, &rot.cid_new).unwrap();
+    assert_eq!(new.signing_key.verifying_key().to_bytes(), rot.sig_pk_new);
+    assert_ne!(new.ssk_bytes, old.ssk_bytes);
+    assert_eq!((new.rsp, new.k0), (old.rsp, old.k0));
+
+    let m = &rot.per_sp[0];
+    assert_eq!(
+        verify_key_rotation_for_sp(2, m, &out.sig_pk, 0),
+        Err(CommandError::SpIdMismatch {
+            expected: 2,
+            got: 1
+        })
+    );
+    assert_eq!(
+        verify_key_rotation_for_sp(1, m, &out.sig_pk, 5),
+        Err(CommandError::StaleTimestamp { last: 5, got: 5 })
+    );
+    assert_eq!(
+        verify_key_rotation_for_sp(1, m, &rot.sig_pk_new, 0),
+        Err(CommandError::Signature)
+    );
+
+    // Later Π5 requests are signed by the new key.
+    let pu = client_password_update(
+        uid,
+        &state_key,
+        &rot.cid_new,
+        &[1, 2, 3],
+        2,
+        b"pw2",
+        6,
+        &mut rng,
+    )
+    .unwrap();
+    let sig_msg = pwd_update_sig_msg(&pu.cid_new, &pu.per_sp[0].k_i_new, 6, 1);
+    verify_detached(&rot.sig_pk_new, &sig_msg, &pu.per_sp[0].sig).unwrap();
+}
+
+#[test]
+fn rotation_rejects_bad_sp_ids() {
+    let uid = b"user123";
+    let mut rng = ChaCha20Rng::from_seed([34u8; 32]);
+    let (out, _) = setup::client_setup(uid, b"pw", &[1, 2, 3], 2, &mut rng).unwrap();
+    let state_key = login(b"pw", &out.shares[..2], &mut rng);
+
+    assert!(matches!(
+        client_key_rotation(uid, &state_key, &out.cid, &[], 5, &mut rng),
+        Err(UpspaError::InvalidThreshold { tsp: 1, nsp: 0 })
+    ));
+    for bad in [&[1, 2, 2][..], &[0, 1]] {
+        assert!(matches!(
+            client_key_rotation(uid, &state_key, &out.cid, bad, 5, &mut rng),
+            Err(UpspaError::InvalidSpId(_))
+        ));
+    }
+}
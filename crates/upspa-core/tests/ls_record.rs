@@ -0,0 +1,164 @@
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+
+use upspa_core::protocol::{
+    authenticate, decrypt_cj, encrypt_cj, register, secret_update, setup, CipherSpPlaintext,
+    LsRecord,
+};
+use upspa_core::toprf::{toprf_server_eval, ToprfClient, ToprfPartial};
+use upspa_core::types::MAX_CT_LEN;
+use upspa_core::UpspaError;
+
+fn account(rng: &mut ChaCha20Rng) -> ([u8; 32], upspa_core::protocol::CipherId) {
+    let (out, _) = setup::client_setup(b"user123", b"pw", &[1, 2, 3], 2, rng).unwrap();
+    let (state, blinded) = ToprfClient::begin(b"pw", rng);
+    let partials: Vec<ToprfPartial> = out.shares[..2]
+        .iter()
+        .map(|(id, k)| ToprfPartial {
+            id: *id,
+            y: toprf_server_eval(&blinded, k).unwrap(),
+            proof: None,
+        })
+        .collect();
+    (
+        *ToprfClient::finish(b"pw", &state, &partials).unwrap(),
+        out.cid,
+    )
+}
+
+#[test]
+fn record_round_trips_through_register_auth_and_secret_update() {
+    let uid = b"user123";
+    let lsj = b"legacy.example";
+    let mut rng = ChaCha20Rng::from_seed([51u8; 32]);
+    let (state_key, cid) = account(&mut rng);
+
+    let records = [
+        LsRecord::Password {
+            password: "hunter2-ünïcode".into(),
+        },
+        LsRecord::Totp {
+            seed: vec![7u8; 20],
+            digits: 6,
+            period: 30,
+        },
+        LsRecord::RecoveryCodes {
+            codes: vec!["abcd-efgh".into(), String::new(), "1234".into()],
+        },
+    ];
+    for record in records {
+        let reg =
+            register::client_register(uid, lsj, &state_key, &cid, &[1, 2, 3], &record, &mut rng)
+                .unwrap();
+        let q = authenticate::client_auth_prepare(uid, lsj, &state_key, &cid, &[1, 2, 3]).unwrap();
+        let cjs: Vec<_> = reg.per_sp.iter().map(|m| (m.sp_id, m.cj.clone())).collect();
+
+        let auth = authenticate::client_auth_finish(uid, lsj, &q.k0, &cjs, 2).unwrap();
+        assert_eq!(auth.record, record);
+        assert_eq!(auth.vinfo_prime, reg.to_ls.vinfo);
+
+        let su =
+            secret_update::client_secret_update_finish(uid, lsj, &q.k0, &cjs, 2, &mut rng).unwrap();
+        let auth2 =
+            authenticate::client_auth_finish(uid, lsj, &q.k0, &[cjs[0].clone(), (2, su.cj_new)], 1)
+                .unwrap();
+        assert_eq!(auth2.best_ctr, 1);
+        assert_eq!(auth2.record, record);
+    }
+}
+
+#[test]
+fn record_encoding_is_checked() {
+    assert_eq!(*LsRecord::None.to_bytes().unwrap(), vec![0]);
+    assert_eq!(
+        *LsRecord::Totp {
+            seed: vec![9, 9],
+            digits: 8,
+            period: 60
+        }
+        .to_bytes()
+        .unwrap(),
+        vec![2, 8, 60, 0, 0, 0, 9, 9]
+    );
+    assert_eq!(
+        *LsRecord::RecoveryCodes {
+            codes: vec!["ab".into()]
+        }
+        .to_bytes()
+        .unwrap(),
+        vec![3, 2, 0, b'a', b'b']
+    );
+    let long_code = LsRecord::RecoveryCodes {
+        codes: vec!["x".repeat(1 << 16)],
+    };
+    assert!(matches!(
+        long_code.to_bytes(),
+        Err(UpspaError::InvalidRecord)
+    ));
+
+    for bad in [
+        &[][..],
+        &[0, 1],
+        &[1, 0xff],
+        &[2, 6, 30],
+        &[3, 5, 0, b'a'],
+        &[9],
+    ] {
+        assert!(matches!(
+            LsRecord::from_bytes(bad),
+            Err(UpspaError::InvalidRecord)
+        ));
+    }
+
+    let pt = CipherSpPlaintext::with_record([1u8; 32], 3, LsRecord::None);
+    assert_eq!(pt.to_bytes().unwrap().len(), 1 + 40 + 1);
+
+    let uid = b"user123";
+    let mut rng = ChaCha20Rng::from_seed([52u8; 32]);
+    let oversized = CipherSpPlaintext::with_record(
+        [1u8; 32],
+        3,
+        LsRecord::Totp {
+            seed: vec![0u8; MAX_CT_LEN],
+            digits: 6,
+            period: 30,
+        },
+    );
+    assert!(matches!(
+        encrypt_cj(uid, &[2u8; 32], &oversized, &mut rng),
+        Err(UpspaError::InvalidLength { .. })
+    ));
+    assert!(matches!(
+        encrypt_cj(
+            uid,
+            &[2u8; 32],
+            &CipherSpPlaintext::with_record([1u8; 32], 3, long_code),
+            &mut rng
+        ),
+        Err(UpspaError::InvalidRecord)
+    ));
+    let (state_key, cid) = account(&mut rng);
+    let huge = LsRecord::Password {
+        password: "x".repeat(5000),
+    };
+    assert!(matches!(
+        register::client_register(uid, b"LS1", &state_key, &cid, &[1, 2, 3], &huge, &mut rng),
+        Err(UpspaError::InvalidLength { .. })
+    ));
+
+    let reg = register::client_register(
+        uid,
+        b"LS1",
+        &state_key,
+        &cid,
+        &[1, 2, 3],
+        &LsRecord::None,
+        &mut rng,
+    )
+    .unwrap();
+    let q = authenticate::client_auth_prepare(uid, b"LS1", &state_key, &cid, &[1, 2, 3]).unwrap();
+    assert_eq!(
+        decrypt_cj(uid, &q.k0, &reg.per_sp[0].cj).unwrap().record,
+        LsRecord::None
+    );
+}
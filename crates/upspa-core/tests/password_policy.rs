@@ -0,0 +1,149 @@
+use std::collections::BTreeSet;
+
+use upspa_core::hash::hash_vinfo;
+use upspa_core::password_policy::{
+    render_site_password, ClassRule, PasswordPolicy, PolicyPreset, PolicySpec,
+};
+use upspa_core::protocol::authenticate::AuthResult;
+use upspa_core::protocol::LsRecord;
+use upspa_core::UpspaError;
+
+fn vinfo(i: u32) -> [u8; 32] {
+    hash_vinfo(&[7u8; 32], &i.to_le_bytes())
+}
+
+#[test]
+fn presets_render_deterministic_passwords_that_satisfy_them() {
+    for preset in [
+        PolicyPreset::Strong,
+        PolicyPreset::Alphanumeric,
+        PolicyPreset::Legacy,
+        PolicyPreset::Pin,
+    ] {
+        let policy = preset.policy();
+        for i in 0..50 {
+            let pw = render_site_password(&vinfo(i), &policy).unwrap();
+            assert_eq!(pw, render_site_password(&vinfo(i), &policy).unwrap());
+            assert_eq!(pw.len(), policy.length as usize);
+
+            let has = |f: fn(&char) -> bool| pw.chars().any(|c| f(&c));
+            assert_eq!(
+                has(char::is_ascii_lowercase),
+                policy.lower == ClassRule::Required
+            );
+            assert_eq!(
+                has(char::is_ascii_uppercase),
+                policy.upper == ClassRule::Required
+            );
+            assert!(has(char::is_ascii_digit));
+            assert_eq!(
+                has(char::is_ascii_punctuation),
+                policy.symbols == ClassRule::Required
+            );
+            assert!(pw
+                .chars()
+                .filter(char::is_ascii_punctuation)
+                .all(|c| policy.symbol_set.contains(c)));
+        }
+    }
+
+    let strong = PolicyPreset::Strong.policy();
+    assert_ne!(
+        render_site_password(&vinfo(0), &strong).unwrap(),
+        render_site_password(&vinfo(1), &strong).unwrap()
+    );
+}
+
+#[test]
+fn banned_characters_never_appear_and_alphabet_is_covered() {
+    let policy = PasswordPolicy {
+        length: 8,
+        lower: ClassRule::Allowed,
+        upper: ClassRule::Forbidden,
+        digits: ClassRule::Required,
+        symbols: ClassRule::Forbidden,
+        symbol_set: String::new(),
+        banned: "abcdefghijklmnopqrstuvw01".into(),
+    };
+    let mut seen = BTreeSet::new();
+    for i in 0..200 {
+        let pw = render_site_password(&vinfo(i), &policy).unwrap();
+        assert!(pw.chars().any(|c| c.is_ascii_digit()));
+        seen.extend(pw.chars());
+    }
+    assert_eq!(
+        seen.into_iter().collect::<String>(),
+        "23456789xyz",
+        "every allowed character should show up"
+    );
+}
+
+#[test]
+fn invalid_policies_are_rejected() {
+    let base = PolicyPreset::Strong.policy();
+    let cases = [
+        PasswordPolicy {
+            length: 0,
+            ..base.clone()
+        },
+        PasswordPolicy {
+            length: 3,
+            ..base.clone()
+        },
+        PasswordPolicy {
+            symbol_set: "ab".into(),
+            ..base.clone()
+        },
+        PasswordPolicy {
+            banned: "0123456789".into(),
+            ..base.clone()
+        },
+        PasswordPolicy {
+            lower: ClassRule::Forbidden,
+            upper: ClassRule::Forbidden,
+            digits: ClassRule::Forbidden,
+            symbols: ClassRule::Forbidden,
+            ..base.clone()
+        },
+    ];
+    for policy in cases {
+        assert!(matches!(
+            render_site_password(&vinfo(0), &policy),
+            Err(UpspaError::InvalidPolicy(_))
+        ));
+    }
+}
+
+#[test]
+fn policies_serialize_and_round_trip_through_the_record() {
+    let spec: PolicySpec = serde_json::from_str("\"pin\"").unwrap();
+    assert_eq!(PasswordPolicy::from(spec), PolicyPreset::Pin.policy());
+
+    let legacy = PolicyPreset::Legacy.policy();
+    let json = serde_json::to_string(&legacy).unwrap();
+    let spec: PolicySpec = serde_json::from_str(&json).unwrap();
+    assert_eq!(PasswordPolicy::from(spec), legacy);
+
+    assert_eq!(
+        PasswordPolicy::from_bytes(&legacy.to_bytes()).unwrap(),
+        legacy
+    );
+    let record = LsRecord::Policy {
+        policy: legacy.clone(),
+    };
+    assert_eq!(LsRecord::from_bytes(&record.to_bytes().unwrap()).unwrap(), record);
+
+    let auth = AuthResult {
+        vinfo_prime: vinfo(3).into(),
+        best_ctr: 0,
+        record,
+        sp_ctrs: vec![(1, 0)],
+        stale: Vec::new(),
+        invalid: Vec::new(),
+        repairs: Vec::new(),
+    };
+    assert_eq!(
+        auth.site_password().unwrap(),
+        Some(render_site_password(&vinfo(3), &legacy).unwrap())
+    );
+}
@@ -0,0 +1,131 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+
+use upspa_core::hash::hash_suid;
+use upspa_core::protocol::deprovision::verify_deprovision_for_sp;
+use upspa_core::protocol::password_update::verify_password_update_for_sp;
+use upspa_core::protocol::reconfigure::{client_reconfigure, KnownRecord};
+use upspa_core::protocol::{authenticate, decrypt_cid, register, setup, LsRecord};
+use upspa_core::toprf::toprf_share_commitment;
+use upspa_core::{OprfSuite, SecretBytes, UpspaError};
+
+use common::login;
+
+#[test]
+fn reconfigure_moves_account_to_new_provider_set() {
+    let uid = b"user123";
+    let lsj = b"LS1";
+    let mut rng = ChaCha20Rng::from_seed([31u8; 32]);
+    let (out, _) = setup::client_setup(uid, b"pw", &[1, 2, 3], 2, &mut rng).unwrap();
+    let state_key = login(b"pw", &out.shares[..2], &mut rng);
+    let reg = register::client_register(uid, lsj, &state_key, &out.cid, &[1, 2, 3], &LsRecord::None, &mut rng).unwrap();
+
+    // 2-of-{1,2,3} -> 3-of-{1,3,4,5}: SP 2 leaves, SPs 4 and 5 join.
+    let records = [KnownRecord {
+        lsj: lsj.to_vec(),
+        cj: reg.per_sp[0].cj.clone(),
+    }];
+    let re = client_reconfigure(
+        uid,
+        b"pw",
+        OprfSuite::Blake3,
+        &state_key,
+        &out.cid,
+        &[1, 2, 3],
+        &[1, 3, 4, 5],
+        3,
+        &records,
+        500,
+        &mut rng,
+    )
+    .unwrap();
+
+    let mut shares = BTreeMap::new();
+    for m in &re.updates {
+        verify_password_update_for_sp(m.sp_id, m, &out.sig_pk, 0).unwrap();
+        shares.insert(m.sp_id, m.k_i_new.clone());
+    }
+    for p in &re.setups {
+        assert_eq!(p.sig_pk, out.sig_pk);
+        assert_eq!(p.cid, re.cid_new);
+        shares.insert(p.sp_id, p.k_i.clone());
+    }
+    assert_eq!(shares.keys().copied().collect::<Vec<_>>(), vec![1, 3, 4, 5]);
+    assert_eq!(
+        re.updates.iter().map(|m| m.sp_id).collect::<Vec<_>>(),
+        vec![1, 3]
+    );
+    for (id, c) in &re.commitments {
+        assert_eq!(*c, toprf_share_commitment(&shares[id]).unwrap());
+    }
+
+    let new_shares: Vec<(u32, SecretBytes<32>)> = shares.into_iter().collect();
+    let state_key_new = login(b"pw", &new_shares[1..], &mut rng);
+    let cid_pt = decrypt_cid(uid, &state_key_new, &re.cid_new).unwrap();
+    let old_pt = decrypt_cid(uid, &state_key, &out.cid).unwrap();
+    assert_eq!(old_pt.sp_ids, vec![1, 2, 3]);
+    assert_eq!(cid_pt.sp_ids, vec![1, 3, 4, 5]);
+    assert_eq!(
+        cid_pt.to_bytes().unwrap(),
+        old_pt.upgraded(&[1, 3, 4, 5]).to_bytes().unwrap()
+    );
+
+    let q = authenticate::client_auth_prepare(uid, lsj, &state_key_new, &re.cid_new, &[1, 3, 4, 5]).unwrap();
+    assert_eq!(re.record_copies.len(), 2);
+    for copy in &re.record_copies {
+        let (_, suid) = q.per_sp.iter().find(|(id, _)| *id == copy.sp_id).unwrap();
+        assert_eq!(copy.suid, *suid);
+    }
+    let copy = &re.record_copies[0];
+    let auth =
+        authenticate::client_auth_finish(uid, lsj, &q.k0, &[(copy.sp_id, copy.cj.clone())], 1)
+            .unwrap();
+    assert_eq!(auth.vinfo_prime, reg.to_ls.vinfo);
+
+    assert_eq!(re.deletions.len(), 1);
+    let d = &re.deletions[0];
+    assert_eq!(d.sp_id, 2);
+    assert_eq!(d.suids, vec![reg.per_sp[1].suid]);
+    assert_eq!(d.suids[0], hash_suid(&cid_pt.rsp, lsj, 2));
+    verify_deprovision_for_sp(2, d, &out.sig_pk, 0).unwrap();
+    assert!(verify_deprovision_for_sp(1, d, &out.sig_pk, 0).is_err());
+}
+
+#[test]
+fn reconfigure_rejects_bad_provider_sets() {
+    let uid = b"user123";
+    let mut rng = ChaCha20Rng::from_seed([32u8; 32]);
+    let (out, _) = setup::client_setup(uid, b"pw", &[1, 2, 3], 2, &mut rng).unwrap();
+    let state_key = login(b"pw", &out.shares[..2], &mut rng);
+
+    let mut run = |new_ids: &[u32], tsp: usize| {
+        client_reconfigure(
+            uid,
+            b"pw",
+            OprfSuite::Blake3,
+            &state_key,
+            &out.cid,
+            &[1, 2, 3],
+            new_ids,
+            tsp,
+            &[],
+            1,
+            &mut rng,
+        )
+        .map(|_| ())
+    };
+    assert!(matches!(
+        run(&[1, 2, 2], 2),
+        Err(UpspaError::InvalidSpId(2))
+    ));
+    assert!(matches!(run(&[0, 1], 1), Err(UpspaError::InvalidSpId(0))));
+    assert!(matches!(
+        run(&[1, 2], 3),
+        Err(UpspaError::InvalidThreshold { tsp: 3, nsp: 2 })
+    ));
+    assert!(run(&[1, 2, 3, 4], 4).is_ok());
+}
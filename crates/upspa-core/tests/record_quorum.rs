@@ -0,0 +1,219 @@
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+
+use upspa_core::protocol::{authenticate, register, secret_update, setup, CipherSp, LsRecord};
+use upspa_core::toprf::{toprf_server_eval, ToprfClient, ToprfPartial};
+use upspa_core::UpspaError;
+
+const UID: &[u8] = b"user123";
+const LSJ: &[u8] = b"ls.example";
+
+/// `K0` and the Π3 `c_j` for an account on SPs 1..=3.
+fn registered(rng: &mut ChaCha20Rng) -> ([u8; 32], CipherSp) {
+    let (out, _) = setup::client_setup(UID, b"pw", &[1, 2, 3], 2, rng).unwrap();
+    let (state, blinded) = ToprfClient::begin(b"pw", rng);
+    let partials: Vec<ToprfPartial> = out.shares[..2]
+        .iter()
+        .map(|(id, k)| ToprfPartial {
+            id: *id,
+            y: toprf_server_eval(&blinded, k).unwrap(),
+            proof: None,
+        })
+        .collect();
+    let state_key = ToprfClient::finish(b"pw", &state, &partials).unwrap();
+    let reg = register::client_register(
+        UID,
+        LSJ,
+        &state_key,
+        &out.cid,
+        &[1, 2, 3],
+        &LsRecord::None,
+        rng,
+    )
+    .unwrap();
+    let q = authenticate::client_auth_prepare(UID, LSJ, &state_key, &out.cid, &[1, 2, 3]).unwrap();
+    (*q.k0, reg.per_sp[0].cj.clone())
+}
+
+#[test]
+fn stale_sps_are_reported_with_repairs() {
+    let mut rng = ChaCha20Rng::from_seed([61u8; 32]);
+    let (k0, cj0) = registered(&mut rng);
+    let su = secret_update::client_secret_update_finish(
+        UID,
+        LSJ,
+        &k0,
+        &[(1, cj0.clone()), (2, cj0.clone())],
+        2,
+        &mut rng,
+    )
+    .unwrap();
+
+    // SP 3 missed the update.
+    let cjs = [
+        (1, su.cj_new.clone()),
+        (2, su.cj_new.clone()),
+        (3, cj0.clone()),
+    ];
+    let auth = authenticate::client_auth_finish(UID, LSJ, &k0, &cjs, 2).unwrap();
+    assert_eq!((&auth.vinfo_prime, auth.best_ctr), (&su.vinfo_new, 1));
+    assert_eq!(auth.sp_ctrs, vec![(1, 1), (2, 1), (3, 0)]);
+    assert_eq!(auth.stale, vec![3]);
+    assert_eq!(auth.repairs.len(), 1);
+    assert_eq!(auth.repairs[0].0, 3);
+    assert_eq!(auth.repairs[0].1.to_b64(), su.cj_new.to_b64());
+
+    // Applying the repair leaves nothing stale.
+    let repaired = [
+        (1, su.cj_new.clone()),
+        (2, su.cj_new.clone()),
+        auth.repairs[0].clone(),
+    ];
+    let auth = authenticate::client_auth_finish(UID, LSJ, &k0, &repaired, 3).unwrap();
+    assert!(auth.stale.is_empty() && auth.repairs.is_empty());
+
+    assert!(matches!(
+        authenticate::client_auth_finish(UID, LSJ, &k0, &cjs, 3),
+        Err(UpspaError::RecordQuorum {
+            ctr: 1,
+            got: 2,
+            needed: 3,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn unconfirmed_newest_record_is_refused() {
+    let mut rng = ChaCha20Rng::from_seed([62u8; 32]);
+    let (k0, cj0) = registered(&mut rng);
+    let su =
+        secret_update::client_secret_update_finish(UID, LSJ, &k0, &[(1, cj0.clone())], 1, &mut rng)
+            .unwrap();
+
+    // Only SP 1 holds ctr 1: either the update is half-applied or SPs 2 and 3
+    // rolled back. Either way the caller has to find out.
+    let cjs = [(1, su.cj_new.clone()), (2, cj0.clone()), (3, cj0.clone())];
+    assert!(matches!(
+        authenticate::client_auth_finish(UID, LSJ, &k0, &cjs, 2),
+        Err(UpspaError::RecordQuorum {
+            ctr: 1,
+            got: 1,
+            needed: 2,
+            ..
+        })
+    ));
+    assert!(matches!(
+        secret_update::client_secret_update_finish(UID, LSJ, &k0, &cjs, 2, &mut rng),
+        Err(UpspaError::RecordQuorum { ctr: 1, got: 1, .. })
+    ));
+
+    // Two concurrent updates can both produce ctr 1 with different secrets.
+    let other =
+        secret_update::client_secret_update_finish(UID, LSJ, &k0, &[(1, cj0.clone())], 1, &mut rng)
+            .unwrap();
+    let forked = [(1, su.cj_new.clone()), (2, other.cj_new.clone())];
+    assert!(matches!(
+        authenticate::client_auth_finish(UID, LSJ, &k0, &forked, 2),
+        Err(UpspaError::RecordQuorum { got: 1, .. })
+    ));
+}
+
+#[test]
+fn record_sp_ids_and_quorum_are_validated() {
+    let mut rng = ChaCha20Rng::from_seed([63u8; 32]);
+    let (k0, cj0) = registered(&mut rng);
+
+    let dup = [(2, cj0.clone()), (2, cj0.clone())];
+    assert!(matches!(
+        authenticate::client_auth_finish(UID, LSJ, &k0, &dup, 1),
+        Err(UpspaError::InvalidSpId(2))
+    ));
+    assert!(matches!(
+        authenticate::client_auth_finish(UID, LSJ, &k0, &[(1, cj0)], 0),
+        Err(UpspaError::InvalidThreshold { tsp: 0, .. })
+    ));
+}
+
+fn corrupt(cj: &CipherSp) -> CipherSp {
+    let mut bad = cj.clone();
+    bad.ct[0] ^= 1;
+    bad
+}
+
+#[test]
+fn undecryptable_blobs_are_skipped_and_reported() {
+    let mut rng = ChaCha20Rng::from_seed([64u8; 32]);
+    let (k0, cj0) = registered(&mut rng);
+
+    // SP 2 returns garbage; SPs 1 and 3 still make the quorum.
+    let cjs = [(1, cj0.clone()), (2, corrupt(&cj0)), (3, cj0.clone())];
+    let auth = authenticate::client_auth_finish(UID, LSJ, &k0, &cjs, 2).unwrap();
+    assert_eq!(auth.best_ctr, 0);
+    assert_eq!(auth.sp_ctrs, vec![(1, 0), (3, 0)]);
+    assert!(auth.stale.is_empty());
+    assert_eq!(auth.invalid, vec![2]);
+    assert_eq!(auth.repairs.len(), 1);
+    assert_eq!(auth.repairs[0].0, 2);
+
+    let su = secret_update::client_secret_update_finish(UID, LSJ, &k0, &cjs, 2, &mut rng).unwrap();
+    assert_eq!((su.old_ctr, su.invalid), (0, vec![2]));
+    let survey = secret_update::survey_records(UID, LSJ, &k0, &cjs).unwrap();
+    assert!(survey.is_consistent());
+    assert_eq!(survey.invalid, vec![2]);
+
+    // Too few valid records left: the error still names the bad SP.
+    match authenticate::client_auth_finish(UID, LSJ, &k0, &cjs, 3) {
+        Err(UpspaError::RecordQuorum {
+            got: 2,
+            needed: 3,
+            invalid,
+            ..
+        }) => assert_eq!(invalid, vec![2]),
+        other => panic!("expected quorum error, got {other:?}"),
+    }
+
+    let garbage = [(1, corrupt(&cj0)), (3, corrupt(&cj0))];
+    match authenticate::client_auth_finish(UID, LSJ, &k0, &garbage, 1) {
+        Err(UpspaError::InvalidRecords(ids)) => assert_eq!(ids, vec![1, 3]),
+        other => panic!("expected invalid records, got {other:?}"),
+    }
+}
+
+#[test]
+fn equal_ctr_forks_resolve_by_holders_in_any_order() {
+    let mut rng = ChaCha20Rng::from_seed([65u8; 32]);
+    let (k0, cj0) = registered(&mut rng);
+    let a =
+        secret_update::client_secret_update_finish(UID, LSJ, &k0, &[(1, cj0.clone())], 1, &mut rng)
+            .unwrap();
+    let b =
+        secret_update::client_secret_update_finish(UID, LSJ, &k0, &[(1, cj0.clone())], 1, &mut rng)
+            .unwrap();
+
+    // Both updates wrote ctr 1; SPs 2 and 3 hold `b`.
+    let cjs = [
+        (1, a.cj_new.clone()),
+        (2, b.cj_new.clone()),
+        (3, b.cj_new.clone()),
+    ];
+    let mut reversed = cjs.clone();
+    reversed.reverse();
+    for cjs in [&cjs, &reversed] {
+        let auth = authenticate::client_auth_finish(UID, LSJ, &k0, cjs, 2).unwrap();
+        assert_eq!((&auth.vinfo_prime, auth.best_ctr), (&b.vinfo_new, 1));
+        assert_eq!(auth.stale, vec![1]);
+
+        let survey = secret_update::survey_records(UID, LSJ, &k0, cjs).unwrap();
+        assert!(!survey.is_consistent());
+        assert_eq!(survey.stale, vec![(1, 1)]);
+        assert_eq!(survey.vinfo_latest, b.vinfo_new);
+        assert_eq!(survey.vinfo_previous, Some(a.vinfo_new.clone()));
+    }
+
+    // With one holder each, the lowest sp_id wins whatever the order.
+    let even = [(2, b.cj_new.clone()), (1, a.cj_new.clone())];
+    let survey = secret_update::survey_records(UID, LSJ, &k0, &even).unwrap();
+    assert_eq!(survey.vinfo_latest, a.vinfo_new);
+    assert_eq!(survey.stale, vec![(2, 1)]);
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+
+use upspa_core::protocol::password_update::{
+    client_password_update_from_plaintext, verify_password_update_for_sp,
+};
+use upspa_core::protocol::recovery::{
+    client_recovery_setup, recover_cid_plaintext, verify_recovery_for_sp, RecoveryCode,
+};
+use upspa_core::protocol::{decrypt_cid, setup};
+use upspa_core::{OprfSuite, PasswordKdf, SecretBytes, UpspaError};
+
+use common::login;
+
+#[test]
+fn recovery_codes_rebuild_cid_and_reset_password() {
+    let uid = b"user123";
+    let mut rng = ChaCha20Rng::from_seed([66u8; 32]);
+    let (out, _) = setup::client_setup(uid, b"pw", &[1, 2, 3], 2, &mut rng).unwrap();
+    let state_key = login(b"pw", &out.shares[..2], &mut rng);
+    let cid_pt = decrypt_cid(uid, &state_key, &out.cid).unwrap();
+
+    let kit =
+        client_recovery_setup(uid, &state_key, &out.cid, &[1, 2, 3], 5, 3, 10, &mut rng).unwrap();
+    assert_eq!(kit.codes.len(), 5);
+    for m in &kit.per_sp {
+        verify_recovery_for_sp(m.sp_id, m, &out.sig_pk, 9).unwrap();
+    }
+
+    // Codes survive printing; a typo is caught before any crypto runs.
+    let printed: Vec<String> = kit.codes.iter().map(|c| c.to_string()).collect();
+    assert!(printed[0].starts_with("upspa-rc1-1-"));
+    let parsed: Vec<RecoveryCode> = printed.iter().map(|s| s.parse().unwrap()).collect();
+    assert_eq!(parsed, kit.codes);
+    let mut typo = printed[0].clone().into_bytes();
+    let last = typo.len() - 1;
+    typo[last] = if typo[last] == b'A' { b'B' } else { b'A' };
+    assert!(matches!(
+        String::from_utf8(typo).unwrap().parse::<RecoveryCode>(),
+        Err(UpspaError::InvalidRecoveryCode)
+    ));
+
+    // Any three codes work; two do not.
+    for subset in [[0, 1, 2], [4, 2, 0], [1, 3, 4]] {
+        let codes: Vec<RecoveryCode> = subset.iter().map(|&i| kit.codes[i].clone()).collect();
+        let recovered = recover_cid_plaintext(uid, &kit.recovery_cid, &codes).unwrap();
+        assert_eq!(recovered.to_bytes().unwrap(), cid_pt.to_bytes().unwrap());
+    }
+    assert!(matches!(
+        recover_cid_plaintext(uid, &kit.recovery_cid, &kit.codes[..2]),
+        Err(UpspaError::Aead)
+    ));
+
+    // The rebuilt plaintext is enough to run Π5 without the old password.
+    let recovered = recover_cid_plaintext(uid, &kit.recovery_cid, &kit.codes[2..]).unwrap();
+    let upd = client_password_update_from_plaintext(
+        uid,
+        &recovered,
+        &[1, 2, 3],
+        2,
+        b"pw2",
+        &PasswordKdf::None,
+        OprfSuite::Blake3,
+        11,
+        &mut rng,
+    )
+    .unwrap();
+    for m in &upd.per_sp {
+        verify_password_update_for_sp(m.sp_id, m, &out.sig_pk, 10).unwrap();
+    }
+    let new_shares: Vec<(u32, SecretBytes<32>)> = upd
+        .per_sp
+        .iter()
+        .map(|m| (m.sp_id, m.k_i_new.clone()))
+        .collect();
+    let new_state_key = login(b"pw2", &new_shares[1..], &mut rng);
+    let reopened = decrypt_cid(uid, &new_state_key, &upd.cid_new).unwrap();
+    assert_eq!((reopened.rsp, reopened.k0), (cid_pt.rsp, cid_pt.k0));
+}
@@ -0,0 +1,184 @@
+use rand_chacha::ChaCha20Rng;
+use rand_core::{RngCore, SeedableRng};
+
+use curve25519_dalek::ristretto::RistrettoPoint;
+
+use upspa_core::protocol::{decrypt_cid, login, setup};
+use upspa_core::toprf::{
+    toprf_server_eval_verifiable, ToprfClient, ToprfClientState, ToprfPartial,
+};
+use upspa_core::types::UpspaError;
+
+const UID: &[u8] = b"user123";
+const PASSWORD: &[u8] = b"robust password";
+
+fn garbage_point(rng: &mut ChaCha20Rng) -> [u8; 32] {
+    let mut wide = [0u8; 64];
+    rng.fill_bytes(&mut wide);
+    RistrettoPoint::from_uniform_bytes(&wide)
+        .compress()
+        .to_bytes()
+}
+
+struct Round {
+    out: setup::SetupOutput,
+    state: ToprfClientState,
+    blinded: [u8; 32],
+    partials: Vec<ToprfPartial>,
+}
+
+fn honest_round(rng: &mut ChaCha20Rng) -> Round {
+    let (out, _payloads) = setup::client_setup(UID, PASSWORD, &[1, 2, 3, 4, 5], 3, rng).unwrap();
+    let (state, blinded) = ToprfClient::begin(PASSWORD, rng);
+    let partials = out
+        .shares
+        .iter()
+        .map(|(id, k)| {
+            let (y, proof) = toprf_server_eval_verifiable(&blinded, k, rng).unwrap();
+            ToprfPartial {
+                id: *id,
+                y,
+                proof: Some(proof),
+            }
+        })
+        .collect();
+    Round {
+        out,
+        state,
+        blinded,
+        partials,
+    }
+}
+
+#[test]
+fn robust_finish_finds_honest_subset_and_names_faulty_sps() {
+    let mut rng = ChaCha20Rng::from_seed([31u8; 32]);
+    let Round {
+        out,
+        state,
+        mut partials,
+        ..
+    } = honest_round(&mut rng);
+
+    partials[0].y = garbage_point(&mut rng);
+    partials[3].y = garbage_point(&mut rng);
+    for p in partials.iter_mut() {
+        p.proof = None;
+    }
+
+    // A plain finish over all five is poisoned.
+    let poisoned = ToprfClient::finish(PASSWORD, &state, &partials).unwrap();
+    assert!(decrypt_cid(UID, &poisoned, &out.cid).is_err());
+
+    let (res, cid_pt) =
+        login::client_login_robust(UID, PASSWORD, &state, &partials, 3, None, &out.cid).unwrap();
+    assert_eq!(res.inconsistent, vec![1, 4]);
+    assert_eq!(
+        cid_pt.rsp,
+        decrypt_cid(UID, &res.state_key, &out.cid).unwrap().rsp
+    );
+}
+
+#[test]
+fn robust_finish_uses_proofs_before_searching() {
+    let mut rng = ChaCha20Rng::from_seed([32u8; 32]);
+    let Round {
+        out,
+        state,
+        blinded,
+        mut partials,
+    } = honest_round(&mut rng);
+
+    // SP 2 answers with SP 5's share but a valid-looking proof for it.
+    let (y, proof) = toprf_server_eval_verifiable(&blinded, &out.shares[4].1, &mut rng).unwrap();
+    partials[1] = ToprfPartial {
+        id: 2,
+        y,
+        proof: Some(proof),
+    };
+
+    let mut attempts = 0;
+    let res = ToprfClient::finish_robust(
+        PASSWORD,
+        &state,
+        &partials,
+        3,
+        Some(&out.commitments),
+        |key| {
+            attempts += 1;
+            decrypt_cid(UID, key, &out.cid).is_ok()
+        },
+    )
+    .unwrap();
+    assert_eq!(attempts, 1);
+    assert_eq!(res.inconsistent, vec![2]);
+}
+
+#[test]
+fn robust_finish_reports_partials_with_bad_proofs() {
+    let mut rng = ChaCha20Rng::from_seed([34u8; 32]);
+    let Round {
+        out,
+        state,
+        mut partials,
+        ..
+    } = honest_round(&mut rng);
+
+    // SP 3's evaluation is right, but its proof belongs to SP 4.
+    partials[2].proof = partials[3].proof.clone();
+
+    let res = ToprfClient::finish_robust(
+        PASSWORD,
+        &state,
+        &partials,
+        3,
+        Some(&out.commitments),
+        |key| decrypt_cid(UID, key, &out.cid).is_ok(),
+    )
+    .unwrap();
+    assert_eq!(res.inconsistent, vec![3]);
+}
+
+#[test]
+fn robust_finish_fails_without_tsp_honest_partials() {
+    let mut rng = ChaCha20Rng::from_seed([33u8; 32]);
+    let Round {
+        out,
+        state,
+        mut partials,
+        ..
+    } = honest_round(&mut rng);
+    for p in partials.iter_mut().take(3) {
+        p.y = garbage_point(&mut rng);
+    }
+
+    let err = login::client_login_robust(UID, PASSWORD, &state, &partials, 3, None, &out.cid)
+        .unwrap_err();
+    assert!(matches!(err, UpspaError::NoConsistentPartials));
+
+    let err = login::client_login_robust(
+        UID,
+        PASSWORD,
+        &state,
+        &partials,
+        3,
+        Some(&out.commitments),
+        &out.cid,
+    )
+    .unwrap_err();
+    assert!(matches!(
+        err,
+        UpspaError::InvalidLength {
+            expected: 3,
+            got: 2
+        }
+    ));
+
+    let dup = vec![
+        partials[3].clone(),
+        partials[3].clone(),
+        partials[4].clone(),
+    ];
+    let err = ToprfClient::finish_robust(PASSWORD, &state, &dup, 2, None, |_| true).unwrap_err();
+    assert!(matches!(err, UpspaError::InvalidPartials(ref ids) if ids == &vec![4]));
+}
@@ -0,0 +1,201 @@
+mod common;
+
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+
+use upspa_core::protocol::authenticate::{client_auth_finish, client_auth_prepare};
+use upspa_core::protocol::password_update::{client_password_update, PasswordUpdateSpMessage};
+use upspa_core::protocol::recovery::RecoveryCode;
+use upspa_core::protocol::register::client_register;
+use upspa_core::protocol::secret_update::{client_secret_update_finish, survey_records};
+use upspa_core::protocol::{setup, CidPlaintext, CipherSpPlaintext, LsRecord};
+use upspa_core::toprf::{toprf_server_eval, ToprfClient, ToprfPartial};
+use upspa_core::SecretBytes;
+
+use common::login;
+
+const KEY: [u8; 32] = [
+    201, 17, 99, 142, 7, 250, 33, 168, 91, 4, 222, 56, 187, 120, 15, 240, 77, 130, 9, 199, 61, 244,
+    108, 2, 175, 88, 213, 40, 156, 23, 232, 145,
+];
+
+/// Whether `debug` shows any 8-byte window of `key`, in decimal or hex.
+fn leaks(debug: &str, key: &[u8; 32]) -> bool {
+    key.windows(8).any(|w| {
+        let dec = w
+            .iter()
+            .map(|b| b.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let hex: String = w.iter().map(|b| format!("{b:02x}")).collect();
+        debug.contains(&dec) || debug.contains(&hex)
+    })
+}
+
+#[test]
+fn secret_bytes_debug_is_redacted() {
+    let s = SecretBytes::new(KEY);
+    let debug = format!("{s:?}");
+    assert_eq!(debug, "SecretBytes<32>(REDACTED)");
+    assert!(leaks(&format!("{KEY:?}"), &KEY));
+    assert!(!leaks(&format!("{s:#?}"), &KEY));
+}
+
+#[test]
+fn secret_bytes_serialize_like_arrays() {
+    let s = SecretBytes::new(KEY);
+    let json = serde_json::to_string(&s).unwrap();
+    assert_eq!(json, serde_json::to_string(&KEY.to_vec()).unwrap());
+    assert_eq!(serde_json::from_str::<SecretBytes<32>>(&json).unwrap(), s);
+    assert_ne!(s, SecretBytes::zero());
+}
+
+#[test]
+fn protocol_types_do_not_print_key_bytes() {
+    let cid_pt = CidPlaintext::new(KEY, KEY, KEY, &[1, 2, 3]);
+    let debug = format!("{cid_pt:?}");
+    assert!(!leaks(&debug, &KEY));
+    assert!(debug.contains("REDACTED"));
+
+    let cj_pt = CipherSpPlaintext::new(KEY, 3);
+    assert!(!leaks(&format!("{cj_pt:?}"), &KEY));
+
+    let code = RecoveryCode {
+        id: 1,
+        share: SecretBytes::new(KEY),
+    };
+    assert!(!leaks(&format!("{code:?}"), &KEY));
+}
+
+#[test]
+fn login_secrets_do_not_print_key_bytes() {
+    let uid = b"user123";
+    let mut rng = ChaCha20Rng::from_seed([23u8; 32]);
+    let (out, _) = setup::client_setup(uid, b"pw", &[1, 2, 3], 2, &mut rng).unwrap();
+
+    let (state, blinded) = ToprfClient::begin(b"pw", &mut rng);
+    assert!(!leaks(&format!("{state:?}"), &state.r));
+
+    let partials: Vec<ToprfPartial> = out.shares[..2]
+        .iter()
+        .map(|(id, k)| ToprfPartial {
+            id: *id,
+            y: toprf_server_eval(&blinded, k).unwrap(),
+            proof: None,
+        })
+        .collect();
+    let state_key = ToprfClient::finish(b"pw", &state, &partials).unwrap();
+    assert!(!leaks(&format!("{state_key:?}"), &state_key));
+
+    let q = client_auth_prepare(uid, b"LS1", &state_key, &out.cid, &[1, 2, 3]).unwrap();
+    assert!(!leaks(&format!("{q:?}"), &q.k0));
+}
+
+#[test]
+fn key_shares_are_not_printed() {
+    let uid = b"user123";
+    let mut rng = ChaCha20Rng::from_seed([25u8; 32]);
+    let (out, payloads) = setup::client_setup(uid, b"pw", &[1, 2, 3], 2, &mut rng).unwrap();
+    let debug = format!("{out:?}");
+    assert!(out.shares.iter().all(|(_, k)| !leaks(&debug, k)));
+    for p in &payloads {
+        assert!(!leaks(&format!("{p:?}"), &p.k_i));
+    }
+
+    let state_key = login(b"pw", &out.shares[..2], &mut rng);
+    let upd = client_password_update(
+        uid,
+        &state_key,
+        &out.cid,
+        &[1, 2, 3],
+        2,
+        b"pw2",
+        1,
+        &mut rng,
+    )
+    .unwrap();
+    for m in &upd.per_sp {
+        assert!(!leaks(&format!("{m:?}"), &m.k_i_new));
+    }
+    let json = serde_json::to_string(&upd.per_sp[0]).unwrap();
+    let back: PasswordUpdateSpMessage = serde_json::from_str(&json).unwrap();
+    assert_eq!(back.k_i_new, upd.per_sp[0].k_i_new);
+}
+
+#[test]
+fn ls_records_do_not_print_secrets() {
+    let password = LsRecord::Password {
+        password: "correct horse battery staple".into(),
+    };
+    let debug = format!("{password:?}");
+    assert!(!debug.contains("horse"));
+    assert!(debug.contains("REDACTED"));
+
+    let totp = LsRecord::Totp {
+        seed: KEY.to_vec(),
+        digits: 6,
+        period: 30,
+    };
+    let debug = format!("{totp:#?}");
+    assert!(!leaks(&debug, &KEY));
+    assert!(debug.contains("period: 30"));
+
+    let codes = LsRecord::RecoveryCodes {
+        codes: vec!["abcd-efgh".into(), "ijkl-mnop".into()],
+    };
+    let debug = format!("{codes:?}");
+    assert!(!debug.contains("abcd") && !debug.contains("mnop"));
+    assert!(debug.contains("[REDACTED; 2]"));
+}
+
+#[test]
+fn vinfo_is_not_printed() {
+    let uid = b"user123";
+    let lsj = b"LS1";
+    let mut rng = ChaCha20Rng::from_seed([24u8; 32]);
+    let (out, _) = setup::client_setup(uid, b"pw", &[1, 2, 3], 2, &mut rng).unwrap();
+    let (state, blinded) = ToprfClient::begin(b"pw", &mut rng);
+    let partials: Vec<ToprfPartial> = out.shares[..2]
+        .iter()
+        .map(|(id, k)| ToprfPartial {
+            id: *id,
+            y: toprf_server_eval(&blinded, k).unwrap(),
+            proof: None,
+        })
+        .collect();
+    let state_key = ToprfClient::finish(b"pw", &state, &partials).unwrap();
+
+    let record = LsRecord::Password {
+        password: "correct horse battery staple".into(),
+    };
+    let reg = client_register(
+        uid,
+        lsj,
+        &state_key,
+        &out.cid,
+        &[1, 2, 3],
+        &record,
+        &mut rng,
+    )
+    .unwrap();
+    assert!(!leaks(&format!("{reg:?}"), &reg.to_ls.vinfo));
+
+    let q = client_auth_prepare(uid, lsj, &state_key, &out.cid, &[1, 2, 3]).unwrap();
+    let cjs: Vec<_> = reg.per_sp.iter().map(|m| (m.sp_id, m.cj.clone())).collect();
+    let auth = client_auth_finish(uid, lsj, &q.k0, &cjs, 2).unwrap();
+    let debug = format!("{auth:?}");
+    assert!(!leaks(&debug, &auth.vinfo_prime));
+    assert!(!debug.contains("horse"));
+
+    let su = client_secret_update_finish(uid, lsj, &q.k0, &cjs, 2, &mut rng).unwrap();
+    let debug = format!("{su:?}");
+    assert!(!leaks(&debug, &su.vinfo_prime));
+    assert!(!leaks(&debug, &su.vinfo_new));
+
+    let mut half_applied = cjs.clone();
+    half_applied[0].1 = su.cj_new.clone();
+    let survey = survey_records(uid, lsj, &q.k0, &half_applied).unwrap();
+    let debug = format!("{survey:?}");
+    assert!(!leaks(&debug, &survey.vinfo_latest));
+    assert!(!leaks(&debug, survey.vinfo_previous.as_ref().unwrap()));
+}
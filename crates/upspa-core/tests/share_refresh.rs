@@ -0,0 +1,105 @@
+mod common;
+
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+
+use upspa_core::protocol::decrypt_cid;
+use upspa_core::protocol::command::CommandError;
+use upspa_core::protocol::setup;
+use upspa_core::protocol::share_refresh::{
+    client_share_refresh, share_refresh_sig_msg, verify_share_refresh_for_sp,
+};
+use upspa_core::toprf::{
+    toprf_refresh_commitments, toprf_refresh_share, toprf_share_commitment, ToprfClient,
+    ToprfClientState,
+};
+use upspa_core::SecretBytes;
+
+use common::partials;
+
+fn finish(
+    state: &ToprfClientState,
+    blinded: &[u8; 32],
+    shares: &[(u32, SecretBytes<32>)],
+) -> SecretBytes<32> {
+    ToprfClient::finish(b"pw", state, &partials(blinded, shares)).unwrap()
+}
+
+#[test]
+fn refresh_keeps_state_key_and_invalidates_old_shares() {
+    let uid = b"user123";
+    let mut rng = ChaCha20Rng::from_seed([21u8; 32]);
+    let (out, _) = setup::client_setup(uid, b"pw", &[1, 2, 3], 2, &mut rng).unwrap();
+    let old = out.shares.clone();
+
+    let (state, blinded) = ToprfClient::begin(b"pw", &mut rng);
+    let state_key = finish(&state, &blinded, &old[..2]);
+
+    let refresh = client_share_refresh(uid, &state_key, &out.cid, &[1, 2, 3], 2, 7, &mut rng).unwrap();
+    let new: Vec<(u32, SecretBytes<32>)> = old
+        .iter()
+        .zip(&refresh.per_sp)
+        .map(|((id, k), m)| {
+            verify_share_refresh_for_sp(*id, m, &out.sig_pk, 0).unwrap();
+            (*id, SecretBytes::new(toprf_refresh_share(k, &m.delta).unwrap()))
+        })
+        .collect();
+    assert!(old.iter().zip(&new).all(|(a, b)| a.1 != b.1));
+
+    assert_eq!(finish(&state, &blinded, &new[..2]), state_key);
+    assert_eq!(finish(&state, &blinded, &new[1..]), state_key);
+    decrypt_cid(uid, &state_key, &out.cid).unwrap();
+
+    // A pre-refresh share no longer combines with refreshed ones.
+    let mixed = [old[0].clone(), new[1].clone()];
+    assert_ne!(finish(&state, &blinded, &mixed), state_key);
+
+    let commitments =
+        toprf_refresh_commitments(&out.commitments, &refresh.delta_commitments).unwrap();
+    for ((id, c), (_, k)) in commitments.iter().zip(&new) {
+        assert_eq!(*c, toprf_share_commitment(k).unwrap(), "sp {id}");
+    }
+}
+
+#[test]
+fn verify_share_refresh_rejects_bad_requests() {
+    let uid = b"user123";
+    let mut rng = ChaCha20Rng::from_seed([22u8; 32]);
+    let (out, _) = setup::client_setup(uid, b"pw", &[1, 2, 3], 2, &mut rng).unwrap();
+    let (state, blinded) = ToprfClient::begin(b"pw", &mut rng);
+    let shares = &out.shares;
+    let state_key = finish(&state, &blinded, &shares[..2]);
+
+    let refresh = client_share_refresh(uid, &state_key, &out.cid, &[1, 2, 3], 2, 50, &mut rng).unwrap();
+    let m = &refresh.per_sp[0];
+
+    let msg = share_refresh_sig_msg(uid, &m.delta, m.timestamp, m.sp_id);
+    assert_eq!(&msg[..17], b"uptspa/command/v1");
+    assert_eq!(&msg[17..19], &3u16.to_le_bytes());
+    assert_eq!(&msg[23..30], uid);
+    assert_eq!(&msg[30..34], &1u32.to_le_bytes());
+    assert_eq!(&msg[34..42], &50u64.to_le_bytes());
+    assert_eq!(&msg[42..46], &32u32.to_le_bytes());
+    assert_eq!(&msg[46..], &m.delta);
+
+    assert_eq!(
+        verify_share_refresh_for_sp(1, m, &out.sig_pk, 50),
+        Err(CommandError::StaleTimestamp { last: 50, got: 50 })
+    );
+    assert_eq!(
+        verify_share_refresh_for_sp(2, m, &out.sig_pk, 0),
+        Err(CommandError::SpIdMismatch {
+            expected: 2,
+            got: 1
+        })
+    );
+
+    let mut swapped = m.clone();
+    swapped.delta = refresh.per_sp[1].delta;
+    assert_eq!(
+        verify_share_refresh_for_sp(1, &swapped, &out.sig_pk, 0),
+        Err(CommandError::Signature)
+    );
+
+    assert!(client_share_refresh(uid, &[0u8; 32], &out.cid, &[1, 2, 3], 2, 51, &mut rng).is_err());
+}
@@ -0,0 +1,138 @@
+mod common;
+
+use curve25519_dalek::ristretto::CompressedRistretto;
+use curve25519_dalek::scalar::Scalar;
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+
+use upspa_core::hash::{rfc9497_finalize, rfc9497_hash_to_group};
+use upspa_core::protocol::decrypt_cid;
+use upspa_core::protocol::setup::{client_setup, client_setup_with_suite, SetupOutput};
+use upspa_core::{OprfSuite, PasswordKdf, UpspaError};
+
+use common::{login, login_with_suite};
+
+fn hex(s: &str) -> Vec<u8> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+fn hex32(s: &str) -> [u8; 32] {
+    hex(s).try_into().unwrap()
+}
+
+// RFC 9497 Appendix A.1.1, ristretto255-SHA512, OPRF mode.
+const SK_SM: &str = "5ebcea5ee37023ccb9fc2d2019f9d7737be85591ae8652ffa9ef0f4d37063b0e";
+const BLIND: &str = "64d37aed22a27f5191de1c1d69fadb899d8862b58eb4220029e036ec4c1f6706";
+
+struct Vector {
+    input: &'static str,
+    blinded_element: &'static str,
+    evaluation_element: &'static str,
+    output: &'static str,
+}
+
+const VECTORS: [Vector; 2] = [
+    Vector {
+        input: "00",
+        blinded_element: "609a0ae68c15a3cf6903766461307e5c8bb2f95e7e6550e1ffa2dc99e412803c",
+        evaluation_element: "7ec6578ae5120958eb2db1745758ff379e77cb64fe77b0b2d8cc917ea0869c7e",
+        output: "527759c3d9366f277d8c6020418d96bb393ba2afb20ff90df23fb7708264e2f3ab9135e3bd69955851de4b1f9fe8a0973396719b7912ba9ee8aa7d0b5e24bcf6",
+    },
+    Vector {
+        input: "5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a",
+        blinded_element: "da27ef466870f5f15296299850aa088629945a17d1f5b7f5ff043f76b3c06418",
+        evaluation_element: "b4cbf5a4f1eeda5a63ce7b77c7d23f461db3fcab0dd28e4e17cecb5c90d02c25",
+        output: "f4a74c9c592497375e796aa837e907b1a045d34306a749db9f34221f7e750cb4f2a6413a6bf6fa5e19ba6348eb673934a722a7ede2e7621306d18951e7cf2c73",
+    },
+];
+
+#[test]
+fn rfc9497_ristretto255_sha512_vectors() {
+    let sk = Scalar::from_canonical_bytes(hex32(SK_SM)).unwrap();
+    let blind = Scalar::from_canonical_bytes(hex32(BLIND)).unwrap();
+    for v in VECTORS.iter() {
+        let input = hex(v.input);
+        let blinded = rfc9497_hash_to_group(&input) * blind;
+        assert_eq!(blinded.compress().to_bytes(), hex32(v.blinded_element));
+
+        let evaluated = blinded * sk;
+        assert_eq!(evaluated.compress().to_bytes(), hex32(v.evaluation_element));
+
+        let y = CompressedRistretto(hex32(v.evaluation_element))
+            .decompress()
+            .unwrap()
+            * blind.invert();
+        assert_eq!(
+            rfc9497_finalize(&input, &y).unwrap().to_vec(),
+            hex(v.output)
+        );
+    }
+}
+
+#[test]
+fn rfc9497_inputs_must_fit_the_length_prefix() {
+    let y = rfc9497_hash_to_group(b"y");
+    assert!(rfc9497_finalize(&vec![0u8; 0xffff], &y).is_ok());
+    assert!(matches!(
+        rfc9497_finalize(&vec![0u8; 0x10000], &y),
+        Err(UpspaError::InvalidLength {
+            expected: 0xffff,
+            got: 0x10000
+        })
+    ));
+
+    let mut rng = ChaCha20Rng::from_seed([90u8; 32]);
+    let long_password = vec![b'p'; 0x10000];
+    assert!(client_setup_with_suite(
+        b"user123",
+        &long_password,
+        &[1, 2, 3],
+        2,
+        &PasswordKdf::None,
+        OprfSuite::Ristretto255Sha512,
+        &mut rng,
+    )
+    .is_err());
+}
+
+#[test]
+fn setup_records_the_suite_login_must_use() {
+    let uid = b"user123";
+    let mut rng = ChaCha20Rng::from_seed([91u8; 32]);
+    let suite = OprfSuite::Ristretto255Sha512;
+    let (out, _) = client_setup_with_suite(
+        uid,
+        b"pw",
+        &[1, 2, 3],
+        2,
+        &PasswordKdf::None,
+        suite,
+        &mut rng,
+    )
+    .unwrap();
+    assert_eq!(out.suite, suite);
+    let json = serde_json::to_value(&out).unwrap();
+    assert_eq!(json["suite"], "ristretto255-sha512");
+
+    let key = login_with_suite(b"pw", suite, &out.shares[1..], &mut rng);
+    assert!(decrypt_cid(uid, &key, &out.cid).is_ok());
+    let key = login(b"pw", &out.shares[1..], &mut rng);
+    assert!(decrypt_cid(uid, &key, &out.cid).is_err());
+}
+
+#[test]
+fn outputs_without_a_suite_are_blake3() {
+    let mut rng = ChaCha20Rng::from_seed([92u8; 32]);
+    let (out, _) = client_setup(b"user123", b"pw", &[1, 2, 3], 2, &mut rng).unwrap();
+    let mut saved = serde_json::to_value(&out).unwrap();
+    assert_eq!(saved["suite"], "blake3");
+    saved.as_object_mut().unwrap().remove("suite");
+    let saved: SetupOutput = serde_json::from_value(saved).unwrap();
+    assert_eq!(saved.suite, OprfSuite::Blake3);
+
+    let key = login(b"pw", &out.shares[..2], &mut rng);
+    assert!(decrypt_cid(b"user123", &key, &out.cid).is_ok());
+}
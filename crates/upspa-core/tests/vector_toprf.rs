@@ -5,7 +5,7 @@ use curve25519_dalek::ristretto::RistrettoPoint;
 use curve25519_dalek::scalar::Scalar;
 
 use upspa_core::hash::{hash_to_point, oprf_finalize};
-use upspa_core::toprf::toprf_gen;
+use upspa_core::toprf::{lagrange_coeffs_at_zero, toprf_gen, toprf_gen_for_ids};
 use upspa_core::types::UpspaError;
 
 fn rng_from_seed(byte: u8) -> ChaCha20Rng {
@@ -34,9 +34,9 @@ fn lagrange_at_zero(xs: &[Scalar], i: usize) -> Scalar {
 fn combine_in_exponent(xs: &[Scalar], ys: &[RistrettoPoint]) -> RistrettoPoint {
     assert_eq!(xs.len(), ys.len());
     let mut acc = RistrettoPoint::default();
-    for i in 0..xs.len() {
+    for (i, y) in ys.iter().enumerate() {
         let lambda = lagrange_at_zero(xs, i);
-        acc += ys[i] * lambda;
+        acc += y * lambda;
     }
     acc
 }
@@ -109,5 +109,24 @@ fn toprf_reconstruction_fails_with_t_minus_1_shares() -> Result<(), UpspaError>
     let y_direct = p * k_master;
     assert_ne!(y_bad, y_direct);
 
+    Ok(())
+}
+
+#[test]
+fn non_contiguous_sp_ids_reconstruct_and_bad_ids_are_rejected() -> Result<(), UpspaError> {
+    let mut rng = rng_from_seed(9);
+    let (k_master, shares) = toprf_gen_for_ids(&[17, 42, 103], 2, &mut rng)?;
+    assert_eq!(shares.iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec![17, 42, 103]);
+
+    let ids = [shares[0].0, shares[2].0];
+    let lambdas = lagrange_coeffs_at_zero(&ids)?;
+    assert_eq!(shares[0].1 * lambdas[0] + shares[2].1 * lambdas[1], k_master);
+
+    assert!(matches!(lagrange_coeffs_at_zero(&[1, 0]), Err(UpspaError::InvalidSpId(0))));
+    assert!(matches!(lagrange_coeffs_at_zero(&[4, 2, 4]), Err(UpspaError::InvalidSpId(4))));
+    assert!(matches!(
+        toprf_gen_for_ids(&[1, 2], 3, &mut rng),
+        Err(UpspaError::InvalidThreshold { tsp: 3, nsp: 2 })
+    ));
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,80 @@
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+
+use upspa_core::dleq::DleqProof;
+use upspa_core::protocol::{decrypt_cid, setup};
+use upspa_core::toprf::{
+    toprf_server_eval, toprf_server_eval_verifiable, toprf_share_commitment, ToprfClient,
+    ToprfPartial,
+};
+use upspa_core::types::UpspaError;
+
+#[test]
+fn verified_finish_matches_plain_finish() {
+    let uid = b"user123";
+    let password = b"dleq password";
+    let mut rng = ChaCha20Rng::from_seed([21u8; 32]);
+    let (out, _payloads) = setup::client_setup(uid, password, &[1, 2, 3, 4, 5], 3, &mut rng).unwrap();
+
+    for (id, k) in out.shares.iter() {
+        let (_, c) = out.commitments.iter().find(|(cid, _)| cid == id).unwrap();
+        assert_eq!(*c, toprf_share_commitment(k).unwrap());
+    }
+
+    let (state, blinded) = ToprfClient::begin(password, &mut rng);
+    let mut partials = Vec::new();
+    for (id, k) in out.shares.iter().take(3) {
+        let (y, proof) = toprf_server_eval_verifiable(&blinded, k, &mut rng).unwrap();
+        assert_eq!(y, toprf_server_eval(&blinded, k).unwrap());
+        assert_eq!(DleqProof::from_bytes(&proof.to_bytes()), proof);
+        partials.push(ToprfPartial {
+            id: *id,
+            y,
+            proof: Some(proof),
+        });
+    }
+
+    let key = ToprfClient::finish_verified(password, &state, &partials, &out.commitments).unwrap();
+    assert_eq!(
+        key,
+        ToprfClient::finish(password, &state, &partials).unwrap()
+    );
+    decrypt_cid(uid, &key, &out.cid).unwrap();
+}
+
+#[test]
+fn verified_finish_names_lying_sps() {
+    let password = b"dleq password";
+    let mut rng = ChaCha20Rng::from_seed([22u8; 32]);
+    let (out, _payloads) = setup::client_setup(b"user123", password, &[1, 2, 3, 4, 5], 3, &mut rng).unwrap();
+    let (state, blinded) = ToprfClient::begin(password, &mut rng);
+
+    let mut partials = Vec::new();
+    for (id, k) in out.shares.iter().take(4) {
+        let (y, proof) = toprf_server_eval_verifiable(&blinded, k, &mut rng).unwrap();
+        partials.push(ToprfPartial {
+            id: *id,
+            y,
+            proof: Some(proof),
+        });
+    }
+
+    // SP 2 evaluates with another SP's share and proves against that share.
+    let (y, proof) = toprf_server_eval_verifiable(&blinded, &out.shares[4].1, &mut rng).unwrap();
+    partials[1] = ToprfPartial {
+        id: 2,
+        y,
+        proof: Some(proof),
+    };
+    // SP 4 omits its proof.
+    partials[3].proof = None;
+
+    let err =
+        ToprfClient::finish_verified(password, &state, &partials, &out.commitments).unwrap_err();
+    assert!(matches!(err, UpspaError::InvalidPartials(ref ids) if ids == &vec![2, 4]));
+
+    // Without a commitment for an sp_id the partial cannot be trusted either.
+    let err = ToprfClient::finish_verified(password, &state, &partials[..1], &out.commitments[1..])
+        .unwrap_err();
+    assert!(matches!(err, UpspaError::InvalidPartials(ref ids) if ids == &vec![1]));
+}
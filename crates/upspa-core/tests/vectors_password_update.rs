@@ -0,0 +1,102 @@
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+
+use upspa_core::protocol::password_update::{
+    client_password_update, pwd_update_sig_msg, verify_password_update,
+    verify_password_update_for_sp, PasswordUpdateError, PWD_UPDATE_SIG_MSG_LEN,
+};
+use upspa_core::protocol::{setup, CIPHERID_PT_LEN};
+use upspa_core::sign::verify_detached;
+use upspa_core::toprf::{toprf_server_eval, ToprfClient, ToprfPartial};
+
+fn updated_account() -> (
+    [u8; 32],
+    Vec<upspa_core::protocol::password_update::PasswordUpdateSpMessage>,
+) {
+    let uid = b"user123";
+    let mut rng = ChaCha20Rng::from_seed([11u8; 32]);
+    let (out, _payloads) = setup::client_setup(uid, b"old pw", &[1, 2, 3], 2, &mut rng).unwrap();
+
+    let (state, blinded) = ToprfClient::begin(b"old pw", &mut rng);
+    let partials: Vec<ToprfPartial> = out
+        .shares
+        .iter()
+        .take(2)
+        .map(|(id, k)| ToprfPartial {
+            id: *id,
+            y: toprf_server_eval(&blinded, k).unwrap(),
+            proof: None,
+        })
+        .collect();
+    let state_key = ToprfClient::finish(b"old pw", &state, &partials).unwrap();
+
+    let upd = client_password_update(uid, &state_key, &out.cid, &[1, 2, 3], 2, b"new pw", 1_000, &mut rng)
+        .unwrap();
+    (out.sig_pk, upd.per_sp)
+}
+
+#[test]
+fn sig_msg_layout_matches_spec() {
+    let (sig_pk, per_sp) = updated_account();
+    let m = &per_sp[1];
+    let msg = pwd_update_sig_msg(&m.cid_new, &m.k_i_new, m.timestamp, m.sp_id);
+
+    // v2 cid: version, kdf_id, 96 secret bytes, then three 4-byte sp_ids.
+    let n = m.cid_new.ct.len();
+    assert_eq!(n, 2 + CIPHERID_PT_LEN + 2 + 3 * 4);
+    assert_eq!(msg.len(), PWD_UPDATE_SIG_MSG_LEN - CIPHERID_PT_LEN + n);
+    assert_eq!(&msg[0..24], &m.cid_new.nonce);
+    assert_eq!(&msg[24..24 + n], &m.cid_new.ct[..]);
+    let tail = &msg[n - CIPHERID_PT_LEN..];
+    assert_eq!(&tail[120..136], &m.cid_new.tag);
+    assert_eq!(&tail[136..168], m.k_i_new.expose());
+    assert_eq!(&tail[168..176], &1_000u64.to_le_bytes());
+    assert_eq!(&tail[176..180], &2u32.to_le_bytes());
+    verify_detached(&sig_pk, &msg, &m.sig).unwrap();
+}
+
+#[test]
+fn verify_password_update_rejects_bad_requests() {
+    let (sig_pk, per_sp) = updated_account();
+    let m = &per_sp[0];
+
+    verify_password_update(m, &sig_pk, 999).unwrap();
+    verify_password_update_for_sp(1, m, &sig_pk, 0).unwrap();
+
+    assert_eq!(
+        verify_password_update(m, &sig_pk, 1_000),
+        Err(PasswordUpdateError::StaleTimestamp {
+            last: 1_000,
+            got: 1_000
+        })
+    );
+    assert_eq!(
+        verify_password_update_for_sp(2, m, &sig_pk, 0),
+        Err(PasswordUpdateError::SpIdMismatch {
+            expected: 2,
+            got: 1
+        })
+    );
+
+    // Re-addressing a message to another SP breaks the signature.
+    let mut redirected = m.clone();
+    redirected.sp_id = 2;
+    assert_eq!(
+        verify_password_update_for_sp(2, &redirected, &sig_pk, 0),
+        Err(PasswordUpdateError::Signature)
+    );
+
+    let mut tampered = m.clone();
+    tampered.k_i_new = per_sp[1].k_i_new.clone();
+    assert_eq!(
+        verify_password_update(&tampered, &sig_pk, 0),
+        Err(PasswordUpdateError::Signature)
+    );
+
+    let mut bumped = m.clone();
+    bumped.timestamp += 1;
+    assert_eq!(
+        verify_password_update(&bumped, &sig_pk, 0),
+        Err(PasswordUpdateError::Signature)
+    );
+}
@@ -13,7 +13,7 @@ fn setup_produces_decryptable_cid_via_toprf() {
 
     let mut rng = ChaCha20Rng::from_seed([1u8; 32]);
 
-    let (out, _payloads) = setup::client_setup(uid, password, nsp, tsp, &mut rng);
+    let (out, _payloads) = setup::client_setup(uid, password, &[1, 2, 3, 4, 5], tsp, &mut rng).unwrap();
     assert_eq!(out.shares.len(), nsp);
 
     let (state, blinded) = ToprfClient::begin(password, &mut rng);
@@ -21,7 +21,7 @@ fn setup_produces_decryptable_cid_via_toprf() {
     let mut partials = Vec::new();
     for (id, share_bytes) in out.shares.iter().take(tsp) {
         let y_i = toprf_server_eval(&blinded, share_bytes).unwrap();
-        partials.push(ToprfPartial { id: *id, y: y_i });
+        partials.push(ToprfPartial { id: *id, y: y_i, proof: None });
     }
 
     let state_key = ToprfClient::finish(password, &state, &partials).unwrap();
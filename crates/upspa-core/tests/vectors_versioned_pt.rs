@@ -0,0 +1,127 @@
+use rand_chacha::ChaCha20Rng;
+use rand_core::{RngCore, SeedableRng};
+
+use upspa_core::aead::{xchacha_encrypt_detached, xchacha_encrypt_detached_vec};
+use upspa_core::protocol::{
+    cipherid_aad, cipherid_aad_v, ciphersp_aad, decrypt_cid, decrypt_cj, encrypt_cid, encrypt_cj,
+    upgrade_cid, upgrade_cj, CidPlaintext, CipherId, CipherSp, CipherSpPlaintext, LsRecord,
+    CID_VERSION, CIPHERID_PT_LEN, CJ_VERSION, KDF_NONE,
+};
+use upspa_core::UpspaError;
+
+fn random32(rng: &mut ChaCha20Rng) -> [u8; 32] {
+    let mut b = [0u8; 32];
+    rng.fill_bytes(&mut b);
+    b
+}
+
+#[test]
+fn v1_blobs_still_decrypt_and_upgrade_to_v2() {
+    let uid = b"user123";
+    let mut rng = ChaCha20Rng::from_seed([41u8; 32]);
+    let state_key = random32(&mut rng);
+    let (ssk, rsp, k0) = (random32(&mut rng), random32(&mut rng), random32(&mut rng));
+
+    let mut v1_pt = [0u8; CIPHERID_PT_LEN];
+    v1_pt[0..32].copy_from_slice(&ssk);
+    v1_pt[32..64].copy_from_slice(&rsp);
+    v1_pt[64..96].copy_from_slice(&k0);
+    let cid_v1: CipherId =
+        xchacha_encrypt_detached(&state_key, &cipherid_aad(uid), &v1_pt, &mut rng).into();
+
+    let pt = decrypt_cid(uid, &state_key, &cid_v1).unwrap();
+    assert_eq!((pt.version, pt.kdf_id), (1, KDF_NONE));
+    assert_eq!((*pt.ssk_bytes, *pt.rsp, *pt.k0), (ssk, rsp, k0));
+    assert!(pt.sp_ids.is_empty());
+    assert_eq!(*pt.to_bytes().unwrap(), v1_pt.to_vec());
+
+    let cid_v2 = upgrade_cid(uid, &state_key, &cid_v1, &[4, 9], &mut rng).unwrap();
+    assert_eq!(cid_v2.ct.len(), 2 + CIPHERID_PT_LEN + 2 + 2 * 4);
+    let pt2 = decrypt_cid(uid, &state_key, &cid_v2).unwrap();
+    assert_eq!(pt2.version, CID_VERSION);
+    assert_eq!((*pt2.ssk_bytes, *pt2.rsp, *pt2.k0), (ssk, rsp, k0));
+    assert_eq!(pt2.sp_ids, vec![4, 9]);
+
+    let mut cj_pt = [0u8; 40];
+    cj_pt[0..32].copy_from_slice(&rsp);
+    cj_pt[32..40].copy_from_slice(&7u64.to_le_bytes());
+    let cj_v1: CipherSp =
+        xchacha_encrypt_detached(&k0, &ciphersp_aad(uid), &cj_pt, &mut rng).into();
+    assert_eq!(
+        decrypt_cj(uid, &k0, &cj_v1).unwrap(),
+        CipherSpPlaintext {
+            version: 1,
+            rlsj: rsp.into(),
+            ctr: 7,
+            record: LsRecord::None,
+        }
+    );
+
+    let cj_v2 = upgrade_cj(uid, &k0, &cj_v1, &mut rng).unwrap();
+    assert_eq!(cj_v2.ct.len(), 1 + 40 + 1);
+    assert_eq!(
+        decrypt_cj(uid, &k0, &cj_v2).unwrap(),
+        CipherSpPlaintext::new(rsp, 7)
+    );
+}
+
+#[test]
+fn version_is_bound_by_aad() {
+    let uid = b"user123";
+    let mut rng = ChaCha20Rng::from_seed([42u8; 32]);
+    let state_key = random32(&mut rng);
+    let k0 = random32(&mut rng);
+
+    let pt = CidPlaintext::new(random32(&mut rng), random32(&mut rng), k0, &[1, 2, 3]);
+    let cid = encrypt_cid(uid, &state_key, &pt, &mut rng).unwrap();
+    assert_eq!(cid.ct.len(), 2 + CIPHERID_PT_LEN + 2 + 3 * 4);
+    assert!(decrypt_cid(b"user124", &state_key, &cid).is_err());
+
+    // A v2 body sealed under the v1 AAD is not accepted as either version.
+    let relabeled: CipherId =
+        xchacha_encrypt_detached_vec(&state_key, &cipherid_aad(uid), &pt.to_bytes().unwrap(), &mut rng);
+    assert!(matches!(
+        decrypt_cid(uid, &state_key, &relabeled),
+        Err(UpspaError::Aead)
+    ));
+
+    // The version byte inside the plaintext must match the AAD.
+    let mut bytes = pt.to_bytes().unwrap();
+    bytes[0] = 3;
+    let mismatched: CipherId = xchacha_encrypt_detached_vec(
+        &state_key,
+        &cipherid_aad_v(uid, CID_VERSION),
+        &bytes,
+        &mut rng,
+    );
+    assert!(matches!(
+        decrypt_cid(uid, &state_key, &mismatched),
+        Err(UpspaError::UnsupportedVersion(3))
+    ));
+
+    let cj = encrypt_cj(uid, &k0, &CipherSpPlaintext::new([5u8; 32], 1), &mut rng).unwrap();
+    assert_eq!(decrypt_cj(uid, &k0, &cj).unwrap().version, CJ_VERSION);
+    assert!(decrypt_cj(uid, &random32(&mut rng), &cj).is_err());
+}
+
+#[test]
+fn sp_id_count_must_fit_the_length_prefix() {
+    let uid = b"user123";
+    let mut rng = ChaCha20Rng::from_seed([43u8; 32]);
+    let state_key = random32(&mut rng);
+    let ids: Vec<u32> = (1..=u16::MAX as u32 + 1).collect();
+
+    let pt = CidPlaintext::new([1u8; 32], [2u8; 32], [3u8; 32], &ids);
+    assert!(matches!(
+        pt.to_bytes(),
+        Err(UpspaError::InvalidLength {
+            expected: 65535,
+            got: 65536
+        })
+    ));
+    assert!(encrypt_cid(uid, &state_key, &pt, &mut rng).is_err());
+
+    let pt = pt.upgraded(&ids[..u16::MAX as usize]);
+    let cid = encrypt_cid(uid, &state_key, &pt, &mut rng).unwrap();
+    assert_eq!(decrypt_cid(uid, &state_key, &cid).unwrap().sp_ids.len(), 65535);
+}
@@ -0,0 +1,96 @@
+use std::sync::Arc;
+
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{DefaultBodyLimit, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+
+use crate::error::LsError;
+use crate::model::{
+    ChangePasswordRequest, ErrorDetail, ErrorResponse, LoginRequest, RegisterRequest,
+};
+use crate::service::LsService;
+
+pub const MAX_BODY_BYTES: usize = 4 * 1024;
+
+type Shared = State<Arc<LsService>>;
+
+impl IntoResponse for LsError {
+    fn into_response(self) -> Response {
+        let status =
+            StatusCode::from_u16(self.status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let body = ErrorResponse {
+            error: ErrorDetail {
+                code: self.code(),
+                message: self.to_string(),
+            },
+        };
+        (status, Json(body)).into_response()
+    }
+}
+
+fn body<T>(payload: Result<Json<T>, JsonRejection>) -> Result<T, LsError> {
+    payload.map(|Json(v)| v).map_err(|_| LsError::InvalidJson)
+}
+
+pub fn router(service: Arc<LsService>) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/register", post(register))
+        .route("/login", post(login))
+        .route("/change-password", post(change_password))
+        .layer(DefaultBodyLimit::max(MAX_BODY_BYTES))
+        .with_state(service)
+}
+
+async fn health() -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "ok": true }))
+}
+
+// Argon2 is deliberately slow, so hashing runs off the async workers.
+async fn blocking<F>(svc: Arc<LsService>, f: F) -> Result<StatusCode, LsError>
+where
+    F: FnOnce(&LsService) -> Result<StatusCode, LsError> + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || f(&svc))
+        .await
+        .map_err(|e| LsError::Storage(e.to_string()))?
+}
+
+async fn register(
+    State(svc): Shared,
+    payload: Result<Json<RegisterRequest>, JsonRejection>,
+) -> Result<StatusCode, LsError> {
+    let req = body(payload)?;
+    blocking(svc, move |svc| {
+        svc.register(&req)?;
+        Ok(StatusCode::CREATED)
+    })
+    .await
+}
+
+async fn login(
+    State(svc): Shared,
+    payload: Result<Json<LoginRequest>, JsonRejection>,
+) -> Result<StatusCode, LsError> {
+    let req = body(payload)?;
+    blocking(svc, move |svc| {
+        svc.login(&req)?;
+        Ok(StatusCode::OK)
+    })
+    .await
+}
+
+async fn change_password(
+    State(svc): Shared,
+    payload: Result<Json<ChangePasswordRequest>, JsonRejection>,
+) -> Result<StatusCode, LsError> {
+    let req = body(payload)?;
+    blocking(svc, move |svc| {
+        svc.change_password(&req)?;
+        Ok(StatusCode::OK)
+    })
+    .await
+}
@@ -0,0 +1,45 @@
+use std::env;
+
+/// Runtime settings for the reference LS.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    pub port: u16,
+    /// Argon2id memory cost in KiB.
+    pub argon2_m_cost: u32,
+    /// Argon2id passes.
+    pub argon2_t_cost: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            port: 8090,
+            argon2_m_cost: argon2::Params::DEFAULT_M_COST,
+            argon2_t_cost: argon2::Params::DEFAULT_T_COST,
+        }
+    }
+}
+
+impl Config {
+    /// Load `PORT`, `ARGON2_M_COST` and `ARGON2_T_COST`, falling back to the
+    /// defaults for anything missing or unparsable.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            port: parse_var("PORT").unwrap_or(defaults.port),
+            argon2_m_cost: parse_var("ARGON2_M_COST").unwrap_or(defaults.argon2_m_cost),
+            argon2_t_cost: parse_var("ARGON2_T_COST").unwrap_or(defaults.argon2_t_cost),
+        }
+    }
+}
+
+fn parse_var<T: std::str::FromStr>(name: &str) -> Option<T> {
+    let raw = env::var(name).ok()?;
+    match raw.parse() {
+        Ok(v) => Some(v),
+        Err(_) => {
+            eprintln!("warning: invalid {name} setting ({raw}); using default");
+            None
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use upspa_core::types::UpspaError;
+
+#[derive(Clone, Debug, thiserror::Error, PartialEq, Eq)]
+pub enum LsError {
+    #[error("invalid JSON body")]
+    InvalidJson,
+
+    #[error("invalid {0} format or length")]
+    InvalidField(&'static str),
+
+    #[error("account already exists")]
+    Conflict,
+
+    /// Unknown account or wrong `vinfo`; deliberately indistinguishable.
+    #[error("invalid credentials")]
+    InvalidCredentials,
+
+    #[error("storage error: {0}")]
+    Storage(String),
+
+    #[error("password hashing failed: {0}")]
+    Hash(String),
+}
+
+impl LsError {
+    /// Stable machine-readable code, in the same style as the SP.
+    pub fn code(&self) -> String {
+        match self {
+            LsError::InvalidJson => "invalid_json".to_string(),
+            LsError::InvalidField(field) => format!("invalid_{field}"),
+            LsError::Conflict => "conflict".to_string(),
+            LsError::InvalidCredentials => "invalid_credentials".to_string(),
+            LsError::Storage(_) | LsError::Hash(_) => "internal_error".to_string(),
+        }
+    }
+
+    pub fn status(&self) -> u16 {
+        match self {
+            LsError::InvalidJson | LsError::InvalidField(_) => 400,
+            LsError::InvalidCredentials => 401,
+            LsError::Conflict => 409,
+            LsError::Storage(_) | LsError::Hash(_) => 500,
+        }
+    }
+
+    /// Tag a core decoding error with the request field it came from.
+    pub fn field(field: &'static str) -> impl FnOnce(UpspaError) -> LsError {
+        move |_| LsError::InvalidField(field)
+    }
+}
@@ -0,0 +1,23 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use upspa_ls::api::router;
+use upspa_ls::config::Config;
+use upspa_ls::store::MemoryStore;
+use upspa_ls::LsService;
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let cfg = Config::from_env();
+
+    let params = argon2::Params::new(cfg.argon2_m_cost, cfg.argon2_t_cost, 1, None)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e.to_string()))?;
+    let service = LsService::new(Box::new(MemoryStore::new())).with_params(params);
+    let app = router(Arc::new(service));
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], cfg.port));
+    eprintln!("Login Server (LS) listening on {addr}");
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, app).await
+}
@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+/// POST /register request (Π3): `vinfo` from `RegistrationLsMessage`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RegisterRequest {
+    pub uid_b64: String,
+    pub vinfo_b64: String,
+}
+
+/// POST /login request (Π4): `vinfo_prime` from `AuthResult`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LoginRequest {
+    pub uid_b64: String,
+    pub vinfo_prime_b64: String,
+}
+
+/// POST /change-password request (Π4 secret update): the current
+/// `vinfo_prime` authorizes replacing the verifier with one for `vinfo_new`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChangePasswordRequest {
+    pub uid_b64: String,
+    pub vinfo_prime_b64: String,
+    pub vinfo_new_b64: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ErrorDetail {
+    pub code: String,
+    pub message: String,
+}
+
+/// Same error body as the SP.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: ErrorDetail,
+}
@@ -0,0 +1,97 @@
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use rand_core::OsRng;
+use upspa_core::types::{b64_decode, b64_decode_array};
+
+use crate::error::LsError;
+use crate::model::{ChangePasswordRequest, LoginRequest, RegisterRequest};
+use crate::store::LsStore;
+
+/// Transport-independent LS logic. Stores only an Argon2id verifier of
+/// `vinfo` with a per-account random salt, so a leaked store still needs a
+/// slow hash per guess.
+pub struct LsService {
+    argon2: Argon2<'static>,
+    store: Box<dyn LsStore>,
+}
+
+impl LsService {
+    /// Argon2id with the `argon2` crate's default cost (19 MiB, 2 passes).
+    pub fn new(store: Box<dyn LsStore>) -> Self {
+        Self {
+            argon2: Argon2::default(),
+            store,
+        }
+    }
+
+    /// Cost for newly created verifiers. Existing verifiers keep the cost
+    /// recorded in their PHC string.
+    pub fn with_params(mut self, params: Params) -> Self {
+        self.argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+        self
+    }
+
+    /// Π3: create the account with a verifier for `vinfo`.
+    pub fn register(&self, req: &RegisterRequest) -> Result<(), LsError> {
+        let uid = decode_uid(&req.uid_b64)?;
+        let vinfo = b64_decode_array::<32>(&req.vinfo_b64).map_err(LsError::field("vinfo"))?;
+        if !self.store.insert(&uid, self.hash(&vinfo)?)? {
+            return Err(LsError::Conflict);
+        }
+        Ok(())
+    }
+
+    /// Π4: accept the login iff `vinfo_prime` matches the stored verifier.
+    pub fn login(&self, req: &LoginRequest) -> Result<(), LsError> {
+        let uid = decode_uid(&req.uid_b64)?;
+        let vinfo_prime =
+            b64_decode_array::<32>(&req.vinfo_prime_b64).map_err(LsError::field("vinfo_prime"))?;
+        self.check(&uid, &vinfo_prime).map(drop)
+    }
+
+    /// Π4 secret update: after checking `vinfo_prime`, replace the verifier
+    /// with one for `vinfo_new`. Fails if another change won the race.
+    pub fn change_password(&self, req: &ChangePasswordRequest) -> Result<(), LsError> {
+        let uid = decode_uid(&req.uid_b64)?;
+        let vinfo_prime =
+            b64_decode_array::<32>(&req.vinfo_prime_b64).map_err(LsError::field("vinfo_prime"))?;
+        let vinfo_new =
+            b64_decode_array::<32>(&req.vinfo_new_b64).map_err(LsError::field("vinfo_new"))?;
+
+        let current = self.check(&uid, &vinfo_prime)?;
+        if !self.store.replace(&uid, &current, self.hash(&vinfo_new)?)? {
+            return Err(LsError::InvalidCredentials);
+        }
+        Ok(())
+    }
+
+    fn hash(&self, vinfo: &[u8; 32]) -> Result<String, LsError> {
+        let salt = SaltString::generate(&mut OsRng);
+        self.argon2
+            .hash_password(vinfo, &salt)
+            .map(|h| h.to_string())
+            .map_err(|e| LsError::Hash(e.to_string()))
+    }
+
+    /// The stored verifier for `uid` if `vinfo` matches it. Unknown accounts
+    /// cost one hash as well, so timing does not reveal which uids exist.
+    fn check(&self, uid: &[u8], vinfo: &[u8; 32]) -> Result<String, LsError> {
+        let Some(stored) = self.store.get(uid)? else {
+            self.hash(vinfo)?;
+            return Err(LsError::InvalidCredentials);
+        };
+        let parsed = PasswordHash::new(&stored)
+            .map_err(|_| LsError::Storage("malformed verifier".to_string()))?;
+        self.argon2
+            .verify_password(vinfo, &parsed)
+            .map_err(|_| LsError::InvalidCredentials)?;
+        Ok(stored)
+    }
+}
+
+fn decode_uid(uid_b64: &str) -> Result<Vec<u8>, LsError> {
+    match b64_decode(uid_b64) {
+        Ok(uid) if !uid.is_empty() => Ok(uid),
+        _ => Err(LsError::InvalidField("uid")),
+    }
+}
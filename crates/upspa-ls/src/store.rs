@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::error::LsError;
+
+/// Storage backend for the LS. Verifiers are PHC strings
+/// (`$argon2id$v=19$m=...,t=...,p=...$salt$hash`), keyed by raw uid bytes.
+pub trait LsStore: Send + Sync {
+    /// Store the verifier for a new account; returns `false` if `uid`
+    /// already has one.
+    fn insert(&self, uid: &[u8], verifier: String) -> Result<bool, LsError>;
+
+    fn get(&self, uid: &[u8]) -> Result<Option<String>, LsError>;
+
+    /// Swap in `verifier` only if the stored one still equals `expected`;
+    /// returns `false` otherwise.
+    fn replace(&self, uid: &[u8], expected: &str, verifier: String) -> Result<bool, LsError>;
+}
+
+/// Lets the caller keep a handle on a store it hands to the service.
+impl<S: LsStore + ?Sized> LsStore for Arc<S> {
+    fn insert(&self, uid: &[u8], verifier: String) -> Result<bool, LsError> {
+        (**self).insert(uid, verifier)
+    }
+
+    fn get(&self, uid: &[u8]) -> Result<Option<String>, LsError> {
+        (**self).get(uid)
+    }
+
+    fn replace(&self, uid: &[u8], expected: &str, verifier: String) -> Result<bool, LsError> {
+        (**self).replace(uid, expected, verifier)
+    }
+}
+
+/// In-process store for tests and demos.
+#[derive(Default)]
+pub struct MemoryStore {
+    inner: Mutex<HashMap<Vec<u8>, String>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, HashMap<Vec<u8>, String>>, LsError> {
+        self.inner
+            .lock()
+            .map_err(|_| LsError::Storage("memory store lock poisoned".to_string()))
+    }
+}
+
+impl LsStore for MemoryStore {
+    fn insert(&self, uid: &[u8], verifier: String) -> Result<bool, LsError> {
+        let mut st = self.lock()?;
+        if st.contains_key(uid) {
+            return Ok(false);
+        }
+        st.insert(uid.to_vec(), verifier);
+        Ok(true)
+    }
+
+    fn get(&self, uid: &[u8]) -> Result<Option<String>, LsError> {
+        Ok(self.lock()?.get(uid).cloned())
+    }
+
+    fn replace(&self, uid: &[u8], expected: &str, verifier: String) -> Result<bool, LsError> {
+        let mut st = self.lock()?;
+        match st.get_mut(uid) {
+            Some(current) if current == expected => {
+                *current = verifier;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{Method, Request, StatusCode};
+use axum::Router;
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+use upspa_core::types::b64_encode;
+use upspa_ls::api::router;
+use upspa_ls::store::{LsStore, MemoryStore};
+use upspa_ls::LsService;
+
+fn app() -> (Router, Arc<MemoryStore>) {
+    let store = Arc::new(MemoryStore::new());
+    let params = argon2::Params::new(64, 1, 1, None).unwrap();
+    let svc = LsService::new(Box::new(store.clone())).with_params(params);
+    (router(Arc::new(svc)), store)
+}
+
+async fn post(app: &Router, uri: &str, body: Value) -> (StatusCode, Value) {
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    let status = resp.status();
+    let bytes = resp.into_body().collect().await.unwrap().to_bytes();
+    let value = if bytes.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice(&bytes).unwrap()
+    };
+    (status, value)
+}
+
+#[tokio::test]
+async fn register_login_and_change_password() {
+    let (app, store) = app();
+    let uid = b64_encode(b"alice");
+    let (v1, v2) = (b64_encode(&[1u8; 32]), b64_encode(&[2u8; 32]));
+
+    let reg = json!({"uid_b64": uid, "vinfo_b64": v1});
+    assert_eq!(
+        post(&app, "/register", reg.clone()).await.0,
+        StatusCode::CREATED
+    );
+    let (status, err) = post(&app, "/register", reg).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(err["error"]["code"], "conflict");
+
+    let verifier = store.get(b"alice").unwrap().unwrap();
+    assert!(verifier.starts_with("$argon2id$v=19$m=64,t=1,p=1$"));
+    assert!(!verifier.contains(&v1));
+
+    let login = |v: &str| json!({"uid_b64": uid, "vinfo_prime_b64": v});
+    assert_eq!(post(&app, "/login", login(&v1)).await.0, StatusCode::OK);
+    let (status, err) = post(&app, "/login", login(&v2)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(err["error"]["code"], "invalid_credentials");
+
+    let change = |old: &str, new: &str| {
+        json!({
+            "uid_b64": uid,
+            "vinfo_prime_b64": old,
+            "vinfo_new_b64": new,
+        })
+    };
+    assert_eq!(
+        post(&app, "/change-password", change(&v2, &v2)).await.0,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        post(&app, "/change-password", change(&v1, &v2)).await.0,
+        StatusCode::OK
+    );
+    assert_eq!(
+        post(&app, "/login", login(&v1)).await.0,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(post(&app, "/login", login(&v2)).await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn unknown_accounts_and_bad_input_are_rejected() {
+    let (app, _) = app();
+    let v = b64_encode(&[1u8; 32]);
+
+    let (status, err) = post(
+        &app,
+        "/login",
+        json!({"uid_b64": b64_encode(b"nobody"), "vinfo_prime_b64": v}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(err["error"]["code"], "invalid_credentials");
+
+    for (body, code) in [
+        (json!({"uid_b64": "", "vinfo_b64": v}), "invalid_uid"),
+        (
+            json!({"uid_b64": b64_encode(b"a"), "vinfo_b64": b64_encode(&[0u8; 31])}),
+            "invalid_vinfo",
+        ),
+        (
+            json!({"uid_b64": b64_encode(b"a"), "vinfo_b64": v, "extra": 1}),
+            "invalid_json",
+        ),
+    ] {
+        let (status, err) = post(&app, "/register", body).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(err["error"]["code"], code);
+    }
+}
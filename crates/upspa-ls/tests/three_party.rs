@@ -0,0 +1,245 @@
+use std::sync::Arc;
+
+use std::time::Duration;
+
+use tokio::net::TcpListener;
+use upspa_client::{
+    ClientConfig, ClientError, LsHttpTransport, LsTransport, MemoryTransport, PendingSecretUpdate,
+    Recovery, SecretUpdatePhase, Session, SpTransport, UpspaClient,
+};
+use upspa_core::protocol::LsRecord;
+use upspa_core::types::b64_encode;
+use upspa_ls::api::router;
+use upspa_ls::model::{ChangePasswordRequest, LoginRequest, RegisterRequest};
+use upspa_ls::store::MemoryStore;
+use upspa_ls::{LsError, LsService};
+
+fn login(uid: &[u8], vinfo_prime: &[u8; 32]) -> LoginRequest {
+    LoginRequest {
+        uid_b64: b64_encode(uid),
+        vinfo_prime_b64: b64_encode(vinfo_prime),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn client_sps_and_ls_agree_through_secret_update() {
+    let uid = b"user123";
+    let sps: Vec<Arc<dyn SpTransport>> = [1, 2, 3]
+        .into_iter()
+        .map(|id| Arc::new(MemoryTransport::new(id)) as Arc<dyn SpTransport>)
+        .collect();
+    let client =
+        UpspaClient::with_transports(ClientConfig::new(uid.to_vec(), Vec::new(), 2), sps).unwrap();
+    let ls = LsService::new(Box::new(MemoryStore::new()))
+        .with_params(argon2::Params::new(64, 1, 1, None).unwrap());
+
+    client.setup(b"pw").await.unwrap();
+    let session = client.login(b"pw").await.unwrap();
+
+    // Π3: the LS only ever sees `vinfo`.
+    let reg = client
+        .register(&session, b"ls.example", &LsRecord::None)
+        .await
+        .unwrap();
+    ls.register(&RegisterRequest {
+        uid_b64: b64_encode(&reg.to_ls.uid),
+        vinfo_b64: b64_encode(reg.to_ls.vinfo.expose()),
+    })
+    .unwrap();
+
+    // Π4: a fresh login recomputes `vinfo_prime` from the SPs alone.
+    let session = client.login(b"pw").await.unwrap();
+    let auth = client.authenticate(&session, b"ls.example").await.unwrap();
+    ls.login(&login(uid, &auth.vinfo_prime)).unwrap();
+
+    // Π4 secret update: `vinfo_prime` authorizes the switch to `vinfo_new`.
+    let su = client.secret_update(&session, b"ls.example").await.unwrap();
+    ls.change_password(&ChangePasswordRequest {
+        uid_b64: b64_encode(uid),
+        vinfo_prime_b64: b64_encode(su.vinfo_prime.expose()),
+        vinfo_new_b64: b64_encode(su.vinfo_new.expose()),
+    })
+    .unwrap();
+
+    let auth = client.authenticate(&session, b"ls.example").await.unwrap();
+    assert_eq!(auth.vinfo_prime, su.vinfo_new);
+    ls.login(&login(uid, &auth.vinfo_prime)).unwrap();
+    assert_eq!(
+        ls.login(&login(uid, &su.vinfo_prime)),
+        Err(LsError::InvalidCredentials)
+    );
+
+    // A different LS id gives an unrelated `vinfo`.
+    let other = client
+        .register(&session, b"other.example", &LsRecord::None)
+        .await
+        .unwrap();
+    assert_eq!(
+        ls.login(&login(uid, &other.to_ls.vinfo)),
+        Err(LsError::InvalidCredentials)
+    );
+}
+
+struct Parties {
+    sps: Vec<MemoryTransport>,
+    client: UpspaClient,
+    ls_url: String,
+    ls: LsHttpTransport,
+}
+
+async fn parties(uid: &[u8]) -> Parties {
+    let sps: Vec<MemoryTransport> = [1, 2, 3].into_iter().map(MemoryTransport::new).collect();
+    let client = UpspaClient::with_transports(
+        ClientConfig::new(uid.to_vec(), Vec::new(), 2).with_retries(0, Duration::ZERO),
+        sps.iter()
+            .map(|sp| Arc::new(sp.clone()) as Arc<dyn SpTransport>)
+            .collect(),
+    )
+    .unwrap();
+    let service = LsService::new(Box::new(MemoryStore::new()))
+        .with_params(argon2::Params::new(64, 1, 1, None).unwrap());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = router(Arc::new(service));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    let ls_url = format!("http://{addr}");
+    let ls = LsHttpTransport::new(ls_url.clone(), reqwest::Client::new());
+    Parties {
+        sps,
+        client,
+        ls_url,
+        ls,
+    }
+}
+
+async fn enroll(p: &Parties, lsj: &[u8]) -> Session {
+    p.client.setup(b"pw").await.unwrap();
+    let session = p.client.login(b"pw").await.unwrap();
+    let reg = p
+        .client
+        .register(&session, lsj, &LsRecord::None)
+        .await
+        .unwrap();
+    let req = RegisterRequest {
+        uid_b64: b64_encode(&reg.to_ls.uid),
+        vinfo_b64: b64_encode(reg.to_ls.vinfo.expose()),
+    };
+    reqwest::Client::new()
+        .post(format!("{}/register", p.ls_url))
+        .json(&req)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    session
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn two_phase_secret_update_resumes_from_saved_state() {
+    let uid = b"user123";
+    let p = parties(uid).await;
+    let session = enroll(&p, b"ls.example").await;
+    let before = p
+        .client
+        .authenticate(&session, b"ls.example")
+        .await
+        .unwrap();
+
+    let mut pending = p
+        .client
+        .begin_secret_update(&session, b"ls.example")
+        .await
+        .unwrap();
+    assert_eq!(pending.phase, SecretUpdatePhase::Staged);
+    assert_eq!(pending.vinfo_prime, before.vinfo_prime);
+
+    // SP 3 is down while finalizing: the LS is switched, SPs 1 and 2 are
+    // written, and the saved state remembers SP 3.
+    p.sps[2].set_offline(true);
+    let mut saved = Vec::new();
+    let err = p
+        .client
+        .finish_secret_update(&mut pending, &p.ls, |s| {
+            saved.push(serde_json::to_string(s).unwrap())
+        })
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ClientError::Quorum { got: 2, .. }));
+    assert_eq!(saved.len(), 2);
+    assert!(p.ls.check(uid, &pending.vinfo_new).await.unwrap());
+    assert!(!p.ls.check(uid, &pending.vinfo_prime).await.unwrap());
+
+    // Resume from what was persisted, as after a restart.
+    p.sps[2].set_offline(false);
+    let mut resumed: PendingSecretUpdate = serde_json::from_str(saved.last().unwrap()).unwrap();
+    assert_eq!(
+        resumed.phase,
+        SecretUpdatePhase::LsCommitted { remaining: vec![3] }
+    );
+    p.client
+        .finish_secret_update(&mut resumed, &p.ls, |_| {})
+        .await
+        .unwrap();
+    assert_eq!(resumed.phase, SecretUpdatePhase::Done);
+
+    let auth = p
+        .client
+        .authenticate(&session, b"ls.example")
+        .await
+        .unwrap();
+    assert_eq!((&auth.vinfo_prime, auth.best_ctr), (&resumed.vinfo_new, 1));
+    assert_eq!(
+        p.client
+            .recover_secret_update(&session, b"ls.example", &p.ls)
+            .await
+            .unwrap(),
+        Recovery::Consistent { ctr: 1 }
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn recovery_finishes_half_applied_update_from_ctr() {
+    let uid = b"user123";
+    let p = parties(uid).await;
+    let session = enroll(&p, b"ls.example").await;
+
+    // A client that crashed after writing SPs 1 and 2 but before the LS
+    // and SP 3 heard about it.
+    p.sps[2].set_offline(true);
+    let su = p
+        .client
+        .secret_update(&session, b"ls.example")
+        .await
+        .unwrap();
+    p.sps[2].set_offline(false);
+    assert!(p.ls.check(uid, &su.vinfo_prime).await.unwrap());
+
+    match p.client.begin_secret_update(&session, b"ls.example").await {
+        Err(ClientError::UnfinishedUpdate { stale }) => assert_eq!(stale, vec![3]),
+        other => panic!("expected unfinished update, got {other:?}"),
+    }
+
+    let recovery = p
+        .client
+        .recover_secret_update(&session, b"ls.example", &p.ls)
+        .await
+        .unwrap();
+    assert_eq!(
+        recovery,
+        Recovery::Finalized {
+            ctr: 1,
+            repaired: vec![3],
+            ls_committed: true,
+        }
+    );
+    assert!(p.ls.check(uid, &su.vinfo_new).await.unwrap());
+
+    // Every SP now yields `vinfo_new`, so a new update can start.
+    let pending = p
+        .client
+        .begin_secret_update(&session, b"ls.example")
+        .await
+        .unwrap();
+    assert_eq!((&pending.vinfo_prime, pending.new_ctr), (&su.vinfo_new, 2));
+}
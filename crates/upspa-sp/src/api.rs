@@ -0,0 +1,198 @@
+use std::sync::Arc;
+
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{DefaultBodyLimit, Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+
+use crate::error::SpError;
+use crate::model::{
+    DeprovisionRequest, DeviceRevocationRequest, DeviceRevocationResponse, ErrorDetail,
+    ErrorResponse, KeyRotationRequest, PasswordUpdateFinalizeRequest, PasswordUpdateRequest,
+    PasswordUpdateResponse, RecordCreateRequest, RecordResponse, RecordUpdateRequest,
+    RecoveryRequest, RecoveryResponse, SetupRequest, SetupResponse, ShareRefreshRequest,
+    ToprfEvalRequest, ToprfEvalResponse,
+};
+use crate::service::SpService;
+use crate::store::PutOutcome;
+
+/// Maximum accepted request body, same as the Go SP.
+pub const MAX_BODY_BYTES: usize = 8 * 1024;
+
+type Shared = State<Arc<SpService>>;
+
+impl IntoResponse for SpError {
+    fn into_response(self) -> Response {
+        let status =
+            StatusCode::from_u16(self.status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let body = ErrorResponse {
+            error: ErrorDetail {
+                code: self.code(),
+                message: self.to_string(),
+            },
+        };
+        (status, Json(body)).into_response()
+    }
+}
+
+fn body<T>(payload: Result<Json<T>, JsonRejection>) -> Result<T, SpError> {
+    payload.map(|Json(v)| v).map_err(|_| SpError::InvalidJson)
+}
+
+pub fn router(service: Arc<SpService>) -> Router {
+    Router::new()
+        .route("/v1/health", get(health))
+        .route("/v1/setup", post(setup))
+        .route("/v1/setup/{uid_b64}", get(setup_get))
+        .route("/v1/toprf/eval", post(toprf_eval))
+        .route("/v1/records", post(record_create))
+        .route(
+            "/v1/records/{suid_b64}",
+            get(record_get).put(record_update).delete(record_delete),
+        )
+        .route("/v1/password-update", post(password_update))
+        .route(
+            "/v1/password-update/finalize",
+            post(password_update_finalize),
+        )
+        .route("/v1/key-rotation", post(key_rotation))
+        .route("/v1/share-refresh", post(share_refresh))
+        .route("/v1/deprovision", post(deprovision))
+        .route("/v1/recovery", post(recovery_escrow))
+        .route("/v1/recovery/{uid_b64}", get(recovery_get))
+        .route("/v1/devices/revocations", post(device_revocations_put))
+        .route("/v1/devices/revocations/{uid_b64}", get(device_revocations_get))
+        .layer(DefaultBodyLimit::max(MAX_BODY_BYTES))
+        .with_state(service)
+}
+
+async fn health() -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "ok": true }))
+}
+
+async fn setup(
+    State(svc): Shared,
+    payload: Result<Json<SetupRequest>, JsonRejection>,
+) -> Result<StatusCode, SpError> {
+    match svc.setup(&body(payload)?)? {
+        PutOutcome::Created => Ok(StatusCode::CREATED),
+        _ => Ok(StatusCode::OK),
+    }
+}
+
+async fn setup_get(
+    State(svc): Shared,
+    Path(uid_b64): Path<String>,
+) -> Result<Json<SetupResponse>, SpError> {
+    svc.get_setup(&uid_b64).map(Json)
+}
+
+async fn toprf_eval(
+    State(svc): Shared,
+    payload: Result<Json<ToprfEvalRequest>, JsonRejection>,
+) -> Result<Json<ToprfEvalResponse>, SpError> {
+    svc.toprf_eval(&body(payload)?).map(Json)
+}
+
+async fn record_create(
+    State(svc): Shared,
+    payload: Result<Json<RecordCreateRequest>, JsonRejection>,
+) -> Result<StatusCode, SpError> {
+    svc.record_create(&body(payload)?)?;
+    Ok(StatusCode::CREATED)
+}
+
+async fn record_get(
+    State(svc): Shared,
+    Path(suid_b64): Path<String>,
+) -> Result<Json<RecordResponse>, SpError> {
+    svc.record_get(&suid_b64).map(Json)
+}
+
+async fn record_update(
+    State(svc): Shared,
+    Path(suid_b64): Path<String>,
+    payload: Result<Json<RecordUpdateRequest>, JsonRejection>,
+) -> Result<StatusCode, SpError> {
+    svc.record_update(&suid_b64, &body(payload)?)?;
+    Ok(StatusCode::OK)
+}
+
+async fn record_delete(
+    State(svc): Shared,
+    Path(suid_b64): Path<String>,
+) -> Result<StatusCode, SpError> {
+    svc.record_delete(&suid_b64)?;
+    Ok(StatusCode::OK)
+}
+
+async fn password_update(
+    State(svc): Shared,
+    payload: Result<Json<PasswordUpdateRequest>, JsonRejection>,
+) -> Result<Json<PasswordUpdateResponse>, SpError> {
+    svc.password_update(&body(payload)?).map(Json)
+}
+
+async fn password_update_finalize(
+    State(svc): Shared,
+    payload: Result<Json<PasswordUpdateFinalizeRequest>, JsonRejection>,
+) -> Result<StatusCode, SpError> {
+    svc.password_update_finalize(&body(payload)?)?;
+    Ok(StatusCode::OK)
+}
+
+async fn key_rotation(
+    State(svc): Shared,
+    payload: Result<Json<KeyRotationRequest>, JsonRejection>,
+) -> Result<StatusCode, SpError> {
+    svc.key_rotation(&body(payload)?)?;
+    Ok(StatusCode::OK)
+}
+
+async fn share_refresh(
+    State(svc): Shared,
+    payload: Result<Json<ShareRefreshRequest>, JsonRejection>,
+) -> Result<StatusCode, SpError> {
+    svc.share_refresh(&body(payload)?)?;
+    Ok(StatusCode::OK)
+}
+
+async fn deprovision(
+    State(svc): Shared,
+    payload: Result<Json<DeprovisionRequest>, JsonRejection>,
+) -> Result<StatusCode, SpError> {
+    svc.deprovision(&body(payload)?)?;
+    Ok(StatusCode::OK)
+}
+
+async fn recovery_escrow(
+    State(svc): Shared,
+    payload: Result<Json<RecoveryRequest>, JsonRejection>,
+) -> Result<StatusCode, SpError> {
+    svc.recovery_escrow(&body(payload)?)?;
+    Ok(StatusCode::OK)
+}
+
+async fn recovery_get(
+    State(svc): Shared,
+    Path(uid_b64): Path<String>,
+) -> Result<Json<RecoveryResponse>, SpError> {
+    svc.get_recovery(&uid_b64).map(Json)
+}
+
+async fn device_revocations_put(
+    State(svc): Shared,
+    payload: Result<Json<DeviceRevocationRequest>, JsonRejection>,
+) -> Result<StatusCode, SpError> {
+    svc.put_device_revocations(&body(payload)?)?;
+    Ok(StatusCode::OK)
+}
+
+async fn device_revocations_get(
+    State(svc): Shared,
+    Path(uid_b64): Path<String>,
+) -> Result<Json<DeviceRevocationResponse>, SpError> {
+    svc.get_device_revocations(&uid_b64).map(Json)
+}
@@ -0,0 +1,69 @@
+use std::env;
+
+use upspa_core::types::b64_decode_array;
+
+/// Runtime settings, read from the same environment variables as the Go SP.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    pub port: u16,
+    pub sp_id: u32,
+    pub enforce_pwd_update_time: bool,
+    /// Accept Π5 requests in the pre-command signature layout.
+    pub accept_legacy_signatures: bool,
+    /// Seed of the Ed25519 key that signs Π5 acknowledgements; random per
+    /// process when unset.
+    pub signing_key_seed: Option<[u8; 32]>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            port: 8080,
+            sp_id: 1,
+            enforce_pwd_update_time: true,
+            accept_legacy_signatures: true,
+            signing_key_seed: None,
+        }
+    }
+}
+
+impl Config {
+    /// Load `PORT`, `SP_ID`, `ENFORCE_PWD_UPDATE_TIME`,
+    /// `ACCEPT_LEGACY_SIGNATURES` and `SP_SIGNING_KEY` (base64url seed),
+    /// falling back to local development defaults for anything missing or
+    /// unparsable.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            port: parse_var("PORT").unwrap_or(defaults.port),
+            sp_id: parse_var("SP_ID").unwrap_or(defaults.sp_id),
+            enforce_pwd_update_time: parse_var("ENFORCE_PWD_UPDATE_TIME")
+                .unwrap_or(defaults.enforce_pwd_update_time),
+            accept_legacy_signatures: parse_var("ACCEPT_LEGACY_SIGNATURES")
+                .unwrap_or(defaults.accept_legacy_signatures),
+            signing_key_seed: seed_var("SP_SIGNING_KEY"),
+        }
+    }
+}
+
+fn seed_var(name: &str) -> Option<[u8; 32]> {
+    let raw = env::var(name).ok()?;
+    match b64_decode_array::<32>(&raw) {
+        Ok(seed) => Some(seed),
+        Err(_) => {
+            eprintln!("warning: invalid {name} setting; using a random key");
+            None
+        }
+    }
+}
+
+fn parse_var<T: std::str::FromStr>(name: &str) -> Option<T> {
+    let raw = env::var(name).ok()?;
+    match raw.parse() {
+        Ok(v) => Some(v),
+        Err(_) => {
+            eprintln!("warning: invalid {name} setting ({raw}); using default");
+            None
+        }
+    }
+}
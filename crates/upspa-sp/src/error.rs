@@ -0,0 +1,84 @@
+use upspa_core::protocol::command::CommandError;
+use upspa_core::protocol::password_update::PasswordUpdateError;
+use upspa_core::types::UpspaError;
+
+#[derive(Clone, Debug, thiserror::Error, PartialEq, Eq)]
+pub enum SpError {
+    #[error("invalid JSON body")]
+    InvalidJson,
+
+    #[error("invalid {0} format or length")]
+    InvalidField(&'static str),
+
+    #[error("{0} not found")]
+    NotFound(&'static str),
+
+    #[error("{0} already exists with different values")]
+    Conflict(&'static str),
+
+    #[error("sp_id {got} does not match this provider ({expected})")]
+    SpIdMismatch { expected: u32, got: u32 },
+
+    #[error("timestamp must be strictly greater than last update")]
+    StaleTimestamp,
+
+    #[error("Ed25519 signature is invalid")]
+    InvalidSignature,
+
+    #[error("no pending password update with this timestamp")]
+    NoPendingUpdate,
+
+    #[error("storage error: {0}")]
+    Storage(String),
+}
+
+impl SpError {
+    /// Stable machine-readable code, matching the Go SP where one exists.
+    pub fn code(&self) -> String {
+        match self {
+            SpError::InvalidJson => "invalid_json".to_string(),
+            SpError::InvalidField(field) => format!("invalid_{field}"),
+            SpError::NotFound(_) => "not_found".to_string(),
+            SpError::Conflict(_) => "conflict".to_string(),
+            SpError::SpIdMismatch { .. } => "sp_id_mismatch".to_string(),
+            SpError::StaleTimestamp => "stale_timestamp".to_string(),
+            SpError::InvalidSignature => "invalid_signature".to_string(),
+            SpError::NoPendingUpdate => "no_pending_update".to_string(),
+            SpError::Storage(_) => "internal_error".to_string(),
+        }
+    }
+
+    pub fn status(&self) -> u16 {
+        match self {
+            SpError::InvalidJson | SpError::InvalidField(_) | SpError::SpIdMismatch { .. } => 400,
+            SpError::InvalidSignature => 401,
+            SpError::NotFound(_) => 404,
+            SpError::Conflict(_) | SpError::StaleTimestamp | SpError::NoPendingUpdate => 409,
+            SpError::Storage(_) => 500,
+        }
+    }
+
+    /// Tag a core decoding error with the request field it came from.
+    pub fn field(field: &'static str) -> impl FnOnce(UpspaError) -> SpError {
+        move |_| SpError::InvalidField(field)
+    }
+}
+
+impl From<CommandError> for SpError {
+    fn from(e: CommandError) -> Self {
+        PasswordUpdateError::from(e).into()
+    }
+}
+
+impl From<PasswordUpdateError> for SpError {
+    fn from(e: PasswordUpdateError) -> Self {
+        match e {
+            PasswordUpdateError::SpIdMismatch { expected, got } => {
+                SpError::SpIdMismatch { expected, got }
+            }
+            PasswordUpdateError::StaleTimestamp { .. } => SpError::StaleTimestamp,
+            PasswordUpdateError::Signature => SpError::InvalidSignature,
+            PasswordUpdateError::NotPending { .. } => SpError::NoPendingUpdate,
+        }
+    }
+}
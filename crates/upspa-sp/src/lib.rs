@@ -0,0 +1,11 @@
+#![forbid(unsafe_code)]
+
+pub mod api;
+pub mod config;
+pub mod error;
+pub mod model;
+pub mod service;
+pub mod store;
+
+pub use error::SpError;
+pub use service::SpService;
@@ -0,0 +1,32 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use upspa_core::sign::SigningKey;
+use upspa_core::types::b64_encode;
+use upspa_sp::api::router;
+use upspa_sp::config::Config;
+use upspa_sp::store::MemoryStore;
+use upspa_sp::SpService;
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let cfg = Config::from_env();
+
+    let mut service = SpService::new(cfg.sp_id, Box::new(MemoryStore::new()))
+        .with_enforce_pwd_update_time(cfg.enforce_pwd_update_time)
+        .with_accept_legacy_signatures(cfg.accept_legacy_signatures);
+    if let Some(seed) = cfg.signing_key_seed {
+        service = service.with_signing_key(SigningKey::from_bytes(&seed));
+    }
+    let sp_pk = b64_encode(&service.public_key());
+    let app = router(Arc::new(service));
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], cfg.port));
+    eprintln!(
+        "Storage Provider (SP) listening on {addr} | SP_ID: {} | SP_PK: {sp_pk}",
+        cfg.sp_id
+    );
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, app).await
+}
@@ -0,0 +1,179 @@
+use serde::{Deserialize, Serialize};
+use upspa_core::protocol::password_update::{PasswordUpdateAction, SigFormat};
+use upspa_core::types::CtBlobB64;
+
+/// POST /v1/setup request (Π1).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SetupRequest {
+    pub uid_b64: String,
+    pub sig_pk_b64: String,
+    pub cid: CtBlobB64,
+    pub k_i_b64: String,
+}
+
+/// GET /v1/setup/{uid_b64} response.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SetupResponse {
+    pub uid_b64: String,
+    pub sig_pk_b64: String,
+    pub cid: CtBlobB64,
+    /// `K_i = k_i * G`, the commitment DLEQ proofs are checked against.
+    pub k_i_commit_b64: String,
+}
+
+/// POST /v1/toprf/eval request (Π2).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ToprfEvalRequest {
+    pub uid_b64: String,
+    pub blinded_b64: String,
+}
+
+/// POST /v1/toprf/eval response.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ToprfEvalResponse {
+    pub sp_id: u32,
+    pub y_b64: String,
+    /// DLEQ proof `c(32) || s(32)` binding `y` to `k_i_commit_b64`.
+    pub proof_b64: String,
+}
+
+/// POST /v1/records request (Π3).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RecordCreateRequest {
+    pub suid_b64: String,
+    pub cj: CtBlobB64,
+}
+
+/// PUT /v1/records/{suid_b64} request (Π4).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RecordUpdateRequest {
+    pub cj: CtBlobB64,
+}
+
+/// GET /v1/records/{suid_b64} response.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordResponse {
+    pub suid_b64: String,
+    pub cj: CtBlobB64,
+}
+
+/// POST /v1/password-update request (Π5).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PasswordUpdateRequest {
+    pub uid_b64: String,
+    pub sp_id: u32,
+    pub timestamp: u64,
+    pub sig_b64: String,
+    pub cid_new: CtBlobB64,
+    pub k_i_new_b64: String,
+    /// Layout `sig_b64` covers; absent means the legacy Π5 layout.
+    #[serde(default)]
+    pub sig_format: SigFormat,
+}
+
+/// POST /v1/password-update response: the SP's signed acknowledgement.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PasswordUpdateResponse {
+    pub sp_id: u32,
+    pub timestamp: u64,
+    pub sig_b64: String,
+}
+
+/// POST /v1/password-update/finalize request.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PasswordUpdateFinalizeRequest {
+    pub uid_b64: String,
+    pub sp_id: u32,
+    pub timestamp: u64,
+    pub action: PasswordUpdateAction,
+    pub sig_b64: String,
+}
+
+/// POST /v1/key-rotation request.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeyRotationRequest {
+    pub uid_b64: String,
+    pub sp_id: u32,
+    pub timestamp: u64,
+    pub sig_b64: String,
+    pub sig_pk_new_b64: String,
+    pub cid_new: CtBlobB64,
+}
+
+/// POST /v1/share-refresh request.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ShareRefreshRequest {
+    pub uid_b64: String,
+    pub sp_id: u32,
+    pub timestamp: u64,
+    pub sig_b64: String,
+    pub delta_b64: String,
+}
+
+/// POST /v1/deprovision request.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeprovisionRequest {
+    pub uid_b64: String,
+    pub sp_id: u32,
+    pub timestamp: u64,
+    pub sig_b64: String,
+    pub suids_b64: Vec<String>,
+}
+
+/// POST /v1/recovery request.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RecoveryRequest {
+    pub uid_b64: String,
+    pub sp_id: u32,
+    pub timestamp: u64,
+    pub sig_b64: String,
+    pub recovery_cid: CtBlobB64,
+}
+
+/// GET /v1/recovery/{uid_b64} response.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecoveryResponse {
+    pub uid_b64: String,
+    pub recovery_cid: CtBlobB64,
+}
+
+/// POST /v1/devices/revocations request.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceRevocationRequest {
+    pub uid_b64: String,
+    pub issued_at: u64,
+    pub revoked_b64: Vec<String>,
+    pub sig_b64: String,
+}
+
+/// GET /v1/devices/revocations/{uid_b64} response: the stored list as sent.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeviceRevocationResponse {
+    pub uid_b64: String,
+    pub issued_at: u64,
+    pub revoked_b64: Vec<String>,
+    pub sig_b64: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ErrorDetail {
+    pub code: String,
+    pub message: String,
+}
+
+/// Standard error body, shared with the Go SP.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: ErrorDetail,
+}
@@ -0,0 +1,500 @@
+use rand_core::{OsRng, RngCore};
+use upspa_core::protocol::deprovision::{verify_deprovision_for_sp, DeprovisionSpMessage};
+use upspa_core::protocol::device::{verify_device_revocations, DeviceRevocationList};
+use upspa_core::protocol::key_rotation::{verify_key_rotation_for_sp, KeyRotationSpMessage};
+use upspa_core::protocol::password_update::{
+    sign_password_update_ack, verify_password_update_finalize_for_sp,
+    verify_password_update_for_sp, PasswordUpdateFinalizeSpMessage, PasswordUpdateSpMessage,
+    SigFormat,
+};
+use upspa_core::protocol::recovery::{verify_recovery_for_sp, RecoverySpMessage};
+use upspa_core::protocol::share_refresh::{verify_share_refresh_for_sp, ShareRefreshSpMessage};
+use upspa_core::protocol::{CipherId, CipherSp};
+use upspa_core::sign::SigningKey;
+use upspa_core::toprf::{
+    toprf_refresh_share, toprf_server_eval_verifiable, toprf_share_commitment,
+};
+use upspa_core::types::{b64_decode, b64_decode_array, b64_encode};
+use upspa_core::SecretBytes;
+
+use crate::error::SpError;
+use crate::model::{
+    DeprovisionRequest, DeviceRevocationRequest, DeviceRevocationResponse, KeyRotationRequest,
+    PasswordUpdateFinalizeRequest, PasswordUpdateRequest, PasswordUpdateResponse,
+    RecordCreateRequest, RecordResponse, RecordUpdateRequest, RecoveryRequest, RecoveryResponse,
+    SetupRequest, SetupResponse, ShareRefreshRequest, ToprfEvalRequest, ToprfEvalResponse,
+};
+use crate::store::{PutOutcome, SetupRecord, SpStore};
+
+/// Transport-independent SP logic. The HTTP layer in [`crate::api`] is a thin
+/// wrapper around these methods.
+pub struct SpService {
+    sp_id: u32,
+    enforce_pwd_update_time: bool,
+    accept_legacy_signatures: bool,
+    /// Signs Π5 acknowledgements.
+    signing_key: SigningKey,
+    store: Box<dyn SpStore>,
+}
+
+impl SpService {
+    /// New SP with a random acknowledgement key; see
+    /// [`SpService::with_signing_key`].
+    pub fn new(sp_id: u32, store: Box<dyn SpStore>) -> Self {
+        let mut seed = [0u8; 32];
+        OsRng.fill_bytes(&mut seed);
+        Self {
+            sp_id,
+            enforce_pwd_update_time: true,
+            accept_legacy_signatures: true,
+            signing_key: SigningKey::from_bytes(&seed),
+            store,
+        }
+    }
+
+    /// Use a fixed key for Π5 acknowledgements, so clients can pin
+    /// [`SpService::public_key`] across restarts.
+    pub fn with_signing_key(mut self, key: SigningKey) -> Self {
+        self.signing_key = key;
+        self
+    }
+
+    /// Disable the Π5 replay check (`timestamp > last_pwd_update_time`).
+    /// Only meant for local experiments. Share refresh, key rotation and
+    /// recovery escrow are not idempotent, so they always check it.
+    pub fn with_enforce_pwd_update_time(mut self, enforce: bool) -> Self {
+        self.enforce_pwd_update_time = enforce;
+        self
+    }
+
+    /// Reject Π5 requests signed in [`SigFormat::Legacy`] once every client
+    /// sends signed commands.
+    pub fn with_accept_legacy_signatures(mut self, accept: bool) -> Self {
+        self.accept_legacy_signatures = accept;
+        self
+    }
+
+    pub fn sp_id(&self) -> u32 {
+        self.sp_id
+    }
+
+    /// Ed25519 key clients verify Π5 acknowledgements against.
+    pub fn public_key(&self) -> [u8; 32] {
+        self.signing_key.verifying_key().to_bytes()
+    }
+
+    /// Π1: store `sig_pk`, `cid` and `k_i`. Returns `Created` or `Unchanged`;
+    /// a differing existing setup is a conflict.
+    pub fn setup(&self, req: &SetupRequest) -> Result<PutOutcome, SpError> {
+        let uid = decode_uid(&req.uid_b64)?;
+        let sig_pk = b64_decode_array::<32>(&req.sig_pk_b64).map_err(SpError::field("sig_pk"))?;
+        let cid = CipherId::from_b64(&req.cid).map_err(SpError::field("cid"))?;
+        let k_i = b64_decode_array::<32>(&req.k_i_b64).map_err(SpError::field("k_i"))?;
+
+        let rec = SetupRecord {
+            sig_pk,
+            cid,
+            k_i,
+            last_pwd_update_time: 0,
+            previous: None,
+            recovery_cid: None,
+            device_revocations: None,
+        };
+        match self.store.put_setup(&uid, rec)? {
+            PutOutcome::Conflict => Err(SpError::Conflict("setup")),
+            outcome => Ok(outcome),
+        }
+    }
+
+    pub fn get_setup(&self, uid_b64: &str) -> Result<SetupResponse, SpError> {
+        let uid = decode_uid(uid_b64)?;
+        let rec = self
+            .store
+            .get_setup(&uid)?
+            .ok_or(SpError::NotFound("setup"))?;
+        let k_i_commit = toprf_share_commitment(&rec.k_i).map_err(SpError::field("k_i"))?;
+        Ok(SetupResponse {
+            uid_b64: b64_encode(&uid),
+            sig_pk_b64: b64_encode(&rec.sig_pk),
+            cid: rec.cid.to_b64(),
+            k_i_commit_b64: b64_encode(&k_i_commit),
+        })
+    }
+
+    /// Π2: `y_i = blinded * k_i`, with a DLEQ proof against `K_i`.
+    pub fn toprf_eval(&self, req: &ToprfEvalRequest) -> Result<ToprfEvalResponse, SpError> {
+        let uid = decode_uid(&req.uid_b64)?;
+        let blinded =
+            b64_decode_array::<32>(&req.blinded_b64).map_err(SpError::field("blinded"))?;
+        let rec = self
+            .store
+            .get_setup(&uid)?
+            .ok_or(SpError::NotFound("setup"))?;
+
+        let (y, proof) = toprf_server_eval_verifiable(&blinded, &rec.k_i, &mut OsRng)
+            .map_err(SpError::field("blinded"))?;
+        Ok(ToprfEvalResponse {
+            sp_id: self.sp_id,
+            y_b64: b64_encode(&y),
+            proof_b64: b64_encode(&proof.to_bytes()),
+        })
+    }
+
+    pub fn record_create(&self, req: &RecordCreateRequest) -> Result<(), SpError> {
+        let suid = decode_suid(&req.suid_b64)?;
+        let cj = CipherSp::from_b64(&req.cj).map_err(SpError::field("cj"))?;
+        match self.store.put_record(&suid, cj)? {
+            PutOutcome::Created => Ok(()),
+            _ => Err(SpError::Conflict("record")),
+        }
+    }
+
+    pub fn record_get(&self, suid_b64: &str) -> Result<RecordResponse, SpError> {
+        let suid = decode_suid(suid_b64)?;
+        let cj = self
+            .store
+            .get_record(&suid)?
+            .ok_or(SpError::NotFound("record"))?;
+        Ok(RecordResponse {
+            suid_b64: b64_encode(&suid),
+            cj: cj.to_b64(),
+        })
+    }
+
+    pub fn record_update(&self, suid_b64: &str, req: &RecordUpdateRequest) -> Result<(), SpError> {
+        let suid = decode_suid(suid_b64)?;
+        let cj = CipherSp::from_b64(&req.cj).map_err(SpError::field("cj"))?;
+        if !self.store.update_record(&suid, cj)? {
+            return Err(SpError::NotFound("record"));
+        }
+        Ok(())
+    }
+
+    pub fn record_delete(&self, suid_b64: &str) -> Result<(), SpError> {
+        let suid = decode_suid(suid_b64)?;
+        if !self.store.delete_record(&suid)? {
+            return Err(SpError::NotFound("record"));
+        }
+        Ok(())
+    }
+
+    /// Π5: verify the client's signature over the new `cid`/`k_i`, swap them
+    /// in atomically and acknowledge with this SP's key. The old pair is kept
+    /// until [`SpService::password_update_finalize`].
+    pub fn password_update(
+        &self,
+        req: &PasswordUpdateRequest,
+    ) -> Result<PasswordUpdateResponse, SpError> {
+        let uid = decode_uid(&req.uid_b64)?;
+        let sig = b64_decode_array::<64>(&req.sig_b64).map_err(SpError::field("sig"))?;
+        let cid_new = CipherId::from_b64(&req.cid_new).map_err(SpError::field("cid_new"))?;
+        let k_i_new =
+            b64_decode_array::<32>(&req.k_i_new_b64).map_err(SpError::field("k_i_new"))?;
+        if req.sig_format == SigFormat::Legacy && !self.accept_legacy_signatures {
+            return Err(SpError::InvalidField("sig_format"));
+        }
+
+        let rec = self
+            .store
+            .get_setup(&uid)?
+            .ok_or(SpError::NotFound("setup"))?;
+        let last_timestamp = if self.enforce_pwd_update_time {
+            rec.last_pwd_update_time
+        } else {
+            0
+        };
+
+        let msg = PasswordUpdateSpMessage {
+            uid_b64: req.uid_b64.clone(),
+            sp_id: req.sp_id,
+            timestamp: req.timestamp,
+            sig,
+            k_i_new: SecretBytes::new(k_i_new),
+            cid_new,
+            format: req.sig_format,
+        };
+        verify_password_update_for_sp(self.sp_id, &msg, &rec.sig_pk, last_timestamp)?;
+        let ack = sign_password_update_ack(&self.signing_key, &uid, &msg)
+            .map_err(SpError::field("k_i_new"))?;
+
+        let applied = self.store.apply_password_update(
+            &uid,
+            rec.last_pwd_update_time,
+            msg.cid_new,
+            *msg.k_i_new,
+            msg.timestamp,
+        )?;
+        if !applied {
+            // Another update raced us between the read and the write.
+            return Err(SpError::StaleTimestamp);
+        }
+        Ok(PasswordUpdateResponse {
+            sp_id: ack.sp_id,
+            timestamp: ack.timestamp,
+            sig_b64: b64_encode(&ack.sig),
+        })
+    }
+
+    /// Close the last Π5 update: on commit the replaced `cid`/`k_i` are
+    /// dropped and can no longer be restored; on rollback they are put back.
+    /// The update's timestamp stays spent either way.
+    pub fn password_update_finalize(
+        &self,
+        req: &PasswordUpdateFinalizeRequest,
+    ) -> Result<(), SpError> {
+        let uid = decode_uid(&req.uid_b64)?;
+        let sig = b64_decode_array::<64>(&req.sig_b64).map_err(SpError::field("sig"))?;
+
+        let rec = self
+            .store
+            .get_setup(&uid)?
+            .ok_or(SpError::NotFound("setup"))?;
+
+        let msg = PasswordUpdateFinalizeSpMessage {
+            uid_b64: req.uid_b64.clone(),
+            sp_id: req.sp_id,
+            timestamp: req.timestamp,
+            action: req.action,
+            sig,
+        };
+        verify_password_update_finalize_for_sp(
+            self.sp_id,
+            &msg,
+            &rec.sig_pk,
+            rec.last_pwd_update_time,
+        )?;
+
+        if !self
+            .store
+            .finalize_password_update(&uid, msg.timestamp, msg.action)?
+        {
+            return Err(SpError::NoPendingUpdate);
+        }
+        Ok(())
+    }
+
+    /// Replace `sig_pk` and `cid` with a request signed by the current key.
+    /// Shares the Π5 timestamp, so a retired key cannot be restored by
+    /// replaying an older rotation.
+    pub fn key_rotation(&self, req: &KeyRotationRequest) -> Result<(), SpError> {
+        let uid = decode_uid(&req.uid_b64)?;
+        let sig = b64_decode_array::<64>(&req.sig_b64).map_err(SpError::field("sig"))?;
+        let sig_pk_new =
+            b64_decode_array::<32>(&req.sig_pk_new_b64).map_err(SpError::field("sig_pk_new"))?;
+        let cid_new = CipherId::from_b64(&req.cid_new).map_err(SpError::field("cid_new"))?;
+
+        let rec = self
+            .store
+            .get_setup(&uid)?
+            .ok_or(SpError::NotFound("setup"))?;
+
+        let msg = KeyRotationSpMessage {
+            uid_b64: req.uid_b64.clone(),
+            sp_id: req.sp_id,
+            timestamp: req.timestamp,
+            sig,
+            sig_pk_new,
+            cid_new,
+        };
+        verify_key_rotation_for_sp(self.sp_id, &msg, &rec.sig_pk, rec.last_pwd_update_time)?;
+
+        let applied = self.store.apply_key_rotation(
+            &uid,
+            rec.last_pwd_update_time,
+            msg.sig_pk_new,
+            msg.cid_new,
+            msg.timestamp,
+        )?;
+        if !applied {
+            return Err(SpError::StaleTimestamp);
+        }
+        Ok(())
+    }
+
+    /// Hold the user's recovery copy of `cid`, replacing any earlier one.
+    /// Shares the Π5 timestamp, so an old kit cannot be put back, and
+    /// commits any pending Π5 update.
+    pub fn recovery_escrow(&self, req: &RecoveryRequest) -> Result<(), SpError> {
+        let uid = decode_uid(&req.uid_b64)?;
+        let sig = b64_decode_array::<64>(&req.sig_b64).map_err(SpError::field("sig"))?;
+        let recovery_cid =
+            CipherId::from_b64(&req.recovery_cid).map_err(SpError::field("recovery_cid"))?;
+
+        let rec = self
+            .store
+            .get_setup(&uid)?
+            .ok_or(SpError::NotFound("setup"))?;
+
+        let msg = RecoverySpMessage {
+            uid_b64: req.uid_b64.clone(),
+            sp_id: req.sp_id,
+            timestamp: req.timestamp,
+            sig,
+            recovery_cid,
+        };
+        verify_recovery_for_sp(self.sp_id, &msg, &rec.sig_pk, rec.last_pwd_update_time)?;
+
+        let applied = self.store.set_recovery_cid(
+            &uid,
+            rec.last_pwd_update_time,
+            msg.recovery_cid,
+            msg.timestamp,
+        )?;
+        if !applied {
+            return Err(SpError::StaleTimestamp);
+        }
+        Ok(())
+    }
+
+    /// The recovery copy of `cid`. Like `cid` itself it is only useful with
+    /// the matching key, here the recovery codes.
+    pub fn get_recovery(&self, uid_b64: &str) -> Result<RecoveryResponse, SpError> {
+        let uid = decode_uid(uid_b64)?;
+        let recovery_cid = self
+            .store
+            .get_setup(&uid)?
+            .and_then(|rec| rec.recovery_cid)
+            .ok_or(SpError::NotFound("recovery"))?;
+        Ok(RecoveryResponse {
+            uid_b64: b64_encode(&uid),
+            recovery_cid: recovery_cid.to_b64(),
+        })
+    }
+
+    /// Store a device revocation list signed under the user's `sig_pk`.
+    /// Lists carry their own `issued_at`, separate from the Π5 timestamp, so
+    /// only a newer list replaces the stored one.
+    pub fn put_device_revocations(&self, req: &DeviceRevocationRequest) -> Result<(), SpError> {
+        let uid = decode_uid(&req.uid_b64)?;
+        let sig = b64_decode_array::<64>(&req.sig_b64).map_err(SpError::field("sig"))?;
+        let revoked = req
+            .revoked_b64
+            .iter()
+            .map(|id| b64_decode_array::<16>(id))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(SpError::field("revoked"))?;
+
+        let rec = self
+            .store
+            .get_setup(&uid)?
+            .ok_or(SpError::NotFound("setup"))?;
+        let list = DeviceRevocationList {
+            uid_b64: b64_encode(&uid),
+            issued_at: req.issued_at,
+            revoked,
+            sig,
+        };
+        verify_device_revocations(&list, &rec.sig_pk)?;
+
+        if !self.store.set_device_revocations(&uid, list)? {
+            return Err(SpError::StaleTimestamp);
+        }
+        Ok(())
+    }
+
+    pub fn get_device_revocations(
+        &self,
+        uid_b64: &str,
+    ) -> Result<DeviceRevocationResponse, SpError> {
+        let uid = decode_uid(uid_b64)?;
+        let list = self
+            .store
+            .get_setup(&uid)?
+            .and_then(|rec| rec.device_revocations)
+            .ok_or(SpError::NotFound("device revocations"))?;
+        Ok(DeviceRevocationResponse {
+            uid_b64: list.uid_b64,
+            issued_at: list.issued_at,
+            revoked_b64: list.revoked.iter().map(|id| b64_encode(id)).collect(),
+            sig_b64: b64_encode(&list.sig),
+        })
+    }
+
+    /// Proactive share refresh: verify the signed delta and set
+    /// `k_i = k_i + delta`. Shares the Π5 timestamp so a delta cannot be
+    /// applied twice, and can be finalized the same way.
+    pub fn share_refresh(&self, req: &ShareRefreshRequest) -> Result<(), SpError> {
+        let uid = decode_uid(&req.uid_b64)?;
+        let sig = b64_decode_array::<64>(&req.sig_b64).map_err(SpError::field("sig"))?;
+        let delta = b64_decode_array::<32>(&req.delta_b64).map_err(SpError::field("delta"))?;
+
+        let rec = self
+            .store
+            .get_setup(&uid)?
+            .ok_or(SpError::NotFound("setup"))?;
+
+        let msg = ShareRefreshSpMessage {
+            uid_b64: req.uid_b64.clone(),
+            sp_id: req.sp_id,
+            timestamp: req.timestamp,
+            sig,
+            delta,
+        };
+        verify_share_refresh_for_sp(self.sp_id, &msg, &rec.sig_pk, rec.last_pwd_update_time)?;
+        let k_i_new = toprf_refresh_share(&rec.k_i, &msg.delta).map_err(SpError::field("delta"))?;
+
+        let applied = self.store.apply_password_update(
+            &uid,
+            rec.last_pwd_update_time,
+            rec.cid,
+            k_i_new,
+            msg.timestamp,
+        )?;
+        if !applied {
+            return Err(SpError::StaleTimestamp);
+        }
+        Ok(())
+    }
+
+    /// Remove the user's setup record and the signed list of records. Records
+    /// already gone are skipped, so a retried request still succeeds.
+    pub fn deprovision(&self, req: &DeprovisionRequest) -> Result<(), SpError> {
+        let uid = decode_uid(&req.uid_b64)?;
+        let sig = b64_decode_array::<64>(&req.sig_b64).map_err(SpError::field("sig"))?;
+        let suids = req
+            .suids_b64
+            .iter()
+            .map(|s| decode_suid(s))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let rec = self
+            .store
+            .get_setup(&uid)?
+            .ok_or(SpError::NotFound("setup"))?;
+        let last_timestamp = if self.enforce_pwd_update_time {
+            rec.last_pwd_update_time
+        } else {
+            0
+        };
+
+        let msg = DeprovisionSpMessage {
+            uid_b64: req.uid_b64.clone(),
+            sp_id: req.sp_id,
+            timestamp: req.timestamp,
+            sig,
+            suids,
+        };
+        verify_deprovision_for_sp(self.sp_id, &msg, &rec.sig_pk, last_timestamp)?;
+
+        // Settle the compare-and-swap first: if a concurrent update wins it,
+        // the account keeps both its setup and its records.
+        if !self.store.delete_setup(&uid, rec.last_pwd_update_time)? {
+            return Err(SpError::StaleTimestamp);
+        }
+        for suid in &msg.suids {
+            self.store.delete_record(suid)?;
+        }
+        Ok(())
+    }
+}
+
+fn decode_uid(uid_b64: &str) -> Result<Vec<u8>, SpError> {
+    match b64_decode(uid_b64) {
+        Ok(uid) if !uid.is_empty() => Ok(uid),
+        _ => Err(SpError::InvalidField("uid")),
+    }
+}
+
+fn decode_suid(suid_b64: &str) -> Result<[u8; 32], SpError> {
+    b64_decode_array::<32>(suid_b64).map_err(SpError::field("suid"))
+}
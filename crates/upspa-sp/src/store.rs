@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+use upspa_core::protocol::device::DeviceRevocationList;
+use upspa_core::protocol::password_update::PasswordUpdateAction;
+use upspa_core::protocol::{CipherId, CipherSp};
+
+use crate::error::SpError;
+
+/// Per-user state persisted by an SP after Π1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SetupRecord {
+    pub sig_pk: [u8; 32],
+    pub cid: CipherId,
+    pub k_i: [u8; 32],
+    pub last_pwd_update_time: u64,
+    /// `cid`/`k_i` replaced by the last update, kept until the client
+    /// commits or rolls it back.
+    pub previous: Option<PreviousShare>,
+    /// The `cid` plaintext under the user's recovery key, if they made a
+    /// recovery kit.
+    pub recovery_cid: Option<CipherId>,
+    /// Newest device revocation list the user published.
+    pub device_revocations: Option<DeviceRevocationList>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PreviousShare {
+    pub cid: CipherId,
+    pub k_i: [u8; 32],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PutOutcome {
+    Created,
+    Unchanged,
+    Conflict,
+}
+
+/// Storage backend for the SP. Keys are raw (decoded) bytes, so two textual
+/// encodings of the same uid/suid always hit the same entry.
+pub trait SpStore: Send + Sync {
+    fn put_setup(&self, uid: &[u8], rec: SetupRecord) -> Result<PutOutcome, SpError>;
+    fn get_setup(&self, uid: &[u8]) -> Result<Option<SetupRecord>, SpError>;
+
+    /// Replace `cid`/`k_i` after Π5, keeping the old pair as `previous`. Must
+    /// only apply if the stored timestamp still equals `expected_last_time`;
+    /// returns `false` otherwise.
+    fn apply_password_update(
+        &self,
+        uid: &[u8],
+        expected_last_time: u64,
+        cid_new: CipherId,
+        k_i_new: [u8; 32],
+        timestamp: u64,
+    ) -> Result<bool, SpError>;
+
+    /// Replace `sig_pk`/`cid` after a key rotation, with the same
+    /// compare-and-swap rule as [`SpStore::apply_password_update`]. Drops
+    /// any `previous` pair and `recovery_cid`, which hold the retired key.
+    fn apply_key_rotation(
+        &self,
+        uid: &[u8],
+        expected_last_time: u64,
+        sig_pk_new: [u8; 32],
+        cid_new: CipherId,
+        timestamp: u64,
+    ) -> Result<bool, SpError>;
+
+    /// Store `recovery_cid`, replacing any earlier one, with the same
+    /// compare-and-swap rule as [`SpStore::apply_password_update`]. Spends
+    /// the timestamp, so any `previous` pair is dropped: the pending update
+    /// is committed.
+    fn set_recovery_cid(
+        &self,
+        uid: &[u8],
+        expected_last_time: u64,
+        recovery_cid: CipherId,
+        timestamp: u64,
+    ) -> Result<bool, SpError>;
+
+    /// Store `list` if it is newer than the stored one. Returns whether it
+    /// was stored.
+    fn set_device_revocations(
+        &self,
+        uid: &[u8],
+        list: DeviceRevocationList,
+    ) -> Result<bool, SpError>;
+
+    /// Close the update made at `timestamp`: drop `previous` on commit, or
+    /// put it back on rollback. Returns `false` if that update is no longer
+    /// the latest or was already finalized.
+    fn finalize_password_update(
+        &self,
+        uid: &[u8],
+        timestamp: u64,
+        action: PasswordUpdateAction,
+    ) -> Result<bool, SpError>;
+
+    /// Drop the setup record, with the same compare-and-swap rule as
+    /// [`SpStore::apply_password_update`].
+    fn delete_setup(&self, uid: &[u8], expected_last_time: u64) -> Result<bool, SpError>;
+
+    fn put_record(&self, suid: &[u8; 32], cj: CipherSp) -> Result<PutOutcome, SpError>;
+    fn get_record(&self, suid: &[u8; 32]) -> Result<Option<CipherSp>, SpError>;
+    fn update_record(&self, suid: &[u8; 32], cj: CipherSp) -> Result<bool, SpError>;
+    fn delete_record(&self, suid: &[u8; 32]) -> Result<bool, SpError>;
+}
+
+#[derive(Default)]
+struct MemoryState {
+    setups: HashMap<Vec<u8>, SetupRecord>,
+    records: HashMap<[u8; 32], CipherSp>,
+}
+
+/// In-process store; suitable for tests, demos and single-node deployments
+/// that do not need durability.
+#[derive(Default)]
+pub struct MemoryStore {
+    inner: Mutex<MemoryState>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, MemoryState>, SpError> {
+        self.inner
+            .lock()
+            .map_err(|_| SpError::Storage("memory store lock poisoned".to_string()))
+    }
+}
+
+impl SpStore for MemoryStore {
+    fn put_setup(&self, uid: &[u8], rec: SetupRecord) -> Result<PutOutcome, SpError> {
+        let mut st = self.lock()?;
+        match st.setups.get(uid) {
+            Some(existing) if *existing == rec => Ok(PutOutcome::Unchanged),
+            Some(_) => Ok(PutOutcome::Conflict),
+            None => {
+                st.setups.insert(uid.to_vec(), rec);
+                Ok(PutOutcome::Created)
+            }
+        }
+    }
+
+    fn get_setup(&self, uid: &[u8]) -> Result<Option<SetupRecord>, SpError> {
+        Ok(self.lock()?.setups.get(uid).cloned())
+    }
+
+    fn apply_password_update(
+        &self,
+        uid: &[u8],
+        expected_last_time: u64,
+        cid_new: CipherId,
+        k_i_new: [u8; 32],
+        timestamp: u64,
+    ) -> Result<bool, SpError> {
+        let mut st = self.lock()?;
+        match st.setups.get_mut(uid) {
+            Some(rec) if rec.last_pwd_update_time == expected_last_time => {
+                rec.previous = Some(PreviousShare {
+                    cid: std::mem::replace(&mut rec.cid, cid_new),
+                    k_i: std::mem::replace(&mut rec.k_i, k_i_new),
+                });
+                rec.last_pwd_update_time = timestamp;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn apply_key_rotation(
+        &self,
+        uid: &[u8],
+        expected_last_time: u64,
+        sig_pk_new: [u8; 32],
+        cid_new: CipherId,
+        timestamp: u64,
+    ) -> Result<bool, SpError> {
+        let mut st = self.lock()?;
+        match st.setups.get_mut(uid) {
+            Some(rec) if rec.last_pwd_update_time == expected_last_time => {
+                rec.sig_pk = sig_pk_new;
+                rec.cid = cid_new;
+                rec.previous = None;
+                rec.recovery_cid = None;
+                rec.last_pwd_update_time = timestamp;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn set_recovery_cid(
+        &self,
+        uid: &[u8],
+        expected_last_time: u64,
+        recovery_cid: CipherId,
+        timestamp: u64,
+    ) -> Result<bool, SpError> {
+        let mut st = self.lock()?;
+        match st.setups.get_mut(uid) {
+            Some(rec) if rec.last_pwd_update_time == expected_last_time => {
+                rec.recovery_cid = Some(recovery_cid);
+                rec.previous = None;
+                rec.last_pwd_update_time = timestamp;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn set_device_revocations(
+        &self,
+        uid: &[u8],
+        list: DeviceRevocationList,
+    ) -> Result<bool, SpError> {
+        let mut st = self.lock()?;
+        let Some(rec) = st.setups.get_mut(uid) else {
+            return Ok(false);
+        };
+        match &rec.device_revocations {
+            Some(stored) if stored.issued_at >= list.issued_at => Ok(false),
+            _ => {
+                rec.device_revocations = Some(list);
+                Ok(true)
+            }
+        }
+    }
+
+    fn finalize_password_update(
+        &self,
+        uid: &[u8],
+        timestamp: u64,
+        action: PasswordUpdateAction,
+    ) -> Result<bool, SpError> {
+        let mut st = self.lock()?;
+        let Some(rec) = st.setups.get_mut(uid) else {
+            return Ok(false);
+        };
+        if rec.last_pwd_update_time != timestamp {
+            return Ok(false);
+        }
+        match (rec.previous.take(), action) {
+            (None, _) => Ok(false),
+            (Some(_), PasswordUpdateAction::Commit) => Ok(true),
+            (Some(prev), PasswordUpdateAction::Rollback) => {
+                rec.cid = prev.cid;
+                rec.k_i = prev.k_i;
+                Ok(true)
+            }
+        }
+    }
+
+    fn delete_setup(&self, uid: &[u8], expected_last_time: u64) -> Result<bool, SpError> {
+        let mut st = self.lock()?;
+        match st.setups.get(uid) {
+            Some(rec) if rec.last_pwd_update_time == expected_last_time => {
+                st.setups.remove(uid);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn put_record(&self, suid: &[u8; 32], cj: CipherSp) -> Result<PutOutcome, SpError> {
+        let mut st = self.lock()?;
+        if st.records.contains_key(suid) {
+            return Ok(PutOutcome::Conflict);
+        }
+        st.records.insert(*suid, cj);
+        Ok(PutOutcome::Created)
+    }
+
+    fn get_record(&self, suid: &[u8; 32]) -> Result<Option<CipherSp>, SpError> {
+        Ok(self.lock()?.records.get(suid).cloned())
+    }
+
+    fn update_record(&self, suid: &[u8; 32], cj: CipherSp) -> Result<bool, SpError> {
+        let mut st = self.lock()?;
+        match st.records.get_mut(suid) {
+            Some(existing) => {
+                *existing = cj;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn delete_record(&self, suid: &[u8; 32]) -> Result<bool, SpError> {
+        Ok(self.lock()?.records.remove(suid).is_some())
+    }
+}
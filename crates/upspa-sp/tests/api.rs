@@ -0,0 +1,851 @@
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{Method, Request, StatusCode};
+use axum::Router;
+use http_body_util::BodyExt;
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+use upspa_core::dleq::DleqProof;
+use upspa_core::protocol::{
+    decrypt_cid, deprovision, device, key_rotation, password_update, recovery, register, setup,
+    share_refresh, LsRecord,
+};
+use upspa_core::sign::SigningKey;
+use upspa_core::toprf::{toprf_server_eval, ToprfClient, ToprfPartial};
+use upspa_core::types::{b64_decode_array, b64_encode};
+use upspa_sp::api::router;
+use upspa_sp::store::{MemoryStore, SetupRecord, SpStore};
+use upspa_sp::SpService;
+
+fn app(sp_id: u32) -> Router {
+    router(Arc::new(SpService::new(
+        sp_id,
+        Box::new(MemoryStore::new()),
+    )))
+}
+
+async fn send(app: &Router, method: Method, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+    let req = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(match body {
+            Some(v) => Body::from(v.to_string()),
+            None => Body::empty(),
+        })
+        .unwrap();
+
+    let resp = app.clone().oneshot(req).await.unwrap();
+    let status = resp.status();
+    let bytes = resp.into_body().collect().await.unwrap().to_bytes();
+    let value = if bytes.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice(&bytes).unwrap()
+    };
+    (status, value)
+}
+
+fn setup_body(p: &setup::SetupSpPayload) -> Value {
+    json!({
+        "uid_b64": b64_encode(&p.uid),
+        "sig_pk_b64": b64_encode(&p.sig_pk),
+        "cid": p.cid.to_b64(),
+        "k_i_b64": b64_encode(p.k_i.expose()),
+    })
+}
+
+#[tokio::test]
+async fn setup_and_toprf_eval_match_core() {
+    let app = app(1);
+    let mut rng = ChaCha20Rng::from_seed([3u8; 32]);
+    let (out, payloads) = setup::client_setup(b"user123", b"pw", &[1, 2, 3], 2, &mut rng).unwrap();
+    let p1 = &payloads[0];
+
+    let (status, _) = send(&app, Method::POST, "/v1/setup", Some(setup_body(p1))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let (status, _) = send(&app, Method::POST, "/v1/setup", Some(setup_body(p1))).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = send(
+        &app,
+        Method::POST,
+        "/v1/setup",
+        Some(setup_body(&payloads[1])),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["error"]["code"], "conflict");
+
+    let uri = format!("/v1/setup/{}", b64_encode(b"user123"));
+    let (status, body) = send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["sig_pk_b64"], b64_encode(&out.sig_pk));
+    assert_eq!(body["k_i_commit_b64"], b64_encode(&out.commitments[0].1));
+
+    let (state, blinded) = ToprfClient::begin(b"pw", &mut rng);
+    let req = json!({ "uid_b64": b64_encode(b"user123"), "blinded_b64": b64_encode(&blinded) });
+    let (status, body) = send(&app, Method::POST, "/v1/toprf/eval", Some(req)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["sp_id"], 1);
+    let y = b64_decode_array::<32>(body["y_b64"].as_str().unwrap()).unwrap();
+    assert_eq!(y, toprf_server_eval(&blinded, &p1.k_i).unwrap());
+    let proof = b64_decode_array::<64>(body["proof_b64"].as_str().unwrap()).unwrap();
+    let partial = ToprfPartial {
+        id: 1,
+        y,
+        proof: Some(DleqProof::from_bytes(&proof)),
+    };
+    let bad =
+        ToprfClient::unverified_partials(b"pw", &state, &[partial], &out.commitments).unwrap();
+    assert!(bad.is_empty());
+
+    let req = json!({ "uid_b64": b64_encode(b"nobody"), "blinded_b64": b64_encode(&blinded) });
+    let (status, _) = send(&app, Method::POST, "/v1/toprf/eval", Some(req)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn records_status_codes() {
+    let app = app(1);
+    let mut rng = ChaCha20Rng::from_seed([4u8; 32]);
+    let cj = upspa_core::aead::xchacha_encrypt_detached(&[1u8; 32], b"aad", &[0u8; 40], &mut rng);
+    let suid = b64_encode(&[9u8; 32]);
+    let uri = format!("/v1/records/{suid}");
+
+    let create = json!({ "suid_b64": suid, "cj": cj.to_b64() });
+    let (status, _) = send(&app, Method::POST, "/v1/records", Some(create.clone())).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let (status, _) = send(&app, Method::POST, "/v1/records", Some(create)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, body) = send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["cj"], serde_json::to_value(cj.to_b64()).unwrap());
+
+    let (status, _) = send(&app, Method::PUT, &uri, Some(json!({ "cj": cj.to_b64() }))).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send(&app, Method::DELETE, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = send(&app, Method::PUT, &uri, Some(json!({ "cj": cj.to_b64() }))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, body) = send(&app, Method::GET, "/v1/records/inv!alid", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "invalid_suid");
+
+    let bad_ct = json!({ "suid_b64": suid, "cj": { "nonce": cj.to_b64().nonce, "ct": "AAAA", "tag": cj.to_b64().tag } });
+    let (status, body) = send(&app, Method::POST, "/v1/records", Some(bad_ct)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "invalid_cj");
+
+    let (status, body) = send(
+        &app,
+        Method::POST,
+        "/v1/records",
+        Some(json!({ "suid_b64": suid })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "invalid_json");
+}
+
+#[tokio::test]
+async fn password_update_checks_signature_sp_id_and_replay() {
+    let uid = b"user123";
+    let service = SpService::new(2, Box::new(MemoryStore::new()))
+        .with_signing_key(SigningKey::from_bytes(&[9u8; 32]));
+    let sp_pk = service.public_key();
+    let app = router(Arc::new(service));
+    let mut rng = ChaCha20Rng::from_seed([5u8; 32]);
+    let (out, payloads) = setup::client_setup(uid, b"pw", &[1, 2, 3], 2, &mut rng).unwrap();
+    let (status, _) = send(
+        &app,
+        Method::POST,
+        "/v1/setup",
+        Some(setup_body(&payloads[1])),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (state, blinded) = ToprfClient::begin(b"pw", &mut rng);
+    let partials = out
+        .shares
+        .iter()
+        .take(2)
+        .map(|(id, k)| ToprfPartial {
+            id: *id,
+            y: toprf_server_eval(&blinded, k).unwrap(),
+            proof: None,
+        })
+        .collect::<Vec<_>>();
+    let state_key = ToprfClient::finish(b"pw", &state, &partials).unwrap();
+
+    let upd = password_update::client_password_update(
+        uid, &state_key, &out.cid, &[1, 2, 3], 2, b"pw2", 100, &mut rng,
+    )
+    .unwrap();
+    let body_for = |m: &password_update::PasswordUpdateSpMessage| {
+        json!({
+            "uid_b64": m.uid_b64,
+            "sp_id": m.sp_id,
+            "timestamp": m.timestamp,
+            "sig_b64": b64_encode(&m.sig),
+            "cid_new": m.cid_new.to_b64(),
+            "k_i_new_b64": b64_encode(m.k_i_new.expose()),
+        })
+    };
+
+    let (status, body) = send(
+        &app,
+        Method::POST,
+        "/v1/password-update",
+        Some(body_for(&upd.per_sp[0])),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "sp_id_mismatch");
+
+    let mut forged = body_for(&upd.per_sp[1]);
+    forged["k_i_new_b64"] = json!(b64_encode(upd.per_sp[0].k_i_new.expose()));
+    let (status, _) = send(&app, Method::POST, "/v1/password-update", Some(forged)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, ack) = send(
+        &app,
+        Method::POST,
+        "/v1/password-update",
+        Some(body_for(&upd.per_sp[1])),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let ack = password_update::PasswordUpdateAck {
+        sp_id: ack["sp_id"].as_u64().unwrap() as u32,
+        timestamp: ack["timestamp"].as_u64().unwrap(),
+        sig: b64_decode_array::<64>(ack["sig_b64"].as_str().unwrap()).unwrap(),
+    };
+    let k_i_commit = upd.commitments.iter().find(|(id, _)| *id == 2).unwrap().1;
+    password_update::verify_password_update_ack(
+        &sp_pk,
+        uid,
+        &upd.cid_new,
+        &k_i_commit,
+        100,
+        2,
+        &ack,
+    )
+    .unwrap();
+    let (status, body) = send(
+        &app,
+        Method::POST,
+        "/v1/password-update",
+        Some(body_for(&upd.per_sp[1])),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["error"]["code"], "stale_timestamp");
+
+    let uri = format!("/v1/setup/{}", b64_encode(uid));
+    let (_, body) = send(&app, Method::GET, &uri, None).await;
+    assert_eq!(
+        body["cid"],
+        serde_json::to_value(upd.cid_new.to_b64()).unwrap()
+    );
+
+    // Rolling back restores the old cid once; the timestamp stays spent.
+    let ssk = decrypt_cid(uid, &state_key, &out.cid).unwrap().signing_key;
+    let finalize = |timestamp: u64| {
+        let m = password_update::sign_password_update_finalize(
+            &ssk,
+            uid,
+            2,
+            timestamp,
+            password_update::PasswordUpdateAction::Rollback,
+        );
+        json!({
+            "uid_b64": m.uid_b64,
+            "sp_id": m.sp_id,
+            "timestamp": m.timestamp,
+            "action": "rollback",
+            "sig_b64": b64_encode(&m.sig),
+        })
+    };
+    let (status, body) = send(
+        &app,
+        Method::POST,
+        "/v1/password-update/finalize",
+        Some(finalize(99)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["error"]["code"], "no_pending_update");
+    let (status, _) = send(
+        &app,
+        Method::POST,
+        "/v1/password-update/finalize",
+        Some(finalize(100)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = send(&app, Method::GET, &uri, None).await;
+    assert_eq!(body["cid"], serde_json::to_value(out.cid.to_b64()).unwrap());
+    let (status, body) = send(
+        &app,
+        Method::POST,
+        "/v1/password-update/finalize",
+        Some(finalize(100)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["error"]["code"], "no_pending_update");
+    let (status, _) = send(
+        &app,
+        Method::POST,
+        "/v1/password-update",
+        Some(body_for(&upd.per_sp[1])),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn password_update_sig_format_can_require_commands() {
+    let uid = b"user123";
+    let service = SpService::new(2, Box::new(MemoryStore::new())).with_accept_legacy_signatures(false);
+    let app = router(Arc::new(service));
+    let mut rng = ChaCha20Rng::from_seed([10u8; 32]);
+    let (out, payloads) = setup::client_setup(uid, b"pw", &[1, 2, 3], 2, &mut rng).unwrap();
+    let (status, _) = send(
+        &app,
+        Method::POST,
+        "/v1/setup",
+        Some(setup_body(&payloads[1])),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (state, blinded) = ToprfClient::begin(b"pw", &mut rng);
+    let partials = out
+        .shares
+        .iter()
+        .take(2)
+        .map(|(id, k)| ToprfPartial {
+            id: *id,
+            y: toprf_server_eval(&blinded, k).unwrap(),
+            proof: None,
+        })
+        .collect::<Vec<_>>();
+    let state_key = ToprfClient::finish(b"pw", &state, &partials).unwrap();
+    let signing_key = decrypt_cid(uid, &state_key, &out.cid).unwrap().signing_key;
+
+    let upd = password_update::client_password_update(
+        uid, &state_key, &out.cid, &[1, 2, 3], 2, b"pw2", 30, &mut rng,
+    )
+    .unwrap();
+    let legacy = &upd.per_sp[1];
+    let req = json!({
+        "uid_b64": legacy.uid_b64,
+        "sp_id": legacy.sp_id,
+        "timestamp": legacy.timestamp,
+        "sig_b64": b64_encode(&legacy.sig),
+        "cid_new": legacy.cid_new.to_b64(),
+        "k_i_new_b64": b64_encode(legacy.k_i_new.expose()),
+    });
+    let (status, body) = send(&app, Method::POST, "/v1/password-update", Some(req)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "invalid_sig_format");
+
+    let m = password_update::sign_password_update(
+        &signing_key,
+        uid,
+        2,
+        30,
+        &upd.cid_new,
+        legacy.k_i_new.clone(),
+        password_update::SigFormat::Command,
+    );
+    let req = json!({
+        "uid_b64": m.uid_b64,
+        "sp_id": m.sp_id,
+        "timestamp": m.timestamp,
+        "sig_b64": b64_encode(&m.sig),
+        "cid_new": m.cid_new.to_b64(),
+        "k_i_new_b64": b64_encode(m.k_i_new.expose()),
+        "sig_format": "command",
+    });
+    let mut forged = req.clone();
+    forged["timestamp"] = json!(31);
+    let (status, _) = send(&app, Method::POST, "/v1/password-update", Some(forged)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = send(&app, Method::POST, "/v1/password-update", Some(req.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send(&app, Method::POST, "/v1/password-update", Some(req)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn share_refresh_rerandomizes_k_i_once() {
+    let uid = b"user123";
+    let app = app(1);
+    let mut rng = ChaCha20Rng::from_seed([6u8; 32]);
+    let (out, payloads) = setup::client_setup(uid, b"pw", &[1, 2, 3], 2, &mut rng).unwrap();
+    let (status, _) = send(
+        &app,
+        Method::POST,
+        "/v1/setup",
+        Some(setup_body(&payloads[0])),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (state, blinded) = ToprfClient::begin(b"pw", &mut rng);
+    let partials = out
+        .shares
+        .iter()
+        .take(2)
+        .map(|(id, k)| ToprfPartial {
+            id: *id,
+            y: toprf_server_eval(&blinded, k).unwrap(),
+            proof: None,
+        })
+        .collect::<Vec<_>>();
+    let state_key = ToprfClient::finish(b"pw", &state, &partials).unwrap();
+
+    let refresh =
+        share_refresh::client_share_refresh(uid, &state_key, &out.cid, &[1, 2, 3], 2, 10, &mut rng).unwrap();
+    let m = &refresh.per_sp[0];
+    let req = json!({
+        "uid_b64": m.uid_b64,
+        "sp_id": m.sp_id,
+        "timestamp": m.timestamp,
+        "sig_b64": b64_encode(&m.sig),
+        "delta_b64": b64_encode(&m.delta),
+    });
+
+    let (status, _) = send(&app, Method::POST, "/v1/share-refresh", Some(req.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = send(&app, Method::POST, "/v1/share-refresh", Some(req)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["error"]["code"], "stale_timestamp");
+
+    let commitments =
+        upspa_core::toprf::toprf_refresh_commitments(&out.commitments, &refresh.delta_commitments)
+            .unwrap();
+    let uri = format!("/v1/setup/{}", b64_encode(uid));
+    let (_, body) = send(&app, Method::GET, &uri, None).await;
+    assert_eq!(body["k_i_commit_b64"], b64_encode(&commitments[0].1));
+    assert_eq!(body["cid"], serde_json::to_value(out.cid.to_b64()).unwrap());
+}
+
+#[tokio::test]
+async fn key_rotation_replaces_sig_pk() {
+    let uid = b"user123";
+    let app = app(2);
+    let mut rng = ChaCha20Rng::from_seed([8u8; 32]);
+    let (out, payloads) = setup::client_setup(uid, b"pw", &[1, 2, 3], 2, &mut rng).unwrap();
+    let (status, _) = send(
+        &app,
+        Method::POST,
+        "/v1/setup",
+        Some(setup_body(&payloads[1])),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (state, blinded) = ToprfClient::begin(b"pw", &mut rng);
+    let partials = out
+        .shares
+        .iter()
+        .take(2)
+        .map(|(id, k)| ToprfPartial {
+            id: *id,
+            y: toprf_server_eval(&blinded, k).unwrap(),
+            proof: None,
+        })
+        .collect::<Vec<_>>();
+    let state_key = ToprfClient::finish(b"pw", &state, &partials).unwrap();
+
+    let rot =
+        key_rotation::client_key_rotation(uid, &state_key, &out.cid, &[1, 2, 3], 20, &mut rng).unwrap();
+    let m = &rot.per_sp[1];
+    let req = json!({
+        "uid_b64": m.uid_b64,
+        "sp_id": m.sp_id,
+        "timestamp": m.timestamp,
+        "sig_b64": b64_encode(&m.sig),
+        "sig_pk_new_b64": b64_encode(&m.sig_pk_new),
+        "cid_new": m.cid_new.to_b64(),
+    });
+
+    let mut forged = req.clone();
+    forged["sig_pk_new_b64"] = json!(b64_encode(&[7u8; 32]));
+    let (status, _) = send(&app, Method::POST, "/v1/key-rotation", Some(forged)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = send(&app, Method::POST, "/v1/key-rotation", Some(req.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = send(&app, Method::POST, "/v1/key-rotation", Some(req)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["error"]["code"], "stale_timestamp");
+
+    let uri = format!("/v1/setup/{}", b64_encode(uid));
+    let (_, body) = send(&app, Method::GET, &uri, None).await;
+    assert_eq!(body["sig_pk_b64"], b64_encode(&rot.sig_pk_new));
+    assert_eq!(body["cid"], serde_json::to_value(rot.cid_new.to_b64()).unwrap());
+
+    // The retired key no longer authorizes anything.
+    let refresh =
+        share_refresh::client_share_refresh(uid, &state_key, &out.cid, &[1, 2, 3], 2, 21, &mut rng).unwrap();
+    let m = &refresh.per_sp[1];
+    let req = json!({
+        "uid_b64": m.uid_b64,
+        "sp_id": m.sp_id,
+        "timestamp": m.timestamp,
+        "sig_b64": b64_encode(&m.sig),
+        "delta_b64": b64_encode(&m.delta),
+    });
+    let (status, _) = send(&app, Method::POST, "/v1/share-refresh", Some(req)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn deprovision_removes_setup_and_records() {
+    let uid = b"user123";
+    let app = app(3);
+    let mut rng = ChaCha20Rng::from_seed([7u8; 32]);
+    let (out, payloads) = setup::client_setup(uid, b"pw", &[1, 2, 3], 2, &mut rng).unwrap();
+    let (status, _) = send(
+        &app,
+        Method::POST,
+        "/v1/setup",
+        Some(setup_body(&payloads[2])),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (state, blinded) = ToprfClient::begin(b"pw", &mut rng);
+    let partials = out
+        .shares
+        .iter()
+        .take(2)
+        .map(|(id, k)| ToprfPartial {
+            id: *id,
+            y: toprf_server_eval(&blinded, k).unwrap(),
+            proof: None,
+        })
+        .collect::<Vec<_>>();
+    let state_key = ToprfClient::finish(b"pw", &state, &partials).unwrap();
+    let reg = register::client_register(uid, b"LS1", &state_key, &out.cid, &[1, 2, 3], &LsRecord::None, &mut rng).unwrap();
+    let rec = &reg.per_sp[2];
+    let create = json!({ "suid_b64": b64_encode(&rec.suid), "cj": rec.cj.to_b64() });
+    let (status, _) = send(&app, Method::POST, "/v1/records", Some(create)).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let signing_key = upspa_core::protocol::decrypt_cid(uid, &state_key, &out.cid)
+        .unwrap()
+        .signing_key;
+    let body_for = |m: &deprovision::DeprovisionSpMessage| {
+        json!({
+            "uid_b64": m.uid_b64,
+            "sp_id": m.sp_id,
+            "timestamp": m.timestamp,
+            "sig_b64": b64_encode(&m.sig),
+            "suids_b64": m.suids.iter().map(|s| b64_encode(s)).collect::<Vec<_>>(),
+        })
+    };
+
+    let wrong_sp = deprovision::sign_deprovision(&signing_key, uid, 1, 9, vec![rec.suid]);
+    let (status, _) = send(
+        &app,
+        Method::POST,
+        "/v1/deprovision",
+        Some(body_for(&wrong_sp)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let m = deprovision::sign_deprovision(&signing_key, uid, 3, 9, vec![rec.suid]);
+    let mut forged = body_for(&m);
+    forged["suids_b64"] = json!([]);
+    let (status, _) = send(&app, Method::POST, "/v1/deprovision", Some(forged)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = send(&app, Method::POST, "/v1/deprovision", Some(body_for(&m))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let uri = format!("/v1/setup/{}", b64_encode(uid));
+    let (status, _) = send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let uri = format!("/v1/records/{}", b64_encode(&rec.suid));
+    let (status, _) = send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn recovery_escrow_is_stored_until_key_rotation() {
+    let uid = b"user123";
+    let app = app(1);
+    let mut rng = ChaCha20Rng::from_seed([12u8; 32]);
+    let (out, payloads) = setup::client_setup(uid, b"pw", &[1, 2, 3], 2, &mut rng).unwrap();
+    let (status, _) = send(
+        &app,
+        Method::POST,
+        "/v1/setup",
+        Some(setup_body(&payloads[0])),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (state, blinded) = ToprfClient::begin(b"pw", &mut rng);
+    let partials = out
+        .shares
+        .iter()
+        .take(2)
+        .map(|(id, k)| ToprfPartial {
+            id: *id,
+            y: toprf_server_eval(&blinded, k).unwrap(),
+            proof: None,
+        })
+        .collect::<Vec<_>>();
+    let state_key = ToprfClient::finish(b"pw", &state, &partials).unwrap();
+
+    let uri = format!("/v1/recovery/{}", b64_encode(uid));
+    let (status, _) = send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let kit =
+        recovery::client_recovery_setup(uid, &state_key, &out.cid, &[1, 2, 3], 3, 2, 5, &mut rng).unwrap();
+    let m = &kit.per_sp[0];
+    let req = json!({
+        "uid_b64": m.uid_b64,
+        "sp_id": m.sp_id,
+        "timestamp": m.timestamp,
+        "sig_b64": b64_encode(&m.sig),
+        "recovery_cid": m.recovery_cid.to_b64(),
+    });
+
+    let mut forged = req.clone();
+    forged["recovery_cid"] = serde_json::to_value(out.cid.to_b64()).unwrap();
+    let (status, _) = send(&app, Method::POST, "/v1/recovery", Some(forged)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = send(&app, Method::POST, "/v1/recovery", Some(req.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = send(&app, Method::POST, "/v1/recovery", Some(req)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["error"]["code"], "stale_timestamp");
+
+    let (status, body) = send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body["recovery_cid"],
+        serde_json::to_value(kit.recovery_cid.to_b64()).unwrap()
+    );
+
+    // The kit holds the old signing key, so rotating drops it.
+    let rot =
+        key_rotation::client_key_rotation(uid, &state_key, &out.cid, &[1, 2, 3], 6, &mut rng).unwrap();
+    let m = &rot.per_sp[0];
+    let req = json!({
+        "uid_b64": m.uid_b64,
+        "sp_id": m.sp_id,
+        "timestamp": m.timestamp,
+        "sig_b64": b64_encode(&m.sig),
+        "sig_pk_new_b64": b64_encode(&m.sig_pk_new),
+        "cid_new": m.cid_new.to_b64(),
+    });
+    let (status, _) = send(&app, Method::POST, "/v1/key-rotation", Some(req)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn device_revocations_only_move_forward() {
+    let uid = b"user123";
+    let app = app(1);
+    let mut rng = ChaCha20Rng::from_seed([13u8; 32]);
+    let (out, payloads) = setup::client_setup(uid, b"pw", &[1, 2, 3], 2, &mut rng).unwrap();
+    let (status, _) = send(
+        &app,
+        Method::POST,
+        "/v1/setup",
+        Some(setup_body(&payloads[0])),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (state, blinded) = ToprfClient::begin(b"pw", &mut rng);
+    let partials = out
+        .shares
+        .iter()
+        .take(2)
+        .map(|(id, k)| ToprfPartial {
+            id: *id,
+            y: toprf_server_eval(&blinded, k).unwrap(),
+            proof: None,
+        })
+        .collect::<Vec<_>>();
+    let state_key = ToprfClient::finish(b"pw", &state, &partials).unwrap();
+    let signing_key = decrypt_cid(uid, &state_key, &out.cid).unwrap().signing_key;
+    let body_for = |l: &device::DeviceRevocationList| {
+        json!({
+            "uid_b64": l.uid_b64,
+            "issued_at": l.issued_at,
+            "revoked_b64": l.revoked.iter().map(|id| b64_encode(id)).collect::<Vec<_>>(),
+            "sig_b64": b64_encode(&l.sig),
+        })
+    };
+
+    let uri = format!("/v1/devices/revocations/{}", b64_encode(uid));
+    let (status, _) = send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let old = device::sign_device_revocations(&signing_key, uid, 5, vec![[1u8; 16]]);
+    let new = device::sign_device_revocations(&signing_key, uid, 6, vec![[1u8; 16], [2u8; 16]]);
+    let mut forged = body_for(&new);
+    forged["revoked_b64"] = json!([b64_encode(&[1u8; 16])]);
+    let (status, _) = send(&app, Method::POST, "/v1/devices/revocations", Some(forged)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = send(&app, Method::POST, "/v1/devices/revocations", Some(body_for(&new))).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = send(&app, Method::POST, "/v1/devices/revocations", Some(body_for(&old))).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["error"]["code"], "stale_timestamp");
+
+    let (status, body) = send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, body_for(&new));
+}
+
+#[tokio::test]
+async fn non_idempotent_commands_reject_replays_without_pwd_update_time_check() {
+    let uid = b"user123";
+    let app = router(Arc::new(
+        SpService::new(1, Box::new(MemoryStore::new())).with_enforce_pwd_update_time(false),
+    ));
+    let mut rng = ChaCha20Rng::from_seed([14u8; 32]);
+    let (out, payloads) = setup::client_setup(uid, b"pw", &[1, 2, 3], 2, &mut rng).unwrap();
+    let (status, _) = send(
+        &app,
+        Method::POST,
+        "/v1/setup",
+        Some(setup_body(&payloads[0])),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (state, blinded) = ToprfClient::begin(b"pw", &mut rng);
+    let partials = out
+        .shares
+        .iter()
+        .take(2)
+        .map(|(id, k)| ToprfPartial {
+            id: *id,
+            y: toprf_server_eval(&blinded, k).unwrap(),
+            proof: None,
+        })
+        .collect::<Vec<_>>();
+    let state_key = ToprfClient::finish(b"pw", &state, &partials).unwrap();
+
+    let refresh =
+        share_refresh::client_share_refresh(uid, &state_key, &out.cid, &[1, 2, 3], 2, 10, &mut rng).unwrap();
+    let m = &refresh.per_sp[0];
+    let refresh_req = json!({
+        "uid_b64": m.uid_b64,
+        "sp_id": m.sp_id,
+        "timestamp": m.timestamp,
+        "sig_b64": b64_encode(&m.sig),
+        "delta_b64": b64_encode(&m.delta),
+    });
+
+    let kit =
+        recovery::client_recovery_setup(uid, &state_key, &out.cid, &[1, 2, 3], 3, 2, 11, &mut rng).unwrap();
+    let m = &kit.per_sp[0];
+    let recovery_req = json!({
+        "uid_b64": m.uid_b64,
+        "sp_id": m.sp_id,
+        "timestamp": m.timestamp,
+        "sig_b64": b64_encode(&m.sig),
+        "recovery_cid": m.recovery_cid.to_b64(),
+    });
+
+    let rot =
+        key_rotation::client_key_rotation(uid, &state_key, &out.cid, &[1, 2, 3], 12, &mut rng).unwrap();
+    let m = &rot.per_sp[0];
+    let rotation_req = json!({
+        "uid_b64": m.uid_b64,
+        "sp_id": m.sp_id,
+        "timestamp": m.timestamp,
+        "sig_b64": b64_encode(&m.sig),
+        "sig_pk_new_b64": b64_encode(&m.sig_pk_new),
+        "cid_new": m.cid_new.to_b64(),
+    });
+
+    for (uri, req) in [
+        ("/v1/share-refresh", refresh_req),
+        ("/v1/recovery", recovery_req),
+        ("/v1/key-rotation", rotation_req),
+    ] {
+        let (status, _) = send(&app, Method::POST, uri, Some(req.clone())).await;
+        assert_eq!(status, StatusCode::OK, "{uri}");
+        let (status, body) = send(&app, Method::POST, uri, Some(req)).await;
+        assert_eq!(status, StatusCode::CONFLICT, "{uri}");
+        assert_eq!(body["error"]["code"], "stale_timestamp");
+    }
+
+    // The delta was added once.
+    let commitments =
+        upspa_core::toprf::toprf_refresh_commitments(&out.commitments, &refresh.delta_commitments)
+            .unwrap();
+    let uri = format!("/v1/setup/{}", b64_encode(uid));
+    let (_, body) = send(&app, Method::GET, &uri, None).await;
+    assert_eq!(body["k_i_commit_b64"], b64_encode(&commitments[0].1));
+}
+
+#[test]
+fn timestamp_spending_writes_settle_a_pending_update() {
+    let uid = b"user123";
+    let mut rng = ChaCha20Rng::from_seed([15u8; 32]);
+    let (out, payloads) = setup::client_setup(uid, b"pw", &[1, 2, 3], 2, &mut rng).unwrap();
+    let (other, _) = setup::client_setup(uid, b"pw2", &[1, 2, 3], 2, &mut rng).unwrap();
+    let store = MemoryStore::new();
+    store
+        .put_setup(
+            uid,
+            SetupRecord {
+                sig_pk: out.sig_pk,
+                cid: out.cid.clone(),
+                k_i: *payloads[0].k_i,
+                last_pwd_update_time: 0,
+                previous: None,
+                recovery_cid: None,
+                device_revocations: None,
+            },
+        )
+        .unwrap();
+
+    assert!(store
+        .apply_password_update(uid, 0, other.cid.clone(), *other.shares[0].1, 1)
+        .unwrap());
+    assert!(store.get_setup(uid).unwrap().unwrap().previous.is_some());
+    assert!(store.set_recovery_cid(uid, 1, out.cid.clone(), 2).unwrap());
+    let rec = store.get_setup(uid).unwrap().unwrap();
+    assert!(rec.previous.is_none());
+    assert_eq!(rec.cid, other.cid);
+
+    assert!(store
+        .apply_password_update(uid, 2, out.cid.clone(), *payloads[0].k_i, 3)
+        .unwrap());
+    assert!(store
+        .apply_key_rotation(uid, 3, [7u8; 32], other.cid.clone(), 4)
+        .unwrap());
+    assert!(store.get_setup(uid).unwrap().unwrap().previous.is_none());
+}
@@ -2,10 +2,17 @@ use wasm_bindgen::prelude::*;
 use rand_core::OsRng;
 use serde::{Deserialize, Serialize};
 use upspa_core::protocol::{
-    authenticate, password_update, register, secret_update, setup, CipherId, CipherSp,
+    authenticate, deprovision, device, key_rotation, login, password_update, recovery, register, secret_update,
+    setup, share_refresh, decrypt_cid, CipherId, CipherSp, LsRecord,
 };
+use upspa_core::dleq::DleqProof;
+use upspa_core::hash::hash_suid;
+use upspa_core::kdf::{Argon2idParams, PasswordKdf};
+use upspa_core::password_policy::{self, PolicySpec};
+use upspa_core::suite::OprfSuite;
 use upspa_core::toprf::{ToprfClient, ToprfClientState, ToprfPartial};
 use upspa_core::types::{b64_decode_array, b64_encode, CtBlobB64, UpspaError};
+use upspa_core::SecretBytes;
 #[wasm_bindgen(start)]
 pub fn init() {
     #[cfg(feature = "panic_hook")]
@@ -17,6 +24,70 @@ fn to_js_error(msg: impl std::fmt::Display) -> JsValue {
 fn map_err(e: UpspaError) -> JsValue {
     to_js_error(e)
 }
+/// `PasswordKdf` with the salt in base64url.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kdf", rename_all = "snake_case")]
+pub enum PasswordKdfWasm {
+    None,
+    Argon2id {
+        m_cost_kib: u32,
+        t_cost: u32,
+        p_cost: u32,
+        salt: String,
+    },
+}
+fn kdf_to_wasm(kdf: &PasswordKdf) -> PasswordKdfWasm {
+    match kdf {
+        PasswordKdf::None => PasswordKdfWasm::None,
+        PasswordKdf::Argon2id { params, salt } => PasswordKdfWasm::Argon2id {
+            m_cost_kib: params.m_cost_kib,
+            t_cost: params.t_cost,
+            p_cost: params.p_cost,
+            salt: b64_encode(salt),
+        },
+    }
+}
+/// `undefined` and `null` mean no hardening.
+fn parse_kdf(kdf: JsValue) -> Result<PasswordKdf, JsValue> {
+    if kdf.is_undefined() || kdf.is_null() {
+        return Ok(PasswordKdf::None);
+    }
+    match serde_wasm_bindgen::from_value(kdf).map_err(to_js_error)? {
+        PasswordKdfWasm::None => Ok(PasswordKdf::None),
+        PasswordKdfWasm::Argon2id {
+            m_cost_kib,
+            t_cost,
+            p_cost,
+            salt,
+        } => Ok(PasswordKdf::Argon2id {
+            params: Argon2idParams {
+                m_cost_kib,
+                t_cost,
+                p_cost,
+            },
+            salt: b64_decode_array::<16>(&salt).map_err(map_err)?,
+        }),
+    }
+}
+/// `"blake3"` or `"ristretto255-sha512"`; `undefined` and `null` mean BLAKE3.
+fn parse_suite(suite: JsValue) -> Result<OprfSuite, JsValue> {
+    if suite.is_undefined() || suite.is_null() {
+        return Ok(OprfSuite::Blake3);
+    }
+    serde_wasm_bindgen::from_value(suite).map_err(to_js_error)
+}
+/// A new Argon2id config with a fresh salt, for `protocol_setup` or to
+/// migrate an account with `protocol_password_update`.
+#[wasm_bindgen]
+pub fn password_kdf_argon2id(m_cost_kib: u32, t_cost: u32, p_cost: u32) -> Result<JsValue, JsValue> {
+    let params = Argon2idParams {
+        m_cost_kib,
+        t_cost,
+        p_cost,
+    };
+    let kdf = PasswordKdf::argon2id(params, &mut OsRng);
+    serde_wasm_bindgen::to_value(&kdf_to_wasm(&kdf)).map_err(to_js_error)
+}
 #[derive(Serialize, Deserialize)]
 pub struct SetupShareWasm {
     pub sp_id: u32,
@@ -31,16 +102,38 @@ pub struct SetupSpPayloadWasm {
     pub k_i: String,
 }
 #[derive(Serialize, Deserialize)]
+pub struct ShareCommitmentWasm {
+    pub sp_id: u32,
+    pub k_i_commit: String,
+}
+#[derive(Serialize, Deserialize)]
 pub struct SetupResultWasm {
     pub sig_pk: String,
     pub cid: CtBlobB64,
     pub shares: Vec<SetupShareWasm>,
+    pub commitments: Vec<ShareCommitmentWasm>,
     pub sp_payloads: Vec<SetupSpPayloadWasm>,
+    pub kdf: PasswordKdfWasm,
+    pub suite: OprfSuite,
 }
+/// Π1. `kdf` (from `password_kdf_argon2id`) hardens the password and must be
+/// passed to every later TOPRF call; `undefined` keeps the raw password.
+/// `suite` likewise selects the OPRF suite for the account.
 #[wasm_bindgen]
-pub fn protocol_setup(uid: String, password: String, nsp: usize, tsp: usize) -> Result<JsValue, JsValue> {
+pub fn protocol_setup(
+    uid: String,
+    password: String,
+    sp_ids: Vec<u32>,
+    tsp: usize,
+    kdf: JsValue,
+    suite: JsValue,
+) -> Result<JsValue, JsValue> {
+    let kdf = parse_kdf(kdf)?;
+    let suite = parse_suite(suite)?;
     let mut rng = OsRng;
-    let (out, payloads) = setup::client_setup(uid.as_bytes(), password.as_bytes(), nsp, tsp, &mut rng);
+    let (out, payloads) =
+        setup::client_setup_with_suite(uid.as_bytes(), password.as_bytes(), &sp_ids, tsp, &kdf, suite, &mut rng)
+            .map_err(map_err)?;
 
     let res = SetupResultWasm {
         sig_pk: b64_encode(&out.sig_pk),
@@ -50,9 +143,10 @@ pub fn protocol_setup(uid: String, password: String, nsp: usize, tsp: usize) ->
             .iter()
             .map(|(id, s)| SetupShareWasm {
                 sp_id: *id,
-                k_i: b64_encode(s),
+                k_i: b64_encode(s.expose()),
             })
             .collect(),
+        commitments: commitments_to_wasm(&out.commitments),
         sp_payloads: payloads
             .iter()
             .map(|p| SetupSpPayloadWasm {
@@ -60,9 +154,11 @@ pub fn protocol_setup(uid: String, password: String, nsp: usize, tsp: usize) ->
                 uid: b64_encode(&p.uid),
                 sig_pk: b64_encode(&p.sig_pk),
                 cid: p.cid.to_b64(),
-                k_i: b64_encode(&p.k_i),
+                k_i: b64_encode(p.k_i.expose()),
             })
             .collect(),
+        kdf: kdf_to_wasm(&out.kdf),
+        suite: out.suite,
     };
 
     serde_wasm_bindgen::to_value(&res).map_err(to_js_error)
@@ -75,38 +171,160 @@ pub struct ToprfBeginWasm {
 }
 
 #[wasm_bindgen]
-pub fn toprf_begin(password: String) -> Result<JsValue, JsValue> {
+pub fn toprf_begin(password: String, kdf: JsValue, suite: JsValue) -> Result<JsValue, JsValue> {
+    let password = parse_kdf(kdf)?.harden(password.as_bytes()).map_err(map_err)?;
     let mut rng = OsRng;
-    let (state, blinded) = ToprfClient::begin(password.as_bytes(), &mut rng);
+    let (state, blinded) = ToprfClient::begin_with_suite(&password, parse_suite(suite)?, &mut rng);
 
     let out = ToprfBeginWasm {
-        r: b64_encode(&state.r),
+        r: b64_encode(state.r.expose()),
         blinded: b64_encode(&blinded),
     };
 
     serde_wasm_bindgen::to_value(&out).map_err(to_js_error)
 }
 
+fn commitments_to_wasm(commitments: &[(u32, [u8; 32])]) -> Vec<ShareCommitmentWasm> {
+    commitments
+        .iter()
+        .map(|(id, c)| ShareCommitmentWasm {
+            sp_id: *id,
+            k_i_commit: b64_encode(c),
+        })
+        .collect()
+}
+
 #[derive(Deserialize)]
 pub struct ToprfPartialIn {
     pub id: u32,
     pub y: String,
+    #[serde(default)]
+    pub proof: Option<String>,
 }
 
-#[wasm_bindgen]
-pub fn toprf_finish(password: String, r: String, partials: JsValue) -> Result<String, JsValue> {
-    let r_bytes = b64_decode_array::<32>(&r).map_err(map_err)?;
-    let state = ToprfClientState { r: r_bytes };
-
+fn parse_partials(partials: JsValue) -> Result<Vec<ToprfPartial>, JsValue> {
     let parts_in: Vec<ToprfPartialIn> = serde_wasm_bindgen::from_value(partials).map_err(to_js_error)?;
     let mut parts = Vec::with_capacity(parts_in.len());
     for p in parts_in {
         let y = b64_decode_array::<32>(&p.y).map_err(map_err)?;
-        parts.push(ToprfPartial { id: p.id, y });
+        let proof = match p.proof {
+            Some(b64) => Some(DleqProof::from_bytes(&b64_decode_array::<64>(&b64).map_err(map_err)?)),
+            None => None,
+        };
+        parts.push(ToprfPartial { id: p.id, y, proof });
     }
+    Ok(parts)
+}
+
+fn parse_commitments(commitments: JsValue) -> Result<Vec<(u32, [u8; 32])>, JsValue> {
+    let commitments_in: Vec<ShareCommitmentWasm> =
+        serde_wasm_bindgen::from_value(commitments).map_err(to_js_error)?;
+    let mut out = Vec::with_capacity(commitments_in.len());
+    for c in commitments_in {
+        out.push((c.sp_id, b64_decode_array::<32>(&c.k_i_commit).map_err(map_err)?));
+    }
+    Ok(out)
+}
+
+#[wasm_bindgen]
+pub fn toprf_finish(
+    password: String,
+    r: String,
+    partials: JsValue,
+    kdf: JsValue,
+    suite: JsValue,
+) -> Result<String, JsValue> {
+    let password = parse_kdf(kdf)?.harden(password.as_bytes()).map_err(map_err)?;
+    let r_bytes = b64_decode_array::<32>(&r).map_err(map_err)?;
+    let state = ToprfClientState {
+        r: SecretBytes::new(r_bytes),
+        suite: parse_suite(suite)?,
+    };
+    let parts = parse_partials(partials)?;
+
+    let state_key = ToprfClient::finish(&password, &state, &parts).map_err(map_err)?;
+    Ok(b64_encode(state_key.expose()))
+}
 
-    let state_key = ToprfClient::finish(password.as_bytes(), &state, &parts).map_err(map_err)?;
-    Ok(b64_encode(&state_key))
+/// Like `toprf_finish`, but rejects any partial whose DLEQ proof does not
+/// verify against the commitments returned by `protocol_setup`.
+#[wasm_bindgen]
+pub fn toprf_finish_verified(
+    password: String,
+    r: String,
+    partials: JsValue,
+    commitments: JsValue,
+    kdf: JsValue,
+    suite: JsValue,
+) -> Result<String, JsValue> {
+    let password = parse_kdf(kdf)?.harden(password.as_bytes()).map_err(map_err)?;
+    let r_bytes = b64_decode_array::<32>(&r).map_err(map_err)?;
+    let state = ToprfClientState {
+        r: SecretBytes::new(r_bytes),
+        suite: parse_suite(suite)?,
+    };
+    let parts = parse_partials(partials)?;
+    let commitments = parse_commitments(commitments)?;
+
+    let state_key = ToprfClient::finish_verified(&password, &state, &parts, &commitments)
+        .map_err(map_err)?;
+    Ok(b64_encode(state_key.expose()))
+}
+
+#[derive(Serialize)]
+pub struct ToprfRobustOut {
+    pub state_key: String,
+    pub inconsistent: Vec<u32>,
+}
+
+/// Robust Π2 finish: tolerates faulty SPs as long as `tsp` consistent
+/// partials arrived, confirming the key by decrypting `cid`. `commitments`
+/// may be `undefined` to skip DLEQ filtering. `kdf` and `suite` are the ones
+/// from setup.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn toprf_finish_robust(
+    uid: String,
+    password: String,
+    r: String,
+    partials: JsValue,
+    tsp: usize,
+    cid: JsValue,
+    commitments: JsValue,
+    kdf: JsValue,
+    suite: JsValue,
+) -> Result<JsValue, JsValue> {
+    let password = parse_kdf(kdf)?.harden(password.as_bytes()).map_err(map_err)?;
+    let r_bytes = b64_decode_array::<32>(&r).map_err(map_err)?;
+    let state = ToprfClientState {
+        r: SecretBytes::new(r_bytes),
+        suite: parse_suite(suite)?,
+    };
+    let parts = parse_partials(partials)?;
+    let commitments = if commitments.is_undefined() || commitments.is_null() {
+        None
+    } else {
+        Some(parse_commitments(commitments)?)
+    };
+    let cid_in: CtBlobIn = serde_wasm_bindgen::from_value(cid).map_err(to_js_error)?;
+    let cid = parse_cipherid(cid_in).map_err(map_err)?;
+
+    let (out, _cid_pt) = login::client_login_robust(
+        uid.as_bytes(),
+        &password,
+        &state,
+        &parts,
+        tsp,
+        commitments.as_deref(),
+        &cid,
+    )
+    .map_err(map_err)?;
+
+    serde_wasm_bindgen::to_value(&ToprfRobustOut {
+        state_key: b64_encode(out.state_key.expose()),
+        inconsistent: out.inconsistent,
+    })
+    .map_err(to_js_error)
 }
 
 #[derive(Deserialize)]
@@ -134,6 +352,21 @@ fn parse_ciphersp(obj: CtBlobIn) -> Result<CipherSp, UpspaError> {
     CipherSp::from_b64(&b64)
 }
 
+#[derive(Deserialize)]
+pub struct SpRecordIn {
+    pub sp_id: u32,
+    pub cj: CtBlobIn,
+}
+
+fn parse_sp_records(cjs: JsValue) -> Result<Vec<(u32, CipherSp)>, JsValue> {
+    let cjs_in: Vec<SpRecordIn> = serde_wasm_bindgen::from_value(cjs).map_err(to_js_error)?;
+    let mut out = Vec::with_capacity(cjs_in.len());
+    for r in cjs_in {
+        out.push((r.sp_id, parse_ciphersp(r.cj).map_err(map_err)?));
+    }
+    Ok(out)
+}
+
 #[derive(Serialize)]
 pub struct RegistrationSpOut {
     pub sp_id: u32,
@@ -159,15 +392,29 @@ pub fn protocol_register(
     lsj: String,
     state_key: String,
     cid: JsValue,
-    nsp: usize,
+    sp_ids: Vec<u32>,
+    record: JsValue,
 ) -> Result<JsValue, JsValue> {
-    let state_key = b64_decode_array::<32>(&state_key).map_err(map_err)?;
+    let state_key = SecretBytes::new(b64_decode_array::<32>(&state_key).map_err(map_err)?);
     let cid_in: CtBlobIn = serde_wasm_bindgen::from_value(cid).map_err(to_js_error)?;
     let cid = parse_cipherid(cid_in).map_err(map_err)?;
+    let record: LsRecord = if record.is_undefined() || record.is_null() {
+        LsRecord::None
+    } else {
+        serde_wasm_bindgen::from_value(record).map_err(to_js_error)?
+    };
 
     let mut rng = OsRng;
-    let out = register::client_register(uid.as_bytes(), lsj.as_bytes(), &state_key, &cid, nsp, &mut rng)
-        .map_err(map_err)?;
+    let out = register::client_register(
+        uid.as_bytes(),
+        lsj.as_bytes(),
+        &state_key,
+        &cid,
+        &sp_ids,
+        &record,
+        &mut rng,
+    )
+    .map_err(map_err)?;
 
     let per_sp = out
         .per_sp
@@ -181,7 +428,7 @@ pub fn protocol_register(
 
     let to_ls = RegistrationLsOut {
         uid: uid.clone(),
-        vinfo: b64_encode(&out.to_ls.vinfo),
+        vinfo: b64_encode(out.to_ls.vinfo.expose()),
     };
 
     serde_wasm_bindgen::to_value(&RegistrationOut { per_sp, to_ls }).map_err(to_js_error)
@@ -205,13 +452,13 @@ pub fn protocol_auth_prepare(
     lsj: String,
     state_key: String,
     cid: JsValue,
-    nsp: usize,
+    sp_ids: Vec<u32>,
 ) -> Result<JsValue, JsValue> {
-    let state_key = b64_decode_array::<32>(&state_key).map_err(map_err)?;
+    let state_key = SecretBytes::new(b64_decode_array::<32>(&state_key).map_err(map_err)?);
     let cid_in: CtBlobIn = serde_wasm_bindgen::from_value(cid).map_err(to_js_error)?;
     let cid = parse_cipherid(cid_in).map_err(map_err)?;
 
-    let q = authenticate::client_auth_prepare(uid.as_bytes(), lsj.as_bytes(), &state_key, &cid, nsp)
+    let q = authenticate::client_auth_prepare(uid.as_bytes(), lsj.as_bytes(), &state_key, &cid, &sp_ids)
         .map_err(map_err)?;
 
     let per_sp = q
@@ -224,42 +471,85 @@ pub fn protocol_auth_prepare(
         .collect();
 
     serde_wasm_bindgen::to_value(&AuthPrepareOut {
-        k0: b64_encode(&q.k0),
+        k0: b64_encode(q.k0.expose()),
         per_sp,
     })
     .map_err(to_js_error)
 }
 
+#[derive(Serialize)]
+pub struct SpCtrOut {
+    pub sp_id: u32,
+    pub ctr: u64,
+}
+
+#[derive(Serialize)]
+pub struct RecordRepairOut {
+    pub sp_id: u32,
+    pub cj: CtBlobB64,
+}
+
 #[derive(Serialize)]
 pub struct AuthFinishOut {
     pub vinfo_prime: String,
     pub best_ctr: u64,
+    pub record: LsRecord,
+    pub site_password: Option<String>,
+    pub sp_ctrs: Vec<SpCtrOut>,
+    pub stale: Vec<u32>,
+    pub invalid: Vec<u32>,
+    pub repairs: Vec<RecordRepairOut>,
 }
 
+/// `cjs` is a list of `{ sp_id, cj }`; the newest record must be held by at
+/// least `quorum` of them. Blobs that do not decrypt are skipped and listed
+/// in `invalid`.
 #[wasm_bindgen]
 pub fn protocol_auth_finish(
     uid: String,
     lsj: String,
     k0: String,
     cjs: JsValue,
+    quorum: usize,
 ) -> Result<JsValue, JsValue> {
-    let k0 = b64_decode_array::<32>(&k0).map_err(map_err)?;
-    let cjs_in: Vec<CtBlobIn> = serde_wasm_bindgen::from_value(cjs).map_err(to_js_error)?;
-    let mut cjs_parsed = Vec::with_capacity(cjs_in.len());
-    for cj in cjs_in {
-        cjs_parsed.push(parse_ciphersp(cj).map_err(map_err)?);
-    }
+    let k0 = SecretBytes::new(b64_decode_array::<32>(&k0).map_err(map_err)?);
+    let cjs_parsed = parse_sp_records(cjs)?;
 
-    let out = authenticate::client_auth_finish(uid.as_bytes(), lsj.as_bytes(), &k0, &cjs_parsed)
+    let out = authenticate::client_auth_finish(uid.as_bytes(), lsj.as_bytes(), &k0, &cjs_parsed, quorum)
         .map_err(map_err)?;
 
     serde_wasm_bindgen::to_value(&AuthFinishOut {
-        vinfo_prime: b64_encode(&out.vinfo_prime),
+        vinfo_prime: b64_encode(out.vinfo_prime.expose()),
         best_ctr: out.best_ctr,
+        site_password: out.site_password().map_err(map_err)?,
+        record: out.record,
+        sp_ctrs: out
+            .sp_ctrs
+            .iter()
+            .map(|(sp_id, ctr)| SpCtrOut { sp_id: *sp_id, ctr: *ctr })
+            .collect(),
+        stale: out.stale,
+        invalid: out.invalid,
+        repairs: out
+            .repairs
+            .iter()
+            .map(|(sp_id, cj)| RecordRepairOut {
+                sp_id: *sp_id,
+                cj: cj.to_b64(),
+            })
+            .collect(),
     })
     .map_err(to_js_error)
 }
 
+/// `policy` is a preset name or a policy object.
+#[wasm_bindgen]
+pub fn render_site_password(vinfo: String, policy: JsValue) -> Result<String, JsValue> {
+    let vinfo = b64_decode_array::<32>(&vinfo).map_err(map_err)?;
+    let spec: PolicySpec = serde_wasm_bindgen::from_value(policy).map_err(to_js_error)?;
+    password_policy::render_site_password(&vinfo, &spec.into()).map_err(map_err)
+}
+
 #[derive(Serialize)]
 pub struct SecretUpdatePrepareOut {
     pub k0: String,
@@ -272,13 +562,13 @@ pub fn protocol_secret_update_prepare(
     lsj: String,
     state_key: String,
     cid: JsValue,
-    nsp: usize,
+    sp_ids: Vec<u32>,
 ) -> Result<JsValue, JsValue> {
-    let state_key = b64_decode_array::<32>(&state_key).map_err(map_err)?;
+    let state_key = SecretBytes::new(b64_decode_array::<32>(&state_key).map_err(map_err)?);
     let cid_in: CtBlobIn = serde_wasm_bindgen::from_value(cid).map_err(to_js_error)?;
     let cid = parse_cipherid(cid_in).map_err(map_err)?;
 
-    let q = secret_update::client_secret_update_prepare(uid.as_bytes(), lsj.as_bytes(), &state_key, &cid, nsp)
+    let q = secret_update::client_secret_update_prepare(uid.as_bytes(), lsj.as_bytes(), &state_key, &cid, &sp_ids)
         .map_err(map_err)?;
 
     let per_sp = q
@@ -291,7 +581,7 @@ pub fn protocol_secret_update_prepare(
         .collect();
 
     serde_wasm_bindgen::to_value(&SecretUpdatePrepareOut {
-        k0: b64_encode(&q.k0),
+        k0: b64_encode(q.k0.expose()),
         per_sp,
     })
     .map_err(to_js_error)
@@ -304,32 +594,33 @@ pub struct SecretUpdateFinishOut {
     pub cj_new: CtBlobB64,
     pub old_ctr: u64,
     pub new_ctr: u64,
+    pub invalid: Vec<u32>,
 }
 
+/// `cjs` and `quorum` as for `protocol_auth_finish`.
 #[wasm_bindgen]
 pub fn protocol_secret_update_finish(
     uid: String,
     lsj: String,
     k0: String,
     cjs: JsValue,
+    quorum: usize,
 ) -> Result<JsValue, JsValue> {
-    let k0 = b64_decode_array::<32>(&k0).map_err(map_err)?;
-    let cjs_in: Vec<CtBlobIn> = serde_wasm_bindgen::from_value(cjs).map_err(to_js_error)?;
-    let mut cjs_parsed = Vec::with_capacity(cjs_in.len());
-    for cj in cjs_in {
-        cjs_parsed.push(parse_ciphersp(cj).map_err(map_err)?);
-    }
+    let k0 = SecretBytes::new(b64_decode_array::<32>(&k0).map_err(map_err)?);
+    let cjs_parsed = parse_sp_records(cjs)?;
 
     let mut rng = OsRng;
-    let out = secret_update::client_secret_update_finish(uid.as_bytes(), lsj.as_bytes(), &k0, &cjs_parsed, &mut rng)
-        .map_err(map_err)?;
+    let out =
+        secret_update::client_secret_update_finish(uid.as_bytes(), lsj.as_bytes(), &k0, &cjs_parsed, quorum, &mut rng)
+            .map_err(map_err)?;
 
     serde_wasm_bindgen::to_value(&SecretUpdateFinishOut {
-        vinfo_prime: b64_encode(&out.vinfo_prime),
-        vinfo_new: b64_encode(&out.vinfo_new),
+        vinfo_prime: b64_encode(out.vinfo_prime.expose()),
+        vinfo_new: b64_encode(out.vinfo_new.expose()),
         cj_new: out.cj_new.to_b64(),
         old_ctr: out.old_ctr,
         new_ctr: out.new_ctr,
+        invalid: out.invalid,
     })
     .map_err(to_js_error)
 }
@@ -345,30 +636,314 @@ pub struct PwdUpdateSpOut {
 pub struct PwdUpdateOut {
     pub cid_new: CtBlobB64,
     pub per_sp: Vec<PwdUpdateSpOut>,
+    pub commitments: Vec<ShareCommitmentWasm>,
+    pub kdf: PasswordKdfWasm,
+    pub suite: OprfSuite,
 }
 
+/// Π5 under `new_password`, hardened with `new_kdf` and under `new_suite`.
+/// Passing the current password with a new `new_kdf` or `new_suite`
+/// migrates the account to it.
 #[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
 pub fn protocol_password_update(
     uid: String,
     old_state_key: String,
     cid_old: JsValue,
-    nsp: usize,
+    sp_ids: Vec<u32>,
     tsp: usize,
     new_password: String,
     timestamp: u64,
+    new_kdf: JsValue,
+    new_suite: JsValue,
 ) -> Result<JsValue, JsValue> {
-    let old_state_key = b64_decode_array::<32>(&old_state_key).map_err(map_err)?;
+    let old_state_key = SecretBytes::new(b64_decode_array::<32>(&old_state_key).map_err(map_err)?);
     let cid_in: CtBlobIn = serde_wasm_bindgen::from_value(cid_old).map_err(to_js_error)?;
     let cid_old = parse_cipherid(cid_in).map_err(map_err)?;
+    let new_kdf = parse_kdf(new_kdf)?;
+    let new_suite = parse_suite(new_suite)?;
+    let cid_pt = decrypt_cid(uid.as_bytes(), &old_state_key, &cid_old).map_err(map_err)?;
+
+    let mut rng = OsRng;
+    let out = password_update::client_password_update_from_plaintext(
+        uid.as_bytes(),
+        &cid_pt,
+        &sp_ids,
+        tsp,
+        new_password.as_bytes(),
+        &new_kdf,
+        new_suite,
+        timestamp,
+        &mut rng,
+    )
+    .map_err(map_err)?;
+
+    let per_sp = out
+        .per_sp
+        .iter()
+        .map(|m| PwdUpdateSpOut {
+            sp_id: m.sp_id,
+            sig: b64_encode(&m.sig),
+            k_i_new: b64_encode(m.k_i_new.expose()),
+        })
+        .collect();
+
+    serde_wasm_bindgen::to_value(&PwdUpdateOut {
+        cid_new: out.cid_new.to_b64(),
+        per_sp,
+        commitments: commitments_to_wasm(&out.commitments),
+        kdf: kdf_to_wasm(&out.kdf),
+        suite: out.suite,
+    })
+    .map_err(to_js_error)
+}
+
+#[derive(Serialize)]
+pub struct ShareRefreshSpOut {
+    pub sp_id: u32,
+    pub sig: String,
+    pub delta: String,
+}
+
+#[derive(Serialize)]
+pub struct ShareRefreshOut {
+    pub per_sp: Vec<ShareRefreshSpOut>,
+    pub delta_commitments: Vec<ShareCommitmentWasm>,
+}
+
+#[wasm_bindgen]
+pub fn protocol_share_refresh(
+    uid: String,
+    state_key: String,
+    cid: JsValue,
+    sp_ids: Vec<u32>,
+    tsp: usize,
+    timestamp: u64,
+) -> Result<JsValue, JsValue> {
+    let state_key = SecretBytes::new(b64_decode_array::<32>(&state_key).map_err(map_err)?);
+    let cid_in: CtBlobIn = serde_wasm_bindgen::from_value(cid).map_err(to_js_error)?;
+    let cid = parse_cipherid(cid_in).map_err(map_err)?;
+
+    let mut rng = OsRng;
+    let out = share_refresh::client_share_refresh(
+        uid.as_bytes(),
+        &state_key,
+        &cid,
+        &sp_ids,
+        tsp,
+        timestamp,
+        &mut rng,
+    )
+    .map_err(map_err)?;
+
+    let per_sp = out
+        .per_sp
+        .iter()
+        .map(|m| ShareRefreshSpOut {
+            sp_id: m.sp_id,
+            sig: b64_encode(&m.sig),
+            delta: b64_encode(&m.delta),
+        })
+        .collect();
+
+    serde_wasm_bindgen::to_value(&ShareRefreshOut {
+        per_sp,
+        delta_commitments: commitments_to_wasm(&out.delta_commitments),
+    })
+    .map_err(to_js_error)
+}
+
+#[derive(Serialize)]
+pub struct KeyRotationSpOut {
+    pub sp_id: u32,
+    pub sig: String,
+}
+
+#[derive(Serialize)]
+pub struct KeyRotationOut {
+    pub sig_pk_new: String,
+    pub cid_new: CtBlobB64,
+    pub per_sp: Vec<KeyRotationSpOut>,
+}
+
+#[wasm_bindgen]
+pub fn protocol_key_rotation(
+    uid: String,
+    state_key: String,
+    cid: JsValue,
+    sp_ids: Vec<u32>,
+    timestamp: u64,
+) -> Result<JsValue, JsValue> {
+    let state_key = SecretBytes::new(b64_decode_array::<32>(&state_key).map_err(map_err)?);
+    let cid_in: CtBlobIn = serde_wasm_bindgen::from_value(cid).map_err(to_js_error)?;
+    let cid = parse_cipherid(cid_in).map_err(map_err)?;
 
     let mut rng = OsRng;
-    let out = password_update::client_password_update(
+    let out = key_rotation::client_key_rotation(
         uid.as_bytes(),
-        &old_state_key,
-        &cid_old,
-        nsp,
+        &state_key,
+        &cid,
+        &sp_ids,
+        timestamp,
+        &mut rng,
+    )
+    .map_err(map_err)?;
+
+    let per_sp = out
+        .per_sp
+        .iter()
+        .map(|m| KeyRotationSpOut {
+            sp_id: m.sp_id,
+            sig: b64_encode(&m.sig),
+        })
+        .collect();
+
+    serde_wasm_bindgen::to_value(&KeyRotationOut {
+        sig_pk_new: b64_encode(&out.sig_pk_new),
+        cid_new: out.cid_new.to_b64(),
+        per_sp,
+    })
+    .map_err(to_js_error)
+}
+
+#[derive(Serialize)]
+pub struct DeprovisionSpOut {
+    pub sp_id: u32,
+    pub sig: String,
+    pub suids: Vec<String>,
+}
+
+/// Signed per-SP requests deleting the account and the record of every LS
+/// in `lsjs`.
+#[wasm_bindgen]
+pub fn protocol_delete_account(
+    uid: String,
+    state_key: String,
+    cid: JsValue,
+    sp_ids: Vec<u32>,
+    lsjs: Vec<String>,
+    timestamp: u64,
+) -> Result<JsValue, JsValue> {
+    let state_key = SecretBytes::new(b64_decode_array::<32>(&state_key).map_err(map_err)?);
+    let cid_in: CtBlobIn = serde_wasm_bindgen::from_value(cid).map_err(to_js_error)?;
+    let cid = parse_cipherid(cid_in).map_err(map_err)?;
+    let lsjs: Vec<Vec<u8>> = lsjs.into_iter().map(String::into_bytes).collect();
+
+    let per_sp: Vec<DeprovisionSpOut> = deprovision::client_delete_account(
+        uid.as_bytes(),
+        &state_key,
+        &cid,
+        &sp_ids,
+        &lsjs,
+        timestamp,
+    )
+    .map_err(map_err)?
+    .iter()
+    .map(|m| DeprovisionSpOut {
+        sp_id: m.sp_id,
+        sig: b64_encode(&m.sig),
+        suids: m.suids.iter().map(|s| b64_encode(s)).collect(),
+    })
+    .collect();
+
+    serde_wasm_bindgen::to_value(&per_sp).map_err(to_js_error)
+}
+
+#[derive(Serialize)]
+pub struct RecoverySpOut {
+    pub sp_id: u32,
+    pub sig: String,
+}
+
+#[derive(Serialize)]
+pub struct RecoverySetupOut {
+    pub recovery_cid: CtBlobB64,
+    pub codes: Vec<String>,
+    pub threshold: usize,
+    pub per_sp: Vec<RecoverySpOut>,
+}
+
+/// `n_codes` recovery codes, any `threshold` of which open the returned
+/// `recovery_cid`, plus the signed request escrowing it at each SP.
+#[wasm_bindgen]
+pub fn protocol_recovery_setup(
+    uid: String,
+    state_key: String,
+    cid: JsValue,
+    sp_ids: Vec<u32>,
+    n_codes: usize,
+    threshold: usize,
+    timestamp: u64,
+) -> Result<JsValue, JsValue> {
+    let state_key = SecretBytes::new(b64_decode_array::<32>(&state_key).map_err(map_err)?);
+    let cid_in: CtBlobIn = serde_wasm_bindgen::from_value(cid).map_err(to_js_error)?;
+    let cid = parse_cipherid(cid_in).map_err(map_err)?;
+
+    let mut rng = OsRng;
+    let kit = recovery::client_recovery_setup(
+        uid.as_bytes(),
+        &state_key,
+        &cid,
+        &sp_ids,
+        n_codes,
+        threshold,
+        timestamp,
+        &mut rng,
+    )
+    .map_err(map_err)?;
+
+    serde_wasm_bindgen::to_value(&RecoverySetupOut {
+        recovery_cid: kit.recovery_cid.to_b64(),
+        codes: kit.codes.iter().map(|c| c.to_string()).collect(),
+        threshold: kit.threshold,
+        per_sp: kit
+            .per_sp
+            .iter()
+            .map(|m| RecoverySpOut {
+                sp_id: m.sp_id,
+                sig: b64_encode(&m.sig),
+            })
+            .collect(),
+    })
+    .map_err(to_js_error)
+}
+
+/// Π5 under `new_password`, with `cid` opened from `recovery_cid` and
+/// `codes` instead of the old password. Same output as
+/// `protocol_password_update`.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn protocol_recover(
+    uid: String,
+    recovery_cid: JsValue,
+    codes: Vec<String>,
+    sp_ids: Vec<u32>,
+    tsp: usize,
+    new_password: String,
+    timestamp: u64,
+    new_kdf: JsValue,
+    new_suite: JsValue,
+) -> Result<JsValue, JsValue> {
+    let new_kdf = parse_kdf(new_kdf)?;
+    let new_suite = parse_suite(new_suite)?;
+    let rcid_in: CtBlobIn = serde_wasm_bindgen::from_value(recovery_cid).map_err(to_js_error)?;
+    let recovery_cid = parse_cipherid(rcid_in).map_err(map_err)?;
+    let codes = codes
+        .iter()
+        .map(|c| c.parse::<recovery::RecoveryCode>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(map_err)?;
+    let cid_pt = recovery::recover_cid_plaintext(uid.as_bytes(), &recovery_cid, &codes).map_err(map_err)?;
+
+    let mut rng = OsRng;
+    let out = password_update::client_password_update_from_plaintext(
+        uid.as_bytes(),
+        &cid_pt,
+        &sp_ids,
         tsp,
         new_password.as_bytes(),
+        &new_kdf,
+        new_suite,
         timestamp,
         &mut rng,
     )
@@ -380,13 +955,156 @@ pub fn protocol_password_update(
         .map(|m| PwdUpdateSpOut {
             sp_id: m.sp_id,
             sig: b64_encode(&m.sig),
-            k_i_new: b64_encode(&m.k_i_new),
+            k_i_new: b64_encode(m.k_i_new.expose()),
         })
         .collect();
 
     serde_wasm_bindgen::to_value(&PwdUpdateOut {
         cid_new: out.cid_new.to_b64(),
         per_sp,
+        commitments: commitments_to_wasm(&out.commitments),
+        kdf: kdf_to_wasm(&out.kdf),
+        suite: out.suite,
+    })
+    .map_err(to_js_error)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DeviceEnrollmentWasm {
+    pub device_id: String,
+    pub sig_pk: String,
+    pub expires_at: u64,
+    pub wrapped: CtBlobB64,
+}
+
+/// Same fields as the `/v1/devices/revocations` body.
+#[derive(Serialize, Deserialize)]
+pub struct DeviceRevocationWasm {
+    pub uid_b64: String,
+    pub issued_at: u64,
+    pub revoked_b64: Vec<String>,
+    pub sig_b64: String,
+}
+
+fn parse_enrollment(v: JsValue) -> Result<device::DeviceEnrollment, JsValue> {
+    let e: DeviceEnrollmentWasm = serde_wasm_bindgen::from_value(v).map_err(to_js_error)?;
+    Ok(device::DeviceEnrollment {
+        device_id: b64_decode_array::<16>(&e.device_id).map_err(map_err)?,
+        sig_pk: b64_decode_array::<32>(&e.sig_pk).map_err(map_err)?,
+        expires_at: e.expires_at,
+        wrapped: CipherId::from_b64(&e.wrapped).map_err(map_err)?,
+    })
+}
+
+fn parse_revocations(v: JsValue) -> Result<Option<device::DeviceRevocationList>, JsValue> {
+    if v.is_null() || v.is_undefined() {
+        return Ok(None);
+    }
+    let l: DeviceRevocationWasm = serde_wasm_bindgen::from_value(v).map_err(to_js_error)?;
+    Ok(Some(device::DeviceRevocationList {
+        uid_b64: l.uid_b64,
+        issued_at: l.issued_at,
+        revoked: l
+            .revoked_b64
+            .iter()
+            .map(|id| b64_decode_array::<16>(id))
+            .collect::<Result<_, _>>()
+            .map_err(map_err)?,
+        sig: b64_decode_array::<64>(&l.sig_b64).map_err(map_err)?,
+    }))
+}
+
+/// Wrap the `cid` plaintext under `device_key` until `expires_at`, for
+/// `protocol_device_auth_prepare`. Keep the result on the device.
+#[wasm_bindgen]
+pub fn protocol_device_enroll(
+    uid: String,
+    state_key: String,
+    cid: JsValue,
+    device_key: String,
+    device_id: String,
+    expires_at: u64,
+) -> Result<JsValue, JsValue> {
+    let state_key = SecretBytes::new(b64_decode_array::<32>(&state_key).map_err(map_err)?);
+    let cid_in: CtBlobIn = serde_wasm_bindgen::from_value(cid).map_err(to_js_error)?;
+    let cid = parse_cipherid(cid_in).map_err(map_err)?;
+    let device_key = SecretBytes::new(b64_decode_array::<32>(&device_key).map_err(map_err)?);
+    let device_id = b64_decode_array::<16>(&device_id).map_err(map_err)?;
+
+    let cid_pt = decrypt_cid(uid.as_bytes(), &state_key, &cid).map_err(map_err)?;
+    let mut rng = OsRng;
+    let e = device::enroll_device(uid.as_bytes(), &cid_pt, &device_key, device_id, expires_at, &mut rng)
+        .map_err(map_err)?;
+
+    serde_wasm_bindgen::to_value(&DeviceEnrollmentWasm {
+        device_id: b64_encode(&e.device_id),
+        sig_pk: b64_encode(&e.sig_pk),
+        expires_at: e.expires_at,
+        wrapped: e.wrapped.to_b64(),
+    })
+    .map_err(to_js_error)
+}
+
+/// `protocol_auth_prepare` from a device enrollment instead of a login.
+/// `revocations` is the newest list seen, or `null`.
+#[wasm_bindgen]
+pub fn protocol_device_auth_prepare(
+    uid: String,
+    lsj: String,
+    device_key: String,
+    enrollment: JsValue,
+    now: u64,
+    revocations: JsValue,
+    sp_ids: Vec<u32>,
+) -> Result<JsValue, JsValue> {
+    let device_key = SecretBytes::new(b64_decode_array::<32>(&device_key).map_err(map_err)?);
+    let enrollment = parse_enrollment(enrollment)?;
+    let revocations = parse_revocations(revocations)?;
+    let cid_pt = device::unlock_device(uid.as_bytes(), &device_key, &enrollment, now, revocations.as_ref())
+        .map_err(map_err)?;
+
+    let per_sp = sp_ids
+        .iter()
+        .map(|id| AuthSuidOut {
+            sp_id: *id,
+            suid: b64_encode(&hash_suid(&cid_pt.rsp, lsj.as_bytes(), *id)),
+        })
+        .collect();
+
+    serde_wasm_bindgen::to_value(&AuthPrepareOut {
+        k0: b64_encode(cid_pt.k0.expose()),
+        per_sp,
+    })
+    .map_err(to_js_error)
+}
+
+/// Signed revocation list naming every device in `revoked`; post it to
+/// `/v1/devices/revocations` at each SP.
+#[wasm_bindgen]
+pub fn protocol_device_revoke(
+    uid: String,
+    state_key: String,
+    cid: JsValue,
+    revoked: Vec<String>,
+    issued_at: u64,
+) -> Result<JsValue, JsValue> {
+    let state_key = SecretBytes::new(b64_decode_array::<32>(&state_key).map_err(map_err)?);
+    let cid_in: CtBlobIn = serde_wasm_bindgen::from_value(cid).map_err(to_js_error)?;
+    let cid = parse_cipherid(cid_in).map_err(map_err)?;
+    let revoked = revoked
+        .iter()
+        .map(|id| b64_decode_array::<16>(id))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(map_err)?;
+
+    let cid_pt = decrypt_cid(uid.as_bytes(), &state_key, &cid).map_err(map_err)?;
+    let list = device::sign_device_revocations(&cid_pt.signing_key, uid.as_bytes(), issued_at, revoked);
+
+    serde_wasm_bindgen::to_value(&DeviceRevocationWasm {
+        uid_b64: list.uid_b64,
+        issued_at: list.issued_at,
+        revoked_b64: list.revoked.iter().map(|id| b64_encode(id)).collect(),
+        sig_b64: b64_encode(&list.sig),
     })
     .map_err(to_js_error)
 }
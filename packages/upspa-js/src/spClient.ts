@@ -20,6 +20,13 @@ export interface StorageProviderClient {
     cid_new: CtBlobB64;
     k_i_new_b64: string;
   }): Promise<void>;
+  shareRefresh(req: {
+    uid: string;
+    sp_id: number;
+    timestamp: number;
+    sig_b64: string;
+    delta_b64: string;
+  }): Promise<void>;
 }
 
 async function fetchJson<T>(url: string, init: RequestInit, timeoutMs = 10_000): Promise<T> {
@@ -88,11 +95,14 @@ export class HttpStorageProviderClient implements StorageProviderClient {
 
   async toprfEval(uid: string, blinded_b64: string): Promise<ToprfPartial> {
     const uid_b64 = utf8ToBase64Url(uid);
-    const out = await fetchJson<{ sp_id: number; y_b64: string }>(`${this.baseUrl}/v1/toprf/eval`, {
-      method: 'POST',
-      body: JSON.stringify({ uid_b64, blinded_b64 }),
-    });
-    return { id: out.sp_id, y: out.y_b64 };
+    const out = await fetchJson<{ sp_id: number; y_b64: string; proof_b64?: string }>(
+      `${this.baseUrl}/v1/toprf/eval`,
+      {
+        method: 'POST',
+        body: JSON.stringify({ uid_b64, blinded_b64 }),
+      },
+    );
+    return { id: out.sp_id, y: out.y_b64, proof: out.proof_b64 };
   }
 
   async createRecord(suid_b64: string, cj: CtBlobB64): Promise<void> {
@@ -138,4 +148,24 @@ export class HttpStorageProviderClient implements StorageProviderClient {
       }),
     });
   }
+
+  async shareRefresh(req: {
+    uid: string;
+    sp_id: number;
+    timestamp: number;
+    sig_b64: string;
+    delta_b64: string;
+  }): Promise<void> {
+    const uid_b64 = utf8ToBase64Url(req.uid);
+    await fetchJson(`${this.baseUrl}/v1/share-refresh`, {
+      method: 'POST',
+      body: JSON.stringify({
+        uid_b64,
+        sp_id: req.sp_id,
+        timestamp: req.timestamp,
+        sig_b64: req.sig_b64,
+        delta_b64: req.delta_b64,
+      }),
+    });
+  }
 }
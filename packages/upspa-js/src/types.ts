@@ -18,11 +18,33 @@ export interface SetupSpPayload {
   k_i: Base64Url;
 }
 
+export interface ShareCommitment {
+  sp_id: number;
+  k_i_commit: Base64Url;
+}
+
+/**
+ * How the password is hardened before the TOPRF. Kept by the client next to
+ * the commitments and passed to every TOPRF call; omitted means `none`.
+ */
+export type PasswordKdf =
+  | { kdf: 'none' }
+  | { kdf: 'argon2id'; m_cost_kib: number; t_cost: number; p_cost: number; salt: Base64Url };
+
+/**
+ * TOPRF hash-to-group and finalize suite; `ristretto255-sha512` is RFC 9497.
+ * Kept and passed like `PasswordKdf`; omitted means `blake3`.
+ */
+export type OprfSuite = 'blake3' | 'ristretto255-sha512';
+
 export interface SetupResult {
   sig_pk: Base64Url;
   cid: CtBlobB64;
   shares: SetupShare[];
+  commitments: ShareCommitment[];
   sp_payloads: SetupSpPayload[];
+  kdf: PasswordKdf;
+  suite: OprfSuite;
 }
 
 export interface ToprfBegin {
@@ -33,8 +55,31 @@ export interface ToprfBegin {
 export interface ToprfPartial {
   id: number;
   y: Base64Url;
+  proof?: Base64Url;
+}
+
+export type ClassRule = 'forbidden' | 'allowed' | 'required';
+
+export interface PasswordPolicy {
+  length: number;
+  lower: ClassRule;
+  upper: ClassRule;
+  digits: ClassRule;
+  symbols: ClassRule;
+  symbol_set: string;
+  banned: string;
 }
 
+export type PolicyPreset = 'strong' | 'alphanumeric' | 'legacy' | 'pin';
+
+/** Per-LS secret stored inside `cj`. */
+export type LsRecord =
+  | { kind: 'none' }
+  | { kind: 'password'; password: string }
+  | { kind: 'totp'; seed: number[]; digits: number; period: number }
+  | { kind: 'recovery_codes'; codes: string[] }
+  | { kind: 'policy'; policy: PasswordPolicy };
+
 export interface RegistrationSpOut {
   sp_id: number;
   suid: Base64Url;
@@ -49,6 +94,12 @@ export interface RegistrationOut {
   };
 }
 
+/** One SP's `c_j`. */
+export interface SpRecord {
+  sp_id: number;
+  cj: CtBlobB64;
+}
+
 export interface AuthPrepareOut {
   k0: Base64Url;
   per_sp: Array<{ sp_id: number; suid: Base64Url }>;
@@ -57,6 +108,17 @@ export interface AuthPrepareOut {
 export interface AuthFinishOut {
   vinfo_prime: Base64Url;
   best_ctr: number;
+  record: LsRecord;
+  /** Stored password, or `vinfo_prime` rendered under the stored policy. */
+  site_password: string | null;
+  /** `ctr` returned by each SP. */
+  sp_ctrs: Array<{ sp_id: number; ctr: number }>;
+  /** SPs that returned an older or conflicting record. */
+  stale: number[];
+  /** SPs whose record did not decrypt; skipped. */
+  invalid: number[];
+  /** Record updates for the stale and invalid SPs. */
+  repairs: SpRecord[];
 }
 
 export interface SecretUpdatePrepareOut {
@@ -70,11 +132,64 @@ export interface SecretUpdateFinishOut {
   cj_new: CtBlobB64;
   old_ctr: number;
   new_ctr: number;
+  /** SPs whose record did not decrypt; `cj_new` replaces it. */
+  invalid: number[];
 }
 
 export interface PasswordUpdateOut {
   cid_new: CtBlobB64;
   per_sp: Array<{ sp_id: number; sig: Base64Url; k_i_new: Base64Url }>;
+  commitments: ShareCommitment[];
+  /** Hardening of the new password; replaces the one kept from setup. */
+  kdf: PasswordKdf;
+  /** OPRF suite of the new shares; replaces the one kept from setup. */
+  suite: OprfSuite;
+}
+
+export interface ShareRefreshOut {
+  per_sp: Array<{ sp_id: number; sig: Base64Url; delta: Base64Url }>;
+  delta_commitments: ShareCommitment[];
+}
+
+export interface KeyRotationOut {
+  sig_pk_new: Base64Url;
+  cid_new: CtBlobB64;
+  per_sp: Array<{ sp_id: number; sig: Base64Url }>;
+}
+
+/** One SP's signed deletion request, for `POST /v1/deprovision`. */
+export interface DeprovisionSpOut {
+  sp_id: number;
+  sig: Base64Url;
+  suids: Base64Url[];
+}
+
+/**
+ * Recovery codes and the `recovery_cid` they open; each SP entry is a
+ * signed `POST /v1/recovery` request. Codes are `upspa-rc1-...` strings.
+ */
+export interface RecoverySetupOut {
+  recovery_cid: CtBlobB64;
+  codes: string[];
+  threshold: number;
+  per_sp: Array<{ sp_id: number; sig: Base64Url }>;
+}
+
+/** `cid` wrapped under a device key; stored on the device only. */
+export interface DeviceEnrollment {
+  device_id: Base64Url;
+  sig_pk: Base64Url;
+  /** Unix time at which the enrollment stops unlocking. */
+  expires_at: number;
+  wrapped: CtBlobB64;
+}
+
+/** Signed revocation list; also the `/v1/devices/revocations` body. */
+export interface DeviceRevocationList {
+  uid_b64: Base64Url;
+  issued_at: number;
+  revoked_b64: Base64Url[];
+  sig_b64: Base64Url;
 }
 
 export interface StorageProviderDescriptor {
@@ -86,4 +201,8 @@ export interface UpspaClientConfig {
   uid: string;
   sps: StorageProviderDescriptor[];
   threshold: number;
+  /** From `SetupResult.kdf`; defaults to no hardening. */
+  kdf?: PasswordKdf;
+  /** From `SetupResult.suite`; defaults to `blake3`. */
+  suite?: OprfSuite;
 }
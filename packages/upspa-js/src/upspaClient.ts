@@ -3,11 +3,15 @@ import type {
   AuthFinishOut,
   AuthPrepareOut,
   CtBlobB64,
+  LsRecord,
+  OprfSuite,
+  PasswordKdf,
   PasswordUpdateOut,
   RegistrationOut,
   SecretUpdateFinishOut,
   SecretUpdatePrepareOut,
   SetupResult,
+  SpRecord,
   ToprfBegin,
   ToprfPartial,
   UpspaClientConfig,
@@ -18,16 +22,31 @@ function assert(cond: unknown, msg: string): asserts cond {
   if (!cond) throw new Error(msg);
 }
 
+function fulfilledRecords(
+  perSp: Array<{ sp_id: number }>,
+  reads: PromiseSettledResult<CtBlobB64>[],
+): SpRecord[] {
+  const out: SpRecord[] = [];
+  reads.forEach((r, i) => {
+    if (r.status === 'fulfilled') out.push({ sp_id: perSp[i].sp_id, cj: r.value });
+  });
+  return out;
+}
+
 export class UpspaClient {
   public readonly uid: string;
   public readonly threshold: number;
   public readonly sps: StorageProviderClient[];
+  public readonly kdf: PasswordKdf;
+  public readonly suite: OprfSuite;
 
   private wasm: Awaited<ReturnType<typeof loadUpspaWasm>> | null = null;
 
   constructor(cfg: UpspaClientConfig, spClients?: StorageProviderClient[]) {
     this.uid = cfg.uid;
     this.threshold = cfg.threshold;
+    this.kdf = cfg.kdf ?? { kdf: 'none' };
+    this.suite = cfg.suite ?? 'blake3';
 
     const clients = spClients ?? cfg.sps.map((d) => new HttpStorageProviderClient(d));
     assert(clients.length > 0, 'At least one SP is required');
@@ -50,6 +69,10 @@ export class UpspaClient {
     return this.wasm;
   }
 
+  private spIds(): Uint32Array {
+    return Uint32Array.from(this.sps.map((s) => s.id));
+  }
+
   private spById(id: number): StorageProviderClient {
     const sp = this.sps.find((s) => s.id === id);
     if (!sp) throw new Error(`No SP configured for id=${id}`);
@@ -60,7 +83,7 @@ export class UpspaClient {
     await this.init();
     const nsp = this.sps.length;
 
-    const out = this.w().protocol_setup(this.uid, password, nsp, tsp) as SetupResult;
+    const out = this.w().protocol_setup(this.uid, password, this.spIds(), tsp, this.kdf, this.suite) as SetupResult;
     const results = await Promise.allSettled(out.sp_payloads.map((p) => this.spById(p.sp_id).setup(p)));
     const ok = results.filter((r) => r.status === 'fulfilled').length;
 
@@ -78,7 +101,7 @@ export class UpspaClient {
   async deriveStateKey(password: string): Promise<{ state_key_b64: string; begin: ToprfBegin; partials: ToprfPartial[] }> {
     await this.init();
 
-    const begin = this.w().toprf_begin(password) as ToprfBegin;
+    const begin = this.w().toprf_begin(password, this.kdf, this.suite) as ToprfBegin;
 
     const evals = await Promise.allSettled(this.sps.map((sp) => sp.toprfEval(this.uid, begin.blinded)));
     const partials: ToprfPartial[] = [];
@@ -96,7 +119,7 @@ export class UpspaClient {
     }
 
     const chosen = partials.slice(0, this.threshold);
-    const state_key_b64 = this.w().toprf_finish(password, begin.r, chosen);
+    const state_key_b64 = this.w().toprf_finish(password, begin.r, chosen, this.kdf, this.suite);
 
     return { state_key_b64, begin, partials: chosen };
   }
@@ -112,13 +135,13 @@ export class UpspaClient {
     throw new Error('Failed to fetch cid from all SPs');
   }
 
-  async register(lsj: string, password: string): Promise<RegistrationOut> {
+  async register(lsj: string, password: string, record?: LsRecord): Promise<RegistrationOut> {
     await this.init();
 
     const { state_key_b64 } = await this.deriveStateKey(password);
     const cid = await this.fetchCid();
 
-    const out = this.w().protocol_register(this.uid, lsj, state_key_b64, cid, this.sps.length) as RegistrationOut;
+    const out = this.w().protocol_register(this.uid, lsj, state_key_b64, cid, this.spIds(), record) as RegistrationOut;
 
     const writes = await Promise.allSettled(
       out.per_sp.map((m) => this.spById(m.sp_id).createRecord(m.suid, m.cj)),
@@ -137,22 +160,19 @@ export class UpspaClient {
     const { state_key_b64 } = await this.deriveStateKey(password);
     const cid = await this.fetchCid();
 
-    const prep = this.w().protocol_auth_prepare(this.uid, lsj, state_key_b64, cid, this.sps.length) as AuthPrepareOut;
+    const prep = this.w().protocol_auth_prepare(this.uid, lsj, state_key_b64, cid, this.spIds()) as AuthPrepareOut;
 
     const reads = await Promise.allSettled(
       prep.per_sp.map((m) => this.spById(m.sp_id).getRecord(m.suid)),
     );
 
-    const cjs: CtBlobB64[] = [];
-    for (const r of reads) {
-      if (r.status === 'fulfilled') cjs.push(r.value);
-    }
+    const cjs = fulfilledRecords(prep.per_sp, reads);
 
     if (cjs.length < this.threshold) {
       throw new Error(`Authentication: only ${cjs.length}/${prep.per_sp.length} cj reads succeeded (< threshold ${this.threshold}).`);
     }
 
-    const out = this.w().protocol_auth_finish(this.uid, lsj, prep.k0, cjs) as AuthFinishOut;
+    const out = this.w().protocol_auth_finish(this.uid, lsj, prep.k0, cjs, this.threshold) as AuthFinishOut;
     return out;
   }
 
@@ -162,20 +182,23 @@ export class UpspaClient {
     const { state_key_b64 } = await this.deriveStateKey(password);
     const cid = await this.fetchCid();
 
-    const prep = this.w().protocol_secret_update_prepare(this.uid, lsj, state_key_b64, cid, this.sps.length) as SecretUpdatePrepareOut;
+    const prep = this.w().protocol_secret_update_prepare(this.uid, lsj, state_key_b64, cid, this.spIds()) as SecretUpdatePrepareOut;
 
     const reads = await Promise.allSettled(
       prep.per_sp.map((m) => this.spById(m.sp_id).getRecord(m.suid)),
     );
-    const cjs: CtBlobB64[] = [];
-    for (const r of reads) {
-      if (r.status === 'fulfilled') cjs.push(r.value);
-    }
+    const cjs = fulfilledRecords(prep.per_sp, reads);
     if (cjs.length < this.threshold) {
       throw new Error(`Secret update: only ${cjs.length}/${prep.per_sp.length} cj reads succeeded (< threshold ${this.threshold}).`);
     }
 
-    const out = this.w().protocol_secret_update_finish(this.uid, lsj, prep.k0, cjs) as SecretUpdateFinishOut;
+    const out = this.w().protocol_secret_update_finish(
+      this.uid,
+      lsj,
+      prep.k0,
+      cjs,
+      this.threshold,
+    ) as SecretUpdateFinishOut;
     return { ...out, suids: prep.per_sp };
   }
 
@@ -187,7 +210,18 @@ export class UpspaClient {
     }
   }
 
-  async passwordUpdate(oldPassword: string, newPassword: string, timestamp: number): Promise<PasswordUpdateOut> {
+  /**
+   * Pass `newKdf` (e.g. from `password_kdf_argon2id`) or `newSuite` with
+   * `newPassword === oldPassword` to migrate the account; later clients need
+   * `out.kdf` and `out.suite`.
+   */
+  async passwordUpdate(
+    oldPassword: string,
+    newPassword: string,
+    timestamp: number,
+    newKdf: PasswordKdf = this.kdf,
+    newSuite: OprfSuite = this.suite,
+  ): Promise<PasswordUpdateOut> {
     await this.init();
 
     const { state_key_b64: old_state_key_b64 } = await this.deriveStateKey(oldPassword);
@@ -197,10 +231,12 @@ export class UpspaClient {
       this.uid,
       old_state_key_b64,
       cid_old,
-      this.sps.length,
+      this.spIds(),
       this.threshold,
       newPassword,
       BigInt(timestamp),
+      newKdf,
+      newSuite,
     ) as PasswordUpdateOut;
 
     const writes = await Promise.allSettled(
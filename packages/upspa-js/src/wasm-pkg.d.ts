@@ -1,39 +1,149 @@
 declare module '../wasm-pkg/upspa_wasm.js' {
   const init: (moduleOrPath?: unknown) => Promise<void>;
   export default init;
-  export function protocol_setup(uid: string, password: string, nsp: number, tsp: number): unknown;
-  export function toprf_begin(password: string): unknown;
-  export function toprf_finish(password: string, r: string, partials: unknown): string;
+  export function password_kdf_argon2id(m_cost_kib: number, t_cost: number, p_cost: number): unknown;
+  export function protocol_setup(
+    uid: string,
+    password: string,
+    sp_ids: Uint32Array,
+    tsp: number,
+    kdf?: unknown,
+    suite?: string,
+  ): unknown;
+  export function toprf_begin(password: string, kdf?: unknown, suite?: string): unknown;
+  export function toprf_finish(
+    password: string,
+    r: string,
+    partials: unknown,
+    kdf?: unknown,
+    suite?: string,
+  ): string;
+  export function toprf_finish_verified(
+    password: string,
+    r: string,
+    partials: unknown,
+    commitments: unknown,
+    kdf?: unknown,
+    suite?: string,
+  ): string;
+  export function toprf_finish_robust(
+    uid: string,
+    password: string,
+    r: string,
+    partials: unknown,
+    tsp: number,
+    cid: unknown,
+    commitments?: unknown,
+    kdf?: unknown,
+    suite?: string,
+  ): unknown;
   export function protocol_register(
     uid: string,
     lsj: string,
     state_key: string,
     cid: unknown,
-    nsp: number,
+    sp_ids: Uint32Array,
+    record?: unknown,
   ): unknown;
   export function protocol_auth_prepare(
     uid: string,
     lsj: string,
     state_key: string,
     cid: unknown,
-    nsp: number,
+    sp_ids: Uint32Array,
   ): unknown;
-  export function protocol_auth_finish(uid: string, lsj: string, k0: string, cjs: unknown): unknown;
+  export function protocol_auth_finish(uid: string, lsj: string, k0: string, cjs: unknown, quorum: number): unknown;
+  export function render_site_password(vinfo: string, policy: unknown): string;
   export function protocol_secret_update_prepare(
     uid: string,
     lsj: string,
     state_key: string,
     cid: unknown,
-    nsp: number,
+    sp_ids: Uint32Array,
+  ): unknown;
+  export function protocol_secret_update_finish(
+    uid: string,
+    lsj: string,
+    k0: string,
+    cjs: unknown,
+    quorum: number,
   ): unknown;
-  export function protocol_secret_update_finish(uid: string, lsj: string, k0: string, cjs: unknown): unknown;
   export function protocol_password_update(
     uid: string,
     old_state_key: string,
     cid_old: unknown,
-    nsp: number,
+    sp_ids: Uint32Array,
     tsp: number,
     new_password: string,
     timestamp: number,
+    new_kdf?: unknown,
+    new_suite?: string,
+  ): unknown;
+  export function protocol_share_refresh(
+    uid: string,
+    state_key: string,
+    cid: unknown,
+    sp_ids: Uint32Array,
+    tsp: number,
+    timestamp: number,
+  ): unknown;
+  export function protocol_key_rotation(
+    uid: string,
+    state_key: string,
+    cid: unknown,
+    sp_ids: Uint32Array,
+    timestamp: number,
+  ): unknown;
+  export function protocol_delete_account(
+    uid: string,
+    state_key: string,
+    cid: unknown,
+    sp_ids: Uint32Array,
+    lsjs: string[],
+    timestamp: number,
+  ): unknown;
+  export function protocol_recovery_setup(
+    uid: string,
+    state_key: string,
+    cid: unknown,
+    sp_ids: Uint32Array,
+    n_codes: number,
+    threshold: number,
+    timestamp: number,
+  ): unknown;
+  export function protocol_recover(
+    uid: string,
+    recovery_cid: unknown,
+    codes: string[],
+    sp_ids: Uint32Array,
+    tsp: number,
+    new_password: string,
+    timestamp: number,
+    new_kdf?: unknown,
+    new_suite?: string,
+  ): unknown;
+  export function protocol_device_enroll(
+    uid: string,
+    state_key: string,
+    cid: unknown,
+    device_key: string,
+    device_id: string,
+    expires_at: number,
+  ): unknown;
+  export function protocol_device_auth_prepare(
+    uid: string,
+    lsj: string,
+    device_key: string,
+    enrollment: unknown,
+    now: number,
+    revocations: unknown,
+    sp_ids: Uint32Array,
+  ): unknown;
+  export function protocol_device_revoke(
+    uid: string,
+    state_key: string,
+    cid: unknown,
+    revoked: string[],
+    issued_at: number,
   ): unknown;
 }
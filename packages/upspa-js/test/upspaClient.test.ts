@@ -6,12 +6,12 @@ import { UpspaClient } from '../src/upspaClient.js';
 vi.mock('../src/wasm.js', async () => {
   return {
     loadUpspaWasm: async () => ({
-      protocol_setup: (uid: string, password: string, nsp: number, tsp: number) => ({
+      protocol_setup: (uid: string, password: string, sp_ids: Uint32Array, tsp: number) => ({
         sig_pk: 'sigpk',
         cid: { nonce: 'n', ct: 'c', tag: 't' },
         shares: [],
-        sp_payloads: Array.from({ length: nsp }, (_, i) => ({
-          sp_id: i + 1,
+        sp_payloads: Array.from(sp_ids, (sp_id) => ({
+          sp_id,
           uid: 'uid',
           sig_pk: 'sigpk',
           cid: { nonce: 'n', ct: 'c', tag: 't' },
@@ -40,7 +40,16 @@ vi.mock('../src/wasm.js', async () => {
           { sp_id: 2, suid: 'suid2' },
         ],
       }),
-      protocol_auth_finish: () => ({ vinfo_prime: 'vinfo_prime', best_ctr: 0 }),
+      protocol_auth_finish: () => ({
+        vinfo_prime: 'vinfo_prime',
+        best_ctr: 0,
+        record: { kind: 'none' },
+        site_password: null,
+        sp_ctrs: [],
+        stale: [],
+        invalid: [],
+        repairs: [],
+      }),
       protocol_secret_update_prepare: () => ({
         k0: 'k0',
         per_sp: [
@@ -54,6 +63,7 @@ vi.mock('../src/wasm.js', async () => {
         cj_new: { nonce: 'n2', ct: 'c2', tag: 't2' },
         old_ctr: 0,
         new_ctr: 1,
+        invalid: [],
       }),
       protocol_password_update: () => ({
         cid_new: { nonce: 'n3', ct: 'c3', tag: 't3' },
@@ -3,6 +3,7 @@ package api
 import (
     "bytes"
     "context"
+    "encoding/base64"
     "net/http"
     "net/http/httptest"
     "testing"
@@ -186,6 +187,66 @@ func TestSetupCreate_Success(t *testing.T) {
     rr := httptest.NewRecorder()
     handler.Setup(rr, req)
     
+    if rr.Code != http.StatusCreated {
+        t.Errorf("expected 201 Created, got %d", rr.Code)
+    }
+}
+
+// b64Bytes is base64url (no padding) of n zero bytes.
+func b64Bytes(n int) string {
+    return base64.RawURLEncoding.EncodeToString(make([]byte, n))
+}
+
+func recordCreateStatus(store *FakeStore, suid string, ctLen int) int {
+    body := `{"suid_b64":"` + suid + `","cj":{"nonce":"` + b64Bytes(24) + `","ct":"` + b64Bytes(ctLen) + `","tag":"` + b64Bytes(16) + `"}}`
+    req := httptest.NewRequest("POST", "/v1/records", bytes.NewBufferString(body))
+    req.Header.Set("Content-Type", "application/json")
+
+    rr := httptest.NewRecorder()
+    NewHandler(store).RecordCreate(rr, req)
+    return rr.Code
+}
+
+func TestRecordCreate_CtLengthBounds(t *testing.T) {
+    store := NewFakeStore()
+
+    // v1 records are exactly 40 bytes; v3 records carry a typed payload.
+    cases := []struct {
+        suid  string
+        ctLen int
+        want  int
+    }{
+        {"AQ" + b64Bytes(32)[2:], 39, http.StatusBadRequest},
+        {"Ag" + b64Bytes(32)[2:], 40, http.StatusCreated},
+        {"Aw" + b64Bytes(32)[2:], 120, http.StatusCreated},
+        {"BA" + b64Bytes(32)[2:], 4096, http.StatusCreated},
+        {"BQ" + b64Bytes(32)[2:], 4097, http.StatusBadRequest},
+    }
+    for _, c := range cases {
+        if got := recordCreateStatus(store, c.suid, c.ctLen); got != c.want {
+            t.Errorf("ct of %d bytes: expected %d, got %d", c.ctLen, c.want, got)
+        }
+    }
+}
+
+func TestSetupCreate_VersionedCidAccepted(t *testing.T) {
+    store := NewFakeStore()
+    handler := NewHandler(store)
+
+    // A v2 cid lists the SP ids, so it is longer than the 96-byte v1 layout.
+    body := `{
+        "uid_b64":"` + b64Bytes(32) + `",
+        "sig_pk_b64":"` + b64Bytes(32) + `",
+        "cid":{"nonce":"` + b64Bytes(24) + `","ct":"` + b64Bytes(116) + `","tag":"` + b64Bytes(16) + `"},
+        "k_i_b64":"` + b64Bytes(32) + `"
+    }`
+
+    req := httptest.NewRequest("POST", "/v1/setup", bytes.NewBufferString(body))
+    req.Header.Set("Content-Type", "application/json")
+
+    rr := httptest.NewRecorder()
+    handler.Setup(rr, req)
+
     if rr.Code != http.StatusCreated {
         t.Errorf("expected 201 Created, got %d", rr.Code)
     }
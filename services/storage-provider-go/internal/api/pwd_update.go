@@ -32,7 +32,7 @@ func (h *Handler) PasswordUpdate(w http.ResponseWriter, r *http.Request) {
 		WriteError(w, http.StatusBadRequest, "invalid_cid_nonce", "Bad Request: Invalid cid_nonce format or length", nil)
 		return
 	}
-	if err := validateBase64URLNoPad(req.CIDNew.Ct, 96); err != nil {
+	if err := validateBase64URLNoPadRange(req.CIDNew.Ct, minCidCtLen, maxCtLen); err != nil {
 		WriteError(w, http.StatusBadRequest, "invalid_cid_ct", "Bad Request: Invalid cid_ct format or length", nil)
 		return
 	}
@@ -27,8 +27,8 @@ func (h *Handler) RecordCreate(w http.ResponseWriter, r *http.Request) {
 		WriteError(w, http.StatusBadRequest, "invalid_cj_nonce", "Bad Request: Invalid cj_nonce format or length", nil)
 		return
 	}
-	// CIPHERSP_PT_LEN is 40 bytes
-	if err := validateBase64URLNoPad(req.CJ.Ct, 40); err != nil {
+	// CIPHERSP_PT_LEN is 40 bytes; v3 records may be longer
+	if err := validateBase64URLNoPadRange(req.CJ.Ct, minCjCtLen, maxCtLen); err != nil {
 		WriteError(w, http.StatusBadRequest, "invalid_cj_ct", "Bad Request: Invalid cj_ct format or length", nil)
 		return
 	}
@@ -104,7 +104,7 @@ func (h *Handler) RecordUpdate(w http.ResponseWriter, r *http.Request) {
 		WriteError(w, http.StatusBadRequest, "invalid_cj_nonce", "Bad Request: Invalid cj_nonce format or length", nil)
 		return
 	}
-	if err := validateBase64URLNoPad(req.CJ.Ct, 40); err != nil {
+	if err := validateBase64URLNoPadRange(req.CJ.Ct, minCjCtLen, maxCtLen); err != nil {
 		WriteError(w, http.StatusBadRequest, "invalid_cj_ct", "Bad Request: Invalid cj_ct format or length", nil)
 		return
 	}
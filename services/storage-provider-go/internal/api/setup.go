@@ -17,6 +17,14 @@ var (
 	ErrConflict = errors.New("record conflict")
 )
 
+// Ciphertext lengths accepted for cid and cj. Clients write versioned
+// plaintexts, so only the v1 size is fixed and newer ones may be longer.
+const (
+	minCidCtLen = 96
+	minCjCtLen  = 40
+	maxCtLen    = 4096
+)
+
 // Store defines database operations for the handlers.
 // This interface allows us to use a fake database for testing.
 type Store interface {
@@ -90,7 +98,7 @@ func (h *Handler) Setup(w http.ResponseWriter, r *http.Request) {
 		WriteError(w, http.StatusBadRequest, "invalid_cid_nonce", "Bad Request: Invalid cid_nonce format or length", nil)
 		return
 	}
-	if err := validateBase64URLNoPad(req.CID.Ct, 96); err != nil {
+	if err := validateBase64URLNoPadRange(req.CID.Ct, minCidCtLen, maxCtLen); err != nil {
 		WriteError(w, http.StatusBadRequest, "invalid_cid_ct", "Bad Request: Invalid cid_ct format or length", nil)
 		return
 	}
@@ -143,5 +151,24 @@ func validateBase64URLNoPad(s string, expectedLen int) error {
 		return ErrInvalidLength
 	}
 
+	return nil
+}
+
+// validateBase64URLNoPadRange checks if a base64 string decodes to between
+// minLen and maxLen bytes, inclusive.
+func validateBase64URLNoPadRange(s string, minLen, maxLen int) error {
+	if s == "" {
+		return ErrInvalidBase64
+	}
+
+	decoded, err := base64.RawURLEncoding.DecodeString(s)
+	if err != nil {
+		return ErrInvalidBase64
+	}
+
+	if len(decoded) < minLen || len(decoded) > maxLen {
+		return ErrInvalidLength
+	}
+
 	return nil
 }
\ No newline at end of file